@@ -2,6 +2,7 @@
 extern crate tracing;
 
 mod replication;
+mod storage;
 mod stores;
 
 use std::time::Instant;
@@ -48,6 +49,10 @@ async fn main() -> Result<()> {
     replication::run_datacake(3, Consistency::LocalQuorum).await?;
     replication::run_datacake(5, Consistency::LocalQuorum).await?;
 
+    info!("Beginning LMDB concurrent read benchmark...");
+    storage::run_lmdb_reads(0).await?;
+    storage::run_lmdb_reads(datacake::lmdb::DEFAULT_NUM_READERS).await?;
+
     info!(
         "Benchmark Took: {}",
         humantime::format_duration(start.elapsed())
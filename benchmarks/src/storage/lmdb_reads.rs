@@ -0,0 +1,84 @@
+use std::env::temp_dir;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use datacake::crdt::HLCTimestamp;
+use datacake::eventual_consistency::{Document, Storage};
+use datacake::lmdb::{LmdbOptions, LmdbStorage};
+
+static KEYSPACE: &str = "my-keyspace";
+const NUM_DOCS: u64 = 10_000;
+const READS_PER_TASK: u64 = 1_000;
+
+/// Compares concurrent reads served by the given number of reader threads
+/// while a writer continuously updates documents.
+///
+/// With `0` readers every read is queued behind writes on the single writer thread.
+#[instrument(name = "lmdb-reads-benchmark")]
+pub async fn run_lmdb_reads(num_readers: usize) -> Result<()> {
+    let path = temp_dir().join(format!("datacake-bench-lmdb-{num_readers}"));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path)?;
+
+    let options = LmdbOptions::default()
+        .with_map_size(256 << 20)
+        .with_num_readers(num_readers);
+    let storage = Arc::new(LmdbStorage::open_with_options(&path, options).await?);
+
+    let docs = (0..NUM_DOCS).map(doc);
+    storage.multi_put(KEYSPACE, docs).await?;
+
+    for concurrency in [1, 8, 64, 256] {
+        let start = Instant::now();
+        run_reads_concurrently(storage.clone(), concurrency).await?;
+        info!(
+            "Reading {} docs @ {concurrency} took {}",
+            concurrency as u64 * READS_PER_TASK,
+            humantime::format_duration(start.elapsed())
+        );
+    }
+
+    drop(storage);
+    let _ = std::fs::remove_dir_all(&path);
+
+    Ok(())
+}
+
+async fn run_reads_concurrently(
+    storage: Arc<LmdbStorage>,
+    concurrency: usize,
+) -> Result<()> {
+    let writer = {
+        let storage = storage.clone();
+        tokio::spawn(async move {
+            for id in 0..NUM_DOCS {
+                storage.put(KEYSPACE, doc(id)).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+    };
+
+    let mut handles = Vec::new();
+    for task in 0..concurrency as u64 {
+        let storage = storage.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..READS_PER_TASK {
+                let id = (task * READS_PER_TASK + i) % NUM_DOCS;
+                storage.get(KEYSPACE, id).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        }));
+    }
+
+    for handle in handles {
+        handle.await??;
+    }
+    writer.abort();
+
+    Ok(())
+}
+
+fn doc(id: u64) -> Document {
+    Document::new(id, HLCTimestamp::from_u64(id), b"Hello, world!".to_vec())
+}
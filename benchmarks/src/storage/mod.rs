@@ -0,0 +1,3 @@
+mod lmdb_reads;
+
+pub use lmdb_reads::run_lmdb_reads;
@@ -0,0 +1,354 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use datacake_crdt::Key;
+use parking_lot::Mutex;
+
+/// Marks a value which is stored inline within the inner store.
+const INLINE_TAG: u8 = 0;
+/// Marks a value which is stored in a blob file.
+const BLOB_TAG: u8 = 1;
+/// The length of an encoded blob reference.
+const REFERENCE_LEN: usize = 1 + blake3::OUT_LEN + 8;
+/// The extension of blob files which are still being written.
+const TEMP_EXTENSION: &str = "tmp";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// The content address of a blob, the BLAKE3 hash of its data.
+pub struct BlobId([u8; blake3::OUT_LEN]);
+
+impl BlobId {
+    /// Computes the ID of the given data.
+    pub fn of(data: &[u8]) -> Self {
+        Self(*blake3::hash(data).as_bytes())
+    }
+
+    #[inline]
+    /// The raw hash bytes.
+    pub fn as_bytes(&self) -> &[u8; blake3::OUT_LEN] {
+        &self.0
+    }
+
+    fn from_hex(hex: &str) -> Option<Self> {
+        blake3::Hash::from_hex(hex)
+            .ok()
+            .map(|hash| Self(*hash.as_bytes()))
+    }
+}
+
+impl Display for BlobId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", blake3::Hash::from(self.0).to_hex())
+    }
+}
+
+/// A document value as it is held by the inner store.
+pub(crate) enum StoredValue<'a> {
+    /// The value is stored as is.
+    Inline(&'a [u8]),
+    /// The value is stored in the blob file with the given ID.
+    Blob { id: BlobId, len: u64 },
+}
+
+impl<'a> StoredValue<'a> {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Inline(data) => {
+                let mut buffer = Vec::with_capacity(data.len() + 1);
+                buffer.push(INLINE_TAG);
+                buffer.extend_from_slice(data);
+                buffer
+            },
+            Self::Blob { id, len } => {
+                let mut buffer = Vec::with_capacity(REFERENCE_LEN);
+                buffer.push(BLOB_TAG);
+                buffer.extend_from_slice(id.as_bytes());
+                buffer.extend_from_slice(&len.to_le_bytes());
+                buffer
+            },
+        }
+    }
+
+    /// Decodes a value previously produced by [StoredValue::encode].
+    ///
+    /// Returns `None` if the value was not written by this crate.
+    pub(crate) fn decode(buffer: &'a [u8]) -> Option<Self> {
+        let (tag, data) = buffer.split_first()?;
+        match *tag {
+            INLINE_TAG => Some(Self::Inline(data)),
+            BLOB_TAG if buffer.len() == REFERENCE_LEN => {
+                let (id, len) = data.split_at(blake3::OUT_LEN);
+                Some(Self::Blob {
+                    id: BlobId(id.try_into().ok()?),
+                    len: u64::from_le_bytes(len.try_into().ok()?),
+                })
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+/// Tracks which documents reference which blobs.
+struct BlobIndex {
+    /// The blob referenced by each document which is stored as a blob.
+    docs: HashMap<(String, Key), BlobId>,
+    /// The number of documents referencing, or about to reference, each blob.
+    refs: HashMap<BlobId, usize>,
+    /// The blobs which are no longer referenced and can be removed.
+    orphans: HashSet<BlobId>,
+}
+
+impl BlobIndex {
+    fn acquire(&mut self, id: BlobId) {
+        *self.refs.entry(id).or_default() += 1;
+        self.orphans.remove(&id);
+    }
+
+    fn release(&mut self, id: BlobId) {
+        if let Entry::Occupied(mut entry) = self.refs.entry(id) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+                self.orphans.insert(id);
+            }
+        }
+    }
+}
+
+/// A directory of content-addressed blob files.
+///
+/// Blobs are stored at `<path>/<first 2 hex chars>/<hex hash>`.
+pub(crate) struct BlobStore {
+    path: PathBuf,
+    index: Mutex<BlobIndex>,
+    temp_counter: AtomicU64,
+}
+
+impl BlobStore {
+    /// Opens the blob directory at the given path, creating it if it does not exist.
+    pub(crate) fn open(path: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&path)?;
+
+        Ok(Self {
+            path,
+            index: Mutex::new(BlobIndex::default()),
+            temp_counter: AtomicU64::new(0),
+        })
+    }
+
+    #[inline]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of blobs currently referenced by documents.
+    pub(crate) fn num_blobs(&self) -> usize {
+        self.index.lock().refs.len()
+    }
+
+    /// Records an existing document reference found when loading the store.
+    pub(crate) fn load_reference(&self, keyspace: &str, doc_id: Key, id: BlobId) {
+        let mut index = self.index.lock();
+        index.acquire(id);
+        index.docs.insert((keyspace.to_string(), doc_id), id);
+    }
+
+    /// Marks every blob file which is not referenced by a document as an orphan,
+    /// and removes any partially written files.
+    ///
+    /// This must be called once all references have been loaded.
+    pub(crate) fn load_orphans(&self) -> io::Result<()> {
+        let mut on_disk = HashSet::new();
+        for shard in fs::read_dir(&self.path)? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(shard.path())? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == TEMP_EXTENSION) {
+                    remove_file(&path)?;
+                    continue;
+                }
+
+                match path.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => match BlobId::from_hex(name) {
+                        Some(id) => {
+                            on_disk.insert(id);
+                        },
+                        None => {
+                            warn!(path = %path.display(), "Ignoring unknown file in blob directory.");
+                        },
+                    },
+                    None => continue,
+                }
+            }
+        }
+
+        let mut index = self.index.lock();
+        for id in index.refs.keys() {
+            if !on_disk.contains(id) {
+                error!(blob_id = %id, "Blob file referenced by a document is missing.");
+            }
+        }
+
+        let orphans = on_disk
+            .into_iter()
+            .filter(|id| !index.refs.contains_key(id))
+            .collect::<Vec<_>>();
+        index.orphans.extend(orphans);
+
+        Ok(())
+    }
+
+    /// Writes the given data to its blob file, if it does not already exist,
+    /// and takes a reference to it.
+    ///
+    /// The reference must be completed with [BlobStore::complete] once the
+    /// inner store has been written to.
+    pub(crate) fn write(&self, data: &[u8]) -> io::Result<BlobId> {
+        let id = BlobId::of(data);
+
+        // The reference is taken before checking the file exists so it cannot
+        // be removed by a concurrent garbage collection.
+        self.index.lock().acquire(id);
+        if let Err(e) = self.write_file(id, data) {
+            self.index.lock().release(id);
+            return Err(e);
+        }
+
+        Ok(id)
+    }
+
+    fn write_file(&self, id: BlobId, data: &[u8]) -> io::Result<()> {
+        let path = self.blob_path(id);
+        if path.exists() {
+            return Ok(());
+        }
+
+        let parent = path.parent().expect("Blob path should have a parent");
+        fs::create_dir_all(parent)?;
+
+        let temp_path = path.with_extension(format!(
+            "{}.{TEMP_EXTENSION}",
+            self.temp_counter.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp_path, &path)
+    }
+
+    /// Reads the data of the given blob, checking it matches its ID.
+    ///
+    /// Returns `None` if the blob's data is corrupted.
+    pub(crate) fn read(&self, id: BlobId, len: u64) -> io::Result<Option<Vec<u8>>> {
+        let data = fs::read(self.blob_path(id))?;
+        if data.len() as u64 != len || BlobId::of(&data) != id {
+            return Ok(None);
+        }
+        Ok(Some(data))
+    }
+
+    /// Updates the blob referenced by the given document once the inner store
+    /// has been written to.
+    ///
+    /// If `success` is `false` the reference taken by [BlobStore::write] is dropped,
+    /// otherwise the document's previous reference is dropped.
+    pub(crate) fn complete(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        blob: Option<BlobId>,
+        success: bool,
+    ) {
+        let mut index = self.index.lock();
+
+        if !success {
+            if let Some(id) = blob {
+                index.release(id);
+            }
+            return;
+        }
+
+        let key = (keyspace.to_string(), doc_id);
+        let previous = match blob {
+            Some(id) => index.docs.insert(key, id),
+            None => index.docs.remove(&key),
+        };
+        if let Some(id) = previous {
+            index.release(id);
+        }
+    }
+
+    /// Removes every blob file which is no longer referenced.
+    ///
+    /// Returns the number of blobs removed.
+    pub(crate) fn collect_garbage(&self) -> io::Result<usize> {
+        // The lock is held while removing files so no new reference can be
+        // taken to a blob which is about to be removed.
+        let mut index = self.index.lock();
+
+        let mut num_removed = 0;
+        for id in index.orphans.iter().copied().collect::<Vec<_>>() {
+            remove_file(&self.blob_path(id))?;
+            index.orphans.remove(&id);
+            num_removed += 1;
+        }
+
+        Ok(num_removed)
+    }
+
+    pub(crate) fn blob_path(&self, id: BlobId) -> PathBuf {
+        let hex = id.to_string();
+        self.path.join(&hex[..2]).join(hex)
+    }
+}
+
+/// Removes the file at the given path, ignoring files which do not exist.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_value_encoding() {
+        let encoded = StoredValue::Inline(b"Hello, world").encode();
+        assert!(
+            matches!(StoredValue::decode(&encoded), Some(StoredValue::Inline(data)) if data == b"Hello, world")
+        );
+
+        let id = BlobId::of(b"Hello, world");
+        let encoded = StoredValue::Blob { id, len: 12 }.encode();
+        assert_eq!(encoded.len(), REFERENCE_LEN);
+        assert!(
+            matches!(StoredValue::decode(&encoded), Some(StoredValue::Blob { id: decoded, len: 12 }) if decoded == id)
+        );
+
+        assert!(StoredValue::decode(b"").is_none());
+        assert!(StoredValue::decode(&[BLOB_TAG, 1, 2, 3]).is_none());
+        assert!(StoredValue::decode(&[7, 1, 2, 3]).is_none());
+    }
+
+    #[test]
+    fn test_blob_id_hex() {
+        let id = BlobId::of(b"Hello, world");
+        assert_eq!(BlobId::from_hex(&id.to_string()), Some(id));
+        assert_eq!(BlobId::from_hex("not-a-hash"), None);
+    }
+}
@@ -0,0 +1,745 @@
+//! # Datacake Blob
+//!
+//! A wrapper around any datacake-eventual-consistency `Storage` implementation which
+//! keeps large document values in content-addressed files rather than within the inner store.
+//!
+//! Values at or above the configured threshold are written to a file named after the
+//! BLAKE3 hash of the value, the inner store then only holds a small reference to the file.
+//! Documents with identical values share a single file. Files which are no longer referenced
+//! by any document are removed whenever tombstones are purged.
+//!
+//! The wrapper must be given an empty store, or a store previously wrapped with the same
+//! blob directory, as every value held by the inner store is tagged with how it is stored.
+//!
+//! For more info see <https://github.com/lnx-search/datacake>
+//!
+//! ## Example
+//!
+//! ```rust
+//! use anyhow::Result;
+//! use datacake_blob::BlobStorageBuilder;
+//! use datacake_eventual_consistency::EventuallyConsistentStoreExtension;
+//! use datacake_memory::MemoryStorage;
+//! use datacake_node::{
+//!     ConnectionConfig,
+//!     Consistency,
+//!     DCAwareSelector,
+//!     DatacakeNodeBuilder,
+//! };
+//!
+//! static KEYSPACE: &str = "blob-store";
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     tracing_subscriber::fmt::init();
+//!
+//!     let temp_dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+//!     let store = BlobStorageBuilder::new(temp_dir.join("blobs"))
+//!         .with_threshold(1024)
+//!         .open(MemoryStorage::new())
+//!         .await?;
+//!
+//!     let addr = test_helper::get_unused_addr();
+//!     let connection_cfg = ConnectionConfig::new(addr, addr, Vec::<String>::new());
+//!
+//!     let node = DatacakeNodeBuilder::<DCAwareSelector>::new(1, connection_cfg)
+//!         .connect()
+//!         .await?;
+//!     let store = node
+//!         .add_extension(EventuallyConsistentStoreExtension::new(store))
+//!         .await?;
+//!
+//!     let handle = store.handle();
+//!
+//!     handle
+//!         .put(KEYSPACE, 1, vec![1; 4096], Consistency::All)
+//!         .await
+//!         .expect("Put value.");
+//!
+//!     let doc = handle
+//!         .get(KEYSPACE, 1)
+//!         .await
+//!         .expect("Get value.")
+//!         .expect("Document should not be none");
+//!     assert_eq!(doc.id(), 1);
+//!     assert_eq!(doc.data(), vec![1; 4096]);
+//!
+//!     node.shutdown().await;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+#[macro_use]
+extern crate tracing;
+
+mod blob;
+
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datacake_crdt::{HLCTimestamp, Key};
+use datacake_eventual_consistency::{
+    BulkMutationError,
+    Document,
+    DocumentMetadata,
+    MetadataStream,
+    PutContext,
+    StateSnapshot,
+    Storage,
+};
+use futures::{StreamExt, TryStreamExt};
+
+pub use self::blob::BlobId;
+use self::blob::{BlobStore, StoredValue};
+
+/// The size in bytes at which document values are stored in a blob file by default.
+pub const DEFAULT_BLOB_THRESHOLD: usize = 256 << 10;
+
+#[derive(Debug, thiserror::Error)]
+/// An error which can occur while accessing a [BlobStorage].
+pub enum BlobStorageError<E>
+where
+    E: Error + Send + 'static,
+{
+    #[error("{0}")]
+    /// The inner store failed to complete the operation.
+    Store(E),
+    #[error("Blob IO Error: {0}")]
+    /// An IO error occurred while reading or writing a blob file.
+    Io(#[from] io::Error),
+    #[error("The data of blob {0} does not match its ID.")]
+    /// The blob file has been modified or truncated.
+    Corrupted(BlobId),
+    #[error("The value of document {0} was not written by a blob store.")]
+    /// The inner store holds a value without a blob store tag.
+    InvalidValue(Key),
+}
+
+impl<E> BlobStorageError<E>
+where
+    E: Error + Send + 'static,
+{
+    fn from_bulk(error: BulkMutationError<E>) -> BulkMutationError<Self> {
+        let successful_doc_ids = error.successful_doc_ids().to_vec();
+        BulkMutationError::new(Self::Store(error.into_inner()), successful_doc_ids)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A builder for configuring a [BlobStorage] instance.
+pub struct BlobStorageBuilder {
+    path: PathBuf,
+    threshold: usize,
+}
+
+impl BlobStorageBuilder {
+    /// Creates a new builder storing blob files within the given directory.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            threshold: DEFAULT_BLOB_THRESHOLD,
+        }
+    }
+
+    /// Set the size in bytes at which document values are stored in a blob file.
+    ///
+    /// By default this is [DEFAULT_BLOB_THRESHOLD].
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Wraps the given store, creating the blob directory if it does not exist.
+    ///
+    /// Every document within the inner store is read in order to find which blob
+    /// files are still referenced, any other blob files are removed on the next
+    /// garbage collection.
+    pub async fn open<S: Storage>(
+        self,
+        inner: S,
+    ) -> Result<BlobStorage<S>, BlobStorageError<S::Error>> {
+        let path = self.path;
+        let blobs = tokio::task::spawn_blocking(move || BlobStore::open(path))
+            .await
+            .expect("Spawn background thread")?;
+
+        let storage = BlobStorage {
+            inner,
+            blobs: Arc::new(blobs),
+            threshold: self.threshold,
+        };
+        storage.load_references().await?;
+
+        let blobs = storage.blobs.clone();
+        tokio::task::spawn_blocking(move || blobs.load_orphans())
+            .await
+            .expect("Spawn background thread")?;
+
+        Ok(storage)
+    }
+}
+
+/// A [Storage] wrapper which keeps large document values in content-addressed files.
+///
+/// Document metadata, including timestamps and tombstones, is passed through to the
+/// inner store unchanged.
+pub struct BlobStorage<S: Storage> {
+    inner: S,
+    blobs: Arc<BlobStore>,
+    threshold: usize,
+}
+
+impl<S: Storage> BlobStorage<S> {
+    /// Wraps the given store with the default configuration, storing blob files
+    /// within the given directory.
+    pub async fn open(
+        inner: S,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, BlobStorageError<S::Error>> {
+        BlobStorageBuilder::new(path).open(inner).await
+    }
+
+    #[inline]
+    /// The wrapped store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline]
+    /// The directory containing the blob files.
+    pub fn path(&self) -> &Path {
+        self.blobs.path()
+    }
+
+    #[inline]
+    /// The size in bytes at which document values are stored in a blob file.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    #[inline]
+    /// The number of blob files currently referenced by documents.
+    pub fn num_blobs(&self) -> usize {
+        self.blobs.num_blobs()
+    }
+
+    /// Removes every blob file which is no longer referenced by a document.
+    ///
+    /// This is called automatically after tombstones are purged.
+    /// Returns the number of files removed.
+    pub async fn collect_garbage(&self) -> io::Result<usize> {
+        let blobs = self.blobs.clone();
+        tokio::task::spawn_blocking(move || blobs.collect_garbage())
+            .await
+            .expect("Spawn background thread")
+    }
+
+    /// Finds the blob referenced by every document within the inner store.
+    async fn load_references(&self) -> Result<(), BlobStorageError<S::Error>> {
+        let keyspace_list = self
+            .inner
+            .get_keyspace_list()
+            .await
+            .map_err(BlobStorageError::Store)?;
+
+        for keyspace in keyspace_list {
+            let mut stream = self
+                .inner
+                .stream_metadata(&keyspace)
+                .await
+                .map_err(BlobStorageError::Store)?;
+
+            while let Some(chunk) =
+                stream.try_next().await.map_err(BlobStorageError::Store)?
+            {
+                let doc_ids = chunk
+                    .into_iter()
+                    .filter(|(_, _, is_tombstone)| !is_tombstone)
+                    .map(|(doc_id, _, _)| doc_id);
+                let docs = self
+                    .inner
+                    .multi_get(&keyspace, doc_ids)
+                    .await
+                    .map_err(BlobStorageError::Store)?;
+
+                for doc in docs {
+                    if let Some(StoredValue::Blob { id, .. }) =
+                        StoredValue::decode(doc.data())
+                    {
+                        self.blobs.load_reference(&keyspace, doc.id(), id);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Converts the document into the form held by the inner store, writing
+    /// its value to a blob file if it is large enough.
+    async fn store_document(
+        &self,
+        document: Document,
+    ) -> io::Result<(Document, Option<BlobId>)> {
+        if document.data().len() < self.threshold {
+            let value = StoredValue::Inline(document.data()).encode();
+            let stored = Document::new(document.id(), document.last_updated(), value);
+            return Ok((stored, None));
+        }
+
+        let blobs = self.blobs.clone();
+        let id = {
+            let document = document.clone();
+            tokio::task::spawn_blocking(move || blobs.write(document.data()))
+                .await
+                .expect("Spawn background thread")?
+        };
+
+        let value = StoredValue::Blob {
+            id,
+            len: document.data().len() as u64,
+        }
+        .encode();
+        let stored = Document::new(document.id(), document.last_updated(), value);
+        Ok((stored, Some(id)))
+    }
+
+    /// Converts a set of documents into the form held by the inner store.
+    ///
+    /// If any document fails to be written, the references taken to every
+    /// previously written blob are dropped.
+    async fn store_documents(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document>,
+    ) -> io::Result<(Vec<Document>, Vec<(Key, Option<BlobId>)>)> {
+        let mut stored = Vec::new();
+        let mut blobs = Vec::new();
+        for document in documents {
+            let doc_id = document.id();
+            match self.store_document(document).await {
+                Ok((doc, blob)) => {
+                    stored.push(doc);
+                    blobs.push((doc_id, blob));
+                },
+                Err(e) => {
+                    for (doc_id, blob) in blobs {
+                        self.blobs.complete(keyspace, doc_id, blob, false);
+                    }
+                    return Err(e);
+                },
+            }
+        }
+
+        Ok((stored, blobs))
+    }
+
+    /// Converts a document held by the inner store back into its original form.
+    async fn load_document(
+        &self,
+        document: Document,
+    ) -> Result<Document, BlobStorageError<S::Error>> {
+        let (id, len) = match StoredValue::decode(document.data()) {
+            None => return Err(BlobStorageError::InvalidValue(document.id())),
+            Some(StoredValue::Inline(data)) => {
+                return Ok(Document::new(document.id(), document.last_updated(), data))
+            },
+            Some(StoredValue::Blob { id, len }) => (id, len),
+        };
+
+        let blobs = self.blobs.clone();
+        let data = tokio::task::spawn_blocking(move || blobs.read(id, len))
+            .await
+            .expect("Spawn background thread")?
+            .ok_or(BlobStorageError::Corrupted(id))?;
+
+        Ok(Document::new(document.id(), document.last_updated(), data))
+    }
+}
+
+#[async_trait]
+impl<S: Storage> Storage for BlobStorage<S> {
+    type Error = BlobStorageError<S::Error>;
+    type DocsIter = std::vec::IntoIter<Document>;
+    type MetadataIter = S::MetadataIter;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        self.inner
+            .get_keyspace_list()
+            .await
+            .map_err(BlobStorageError::Store)
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        self.inner
+            .iter_metadata(keyspace)
+            .await
+            .map_err(BlobStorageError::Store)
+    }
+
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let stream = self
+            .inner
+            .stream_metadata(keyspace)
+            .await
+            .map_err(BlobStorageError::Store)?;
+        Ok(stream.map_err(BlobStorageError::Store).boxed())
+    }
+
+    async fn stream_metadata_since(
+        &self,
+        keyspace: &str,
+        watermark: HLCTimestamp,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let stream = self
+            .inner
+            .stream_metadata_since(keyspace, watermark)
+            .await
+            .map_err(BlobStorageError::Store)?;
+        Ok(stream.map_err(BlobStorageError::Store).boxed())
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.inner
+            .remove_tombstones(keyspace, keys)
+            .await
+            .map_err(BlobStorageError::from_bulk)?;
+
+        if let Err(e) = self.collect_garbage().await {
+            warn!(error = ?e, "Failed to remove unreferenced blob files after tombstone purge.");
+        }
+
+        Ok(())
+    }
+
+    async fn put_with_ctx(
+        &self,
+        keyspace: &str,
+        document: Document,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), Self::Error> {
+        let doc_id = document.id();
+        let (stored, blob) = self.store_document(document).await?;
+        let res = self.inner.put_with_ctx(keyspace, stored, ctx).await;
+        self.blobs.complete(keyspace, doc_id, blob, res.is_ok());
+        res.map_err(BlobStorageError::Store)
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        self.put_with_ctx(keyspace, document, None).await
+    }
+
+    async fn multi_put_with_ctx(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let (stored, blobs) = self
+            .store_documents(keyspace, documents)
+            .await
+            .map_err(|e| BulkMutationError::empty_with_error(e.into()))?;
+
+        let res = self
+            .inner
+            .multi_put_with_ctx(keyspace, stored.into_iter(), ctx)
+            .await;
+
+        for (doc_id, blob) in blobs {
+            let success = match &res {
+                Ok(()) => true,
+                Err(e) => e.successful_doc_ids().contains(&doc_id),
+            };
+            self.blobs.complete(keyspace, doc_id, blob, success);
+        }
+
+        res.map_err(BlobStorageError::from_bulk)
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.multi_put_with_ctx(keyspace, documents, None).await
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .mark_as_tombstone(keyspace, doc_id, timestamp)
+            .await
+            .map_err(BlobStorageError::Store)?;
+        self.blobs.complete(keyspace, doc_id, None, true);
+        Ok(())
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let doc_ids = documents.collect::<Vec<_>>();
+        let res = self
+            .inner
+            .mark_many_as_tombstone(keyspace, doc_ids.iter().copied())
+            .await;
+
+        let successful_doc_ids = match &res {
+            Ok(()) => doc_ids.iter().map(|doc| doc.id).collect(),
+            Err(e) => e.successful_doc_ids().to_vec(),
+        };
+        for doc_id in successful_doc_ids {
+            self.blobs.complete(keyspace, doc_id, None, true);
+        }
+
+        res.map_err(BlobStorageError::from_bulk)
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        let document = self
+            .inner
+            .get(keyspace, doc_id)
+            .await
+            .map_err(BlobStorageError::Store)?;
+
+        match document {
+            None => Ok(None),
+            Some(document) => self.load_document(document).await.map(Some),
+        }
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        let documents = self
+            .inner
+            .multi_get(keyspace, doc_ids)
+            .await
+            .map_err(BlobStorageError::Store)?
+            .collect::<Vec<_>>();
+
+        let mut loaded = Vec::with_capacity(documents.len());
+        for document in documents {
+            loaded.push(self.load_document(document).await?);
+        }
+
+        Ok(loaded.into_iter())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .put_state_snapshot(keyspace, snapshot)
+            .await
+            .map_err(BlobStorageError::Store)
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        self.inner
+            .get_state_snapshot(keyspace)
+            .await
+            .map_err(BlobStorageError::Store)
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.inner
+            .remove_state_snapshot(keyspace)
+            .await
+            .map_err(BlobStorageError::Store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use datacake_eventual_consistency::test_suite::run_test_suite;
+    use datacake_eventual_consistency::test_utils::MemStore;
+    use uuid::Uuid;
+
+    use super::*;
+
+    static KEYSPACE: &str = "blobs";
+
+    async fn open_store(path: &Path, inner: MemStore) -> BlobStorage<MemStore> {
+        BlobStorageBuilder::new(path)
+            .with_threshold(64)
+            .open(inner)
+            .await
+            .expect("Open store")
+    }
+
+    fn num_files(path: &Path) -> usize {
+        std::fs::read_dir(path)
+            .unwrap()
+            .map(|shard| std::fs::read_dir(shard.unwrap().path()).unwrap().count())
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn test_storage_logic() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        let storage = BlobStorageBuilder::new(path)
+            .with_threshold(8)
+            .open(MemStore::default())
+            .await
+            .expect("Open store");
+        run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_large_documents_stored_as_blobs() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        let storage = open_store(&path, MemStore::default()).await;
+
+        let large = Document::new(1, HLCTimestamp::from_u64(1), vec![1; 4096]);
+        let small = Document::new(2, HLCTimestamp::from_u64(2), b"small".to_vec());
+        storage
+            .multi_put(KEYSPACE, [large.clone(), small.clone()].into_iter())
+            .await
+            .unwrap();
+
+        let raw = storage.inner().get(KEYSPACE, 1).await.unwrap().unwrap();
+        assert!(
+            raw.data().len() < 64,
+            "Inner store should only hold a reference."
+        );
+        let raw = storage.inner().get(KEYSPACE, 2).await.unwrap().unwrap();
+        assert_eq!(&raw.data()[1..], b"small");
+
+        assert_eq!(storage.num_blobs(), 1);
+        assert_eq!(num_files(&path), 1);
+
+        let docs = storage
+            .multi_get(KEYSPACE, [1, 2].into_iter())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(docs, vec![large.clone(), small]);
+
+        let duplicate = Document::new(3, HLCTimestamp::from_u64(3), vec![1; 4096]);
+        storage.put(KEYSPACE, duplicate.clone()).await.unwrap();
+        assert_eq!(
+            storage.num_blobs(),
+            1,
+            "Identical values should share a blob."
+        );
+        assert_eq!(
+            storage.get(KEYSPACE, 3).await.unwrap(),
+            Some(duplicate.clone())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collection() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        let storage = open_store(&path, MemStore::default()).await;
+
+        let docs = (0..3u8).map(|id| {
+            Document::new(id as Key, HLCTimestamp::from_u64(id as u64), vec![id; 4096])
+        });
+        storage.multi_put(KEYSPACE, docs).await.unwrap();
+        assert_eq!(num_files(&path), 3);
+
+        // Overwriting a document with a small value orphans its blob.
+        storage
+            .put(
+                KEYSPACE,
+                Document::new(0, HLCTimestamp::from_u64(10), b"small".to_vec()),
+            )
+            .await
+            .unwrap();
+        storage
+            .mark_as_tombstone(KEYSPACE, 1, HLCTimestamp::from_u64(11))
+            .await
+            .unwrap();
+        assert_eq!(storage.num_blobs(), 1);
+        assert_eq!(
+            num_files(&path),
+            3,
+            "Files should remain until tombstones are purged."
+        );
+
+        storage
+            .remove_tombstones(KEYSPACE, [1].into_iter())
+            .await
+            .unwrap();
+        assert_eq!(num_files(&path), 1);
+        assert_eq!(
+            storage.get(KEYSPACE, 2).await.unwrap(),
+            Some(Document::new(2, HLCTimestamp::from_u64(2), vec![2; 4096])),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reopen_rebuilds_references() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        let storage = open_store(&path, MemStore::default()).await;
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(1), vec![1; 4096]);
+        storage.put(KEYSPACE, doc.clone()).await.unwrap();
+        let BlobStorage { inner, blobs, .. } = storage;
+
+        // A blob written without a reference, as if the process crashed
+        // before the inner store was written to.
+        let orphan = blobs.write(&[2; 4096]).unwrap();
+        std::fs::write(blobs.blob_path(orphan).with_extension("0.tmp"), b"partial")
+            .unwrap();
+        drop(blobs);
+        assert_eq!(num_files(&path), 3);
+
+        let storage = open_store(&path, inner).await;
+        assert_eq!(storage.num_blobs(), 1);
+        assert_eq!(num_files(&path), 2, "Partial files should be removed.");
+        assert_eq!(storage.collect_garbage().await.unwrap(), 1);
+        assert_eq!(storage.get(KEYSPACE, 1).await.unwrap(), Some(doc));
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_blob() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        let storage = open_store(&path, MemStore::default()).await;
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(1), vec![1; 4096]);
+        storage.put(KEYSPACE, doc).await.unwrap();
+        std::fs::write(storage.blobs.blob_path(BlobId::of(&[1; 4096])), [2; 4096])
+            .unwrap();
+
+        let err = storage
+            .get(KEYSPACE, 1)
+            .await
+            .expect_err("Corrupted blob should be detected");
+        assert!(matches!(err, BlobStorageError::Corrupted(_)), "{err:?}");
+    }
+}
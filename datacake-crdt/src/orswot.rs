@@ -600,19 +600,19 @@ mod tests {
         );
 
         assert!(
-            node_a_set.entries.get(&1).is_some(),
+            node_a_set.entries.contains_key(&1),
             "Expected entry with key 1 to exist."
         );
         assert!(
-            node_a_set.entries.get(&2).is_some(),
+            node_a_set.entries.contains_key(&2),
             "Expected entry with key 2 to exist."
         );
         assert!(
-            node_a_set.entries.get(&3).is_some(),
+            node_a_set.entries.contains_key(&3),
             "Expected entry with key 3 to exist."
         );
         assert!(
-            node_a_set.entries.get(&4).is_some(),
+            node_a_set.entries.contains_key(&4),
             "Expected entry with key 4 to exist."
         );
     }
@@ -651,15 +651,15 @@ mod tests {
         );
 
         assert!(
-            node_a_set.entries.get(&1).is_some(),
+            node_a_set.entries.contains_key(&1),
             "SET A: Expected entry with key 1 to exist."
         );
         assert!(
-            node_a_set.entries.get(&2).is_some(),
+            node_a_set.entries.contains_key(&2),
             "SET A: Expected entry with key 2 to exist."
         );
         assert!(
-            node_a_set.entries.get(&3).is_none(),
+            !node_a_set.entries.contains_key(&3),
             "SET A: Expected entry with key 3 to NOT exist."
         );
 
@@ -672,15 +672,15 @@ mod tests {
         );
 
         assert!(
-            node_b_set.entries.get(&1).is_some(),
+            node_b_set.entries.contains_key(&1),
             "SET B: Expected entry with key 1 to exist."
         );
         assert!(
-            node_b_set.entries.get(&2).is_some(),
+            node_b_set.entries.contains_key(&2),
             "SET B: Expected entry with key 2 to exist."
         );
         assert!(
-            node_b_set.entries.get(&3).is_none(),
+            !node_b_set.entries.contains_key(&3),
             "SET B: Expected entry with key 3 to NOT exist."
         );
     }
@@ -717,15 +717,15 @@ mod tests {
         );
 
         assert!(
-            node_a_set.entries.get(&1).is_some(),
+            node_a_set.entries.contains_key(&1),
             "Expected entry with key 1 to exist."
         );
         assert!(
-            node_a_set.entries.get(&2).is_some(),
+            node_a_set.entries.contains_key(&2),
             "Expected entry with key 2 to exist."
         );
         assert!(
-            node_a_set.entries.get(&3).is_none(),
+            !node_a_set.entries.contains_key(&3),
             "Expected entry with key 3 to NOT exist."
         );
     }
@@ -770,19 +770,19 @@ mod tests {
         );
 
         assert!(
-            node_a_set.entries.get(&1).is_some(),
+            node_a_set.entries.contains_key(&1),
             "Expected entry with key 1 to exist."
         );
         assert!(
-            node_a_set.entries.get(&2).is_some(),
+            node_a_set.entries.contains_key(&2),
             "Expected entry with key 2 to exist."
         );
         assert!(
-            node_a_set.entries.get(&3).is_none(),
+            !node_a_set.entries.contains_key(&3),
             "Expected entry with key 3 to NOT exist."
         );
         assert!(
-            node_a_set.entries.get(&4).is_some(),
+            node_a_set.entries.contains_key(&4),
             "Expected entry with key 4 to exist."
         );
     }
@@ -832,54 +832,54 @@ mod tests {
         node_a_set.purge_old_deletes();
 
         assert!(
-            node_a_set.dead.get(&3).is_some(),
+            node_a_set.dead.contains_key(&3),
             "SET A: Expected key 3 to be left in dead set."
         );
         assert!(
-            node_a_set.dead.get(&2).is_none(),
+            !node_a_set.dead.contains_key(&2),
             "SET A: Expected key 2 to be purged from dead set."
         );
 
         assert!(
-            node_a_set.entries.get(&1).is_some(),
+            node_a_set.entries.contains_key(&1),
             "SET A: Expected entry with key 1 to exist."
         );
         assert!(
-            node_a_set.entries.get(&2).is_none(),
+            !node_a_set.entries.contains_key(&2),
             "SET A: Expected entry with key 2 to exist."
         );
         assert!(
-            node_a_set.entries.get(&3).is_none(),
+            !node_a_set.entries.contains_key(&3),
             "SET A: Expected entry with key 3 to NOT exist."
         );
         assert!(
-            node_a_set.entries.get(&4).is_some(),
+            node_a_set.entries.contains_key(&4),
             "SET A: Expected entry with key 4 to exist."
         );
 
         assert!(
-            node_b_set.dead.get(&3).is_some(),
+            node_b_set.dead.contains_key(&3),
             "SET B: Expected key 3 to be left in dead set."
         );
         assert!(
-            node_b_set.dead.get(&2).is_none(),
+            !node_b_set.dead.contains_key(&2),
             "SET B: Expected key 2 to be purged from dead set."
         );
 
         assert!(
-            node_b_set.entries.get(&1).is_some(),
+            node_b_set.entries.contains_key(&1),
             "SET B: Expected entry with key 1 to exist."
         );
         assert!(
-            node_b_set.entries.get(&2).is_none(),
+            !node_b_set.entries.contains_key(&2),
             "SET B: Expected entry with key 2 to exist."
         );
         assert!(
-            node_b_set.entries.get(&3).is_none(),
+            !node_b_set.entries.contains_key(&3),
             "SET B: Expected entry with key 3 to NOT exist."
         );
         assert!(
-            node_b_set.entries.get(&4).is_some(),
+            node_b_set.entries.contains_key(&4),
             "SET B: Expected entry with key 4 to exist."
         );
     }
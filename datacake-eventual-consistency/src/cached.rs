@@ -0,0 +1,456 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use datacake_crdt::{HLCTimestamp, Key};
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::storage::{MetadataStream, StateSnapshot};
+use crate::{BulkMutationError, Document, DocumentMetadata, PutContext, Storage};
+
+type CacheKey = (String, Key);
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// A point in time view of the cache's hit and miss counters.
+pub struct CacheStats {
+    /// The number of documents served from the cache.
+    pub hits: u64,
+    /// The number of documents which had to be fetched from the inner store.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// The fraction of lookups served from the cache.
+    ///
+    /// Returns `0.0` if no lookups have happened yet.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+/// A [Storage] wrapper which keeps recently read documents in a bounded LRU cache.
+///
+/// Reads are served from the cache where possible, falling back to the inner store
+/// and populating the cache with the result. Any mutation of a document removes it
+/// from the cache, so the cache never serves a value older than the inner store.
+///
+/// Only existing documents are cached, lookups of missing or deleted documents
+/// always reach the inner store.
+pub struct Cached<S: Storage> {
+    inner: S,
+    cache: Mutex<LruCache<CacheKey, Document>>,
+    /// Incremented by every mutation so reads racing with a write do not
+    /// re-populate the cache with the value they read before the write completed.
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<S: Storage> Cached<S> {
+    /// Wraps the given store, caching at most `capacity` documents.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is `0`.
+    pub fn new(inner: S, capacity: usize) -> Self {
+        let capacity =
+            NonZeroUsize::new(capacity).expect("Cache capacity must be non-zero.");
+
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(capacity)),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    /// The wrapped store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline]
+    /// The hit and miss counters of the cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    #[inline]
+    /// The number of documents currently cached.
+    pub fn len(&self) -> usize {
+        self.cache.lock().len()
+    }
+
+    #[inline]
+    /// If the cache is currently empty.
+    pub fn is_empty(&self) -> bool {
+        self.cache.lock().is_empty()
+    }
+
+    #[inline]
+    /// The maximum number of documents the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.cache.lock().cap().get()
+    }
+
+    /// Removes every document from the cache.
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.cache.lock().clear();
+    }
+
+    /// Removes the given documents from the cache.
+    ///
+    /// This must be called *after* the inner store has been mutated.
+    fn invalidate(&self, keyspace: &str, doc_ids: impl Iterator<Item = Key>) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        let mut cache = self.cache.lock();
+        for doc_id in doc_ids {
+            cache.pop(&(keyspace.to_string(), doc_id));
+        }
+    }
+
+    /// Inserts the given documents into the cache if no mutation has
+    /// happened since `generation` was observed.
+    fn populate(
+        &self,
+        keyspace: &str,
+        generation: u64,
+        docs: impl Iterator<Item = Document>,
+    ) {
+        let mut cache = self.cache.lock();
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+
+        for doc in docs {
+            cache.put((keyspace.to_string(), doc.id()), doc);
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Storage> Storage for Cached<S> {
+    type Error = S::Error;
+    type DocsIter = std::vec::IntoIter<Document>;
+    type MetadataIter = S::MetadataIter;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        self.inner.get_keyspace_list().await
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        self.inner.iter_metadata(keyspace).await
+    }
+
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        self.inner.stream_metadata(keyspace).await
+    }
+
+    async fn stream_metadata_since(
+        &self,
+        keyspace: &str,
+        watermark: HLCTimestamp,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        self.inner.stream_metadata_since(keyspace, watermark).await
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let keys = keys.collect::<Vec<_>>();
+        let res = self
+            .inner
+            .remove_tombstones(keyspace, keys.iter().copied())
+            .await;
+        self.invalidate(keyspace, keys.into_iter());
+        res
+    }
+
+    async fn put_with_ctx(
+        &self,
+        keyspace: &str,
+        document: Document,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), Self::Error> {
+        let doc_id = document.id();
+        let res = self.inner.put_with_ctx(keyspace, document, ctx).await;
+        self.invalidate(keyspace, std::iter::once(doc_id));
+        res
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        let doc_id = document.id();
+        let res = self.inner.put(keyspace, document).await;
+        self.invalidate(keyspace, std::iter::once(doc_id));
+        res
+    }
+
+    async fn multi_put_with_ctx(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let documents = documents.collect::<Vec<_>>();
+        let doc_ids = documents.iter().map(|doc| doc.id()).collect::<Vec<_>>();
+        let res = self
+            .inner
+            .multi_put_with_ctx(keyspace, documents.into_iter(), ctx)
+            .await;
+        self.invalidate(keyspace, doc_ids.into_iter());
+        res
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let documents = documents.collect::<Vec<_>>();
+        let doc_ids = documents.iter().map(|doc| doc.id()).collect::<Vec<_>>();
+        let res = self.inner.multi_put(keyspace, documents.into_iter()).await;
+        self.invalidate(keyspace, doc_ids.into_iter());
+        res
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        let res = self
+            .inner
+            .mark_as_tombstone(keyspace, doc_id, timestamp)
+            .await;
+        self.invalidate(keyspace, std::iter::once(doc_id));
+        res
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let documents = documents.collect::<Vec<_>>();
+        let doc_ids = documents.iter().map(|doc| doc.id).collect::<Vec<_>>();
+        let res = self
+            .inner
+            .mark_many_as_tombstone(keyspace, documents.into_iter())
+            .await;
+        self.invalidate(keyspace, doc_ids.into_iter());
+        res
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        let generation = {
+            let mut cache = self.cache.lock();
+            if let Some(doc) = cache.get(&(keyspace.to_string(), doc_id)) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(doc.clone()));
+            }
+            self.generation.load(Ordering::SeqCst)
+        };
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let doc = self.inner.get(keyspace, doc_id).await?;
+        if let Some(doc) = doc.as_ref() {
+            self.populate(keyspace, generation, std::iter::once(doc.clone()));
+        }
+
+        Ok(doc)
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        let mut docs = Vec::new();
+        let mut missing = Vec::new();
+        let generation = {
+            let mut cache = self.cache.lock();
+            for doc_id in doc_ids {
+                match cache.get(&(keyspace.to_string(), doc_id)) {
+                    Some(doc) => docs.push(doc.clone()),
+                    None => missing.push(doc_id),
+                }
+            }
+            self.generation.load(Ordering::SeqCst)
+        };
+
+        self.hits.fetch_add(docs.len() as u64, Ordering::Relaxed);
+        if missing.is_empty() {
+            return Ok(docs.into_iter());
+        }
+
+        self.misses
+            .fetch_add(missing.len() as u64, Ordering::Relaxed);
+        let fetched = self
+            .inner
+            .multi_get(keyspace, missing.into_iter())
+            .await?
+            .collect::<Vec<_>>();
+        self.populate(keyspace, generation, fetched.iter().cloned());
+        docs.extend(fetched);
+
+        Ok(docs.into_iter())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.inner.put_state_snapshot(keyspace, snapshot).await
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        self.inner.get_state_snapshot(keyspace).await
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.inner.remove_state_snapshot(keyspace).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_suite::run_test_suite;
+    use crate::test_utils::MemStore;
+
+    static KEYSPACE: &str = "my-keyspace";
+
+    #[tokio::test]
+    async fn test_cached_storage_suite() {
+        run_test_suite(Cached::new(MemStore::default(), 64)).await;
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_misses() {
+        let storage = Cached::new(MemStore::default(), 2);
+        let doc = Document::new(1, HLCTimestamp::now(0, 0), b"Hello".to_vec());
+        storage.put(KEYSPACE, doc.clone()).await.unwrap();
+
+        assert!(storage.is_empty(), "Writes should not populate the cache.");
+        assert_eq!(storage.get(KEYSPACE, 1).await.unwrap(), Some(doc.clone()));
+        assert_eq!(storage.get(KEYSPACE, 1).await.unwrap(), Some(doc.clone()));
+        assert_eq!(storage.stats(), CacheStats { hits: 1, misses: 1 });
+
+        assert_eq!(storage.get(KEYSPACE, 2).await.unwrap(), None);
+        assert_eq!(storage.get(KEYSPACE, 2).await.unwrap(), None);
+        assert_eq!(
+            storage.stats(),
+            CacheStats { hits: 1, misses: 3 },
+            "Missing documents should not be cached.",
+        );
+
+        let docs = storage
+            .multi_get(KEYSPACE, [1, 2].into_iter())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(docs, vec![doc]);
+        assert_eq!(storage.stats(), CacheStats { hits: 2, misses: 4 });
+        assert_eq!(storage.stats().hit_rate(), 1.0 / 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_mutations_invalidate_cache() {
+        let storage = Cached::new(MemStore::default(), 16);
+        let docs = (1..=3)
+            .map(|id| Document::new(id, HLCTimestamp::now(0, 0), b"old".to_vec()))
+            .collect::<Vec<_>>();
+        storage
+            .multi_put(KEYSPACE, docs.clone().into_iter())
+            .await
+            .unwrap();
+        storage
+            .multi_get(KEYSPACE, [1, 2, 3].into_iter())
+            .await
+            .unwrap();
+        assert_eq!(storage.len(), 3);
+
+        let updated = Document::new(1, HLCTimestamp::now(1, 0), b"new".to_vec());
+        storage.put(KEYSPACE, updated.clone()).await.unwrap();
+        assert_eq!(storage.get(KEYSPACE, 1).await.unwrap(), Some(updated));
+
+        let updated = Document::new(2, HLCTimestamp::now(1, 0), b"new".to_vec());
+        storage
+            .multi_put(KEYSPACE, [updated.clone()].into_iter())
+            .await
+            .unwrap();
+        assert_eq!(storage.get(KEYSPACE, 2).await.unwrap(), Some(updated));
+
+        storage
+            .mark_as_tombstone(KEYSPACE, 3, HLCTimestamp::now(2, 0))
+            .await
+            .unwrap();
+        assert_eq!(storage.get(KEYSPACE, 3).await.unwrap(), None);
+
+        let deleted = DocumentMetadata::new(1, HLCTimestamp::now(3, 0));
+        storage
+            .mark_many_as_tombstone(KEYSPACE, [deleted].into_iter())
+            .await
+            .unwrap();
+        assert_eq!(storage.get(KEYSPACE, 1).await.unwrap(), None);
+
+        assert_eq!(
+            storage.get("other-keyspace", 2).await.unwrap(),
+            None,
+            "Cached entries should be scoped to their keyspace.",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_least_recently_used() {
+        let storage = Cached::new(MemStore::default(), 2);
+        let docs = (1..=3)
+            .map(|id| Document::new(id, HLCTimestamp::now(0, 0), Vec::new()))
+            .collect::<Vec<_>>();
+        storage.multi_put(KEYSPACE, docs.into_iter()).await.unwrap();
+
+        storage.get(KEYSPACE, 1).await.unwrap();
+        storage.get(KEYSPACE, 2).await.unwrap();
+        storage.get(KEYSPACE, 1).await.unwrap();
+        storage.get(KEYSPACE, 3).await.unwrap();
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.capacity(), 2);
+
+        storage.get(KEYSPACE, 1).await.unwrap();
+        storage.get(KEYSPACE, 2).await.unwrap();
+        assert_eq!(
+            storage.stats(),
+            CacheStats { hits: 2, misses: 4 },
+            "Document 2 should have been evicted.",
+        );
+    }
+}
@@ -0,0 +1,712 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use datacake_crdt::{HLCTimestamp, Key};
+use futures::{StreamExt, TryStreamExt};
+use parking_lot::RwLock;
+use rand::RngCore;
+use tokio::sync::RwLockWriteGuard;
+
+use crate::storage::{MetadataStream, StateSnapshot};
+use crate::{BulkMutationError, Document, DocumentMetadata, PutContext, Storage};
+
+/// The version of the encrypted value layout.
+const FORMAT_VERSION: u8 = 1;
+/// The length of the random nonce generated for each value.
+const NONCE_LEN: usize = 24;
+/// The length of the header prefixed to each encrypted value.
+///
+/// The header contains the format version, the ID of the key and the nonce.
+const HEADER_LEN: usize = 1 + 4 + NONCE_LEN;
+
+/// The unique ID of an [EncryptionKey] within a [KeyProvider].
+///
+/// The ID is stored alongside each encrypted value so the value can still be
+/// decrypted once the key has been rotated.
+pub type KeyId = u32;
+
+#[derive(Clone, PartialEq, Eq)]
+/// A 256-bit key used to encrypt document data.
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Creates a key from the given raw bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a new random key.
+    pub fn generate() -> Self {
+        let mut bytes = [0; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    #[inline]
+    /// The raw bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// A source of the keys used by an [Encrypted] store.
+///
+/// Providers are called for every document read or written, so any keys
+/// fetched from an external service should be cached by the provider.
+pub trait KeyProvider: Send + Sync + 'static {
+    /// The key which new documents are encrypted with, along with its ID.
+    fn current_key(&self) -> (KeyId, EncryptionKey);
+
+    /// Retrieves the key with the given ID.
+    ///
+    /// Keys must remain available for as long as any document is encrypted with them.
+    fn get_key(&self, key_id: KeyId) -> Option<EncryptionKey>;
+}
+
+impl<T: KeyProvider> KeyProvider for Arc<T> {
+    fn current_key(&self) -> (KeyId, EncryptionKey) {
+        self.as_ref().current_key()
+    }
+
+    fn get_key(&self, key_id: KeyId) -> Option<EncryptionKey> {
+        self.as_ref().get_key(key_id)
+    }
+}
+
+/// A [KeyProvider] holding a set of keys in memory.
+///
+/// The ring can be shared with the store via an `Arc` in order to rotate keys
+/// while the store is running.
+pub struct KeyRing {
+    state: RwLock<KeyRingState>,
+}
+
+struct KeyRingState {
+    current: KeyId,
+    keys: HashMap<KeyId, EncryptionKey>,
+}
+
+impl KeyRing {
+    /// Creates a new key ring using the given key to encrypt documents.
+    pub fn new(key_id: KeyId, key: EncryptionKey) -> Self {
+        Self {
+            state: RwLock::new(KeyRingState {
+                current: key_id,
+                keys: HashMap::from_iter([(key_id, key)]),
+            }),
+        }
+    }
+
+    /// Adds a key which documents can be decrypted with, but are not encrypted with.
+    ///
+    /// If a key with the same ID already exists, it is replaced.
+    pub fn add_key(&self, key_id: KeyId, key: EncryptionKey) {
+        self.state.write().keys.insert(key_id, key);
+    }
+
+    /// Adds a key and uses it to encrypt all new documents.
+    ///
+    /// Existing documents are re-encrypted with the new key as they are read, or
+    /// all at once with [Encrypted::reencrypt_keyspace].
+    pub fn rotate(&self, key_id: KeyId, key: EncryptionKey) {
+        let mut state = self.state.write();
+        state.keys.insert(key_id, key);
+        state.current = key_id;
+    }
+
+    /// Removes a key which is no longer used by any document.
+    ///
+    /// Returns `false` if the key is the current key, which cannot be removed.
+    pub fn remove_key(&self, key_id: KeyId) -> bool {
+        let mut state = self.state.write();
+        if state.current == key_id {
+            return false;
+        }
+        state.keys.remove(&key_id);
+        true
+    }
+
+    #[inline]
+    /// The ID of the key which new documents are encrypted with.
+    pub fn current_key_id(&self) -> KeyId {
+        self.state.read().current
+    }
+}
+
+impl KeyProvider for KeyRing {
+    fn current_key(&self) -> (KeyId, EncryptionKey) {
+        let state = self.state.read();
+        let key = state
+            .keys
+            .get(&state.current)
+            .cloned()
+            .expect("Current key should always exist");
+        (state.current, key)
+    }
+
+    fn get_key(&self, key_id: KeyId) -> Option<EncryptionKey> {
+        self.state.read().keys.get(&key_id).cloned()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// An error which can occur while accessing an [Encrypted] store.
+pub enum EncryptionError<E>
+where
+    E: Error + Send + 'static,
+{
+    #[error("{0}")]
+    /// The inner store failed to complete the operation.
+    Store(E),
+    #[error("The key {0} used to encrypt the document is not available.")]
+    /// The key provider does not have the key the document was encrypted with.
+    UnknownKey(KeyId),
+    #[error("Failed to encrypt or decrypt document {0}.")]
+    /// The document could not be authenticated, either the data has been tampered
+    /// with or the key is incorrect.
+    Cipher(Key),
+    #[error("The value of document {0} is not encrypted.")]
+    /// The inner store holds a value which was not written by an [Encrypted] store.
+    InvalidValue(Key),
+}
+
+impl<E> EncryptionError<E>
+where
+    E: Error + Send + 'static,
+{
+    fn from_bulk(error: BulkMutationError<E>) -> BulkMutationError<Self> {
+        let successful_doc_ids = error.successful_doc_ids().to_vec();
+        BulkMutationError::new(Self::Store(error.into_inner()), successful_doc_ids)
+    }
+}
+
+/// A [Storage] wrapper which encrypts document data before it reaches the inner store.
+///
+/// Data is encrypted with XChaCha20-Poly1305 using the current key of the [KeyProvider],
+/// with the keyspace, document ID and timestamp authenticated alongside it so encrypted
+/// values cannot be swapped between documents. Document metadata, including timestamps
+/// and tombstones, is passed through unchanged so replication is unaffected.
+///
+/// Documents encrypted with a key other than the current key are lazily re-encrypted
+/// with the current key when they are read.
+///
+/// All writes to the inner store must go through the wrapper.
+pub struct Encrypted<S: Storage, K: KeyProvider = KeyRing> {
+    inner: S,
+    keys: K,
+    /// Held exclusively while documents are re-encrypted so a document is never
+    /// replaced with an older version which was read before a write.
+    rotation_lock: tokio::sync::RwLock<()>,
+}
+
+impl<S: Storage, K: KeyProvider> Encrypted<S, K> {
+    /// Wraps the given store, encrypting documents with keys from the given provider.
+    pub fn new(inner: S, keys: K) -> Self {
+        Self {
+            inner,
+            keys,
+            rotation_lock: tokio::sync::RwLock::new(()),
+        }
+    }
+
+    #[inline]
+    /// The wrapped store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline]
+    /// The key provider used by the store.
+    pub fn keys(&self) -> &K {
+        &self.keys
+    }
+
+    /// Re-encrypts every document in the keyspace which is not encrypted with the
+    /// current key, returning the number of documents re-encrypted.
+    ///
+    /// Once this has completed for every keyspace, old keys can be removed from the provider.
+    /// Writes are paused while each chunk of documents is re-encrypted.
+    pub async fn reencrypt_keyspace(
+        &self,
+        keyspace: &str,
+    ) -> Result<u64, EncryptionError<S::Error>> {
+        let mut stream = self
+            .inner
+            .stream_metadata(keyspace)
+            .await
+            .map_err(EncryptionError::Store)?;
+
+        let mut num_reencrypted = 0;
+        while let Some(chunk) =
+            stream.try_next().await.map_err(EncryptionError::Store)?
+        {
+            let doc_ids = chunk
+                .into_iter()
+                .filter(|(_, _, is_tombstone)| !is_tombstone)
+                .map(|(doc_id, _, _)| doc_id)
+                .collect::<Vec<_>>();
+
+            let guard = self.rotation_lock.write().await;
+            num_reencrypted += self.reencrypt(keyspace, doc_ids, guard).await?;
+        }
+
+        Ok(num_reencrypted)
+    }
+
+    /// Re-encrypts the given documents with the current key if they are still
+    /// encrypted with an older key.
+    async fn reencrypt(
+        &self,
+        keyspace: &str,
+        doc_ids: Vec<Key>,
+        _guard: RwLockWriteGuard<'_, ()>,
+    ) -> Result<u64, EncryptionError<S::Error>> {
+        let (current_key_id, _) = self.keys.current_key();
+        let docs = self
+            .inner
+            .multi_get(keyspace, doc_ids.into_iter())
+            .await
+            .map_err(EncryptionError::Store)?;
+
+        let mut reencrypted = Vec::new();
+        for doc in docs {
+            let (doc, key_id) = self.decrypt(keyspace, doc)?;
+            if key_id != current_key_id {
+                reencrypted.push(self.encrypt(keyspace, &doc)?);
+            }
+        }
+
+        let num_reencrypted = reencrypted.len() as u64;
+        if num_reencrypted > 0 {
+            self.inner
+                .multi_put(keyspace, reencrypted.into_iter())
+                .await
+                .map_err(|e| EncryptionError::Store(e.into_inner()))?;
+        }
+
+        Ok(num_reencrypted)
+    }
+
+    /// Re-encrypts the given documents unless a write is in progress, in which
+    /// case they are left to be re-encrypted on a later read.
+    async fn reencrypt_stale(&self, keyspace: &str, doc_ids: Vec<Key>) {
+        if doc_ids.is_empty() {
+            return;
+        }
+
+        let guard = match self.rotation_lock.try_write() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+
+        if let Err(e) = self.reencrypt(keyspace, doc_ids, guard).await {
+            warn!(keyspace = keyspace, error = ?e, "Failed to re-encrypt documents with the current key.");
+        }
+    }
+
+    fn encrypt(
+        &self,
+        keyspace: &str,
+        doc: &Document,
+    ) -> Result<Document, EncryptionError<S::Error>> {
+        let (key_id, key) = self.keys.current_key();
+
+        let mut header = [0; HEADER_LEN];
+        header[0] = FORMAT_VERSION;
+        header[1..5].copy_from_slice(&key_id.to_le_bytes());
+        rand::thread_rng().fill_bytes(&mut header[5..]);
+
+        let aad = associated_data(keyspace, doc.id(), doc.last_updated(), &header);
+        let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&header[5..]),
+                Payload {
+                    msg: doc.data(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| EncryptionError::Cipher(doc.id()))?;
+
+        let mut data = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&ciphertext);
+        Ok(Document::new(doc.id(), doc.last_updated(), data))
+    }
+
+    /// Decrypts the document, returning the ID of the key it was encrypted with.
+    fn decrypt(
+        &self,
+        keyspace: &str,
+        doc: Document,
+    ) -> Result<(Document, KeyId), EncryptionError<S::Error>> {
+        let data = doc.data();
+        if data.len() < HEADER_LEN || data[0] != FORMAT_VERSION {
+            return Err(EncryptionError::InvalidValue(doc.id()));
+        }
+
+        let (header, ciphertext) = data.split_at(HEADER_LEN);
+        let key_id = KeyId::from_le_bytes(header[1..5].try_into().unwrap());
+        let key = self
+            .keys
+            .get_key(key_id)
+            .ok_or(EncryptionError::UnknownKey(key_id))?;
+
+        let aad = associated_data(keyspace, doc.id(), doc.last_updated(), header);
+        let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
+        let plaintext = cipher
+            .decrypt(
+                XNonce::from_slice(&header[5..]),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| EncryptionError::Cipher(doc.id()))?;
+
+        Ok((
+            Document::new(doc.id(), doc.last_updated(), plaintext),
+            key_id,
+        ))
+    }
+}
+
+/// The data authenticated alongside each document's value.
+fn associated_data(
+    keyspace: &str,
+    doc_id: Key,
+    last_updated: HLCTimestamp,
+    header: &[u8],
+) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + 16 + keyspace.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(&doc_id.to_le_bytes());
+    aad.extend_from_slice(&last_updated.as_u64().to_le_bytes());
+    aad.extend_from_slice(keyspace.as_bytes());
+    aad
+}
+
+#[async_trait]
+impl<S: Storage, K: KeyProvider> Storage for Encrypted<S, K> {
+    type Error = EncryptionError<S::Error>;
+    type DocsIter = std::vec::IntoIter<Document>;
+    type MetadataIter = S::MetadataIter;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        self.inner
+            .get_keyspace_list()
+            .await
+            .map_err(EncryptionError::Store)
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        self.inner
+            .iter_metadata(keyspace)
+            .await
+            .map_err(EncryptionError::Store)
+    }
+
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let stream = self
+            .inner
+            .stream_metadata(keyspace)
+            .await
+            .map_err(EncryptionError::Store)?;
+        Ok(stream.map_err(EncryptionError::Store).boxed())
+    }
+
+    async fn stream_metadata_since(
+        &self,
+        keyspace: &str,
+        watermark: HLCTimestamp,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let stream = self
+            .inner
+            .stream_metadata_since(keyspace, watermark)
+            .await
+            .map_err(EncryptionError::Store)?;
+        Ok(stream.map_err(EncryptionError::Store).boxed())
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.inner
+            .remove_tombstones(keyspace, keys)
+            .await
+            .map_err(EncryptionError::from_bulk)
+    }
+
+    async fn put_with_ctx(
+        &self,
+        keyspace: &str,
+        document: Document,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), Self::Error> {
+        let document = self.encrypt(keyspace, &document)?;
+        let _guard = self.rotation_lock.read().await;
+        self.inner
+            .put_with_ctx(keyspace, document, ctx)
+            .await
+            .map_err(EncryptionError::Store)
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        self.put_with_ctx(keyspace, document, None).await
+    }
+
+    async fn multi_put_with_ctx(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let documents = documents
+            .map(|doc| self.encrypt(keyspace, &doc))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(BulkMutationError::empty_with_error)?;
+
+        let _guard = self.rotation_lock.read().await;
+        self.inner
+            .multi_put_with_ctx(keyspace, documents.into_iter(), ctx)
+            .await
+            .map_err(EncryptionError::from_bulk)
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.multi_put_with_ctx(keyspace, documents, None).await
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        let _guard = self.rotation_lock.read().await;
+        self.inner
+            .mark_as_tombstone(keyspace, doc_id, timestamp)
+            .await
+            .map_err(EncryptionError::Store)
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let _guard = self.rotation_lock.read().await;
+        self.inner
+            .mark_many_as_tombstone(keyspace, documents)
+            .await
+            .map_err(EncryptionError::from_bulk)
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        let doc = match self
+            .inner
+            .get(keyspace, doc_id)
+            .await
+            .map_err(EncryptionError::Store)?
+        {
+            None => return Ok(None),
+            Some(doc) => doc,
+        };
+
+        let (doc, key_id) = self.decrypt(keyspace, doc)?;
+        if key_id != self.keys.current_key().0 {
+            self.reencrypt_stale(keyspace, vec![doc_id]).await;
+        }
+
+        Ok(Some(doc))
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        let (current_key_id, _) = self.keys.current_key();
+        let encrypted = self
+            .inner
+            .multi_get(keyspace, doc_ids)
+            .await
+            .map_err(EncryptionError::Store)?;
+
+        let mut docs = Vec::new();
+        let mut stale = Vec::new();
+        for doc in encrypted {
+            let (doc, key_id) = self.decrypt(keyspace, doc)?;
+            if key_id != current_key_id {
+                stale.push(doc.id());
+            }
+            docs.push(doc);
+        }
+        self.reencrypt_stale(keyspace, stale).await;
+
+        Ok(docs.into_iter())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .put_state_snapshot(keyspace, snapshot)
+            .await
+            .map_err(EncryptionError::Store)
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        self.inner
+            .get_state_snapshot(keyspace)
+            .await
+            .map_err(EncryptionError::Store)
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.inner
+            .remove_state_snapshot(keyspace)
+            .await
+            .map_err(EncryptionError::Store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_suite::run_test_suite;
+    use crate::test_utils::MemStore;
+
+    static KEYSPACE: &str = "my-keyspace";
+
+    fn doc(id: Key, data: &[u8]) -> Document {
+        Document::new(id, HLCTimestamp::from_u64(id), data.to_vec())
+    }
+
+    /// The ID of the key the document is encrypted with in the inner store.
+    async fn stored_key_id(
+        storage: &Encrypted<MemStore, Arc<KeyRing>>,
+        id: Key,
+    ) -> KeyId {
+        let raw = storage.inner().get(KEYSPACE, id).await.unwrap().unwrap();
+        KeyId::from_le_bytes(raw.data()[1..5].try_into().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_suite() {
+        let keys = KeyRing::new(1, EncryptionKey::generate());
+        run_test_suite(Encrypted::new(MemStore::default(), keys)).await;
+    }
+
+    #[tokio::test]
+    async fn test_data_is_encrypted() {
+        let storage = Encrypted::new(
+            MemStore::default(),
+            KeyRing::new(1, EncryptionKey::generate()),
+        );
+        let doc = doc(1, b"Hello, world");
+        storage.put(KEYSPACE, doc.clone()).await.unwrap();
+
+        let raw = storage.inner().get(KEYSPACE, 1).await.unwrap().unwrap();
+        assert_eq!(raw.last_updated(), doc.last_updated());
+        assert!(
+            !raw.data()
+                .windows(doc.data().len())
+                .any(|window| window == doc.data()),
+            "Data should not be stored in plaintext."
+        );
+        assert_eq!(storage.get(KEYSPACE, 1).await.unwrap(), Some(doc));
+
+        // Moving the value to another document must fail authentication.
+        storage
+            .inner()
+            .put(KEYSPACE, Document::new(2, raw.last_updated(), raw.data()))
+            .await
+            .unwrap();
+        let err = storage.get(KEYSPACE, 2).await.expect_err("Value was moved");
+        assert!(matches!(err, EncryptionError::Cipher(2)), "{err:?}");
+
+        let wrong_key = Encrypted::new(
+            MemStore::default(),
+            KeyRing::new(1, EncryptionKey::generate()),
+        );
+        wrong_key.inner().put(KEYSPACE, raw).await.unwrap();
+        let err = wrong_key.get(KEYSPACE, 1).await.expect_err("Wrong key");
+        assert!(matches!(err, EncryptionError::Cipher(1)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation() {
+        let keys = Arc::new(KeyRing::new(1, EncryptionKey::generate()));
+        let storage = Encrypted::new(MemStore::default(), keys.clone());
+
+        let docs = (1..=3).map(|id| doc(id, b"secret")).collect::<Vec<_>>();
+        storage
+            .multi_put(KEYSPACE, docs.clone().into_iter())
+            .await
+            .unwrap();
+
+        keys.rotate(2, EncryptionKey::generate());
+        assert_eq!(stored_key_id(&storage, 1).await, 1);
+
+        assert_eq!(
+            storage.get(KEYSPACE, 1).await.unwrap(),
+            Some(docs[0].clone())
+        );
+        assert_eq!(
+            stored_key_id(&storage, 1).await,
+            2,
+            "Reads should lazily re-encrypt documents."
+        );
+        assert_eq!(stored_key_id(&storage, 2).await, 1);
+
+        assert_eq!(storage.reencrypt_keyspace(KEYSPACE).await.unwrap(), 2);
+        assert_eq!(storage.reencrypt_keyspace(KEYSPACE).await.unwrap(), 0);
+
+        assert!(!keys.remove_key(2), "The current key cannot be removed.");
+        assert!(keys.remove_key(1));
+        let fetched = storage
+            .multi_get(KEYSPACE, [1, 2, 3].into_iter())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(fetched, docs);
+
+        keys.rotate(3, EncryptionKey::generate());
+        keys.remove_key(2);
+        let err = storage.get(KEYSPACE, 1).await.expect_err("Key was removed");
+        assert!(matches!(err, EncryptionError::UnknownKey(2)), "{err:?}");
+    }
+}
@@ -8,9 +8,15 @@ use datacake_node::Clock;
 use puppet::{puppet_actor, ActorMailbox};
 
 use super::messages::{Del, Diff, MultiDel, MultiSet, Set, SymDiff};
-use crate::keyspace::messages::{CorruptedState, PurgeDeletes, Serialize, NUM_SOURCES};
+use crate::keyspace::messages::{
+    CorruptedState,
+    PurgeDeletes,
+    Serialize,
+    TakeSnapshot,
+    NUM_SOURCES,
+};
 use crate::keyspace::LastUpdated;
-use crate::storage::BulkMutationError;
+use crate::storage::{BulkMutationError, StateSnapshot};
 use crate::Storage;
 
 /// Spawns a new keyspace actor, returning the actor's mailbox.
@@ -19,6 +25,7 @@ pub async fn spawn_keyspace<S>(
     storage: Arc<S>,
     clock: Clock,
     state: OrSWotSet<NUM_SOURCES>,
+    snapshot: SnapshotInfo,
     change_timestamp: Arc<AtomicCell<HLCTimestamp>>,
 ) -> ActorMailbox<KeyspaceActor<S>>
 where
@@ -29,6 +36,7 @@ where
         clock,
         storage,
         state,
+        snapshot,
         change_timestamp,
     };
 
@@ -43,9 +51,33 @@ where
     clock: Clock,
     storage: Arc<S>,
     state: OrSWotSet<NUM_SOURCES>,
+    snapshot: SnapshotInfo,
     change_timestamp: Arc<AtomicCell<HLCTimestamp>>,
 }
 
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// Tracks how the in-memory state relates to its persisted snapshot.
+pub struct SnapshotInfo {
+    /// The newest timestamp of any change applied to the state.
+    pub newest_applied: Option<HLCTimestamp>,
+    /// The watermark of the currently persisted snapshot, if any.
+    pub watermark: Option<HLCTimestamp>,
+}
+
+impl SnapshotInfo {
+    /// Records that a change with the given timestamp has been applied to the state.
+    pub(crate) fn observe(&mut self, ts: HLCTimestamp) {
+        self.newest_applied =
+            Some(self.newest_applied.map_or(ts, |newest| newest.max(ts)));
+    }
+
+    /// Returns if a change with the given timestamp would be missed when
+    /// replaying the metadata newer than the snapshot's watermark.
+    fn is_behind(&self, ts: HLCTimestamp) -> bool {
+        matches!(self.watermark, Some(watermark) if ts <= watermark)
+    }
+}
+
 #[puppet_actor]
 impl<S> KeyspaceActor<S>
 where
@@ -56,6 +88,24 @@ where
         self.change_timestamp.store(ts);
     }
 
+    /// Removes the persisted snapshot if a change with the given timestamp
+    /// could not be replayed on top of it.
+    ///
+    /// This must complete before the change is written to the storage, otherwise
+    /// a crash could leave a snapshot behind which silently misses the change.
+    async fn invalidate_snapshot_if_behind(
+        &mut self,
+        oldest: Option<HLCTimestamp>,
+    ) -> Result<(), S::Error> {
+        if !oldest.is_some_and(|ts| self.snapshot.is_behind(ts)) {
+            return Ok(());
+        }
+
+        self.storage.remove_state_snapshot(&self.name).await?;
+        self.snapshot.watermark = None;
+        Ok(())
+    }
+
     #[puppet]
     /// Sets a document value in the store.
     ///
@@ -69,12 +119,14 @@ where
         let doc_id = msg.doc.id();
         let ts = msg.doc.last_updated();
 
+        self.invalidate_snapshot_if_behind(Some(ts)).await?;
         self.storage
             .put_with_ctx(&self.name, msg.doc, msg.ctx.as_ref())
             .await?;
 
         // The change has gone through, let's apply our memory state.
         self.state.insert_with_source(msg.source, doc_id, ts);
+        self.snapshot.observe(ts);
         self.inc_change_timestamp().await;
         Ok(())
     }
@@ -87,6 +139,16 @@ where
         &mut self,
         msg: MultiSet<S>,
     ) -> Result<(), BulkMutationError<S::Error>> {
+        let oldest = msg
+            .docs
+            .iter()
+            .filter(|doc| self.state.will_apply(doc.id(), doc.last_updated()))
+            .map(|doc| doc.last_updated())
+            .min();
+        self.invalidate_snapshot_if_behind(oldest)
+            .await
+            .map_err(BulkMutationError::empty_with_error)?;
+
         let mut valid_entries = Vec::with_capacity(msg.docs.len());
 
         // Only select docs to be inserted if they're able to be applied.
@@ -94,9 +156,8 @@ where
             .docs
             .into_iter()
             .filter(|doc| self.state.will_apply(doc.id(), doc.last_updated()))
-            .map(|doc| {
+            .inspect(|doc| {
                 valid_entries.push((doc.id(), doc.last_updated()));
-                doc
             });
 
         let res = self
@@ -106,6 +167,9 @@ where
 
         // Ensure the insertion order into the set is correct.
         valid_entries.sort_by_key(|entry| entry.1);
+        if let Some((_, newest)) = valid_entries.last() {
+            self.snapshot.observe(*newest);
+        }
         self.inc_change_timestamp().await;
 
         if let Err(error) = res {
@@ -136,6 +200,8 @@ where
             return Ok(());
         }
 
+        self.invalidate_snapshot_if_behind(Some(msg.doc.last_updated))
+            .await?;
         self.storage
             .mark_as_tombstone(&self.name, msg.doc.id, msg.doc.last_updated)
             .await?;
@@ -143,6 +209,7 @@ where
         // The change has gone through, let's apply our memory state.
         self.state
             .delete_with_source(msg.source, msg.doc.id, msg.doc.last_updated);
+        self.snapshot.observe(msg.doc.last_updated);
         self.inc_change_timestamp().await;
         Ok(())
     }
@@ -155,6 +222,16 @@ where
         &mut self,
         msg: MultiDel<S>,
     ) -> Result<(), BulkMutationError<S::Error>> {
+        let oldest = msg
+            .docs
+            .iter()
+            .filter(|doc| self.state.will_apply(doc.id, doc.last_updated))
+            .map(|doc| doc.last_updated)
+            .min();
+        self.invalidate_snapshot_if_behind(oldest)
+            .await
+            .map_err(BulkMutationError::empty_with_error)?;
+
         let mut valid_entries = Vec::with_capacity(msg.docs.len());
 
         // Only select docs to be inserted if they're able to be applied.
@@ -162,15 +239,17 @@ where
             .docs
             .into_iter()
             .filter(|doc| self.state.will_apply(doc.id, doc.last_updated))
-            .map(|doc| {
+            .inspect(|doc| {
                 valid_entries.push((doc.id, doc.last_updated));
-                doc
             });
 
         let res = self.storage.mark_many_as_tombstone(&self.name, docs).await;
 
         // Ensure the insertion order into the set is correct.
         valid_entries.sort_by_key(|entry| entry.1);
+        if let Some((_, newest)) = valid_entries.last() {
+            self.snapshot.observe(*newest);
+        }
         self.inc_change_timestamp().await;
 
         if let Err(error) = res {
@@ -198,6 +277,22 @@ where
     ) -> Result<(), S::Error> {
         let changes = self.state.purge_old_deletes();
 
+        // A snapshot may still hold an older, live entry for a key whose tombstone
+        // was only written after the snapshot was taken, purging that tombstone
+        // would leave nothing to replay the removal from.
+        let newest = changes.iter().map(|(_, ts)| *ts).max();
+        let snapshot_behind = matches!(
+            (self.snapshot.watermark, newest),
+            (Some(watermark), Some(ts)) if ts > watermark,
+        );
+        if snapshot_behind {
+            if let Err(e) = self.storage.remove_state_snapshot(&self.name).await {
+                self.state.add_raw_tombstones(changes);
+                return Err(e);
+            }
+            self.snapshot.watermark = None;
+        }
+
         let res = self
             .storage
             .remove_tombstones(&self.name, changes.iter().map(|(key, _)| *key))
@@ -219,6 +314,32 @@ where
         Ok(())
     }
 
+    #[puppet]
+    /// Persists a snapshot of the current state to the storage.
+    ///
+    /// If the persisted snapshot is already up to date, this is a no-op.
+    async fn on_take_snapshot(&mut self, _msg: TakeSnapshot<S>) -> Result<(), S::Error> {
+        let watermark = match self.snapshot.newest_applied {
+            None => return Ok(()),
+            Some(newest) if self.snapshot.watermark == Some(newest) => return Ok(()),
+            Some(newest) => newest,
+        };
+
+        let state = match self.state.as_bytes() {
+            Ok(state) => state,
+            Err(e) => {
+                warn!(error = ?e, keyspace = %self.name, "Failed to serialize state snapshot.");
+                return Ok(());
+            },
+        };
+
+        self.storage
+            .put_state_snapshot(&self.name, StateSnapshot { watermark, state })
+            .await?;
+        self.snapshot.watermark = Some(watermark);
+        Ok(())
+    }
+
     #[puppet]
     async fn on_diff(&self, msg: Diff) -> (StateChanges, StateChanges) {
         self.state.diff(&msg.0)
@@ -251,13 +372,22 @@ where
 #[cfg(test)]
 mod tests {
     use std::cmp::Reverse;
+    use std::collections::HashMap;
     use std::time::Duration;
 
+    use datacake_crdt::Key;
     use smallvec::smallvec;
 
     use super::*;
-    use crate::core::DocumentMetadata;
-    use crate::test_utils::MockStorage;
+    use crate::core::{DocVec, DocumentMetadata};
+    use crate::test_utils::{
+        Fault,
+        FaultRule,
+        FaultyStorage,
+        MemStore,
+        MockStorage,
+        StorageMethod,
+    };
     use crate::Document;
 
     macro_rules! drift {
@@ -272,16 +402,14 @@ mod tests {
         }};
     }
 
-    async fn make_actor(
-        clock: Clock,
-        storage: MockStorage,
-    ) -> KeyspaceActor<MockStorage> {
+    async fn make_actor<S: Storage>(clock: Clock, storage: S) -> KeyspaceActor<S> {
         let ts = clock.get_time().await;
         KeyspaceActor {
             name: Cow::Borrowed("my-keyspace"),
             clock,
             storage: Arc::new(storage),
             state: OrSWotSet::default(),
+            snapshot: SnapshotInfo::default(),
             change_timestamp: Arc::new(AtomicCell::new(ts)),
         }
     }
@@ -708,4 +836,126 @@ mod tests {
             .collect::<Vec<_>>();
         assert_eq!(changes, expected_deletes);
     }
+
+    /// Checks the in-memory state matches the documents persisted by the store.
+    async fn assert_state_matches_storage(
+        keyspace: &KeyspaceActor<FaultyStorage<MemStore>>,
+        doc_ids: impl Iterator<Item = Key>,
+    ) {
+        let persisted = keyspace
+            .storage
+            .inner()
+            .iter_metadata("my-keyspace")
+            .await
+            .unwrap()
+            .map(|(doc_id, ts, is_tombstone)| (doc_id, (ts, is_tombstone)))
+            .collect::<HashMap<_, _>>();
+
+        for doc_id in doc_ids {
+            let expected = match persisted.get(&doc_id) {
+                Some((ts, false)) => Some(ts),
+                _ => None,
+            };
+            assert_eq!(
+                keyspace.state.get(&doc_id),
+                expected,
+                "State of document {doc_id} should match the store."
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_on_set_converges_after_storage_errors() {
+        let clock = Clock::new(0);
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(
+            FaultRule::new(Fault::Error)
+                .with_method(StorageMethod::Put)
+                .with_limit(1),
+        );
+        let mut keyspace = make_actor(clock.clone(), storage).await;
+
+        let doc = Document::new(1, clock.get_time().await, b"Hello, world".to_vec());
+        let set = || Set {
+            source: 0,
+            doc: doc.clone(),
+            ctx: None,
+            _marker: Default::default(),
+        };
+
+        keyspace
+            .on_set(set())
+            .await
+            .expect_err("Put operation should fail.");
+        assert!(
+            keyspace.state.get(&doc.id()).is_none(),
+            "Failed writes should not be applied to the state."
+        );
+
+        keyspace
+            .on_set(set())
+            .await
+            .expect("Retried put operation should be successful.");
+        assert_eq!(keyspace.state.get(&doc.id()), Some(&doc.last_updated()));
+        assert_state_matches_storage(&keyspace, [doc.id()].into_iter()).await;
+    }
+
+    #[tokio::test]
+    async fn test_on_multi_set_converges_after_partial_failure() {
+        let clock = Clock::new(0);
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(FaultRule::new(Fault::Partial(2)).with_limit(2));
+        let mut keyspace = make_actor(clock.clone(), storage).await;
+
+        let mut docs = DocVec::new();
+        for id in 0..5 {
+            docs.push(Document::new(id, clock.get_time().await, Vec::new()));
+        }
+        let multi_set = || MultiSet {
+            source: 0,
+            docs: docs.clone(),
+            ctx: None,
+            _marker: Default::default(),
+        };
+
+        let err = keyspace
+            .on_multi_set(multi_set())
+            .await
+            .expect_err("Put operation should partially fail.");
+        assert_eq!(err.successful_doc_ids(), [0, 1]);
+        assert_state_matches_storage(&keyspace, 0..5).await;
+
+        let deletes = docs
+            .iter()
+            .map(|doc| {
+                DocumentMetadata::new(doc.id(), HLCTimestamp::new(drift!(1), 0, 0))
+            })
+            .collect::<DocVec<_>>();
+        let err = keyspace
+            .on_multi_del(MultiDel {
+                source: 0,
+                docs: deletes.clone(),
+                _marker: Default::default(),
+            })
+            .await
+            .expect_err("Delete operation should partially fail.");
+        assert_eq!(err.successful_doc_ids(), [0, 1]);
+        assert_state_matches_storage(&keyspace, 0..5).await;
+
+        // Retrying every operation once the store recovers should converge.
+        keyspace
+            .on_multi_set(multi_set())
+            .await
+            .expect("Put operation should be successful.");
+        keyspace
+            .on_multi_del(MultiDel {
+                source: 0,
+                docs: deletes,
+                _marker: Default::default(),
+            })
+            .await
+            .expect("Delete operation should be successful.");
+        assert_state_matches_storage(&keyspace, 0..5).await;
+        assert!((0..5).all(|id| keyspace.state.get(&id).is_none()));
+    }
 }
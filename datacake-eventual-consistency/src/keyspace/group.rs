@@ -6,16 +6,17 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crossbeam_utils::atomic::AtomicCell;
-use datacake_crdt::{HLCTimestamp, Key, OrSWotSet};
+use datacake_crdt::{BadState, HLCTimestamp, Key, OrSWotSet};
 use datacake_node::Clock;
+use futures::TryStreamExt;
 use parking_lot::RwLock;
 use puppet::ActorMailbox;
-use rkyv::{Archive, Deserialize, Serialize};
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
 use tokio::time::interval;
 
 use super::NUM_SOURCES;
-use crate::keyspace::messages::PurgeDeletes;
-use crate::keyspace::KeyspaceActor;
+use crate::keyspace::messages::{PurgeDeletes, TakeSnapshot};
+use crate::keyspace::{KeyspaceActor, SnapshotInfo};
 use crate::Storage;
 
 const PURGE_DELETES_INTERVAL: Duration = if cfg!(test) {
@@ -124,32 +125,17 @@ where
     }
 
     /// Loads existing states from the given storage implementation.
+    ///
+    /// If the storage has a persisted snapshot of a keyspace's state, only the
+    /// metadata updated after the snapshot's watermark is replayed on top of it.
     pub async fn load_states_from_storage(&self) -> Result<(), S::Error> {
         let start = Instant::now();
         let mut states = BTreeMap::new();
 
         for keyspace in self.storage.get_keyspace_list().await? {
             let keyspace = Cow::Owned(keyspace);
-            let mut state = OrSWotSet::default();
-
-            let mut entries = self
-                .storage
-                .iter_metadata(&keyspace)
-                .await?
-                .collect::<Vec<(Key, HLCTimestamp, bool)>>();
-
-            // Must be time ordered to avoid skipping entries.
-            entries.sort_by_key(|entry| entry.1);
-
-            for (key, ts, tombstone) in entries {
-                if tombstone {
-                    state.delete(key, ts);
-                } else {
-                    state.insert(key, ts);
-                }
-            }
-
-            states.insert(keyspace, state);
+            let loaded = self.load_state_from_storage(&keyspace).await?;
+            states.insert(keyspace, loaded);
         }
 
         info!(
@@ -158,36 +144,102 @@ where
             "Loaded persisted state from storage.",
         );
 
-        self.load_states(states.into_iter()).await;
+        let mut counters = Vec::new();
+        let mut created_states = Vec::new();
+        for (name, (state, snapshot)) in states {
+            let (mailbox, update_counter) =
+                self.spawn_state(name.clone(), state, snapshot).await;
+            counters.push((name.clone(), update_counter));
+            created_states.push((name, mailbox));
+        }
+        self.register_states(created_states, counters);
 
         Ok(())
     }
 
-    /// Loads a set of existing keyspace states.
-    pub async fn load_states(
+    async fn load_state_from_storage(
         &self,
-        states: impl Iterator<Item = (impl Into<Cow<'static, str>>, OrSWotSet<NUM_SOURCES>)>,
-    ) {
-        let mut counters = Vec::new();
-        let mut created_states = Vec::new();
-        for (name, state) in states {
-            let name = name.into();
-            let ts = self.clock.get_time().await;
-            let update_counter = Arc::new(AtomicCell::new(ts));
-
-            let state = super::spawn_keyspace(
-                name.clone(),
-                self.storage.clone(),
-                self.clock.clone(),
-                state,
-                update_counter.clone(),
-            )
-            .await;
+        keyspace: &str,
+    ) -> Result<(OrSWotSet<NUM_SOURCES>, SnapshotInfo), S::Error> {
+        let mut state = OrSWotSet::default();
+        let mut snapshot_info = SnapshotInfo::default();
+
+        let snapshot = self.storage.get_state_snapshot(keyspace).await?;
+        let mut stream = match snapshot
+            .map(|snapshot| (snapshot.watermark, decode_state(&snapshot.state)))
+        {
+            Some((watermark, Ok(snapshot_state))) => {
+                state = snapshot_state;
+                snapshot_info.watermark = Some(watermark);
+                snapshot_info.newest_applied = Some(watermark);
+                self.storage
+                    .stream_metadata_since(keyspace, watermark)
+                    .await?
+            },
+            Some((watermark, Err(e))) => {
+                warn!(
+                    error = ?e,
+                    keyspace = keyspace,
+                    watermark = %watermark,
+                    "Failed to load state snapshot, the state will be rebuilt from storage.",
+                );
+                self.storage.stream_metadata(keyspace).await?
+            },
+            None => self.storage.stream_metadata(keyspace).await?,
+        };
 
-            counters.push((name.clone(), update_counter));
-            created_states.push((name, state));
+        // The metadata is streamed in chunks so the storage implementation
+        // is not required to hold every entry in memory alongside our own copy.
+        let mut entries = Vec::<(Key, HLCTimestamp, bool)>::new();
+        while let Some(chunk) = stream.try_next().await? {
+            entries.extend(chunk);
         }
 
+        // Must be time ordered to avoid skipping entries.
+        entries.sort_by_key(|entry| entry.1);
+
+        for (key, ts, tombstone) in entries {
+            if tombstone {
+                state.delete(key, ts);
+            } else {
+                state.insert(key, ts);
+            }
+            snapshot_info.observe(ts);
+        }
+
+        Ok((state, snapshot_info))
+    }
+
+    async fn spawn_state(
+        &self,
+        name: Cow<'static, str>,
+        state: OrSWotSet<NUM_SOURCES>,
+        snapshot: SnapshotInfo,
+    ) -> (
+        ActorMailbox<KeyspaceActor<S>>,
+        Arc<AtomicCell<HLCTimestamp>>,
+    ) {
+        let ts = self.clock.get_time().await;
+        let update_counter = Arc::new(AtomicCell::new(ts));
+
+        let mailbox = super::spawn_keyspace(
+            name,
+            self.storage.clone(),
+            self.clock.clone(),
+            state,
+            snapshot,
+            update_counter.clone(),
+        )
+        .await;
+
+        (mailbox, update_counter)
+    }
+
+    fn register_states(
+        &self,
+        created_states: Vec<(Cow<'static, str>, ActorMailbox<KeyspaceActor<S>>)>,
+        counters: Vec<(Cow<'static, str>, Arc<AtomicCell<HLCTimestamp>>)>,
+    ) {
         {
             let mut guard = self.group.write();
             for (name, state) in created_states {
@@ -198,11 +250,17 @@ where
         {
             let mut guard = self.keyspace_timestamps.write();
             for (name, state) in counters {
-                guard.insert(name.clone(), state);
+                guard.insert(name, state);
             }
         }
     }
 
+    /// Starts a background task which periodically persists a snapshot
+    /// of each keyspace's state to the storage.
+    pub fn start_snapshot_task(&self, interval: Duration) {
+        tokio::spawn(keyspace_snapshot_task(self.clone(), interval));
+    }
+
     /// Adds a new keyspace to the state groups.
     pub async fn add_state(
         &self,
@@ -210,17 +268,9 @@ where
         state: OrSWotSet<NUM_SOURCES>,
     ) -> ActorMailbox<KeyspaceActor<S>> {
         let name = name.into();
-        let ts = self.clock.get_time().await;
-        let update_counter = Arc::new(AtomicCell::new(ts));
-
-        let state = super::spawn_keyspace(
-            name.clone(),
-            self.storage.clone(),
-            self.clock.clone(),
-            state,
-            update_counter.clone(),
-        )
-        .await;
+        let (state, update_counter) = self
+            .spawn_state(name.clone(), state, SnapshotInfo::default())
+            .await;
 
         {
             let mut guard = self.group.write();
@@ -251,13 +301,45 @@ where
         };
 
         for (name, state) in keyspace_set {
-            if let Err(e) = state.send(PurgeDeletes(PhantomData::<S>::default())).await {
+            if let Err(e) = state.send(PurgeDeletes(PhantomData::<S>)).await {
                 warn!(error = ?e, keyspace = %name, "Failed to purge tombstones from state.");
             }
         }
     }
 }
 
+async fn keyspace_snapshot_task<S>(handle: KeyspaceGroup<S>, period: Duration)
+where
+    S: Storage,
+{
+    let mut interval = interval(period);
+
+    loop {
+        interval.tick().await;
+
+        let keyspace_set = {
+            let lock = handle.group.read();
+            lock.deref().clone()
+        };
+
+        for (name, state) in keyspace_set {
+            if let Err(e) = state.send(TakeSnapshot(PhantomData::<S>)).await {
+                warn!(error = ?e, keyspace = %name, "Failed to persist state snapshot.");
+            }
+        }
+    }
+}
+
+/// Deserializes a persisted state snapshot.
+///
+/// The snapshot is copied into an aligned buffer as storage implementations
+/// give no guarantees about the alignment of the bytes they return.
+fn decode_state(data: &[u8]) -> Result<OrSWotSet<NUM_SOURCES>, BadState> {
+    let mut aligned = AlignedVec::with_capacity(data.len());
+    aligned.extend_from_slice(data);
+    OrSWotSet::from_bytes(&aligned)
+}
+
 #[repr(C)]
 #[derive(Serialize, Deserialize, Archive)]
 #[archive(check_bytes)]
@@ -339,7 +421,11 @@ impl DerefMut for KeyspaceTimestamps {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::MockStorage;
+    use crate::core::DocumentMetadata;
+    use crate::keyspace::messages::{Del, Set};
+    use crate::storage::StateSnapshot;
+    use crate::test_utils::{MemStore, MockStorage};
+    use crate::Document;
 
     #[tokio::test]
     async fn test_groups_load_from_blank_storage() {
@@ -390,4 +476,155 @@ mod tests {
         assert!(!group.keyspace_timestamps.read().is_empty());
         assert!(!group.group.read().is_empty());
     }
+
+    async fn set_doc(state: &ActorMailbox<KeyspaceActor<MemStore>>, doc: Document) {
+        state
+            .send(Set {
+                source: 0,
+                doc,
+                ctx: None,
+                _marker: PhantomData,
+            })
+            .await
+            .expect("Put document.");
+    }
+
+    #[tokio::test]
+    async fn test_groups_load_from_snapshot() {
+        let storage = Arc::new(MemStore::default());
+        let group = KeyspaceGroup::new(storage.clone(), Clock::new(0)).await;
+        let clock = group.clock().clone();
+
+        let keyspace = group.get_or_create_keyspace("my-keyspace").await;
+        let doc_1 = Document::new(1, clock.get_time().await, b"Hello, world 1".to_vec());
+        let doc_2 = Document::new(2, clock.get_time().await, b"Hello, world 2".to_vec());
+        set_doc(&keyspace, doc_1.clone()).await;
+        set_doc(&keyspace, doc_2.clone()).await;
+
+        keyspace
+            .send(TakeSnapshot(PhantomData))
+            .await
+            .expect("Take snapshot.");
+        let snapshot = storage
+            .get_state_snapshot("my-keyspace")
+            .await
+            .unwrap()
+            .expect("Snapshot should be persisted.");
+        assert_eq!(snapshot.watermark, doc_2.last_updated());
+
+        // Changes after the snapshot should be replayed on load.
+        let doc_3 = Document::new(3, clock.get_time().await, b"Hello, world 3".to_vec());
+        set_doc(&keyspace, doc_3.clone()).await;
+        let removed = DocumentMetadata::new(doc_1.id(), clock.get_time().await);
+        keyspace
+            .send(Del {
+                source: 0,
+                doc: removed,
+                _marker: PhantomData,
+            })
+            .await
+            .expect("Delete document.");
+        assert!(
+            storage
+                .get_state_snapshot("my-keyspace")
+                .await
+                .unwrap()
+                .is_some(),
+            "Newer changes should not invalidate the snapshot."
+        );
+
+        let (state, info) = group
+            .load_state_from_storage("my-keyspace")
+            .await
+            .expect("Load state from snapshot.");
+        assert_eq!(info.watermark, Some(snapshot.watermark));
+        assert_eq!(info.newest_applied, Some(removed.last_updated));
+        assert_eq!(state.get(&doc_1.id()), None);
+        assert_eq!(state.get(&doc_2.id()), Some(&doc_2.last_updated()));
+        assert_eq!(state.get(&doc_3.id()), Some(&doc_3.last_updated()));
+
+        storage.remove_state_snapshot("my-keyspace").await.unwrap();
+        let (rebuilt, info) = group
+            .load_state_from_storage("my-keyspace")
+            .await
+            .expect("Load state from metadata.");
+        assert_eq!(info.watermark, None);
+        assert_eq!(info.newest_applied, Some(removed.last_updated));
+        assert_eq!(state.diff(&rebuilt), (vec![], vec![]));
+        assert_eq!(rebuilt.diff(&state), (vec![], vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_groups_load_from_corrupted_snapshot() {
+        let storage = Arc::new(MemStore::default());
+        let group = KeyspaceGroup::new(storage.clone(), Clock::new(0)).await;
+        let clock = group.clock().clone();
+
+        let keyspace = group.get_or_create_keyspace("my-keyspace").await;
+        let doc = Document::new(1, clock.get_time().await, b"Hello, world".to_vec());
+        set_doc(&keyspace, doc.clone()).await;
+
+        let snapshot = StateSnapshot {
+            watermark: doc.last_updated(),
+            state: b"not-a-state".to_vec(),
+        };
+        storage
+            .put_state_snapshot("my-keyspace", snapshot)
+            .await
+            .unwrap();
+
+        let (state, info) = group
+            .load_state_from_storage("my-keyspace")
+            .await
+            .expect("Load state from metadata.");
+        assert_eq!(info.watermark, None);
+        assert_eq!(state.get(&doc.id()), Some(&doc.last_updated()));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_invalidated_by_older_change() {
+        let storage = Arc::new(MemStore::default());
+        let group = KeyspaceGroup::new(storage.clone(), Clock::new(0)).await;
+        let clock = group.clock().clone();
+
+        let old_ts =
+            HLCTimestamp::new(clock.get_time().await.datacake_timestamp(), 0, 1);
+        let keyspace = group.get_or_create_keyspace("my-keyspace").await;
+        let doc = Document::new(1, clock.get_time().await, b"Hello, world".to_vec());
+        set_doc(&keyspace, doc).await;
+
+        keyspace
+            .send(TakeSnapshot(PhantomData))
+            .await
+            .expect("Take snapshot.");
+        assert!(storage
+            .get_state_snapshot("my-keyspace")
+            .await
+            .unwrap()
+            .is_some());
+
+        // A repair can apply a change older than the snapshot's watermark, which
+        // would be missed when replaying the newer metadata.
+        let repaired = Document::new(2, old_ts, b"Hello, world 2".to_vec());
+        set_doc(&keyspace, repaired.clone()).await;
+        assert!(
+            storage
+                .get_state_snapshot("my-keyspace")
+                .await
+                .unwrap()
+                .is_none(),
+            "Older changes should invalidate the snapshot."
+        );
+
+        keyspace
+            .send(TakeSnapshot(PhantomData))
+            .await
+            .expect("Take snapshot.");
+        let (state, info) = group
+            .load_state_from_storage("my-keyspace")
+            .await
+            .expect("Load state from snapshot.");
+        assert!(info.watermark.is_some());
+        assert_eq!(state.get(&repaired.id()), Some(&repaired.last_updated()));
+    }
 }
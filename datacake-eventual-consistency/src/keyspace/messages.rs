@@ -72,3 +72,9 @@ pub struct PurgeDeletes<S>(pub PhantomData<S>);
 impl<S: Storage> Message for PurgeDeletes<S> {
     type Output = Result<(), S::Error>;
 }
+
+#[derive(Copy, Clone)]
+pub struct TakeSnapshot<S>(pub PhantomData<S>);
+impl<S: Storage> Message for TakeSnapshot<S> {
+    type Output = Result<(), S::Error>;
+}
@@ -2,10 +2,9 @@ mod actor;
 mod group;
 mod messages;
 
-pub use actor::{spawn_keyspace, KeyspaceActor};
+pub use actor::{spawn_keyspace, KeyspaceActor, SnapshotInfo};
 pub use group::{KeyspaceGroup, KeyspaceInfo, KeyspaceTimestamps};
 pub use messages::{
-    CorruptedState,
     Del,
     Diff,
     LastUpdated,
@@ -13,7 +12,6 @@ pub use messages::{
     MultiSet,
     Serialize,
     Set,
-    SymDiff,
     NUM_SOURCES,
 };
 
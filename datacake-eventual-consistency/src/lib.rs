@@ -54,15 +54,21 @@
 #[macro_use]
 extern crate tracing;
 
+mod cached;
 mod core;
+#[cfg(any(test, feature = "encryption"))]
+mod encrypted;
 mod error;
 mod keyspace;
+mod migrate;
 mod replication;
 mod rpc;
+mod sharded;
 mod statistics;
 mod storage;
 #[cfg(any(test, feature = "test-utils"))]
 pub mod test_utils;
+mod traffic;
 
 use std::borrow::Cow;
 use std::future::Future;
@@ -72,6 +78,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+pub use cached::{CacheStats, Cached};
 use datacake_crdt::Key;
 use datacake_node::{
     ClusterExtension,
@@ -81,13 +88,43 @@ use datacake_node::{
     DatacakeNode,
     Nodes,
 };
+#[cfg(any(test, feature = "encryption"))]
+pub use encrypted::{
+    Encrypted,
+    EncryptionError,
+    EncryptionKey,
+    KeyId,
+    KeyProvider,
+    KeyRing,
+};
 pub use error::StoreError;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
+pub use migrate::{
+    migrate,
+    verify,
+    MigrationError,
+    MigrationOptions,
+    MigrationReport,
+    Mismatch,
+    MismatchKind,
+    VerificationReport,
+};
+pub use sharded::{Crc32ShardHasher, ShardHasher, Sharded};
 pub use statistics::SystemStatistics;
 #[cfg(any(feature = "test-utils", feature = "test-suite"))]
 pub use storage::test_suite;
-pub use storage::{BulkMutationError, ProgressTracker, PutContext, Storage};
+pub use storage::{
+    BulkMutationError,
+    DocsStream,
+    MetadataStream,
+    ProgressTracker,
+    PutContext,
+    StateSnapshot,
+    Storage,
+    STREAM_CHUNK_SIZE,
+};
+pub use traffic::RateLimit;
 
 pub use self::core::{Document, DocumentMetadata};
 use crate::core::DocVec;
@@ -109,6 +146,7 @@ use crate::replication::{
 use crate::rpc::services::consistency_impl::ConsistencyService;
 use crate::rpc::services::replication_impl::ReplicationService;
 use crate::rpc::ConsistencyClient;
+use crate::traffic::TrafficControl;
 
 const TIMEOUT: Duration = Duration::from_secs(2);
 const DEFAULT_REPAIR_INTERVAL: Duration = if cfg!(any(test, feature = "test-utils")) {
@@ -132,6 +170,9 @@ where
 {
     datastore: S,
     repair_interval: Duration,
+    repair_rate_limit: RateLimit,
+    distributor_rate_limit: RateLimit,
+    state_snapshot_interval: Option<Duration>,
 }
 
 impl<S> EventuallyConsistentStoreExtension<S>
@@ -144,6 +185,9 @@ where
         Self {
             datastore: store,
             repair_interval: DEFAULT_REPAIR_INTERVAL,
+            repair_rate_limit: RateLimit::unlimited(),
+            distributor_rate_limit: RateLimit::unlimited(),
+            state_snapshot_interval: None,
         }
     }
 
@@ -152,6 +196,37 @@ where
         self.repair_interval = dur;
         self
     }
+
+    /// Limits the rate at which documents are fetched and applied by the
+    /// replication cycle when repairing keyspaces from other nodes.
+    ///
+    /// By default repairs are not rate limited.
+    pub fn with_repair_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.repair_rate_limit = limit;
+        self
+    }
+
+    /// Limits the rate at which batched mutations are sent by the task
+    /// distributor to the rest of the cluster.
+    ///
+    /// This limit is applied to each node the batch is sent to.
+    /// By default the distributor is not rate limited.
+    pub fn with_distributor_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.distributor_rate_limit = limit;
+        self
+    }
+
+    /// Periodically persists a snapshot of each keyspace's state to the store.
+    ///
+    /// On startup, only the metadata updated since the last snapshot needs to
+    /// be replayed rather than every entry in the store. This requires the store
+    /// to implement [Storage::put_state_snapshot] and [Storage::get_state_snapshot].
+    ///
+    /// By default snapshots are not taken.
+    pub fn with_state_snapshot_interval(mut self, dur: Duration) -> Self {
+        self.state_snapshot_interval = Some(dur);
+        self
+    }
 }
 
 #[async_trait]
@@ -166,8 +241,16 @@ where
         self,
         node: &DatacakeNode,
     ) -> Result<Self::Output, Self::Error> {
-        EventuallyConsistentStore::create(self.datastore, self.repair_interval, node)
-            .await
+        let traffic =
+            TrafficControl::new(self.repair_rate_limit, self.distributor_rate_limit);
+        EventuallyConsistentStore::create(
+            self.datastore,
+            self.repair_interval,
+            self.state_snapshot_interval,
+            traffic,
+            node,
+        )
+        .await
     }
 }
 
@@ -186,6 +269,7 @@ where
 {
     node: DatacakeHandle,
     group: KeyspaceGroup<S>,
+    traffic: TrafficControl,
     task_service: TaskDistributor,
     repair_service: ReplicationHandle,
     statistics: SystemStatistics,
@@ -198,6 +282,8 @@ where
     async fn create(
         datastore: S,
         repair_interval: Duration,
+        state_snapshot_interval: Option<Duration>,
+        traffic: TrafficControl,
         node: &DatacakeNode,
     ) -> Result<Self, StoreError<S::Error>> {
         let storage = Arc::new(datastore);
@@ -207,17 +293,22 @@ where
 
         // Load the keyspace states.
         group.load_states_from_storage().await?;
+        if let Some(interval) = state_snapshot_interval {
+            group.start_snapshot_task(interval);
+        }
 
         let task_ctx = TaskServiceContext {
             clock: node.clock().clone(),
             network: node.network().clone(),
             local_node_id: node.me().node_id,
             public_node_addr: node.me().public_addr,
+            traffic: traffic.clone(),
         };
         let replication_ctx = ReplicationCycleContext {
             repair_interval,
             group: group.clone(),
             network: node.network().clone(),
+            traffic: traffic.clone(),
         };
         let task_service =
             replication::start_task_distributor_service::<S>(task_ctx).await;
@@ -232,12 +323,14 @@ where
         node.add_rpc_service(ConsistencyService::new(
             group.clone(),
             node.network().clone(),
+            traffic.clone(),
         ));
-        node.add_rpc_service(ReplicationService::new(group.clone()));
+        node.add_rpc_service(ReplicationService::new(group.clone(), traffic.clone()));
 
         Ok(Self {
             node: node.handle(),
             group,
+            traffic,
             statistics,
             task_service,
             repair_service,
@@ -259,6 +352,7 @@ where
             task_service: self.task_service.clone(),
             statistics: self.statistics.clone(),
             group: self.group.clone(),
+            traffic: self.traffic.clone(),
         }
     }
 
@@ -293,6 +387,7 @@ where
 {
     node: DatacakeHandle,
     group: KeyspaceGroup<S>,
+    traffic: TrafficControl,
     task_service: TaskDistributor,
     statistics: SystemStatistics,
 }
@@ -305,6 +400,7 @@ where
         Self {
             node: self.node.clone(),
             group: self.group.clone(),
+            traffic: self.traffic.clone(),
             task_service: self.task_service.clone(),
             statistics: self.statistics.clone(),
         }
@@ -349,6 +445,18 @@ where
         storage.iter_metadata(keyspace).await
     }
 
+    /// Retrieves all keys contained within the store as a stream of chunks.
+    ///
+    /// Unlike [ReplicatedStoreHandle::iter_metadata] this allows large keyspaces
+    /// to be processed without collecting every entry at once.
+    pub async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<S::Error>, S::Error> {
+        let storage = self.group.storage();
+        storage.stream_metadata(keyspace).await
+    }
+
     /// Retrieves a document from the underlying storage.
     pub async fn get(
         &self,
@@ -387,6 +495,8 @@ where
     where
         D: Into<Vec<u8>>,
     {
+        let _guard = self.traffic.priority.foreground();
+
         let nodes = self
             .node
             .select_nodes(consistency)
@@ -401,7 +511,7 @@ where
             source: CONSISTENCY_SOURCE_ID,
             doc: document.clone(),
             ctx: None,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
         keyspace.send(msg).await?;
 
@@ -449,6 +559,8 @@ where
         T: Iterator<Item = (Key, D)> + Send,
         I: IntoIterator<IntoIter = T> + Send,
     {
+        let _guard = self.traffic.priority.foreground();
+
         let nodes = self
             .node
             .select_nodes(consistency)
@@ -466,7 +578,7 @@ where
             source: CONSISTENCY_SOURCE_ID,
             docs: docs.clone(),
             ctx: None,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
         keyspace.send(msg).await?;
 
@@ -510,6 +622,8 @@ where
         doc_id: Key,
         consistency: Consistency,
     ) -> Result<(), StoreError<S::Error>> {
+        let _guard = self.traffic.priority.foreground();
+
         let nodes = self
             .node
             .select_nodes(consistency)
@@ -526,7 +640,7 @@ where
         let msg = Del {
             source: CONSISTENCY_SOURCE_ID,
             doc,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
         keyspace.send(msg).await?;
 
@@ -567,6 +681,8 @@ where
         T: Iterator<Item = Key> + Send,
         I: IntoIterator<IntoIter = T> + Send,
     {
+        let _guard = self.traffic.priority.foreground();
+
         let nodes = self
             .node
             .select_nodes(consistency)
@@ -583,7 +699,7 @@ where
         let msg = MultiDel {
             source: CONSISTENCY_SOURCE_ID,
             docs: docs.clone(),
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
         keyspace.send(msg).await?;
 
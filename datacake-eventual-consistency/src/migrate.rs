@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use datacake_crdt::{HLCTimestamp, Key};
+use futures::TryStreamExt;
+
+use crate::{Document, DocumentMetadata, Storage};
+
+#[derive(Debug, thiserror::Error)]
+/// An error which occurred while migrating data between two stores.
+pub enum MigrationError<SE, DE>
+where
+    SE: Error + Send + 'static,
+    DE: Error + Send + 'static,
+{
+    #[error("Failed to read from the source store: {0}")]
+    /// The source store failed to complete an operation.
+    Source(SE),
+
+    #[error("Failed to write to the destination store: {0}")]
+    /// The destination store failed to complete an operation.
+    Destination(DE),
+}
+
+#[derive(Debug, Clone, Default)]
+/// Options for controlling how a migration is performed.
+pub struct MigrationOptions {
+    keyspaces: Option<Vec<String>>,
+    resume: bool,
+}
+
+impl MigrationOptions {
+    /// Only migrate the given keyspaces rather than every keyspace in the source store.
+    pub fn with_keyspaces(
+        mut self,
+        keyspaces: impl IntoIterator<Item = String>,
+    ) -> Self {
+        self.keyspaces = Some(keyspaces.into_iter().collect());
+        self
+    }
+
+    /// Skip entries which already exist in the destination store with the same
+    /// timestamp and tombstone state.
+    ///
+    /// This allows an interrupted migration to be resumed without re-writing
+    /// every entry, at the cost of reading the destination's metadata first.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A summary of a completed migration.
+pub struct MigrationReport {
+    /// The number of keyspaces migrated.
+    pub keyspaces: usize,
+    /// The number of live documents written to the destination.
+    pub documents: u64,
+    /// The number of tombstones written to the destination.
+    pub tombstones: u64,
+    /// The number of entries skipped as they were already migrated.
+    pub skipped: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The way an entry in the destination store differs from the source store.
+pub enum MismatchKind {
+    /// The entry does not exist in the destination.
+    Missing,
+    /// The entry exists in the destination but not in the source.
+    Unexpected,
+    /// The entry was last updated at a different time.
+    Timestamp {
+        /// The timestamp within the source store.
+        expected: HLCTimestamp,
+        /// The timestamp within the destination store.
+        actual: HLCTimestamp,
+    },
+    /// The entry is a tombstone in one store but not the other.
+    Tombstone {
+        /// If the entry is a tombstone within the source store.
+        expected: bool,
+    },
+    /// The document data differs.
+    Data,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single entry which differs between the source and destination stores.
+pub struct Mismatch {
+    /// The keyspace the entry belongs to.
+    pub keyspace: String,
+    /// The ID of the entry.
+    pub doc_id: Key,
+    /// How the entry differs.
+    pub kind: MismatchKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A summary of a verification pass.
+pub struct VerificationReport {
+    /// The number of source entries checked.
+    pub checked: u64,
+    /// Every entry which differs between the two stores.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerificationReport {
+    #[inline]
+    /// If the destination store exactly matches the source store.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Copies every keyspace, document and tombstone from the `source` store into the
+/// `destination` store, preserving their timestamps exactly.
+///
+/// Entries are streamed in chunks so neither store needs to hold an entire keyspace
+/// in memory. Migrating the same data twice is safe, so an interrupted migration can
+/// simply be run again, see [MigrationOptions::with_resume] to avoid re-writing entries.
+///
+/// The stores should not be in use by a running node while being migrated, any state
+/// snapshots in the destination are removed so the node rebuilds its state on startup.
+pub async fn migrate<S, D>(
+    source: &S,
+    destination: &D,
+    options: &MigrationOptions,
+) -> Result<MigrationReport, MigrationError<S::Error, D::Error>>
+where
+    S: Storage,
+    D: Storage,
+{
+    let keyspaces = match options.keyspaces.clone() {
+        Some(keyspaces) => keyspaces,
+        None => source
+            .get_keyspace_list()
+            .await
+            .map_err(MigrationError::Source)?,
+    };
+
+    let mut report = MigrationReport::default();
+    for keyspace in keyspaces {
+        migrate_keyspace(source, destination, &keyspace, options.resume, &mut report)
+            .await?;
+        report.keyspaces += 1;
+    }
+
+    Ok(report)
+}
+
+async fn migrate_keyspace<S, D>(
+    source: &S,
+    destination: &D,
+    keyspace: &str,
+    resume: bool,
+    report: &mut MigrationReport,
+) -> Result<(), MigrationError<S::Error, D::Error>>
+where
+    S: Storage,
+    D: Storage,
+{
+    info!(keyspace = keyspace, resume = resume, "Migrating keyspace.");
+
+    let existing = if resume {
+        load_metadata(destination, keyspace)
+            .await
+            .map_err(MigrationError::Destination)?
+    } else {
+        HashMap::new()
+    };
+
+    destination
+        .remove_state_snapshot(keyspace)
+        .await
+        .map_err(MigrationError::Destination)?;
+
+    let mut stream = source
+        .stream_metadata(keyspace)
+        .await
+        .map_err(MigrationError::Source)?;
+    while let Some(chunk) = stream.try_next().await.map_err(MigrationError::Source)? {
+        let mut live = Vec::new();
+        let mut tombstones = Vec::new();
+        for (doc_id, ts, is_tombstone) in chunk {
+            if existing.get(&doc_id) == Some(&(ts, is_tombstone)) {
+                report.skipped += 1;
+                continue;
+            }
+
+            if is_tombstone {
+                tombstones.push(DocumentMetadata::new(doc_id, ts));
+            } else {
+                live.push(doc_id);
+            }
+        }
+
+        if !tombstones.is_empty() {
+            report.tombstones += tombstones.len() as u64;
+            destination
+                .mark_many_as_tombstone(keyspace, tombstones.into_iter())
+                .await
+                .map_err(|e| MigrationError::Destination(e.into_inner()))?;
+        }
+
+        if !live.is_empty() {
+            let docs = source
+                .multi_get(keyspace, live.into_iter())
+                .await
+                .map_err(MigrationError::Source)?
+                .collect::<Vec<_>>();
+            report.documents += docs.len() as u64;
+            destination
+                .multi_put(keyspace, docs.into_iter())
+                .await
+                .map_err(|e| MigrationError::Destination(e.into_inner()))?;
+        }
+    }
+
+    info!(
+        keyspace = keyspace,
+        documents = report.documents,
+        tombstones = report.tombstones,
+        skipped = report.skipped,
+        "Keyspace migrated.",
+    );
+
+    Ok(())
+}
+
+/// Checks that every entry within the `source` store exists within the
+/// `destination` store with the same timestamp, tombstone state and data.
+///
+/// Entries within the destination which do not exist in the source are also reported.
+pub async fn verify<S, D>(
+    source: &S,
+    destination: &D,
+    options: &MigrationOptions,
+) -> Result<VerificationReport, MigrationError<S::Error, D::Error>>
+where
+    S: Storage,
+    D: Storage,
+{
+    let keyspaces = match options.keyspaces.clone() {
+        Some(keyspaces) => keyspaces,
+        None => source
+            .get_keyspace_list()
+            .await
+            .map_err(MigrationError::Source)?,
+    };
+
+    let mut report = VerificationReport::default();
+    for keyspace in keyspaces {
+        verify_keyspace(source, destination, &keyspace, &mut report).await?;
+    }
+
+    Ok(report)
+}
+
+async fn verify_keyspace<S, D>(
+    source: &S,
+    destination: &D,
+    keyspace: &str,
+    report: &mut VerificationReport,
+) -> Result<(), MigrationError<S::Error, D::Error>>
+where
+    S: Storage,
+    D: Storage,
+{
+    let mut remaining = load_metadata(destination, keyspace)
+        .await
+        .map_err(MigrationError::Destination)?;
+
+    let mismatch = |doc_id, kind| Mismatch {
+        keyspace: keyspace.to_string(),
+        doc_id,
+        kind,
+    };
+
+    let mut stream = source
+        .stream_metadata(keyspace)
+        .await
+        .map_err(MigrationError::Source)?;
+    while let Some(chunk) = stream.try_next().await.map_err(MigrationError::Source)? {
+        let mut live = Vec::new();
+        for (doc_id, expected_ts, expected_tombstone) in chunk {
+            report.checked += 1;
+
+            let kind = match remaining.remove(&doc_id) {
+                None => MismatchKind::Missing,
+                Some((actual_ts, _)) if actual_ts != expected_ts => {
+                    MismatchKind::Timestamp {
+                        expected: expected_ts,
+                        actual: actual_ts,
+                    }
+                },
+                Some((_, actual_tombstone))
+                    if actual_tombstone != expected_tombstone =>
+                {
+                    MismatchKind::Tombstone {
+                        expected: expected_tombstone,
+                    }
+                },
+                Some(_) => {
+                    if !expected_tombstone {
+                        live.push(doc_id);
+                    }
+                    continue;
+                },
+            };
+            report.mismatches.push(mismatch(doc_id, kind));
+        }
+
+        if live.is_empty() {
+            continue;
+        }
+
+        let expected = source
+            .multi_get(keyspace, live.iter().copied())
+            .await
+            .map_err(MigrationError::Source)?
+            .map(|doc| (doc.id(), doc))
+            .collect::<HashMap<_, _>>();
+        let actual = destination
+            .multi_get(keyspace, live.iter().copied())
+            .await
+            .map_err(MigrationError::Destination)?
+            .map(|doc| (doc.id(), doc))
+            .collect::<HashMap<_, _>>();
+
+        for doc_id in live {
+            let expected = expected.get(&doc_id);
+            let actual = actual.get(&doc_id);
+            match (expected, actual) {
+                (Some(expected), Some(actual)) if !documents_match(expected, actual) => {
+                    report.mismatches.push(mismatch(doc_id, MismatchKind::Data));
+                },
+                (Some(_), None) => {
+                    report
+                        .mismatches
+                        .push(mismatch(doc_id, MismatchKind::Missing));
+                },
+                _ => {},
+            }
+        }
+    }
+
+    let mut unexpected = remaining.into_keys().collect::<Vec<_>>();
+    unexpected.sort_unstable();
+    for doc_id in unexpected {
+        report
+            .mismatches
+            .push(mismatch(doc_id, MismatchKind::Unexpected));
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn documents_match(expected: &Document, actual: &Document) -> bool {
+    expected.last_updated() == actual.last_updated() && expected.data() == actual.data()
+}
+
+/// Loads the metadata of every entry within the keyspace.
+async fn load_metadata<S: Storage>(
+    storage: &S,
+    keyspace: &str,
+) -> Result<HashMap<Key, (HLCTimestamp, bool)>, S::Error> {
+    let mut entries = HashMap::new();
+    let mut stream = storage.stream_metadata(keyspace).await?;
+    while let Some(chunk) = stream.try_next().await? {
+        entries.extend(
+            chunk
+                .into_iter()
+                .map(|(doc_id, ts, is_tombstone)| (doc_id, (ts, is_tombstone))),
+        );
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemStore;
+
+    async fn populated_store() -> MemStore {
+        let store = MemStore::default();
+        let docs = (0..25)
+            .map(|id| Document::new(id, HLCTimestamp::from_u64(id), id.to_le_bytes()));
+        store.multi_put("keyspace-1", docs).await.unwrap();
+        store
+            .mark_as_tombstone("keyspace-1", 3, HLCTimestamp::from_u64(100))
+            .await
+            .unwrap();
+        store
+            .mark_as_tombstone("keyspace-2", 7, HLCTimestamp::from_u64(101))
+            .await
+            .unwrap();
+        store
+            .put(
+                "keyspace-2",
+                Document::new(1, HLCTimestamp::from_u64(102), b"Hello".to_vec()),
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn test_migrate_preserves_entries() {
+        let source = populated_store().await;
+        let destination = MemStore::default();
+
+        let options = MigrationOptions::default();
+        let report = migrate(&source, &destination, &options).await.unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                keyspaces: 2,
+                documents: 25,
+                tombstones: 2,
+                skipped: 0,
+            }
+        );
+
+        for keyspace in ["keyspace-1", "keyspace-2"] {
+            let mut expected = load_metadata(&source, keyspace)
+                .await
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>();
+            let mut actual = load_metadata(&destination, keyspace)
+                .await
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>();
+            expected.sort_by_key(|entry| entry.0);
+            actual.sort_by_key(|entry| entry.0);
+            assert_eq!(expected, actual, "Metadata should be copied exactly.");
+        }
+
+        let report = verify(&source, &destination, &options).await.unwrap();
+        assert!(
+            report.is_ok(),
+            "Unexpected mismatches: {:?}",
+            report.mismatches
+        );
+        assert_eq!(report.checked, 27);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_resume() {
+        let source = populated_store().await;
+        let destination = MemStore::default();
+
+        let options = MigrationOptions::default()
+            .with_keyspaces(["keyspace-1".to_string()])
+            .with_resume(true);
+        let report = migrate(&source, &destination, &options).await.unwrap();
+        assert_eq!(report.keyspaces, 1);
+        assert_eq!(report.skipped, 0);
+
+        source
+            .put(
+                "keyspace-1",
+                Document::new(50, HLCTimestamp::from_u64(200), b"new".to_vec()),
+            )
+            .await
+            .unwrap();
+
+        let report = migrate(&source, &destination, &options).await.unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                keyspaces: 1,
+                documents: 1,
+                tombstones: 0,
+                skipped: 25,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_mismatches() {
+        let source = populated_store().await;
+        let destination = MemStore::default();
+
+        let options =
+            MigrationOptions::default().with_keyspaces(["keyspace-2".to_string()]);
+        migrate(&source, &destination, &options).await.unwrap();
+
+        destination
+            .put(
+                "keyspace-2",
+                Document::new(1, HLCTimestamp::from_u64(102), b"Changed".to_vec()),
+            )
+            .await
+            .unwrap();
+        destination
+            .put(
+                "keyspace-2",
+                Document::new(7, HLCTimestamp::from_u64(101), Vec::new()),
+            )
+            .await
+            .unwrap();
+        destination
+            .put(
+                "keyspace-2",
+                Document::new(9, HLCTimestamp::from_u64(103), Vec::new()),
+            )
+            .await
+            .unwrap();
+
+        let report = verify(&source, &destination, &options).await.unwrap();
+        let mut kinds = report
+            .mismatches
+            .iter()
+            .map(|mismatch| (mismatch.doc_id, mismatch.kind))
+            .collect::<Vec<_>>();
+        kinds.sort_by_key(|entry| entry.0);
+        assert_eq!(
+            kinds,
+            vec![
+                (1, MismatchKind::Data),
+                (7, MismatchKind::Tombstone { expected: true }),
+                (9, MismatchKind::Unexpected),
+            ]
+        );
+    }
+}
@@ -18,6 +18,7 @@ use crate::rpc::services::consistency_impl::{
     MultiPutPayload,
     MultiRemovePayload,
 };
+use crate::traffic::TrafficControl;
 use crate::{ConsistencyClient, DocVec, Document, Storage};
 
 const BATCHING_INTERVAL: Duration = Duration::from_secs(1);
@@ -31,6 +32,8 @@ pub struct TaskServiceContext {
     pub(crate) local_node_id: NodeId,
     /// The public RPC address of the node running.
     pub(crate) public_node_addr: SocketAddr,
+    /// The traffic controls used to throttle batches.
+    pub(crate) traffic: TrafficControl,
 }
 
 #[derive(Clone)]
@@ -205,18 +208,23 @@ async fn execute_batch<S>(
 where
     S: Storage,
 {
+    let num_docs = batch.num_docs();
+    let num_bytes = batch.num_bytes();
     let batch = Arc::new(batch);
     let limiter = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
     let mut tasks = Vec::with_capacity(live_members.len());
     for (node_id, &addr) in live_members {
         let node_id = *node_id;
         let limiter = limiter.clone();
+        let traffic = ctx.traffic.clone();
         let batch = batch.clone();
         let channel = ctx.network.get_or_connect(addr);
         let mut client = ConsistencyClient::<S>::new(ctx.clock.clone(), channel);
 
         let task = tokio::spawn(async move {
             let _permit = limiter.acquire().await;
+            traffic.distributor.acquire(num_docs, num_bytes).await;
+            traffic.priority.background().await;
             let resp = client.apply_batch(&batch).await;
             (node_id, addr, resp)
         });
@@ -30,6 +30,7 @@ use crate::keyspace::{
 use crate::replication::MAX_CONCURRENT_REQUESTS;
 use crate::rpc::ReplicationClient;
 use crate::storage::ProgressWatcher;
+use crate::traffic::TrafficControl;
 use crate::{DocVec, ProgressTracker, PutContext, Storage};
 
 const INITIAL_KEYSPACE_WAIT: Duration = if cfg!(any(test, feature = "test-utils")) {
@@ -52,6 +53,8 @@ where
     pub(crate) group: KeyspaceGroup<S>,
     /// The cluster RPC network.
     pub(crate) network: RpcNetwork,
+    /// The traffic controls used to throttle repairs.
+    pub(crate) traffic: TrafficControl,
 }
 
 impl<S> ReplicationCycleContext<S>
@@ -398,6 +401,7 @@ where
     let keyspace = ctx.group.get_or_create_keyspace(&keyspace_name).await;
     let client = ReplicationClient::new(ctx.clock().clone(), channel.clone());
 
+    let traffic = ctx.traffic.clone();
     let progress_tracker = ProgressTracker::default();
     let ctx = PutContext {
         progress: progress_tracker.clone(),
@@ -409,9 +413,14 @@ where
     // The removal task can operate interdependently of the modified handler.
     // If, in the process of handling removals, the modified handler errors,
     // we simply let the removal task continue on as normal.
-    let removal_task = tokio::spawn(handle_removals(keyspace.clone(), removed));
+    let removal_task = tokio::spawn(handle_removals(
+        keyspace.clone(),
+        removed,
+        traffic.clone(),
+        progress_tracker.clone(),
+    ));
 
-    tokio::spawn(handle_modified(client, keyspace, modified, ctx));
+    tokio::spawn(handle_modified(client, keyspace, modified, ctx, traffic));
 
     let start = Instant::now();
     let mut watcher = ProgressWatcher::new(progress_tracker, KEYSPACE_SYNC_TIMEOUT);
@@ -447,28 +456,47 @@ async fn handle_modified<S>(
     keyspace: ActorMailbox<KeyspaceActor<S>>,
     modified: DocVec<DocumentMetadata>,
     ctx: PutContext,
+    traffic: TrafficControl,
 ) -> Result<(), anyhow::Error>
 where
     S: Storage,
 {
+    let chunk_size = traffic
+        .repair
+        .max_docs_per_request(MAX_NUMBER_OF_DOCS_PER_FETCH);
     let doc_id_chunks = modified
-        .chunks(MAX_NUMBER_OF_DOCS_PER_FETCH)
+        .chunks(chunk_size)
         .map(|entries| entries.iter().map(|doc| doc.id).collect::<Vec<_>>());
 
     let total = Instant::now();
     let mut total_num_docs = 0;
     for doc_ids in doc_id_chunks {
+        traffic
+            .repair
+            .acquire_with_progress(doc_ids.len(), 0, &ctx.progress)
+            .await;
+        traffic.priority.background().await;
+
         let start = Instant::now();
         let docs = client.fetch_docs(keyspace.name(), doc_ids).await?;
         debug!(elapsed = ?start.elapsed(), num_docs = docs.len(), "Fetched docs.");
 
         total_num_docs += docs.len();
 
+        // The size of the documents is only known once they've been fetched,
+        // so the bytes are accounted for before they're written to storage.
+        let num_bytes = docs.iter().map(|doc| doc.data().len()).sum();
+        traffic
+            .repair
+            .acquire_with_progress(0, num_bytes, &ctx.progress)
+            .await;
+        traffic.priority.background().await;
+
         let msg = MultiSet {
             source: READ_REPAIR_SOURCE_ID,
             docs: DocVec::from_vec(docs),
             ctx: Some(ctx.clone()),
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
         keyspace.send(msg).await?;
         ctx.progress.register_progress();
@@ -488,6 +516,8 @@ where
 async fn handle_removals<S>(
     keyspace: ActorMailbox<KeyspaceActor<S>>,
     mut removed: DocVec<DocumentMetadata>,
+    traffic: TrafficControl,
+    progress: ProgressTracker,
 ) -> Result<(), anyhow::Error>
 where
     S: Storage,
@@ -496,12 +526,18 @@ where
         return Ok(());
     }
 
+    traffic
+        .repair
+        .acquire_with_progress(removed.len(), 0, &progress)
+        .await;
+    traffic.priority.background().await;
+
     if removed.len() == 1 {
         let doc = removed.remove(0);
         let msg = Del {
             source: READ_REPAIR_SOURCE_ID,
             doc,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
         keyspace.send(msg).await?;
         return Ok(());
@@ -510,7 +546,7 @@ where
     let msg = MultiDel {
         source: READ_REPAIR_SOURCE_ID,
         docs: removed,
-        _marker: PhantomData::<S>::default(),
+        _marker: PhantomData::<S>,
     };
     keyspace.send(msg).await?;
     Ok(())
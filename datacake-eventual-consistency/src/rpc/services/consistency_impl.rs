@@ -8,6 +8,7 @@ use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::core::{Document, DocumentMetadata};
 use crate::keyspace::{KeyspaceGroup, CONSISTENCY_SOURCE_ID};
+use crate::traffic::TrafficControl;
 use crate::{DocVec, ProgressTracker, PutContext, Storage};
 
 macro_rules! try_send {
@@ -27,14 +28,23 @@ where
 {
     group: KeyspaceGroup<S>,
     network: RpcNetwork,
+    traffic: TrafficControl,
 }
 
 impl<S> ConsistencyService<S>
 where
     S: Storage,
 {
-    pub fn new(group: KeyspaceGroup<S>, network: RpcNetwork) -> Self {
-        Self { group, network }
+    pub fn new(
+        group: KeyspaceGroup<S>,
+        network: RpcNetwork,
+        traffic: TrafficControl,
+    ) -> Self {
+        Self {
+            group,
+            network,
+            traffic,
+        }
     }
 
     fn get_put_ctx(&self, ctx: Option<Context>) -> Result<Option<PutContext>, Status> {
@@ -79,6 +89,7 @@ where
         &self,
         msg: Request<PutPayload>,
     ) -> Result<HLCTimestamp, Status> {
+        let _guard = self.traffic.priority.foreground();
         let payload = msg.into_inner().to_owned().map_err(Status::internal)?;
 
         let doc = payload.document;
@@ -90,7 +101,7 @@ where
             source: CONSISTENCY_SOURCE_ID,
             doc,
             ctx,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
 
         let keyspace = self.group.get_or_create_keyspace(&payload.keyspace).await;
@@ -110,6 +121,7 @@ where
         &self,
         msg: Request<MultiPutPayload>,
     ) -> Result<Self::Reply, Status> {
+        let _guard = self.traffic.priority.foreground();
         let payload = msg.into_inner().to_owned().map_err(Status::internal)?;
 
         let ctx = self.get_put_ctx(payload.ctx)?;
@@ -119,7 +131,7 @@ where
             source: CONSISTENCY_SOURCE_ID,
             docs: payload.documents,
             ctx,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
 
         let keyspace = self.group.get_or_create_keyspace(&payload.keyspace).await;
@@ -139,6 +151,7 @@ where
         &self,
         msg: Request<RemovePayload>,
     ) -> Result<Self::Reply, Status> {
+        let _guard = self.traffic.priority.foreground();
         let payload = msg.into_inner().to_owned().map_err(Status::internal)?;
 
         self.group.clock().register_ts(payload.timestamp).await;
@@ -146,7 +159,7 @@ where
         let msg = crate::keyspace::Del {
             source: CONSISTENCY_SOURCE_ID,
             doc: payload.document,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
 
         let keyspace = self.group.get_or_create_keyspace(&payload.keyspace).await;
@@ -166,6 +179,7 @@ where
         &self,
         msg: Request<MultiRemovePayload>,
     ) -> Result<Self::Reply, Status> {
+        let _guard = self.traffic.priority.foreground();
         let payload = msg.into_inner().to_owned().map_err(Status::internal)?;
 
         self.group.clock().register_ts(payload.timestamp).await;
@@ -173,7 +187,7 @@ where
         let msg = crate::keyspace::MultiDel {
             source: CONSISTENCY_SOURCE_ID,
             docs: payload.documents,
-            _marker: PhantomData::<S>::default(),
+            _marker: PhantomData::<S>,
         };
 
         let keyspace = self.group.get_or_create_keyspace(&payload.keyspace).await;
@@ -193,6 +207,9 @@ where
         &self,
         msg: Request<BatchPayload>,
     ) -> Result<Self::Reply, Status> {
+        // Batches are background traffic, client writes should be handled first.
+        self.traffic.priority.background().await;
+
         let msg = msg.into_inner().to_owned().map_err(Status::internal)?;
 
         self.group.clock().register_ts(msg.timestamp).await;
@@ -201,7 +218,7 @@ where
             let msg = crate::keyspace::MultiDel {
                 source: CONSISTENCY_SOURCE_ID,
                 docs: payload.documents,
-                _marker: PhantomData::<S>::default(),
+                _marker: PhantomData::<S>,
             };
 
             let keyspace = self.group.get_or_create_keyspace(&payload.keyspace).await;
@@ -215,7 +232,7 @@ where
                 source: CONSISTENCY_SOURCE_ID,
                 docs: payload.documents,
                 ctx,
-                _marker: PhantomData::<S>::default(),
+                _marker: PhantomData::<S>,
             };
 
             let keyspace = self.group.get_or_create_keyspace(&payload.keyspace).await;
@@ -272,6 +289,33 @@ pub struct BatchPayload {
     pub removed: DocVec<MultiRemovePayload>,
 }
 
+impl BatchPayload {
+    /// The total number of documents modified or removed by the batch.
+    pub fn num_docs(&self) -> usize {
+        let modified = self
+            .modified
+            .iter()
+            .map(|payload| payload.documents.len())
+            .sum::<usize>();
+        let removed = self
+            .removed
+            .iter()
+            .map(|payload| payload.documents.len())
+            .sum::<usize>();
+
+        modified + removed
+    }
+
+    /// The total number of document bytes contained within the batch.
+    pub fn num_bytes(&self) -> usize {
+        self.modified
+            .iter()
+            .flat_map(|payload| payload.documents.iter())
+            .map(|doc| doc.data().len())
+            .sum()
+    }
+}
+
 #[repr(C)]
 #[derive(Serialize, Deserialize, Archive, Debug)]
 #[archive(check_bytes)]
@@ -295,7 +339,11 @@ mod tests {
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
         let storage = group.storage();
-        let service = ConsistencyService::new(group.clone(), RpcNetwork::default());
+        let service = ConsistencyService::new(
+            group.clone(),
+            RpcNetwork::default(),
+            TrafficControl::default(),
+        );
 
         let doc = Document::new(1, clock.get_time().await, b"Hello, world".to_vec());
         let put_req = Request::using_owned(PutPayload {
@@ -332,7 +380,11 @@ mod tests {
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
         let storage = group.storage();
-        let service = ConsistencyService::new(group.clone(), RpcNetwork::default());
+        let service = ConsistencyService::new(
+            group.clone(),
+            RpcNetwork::default(),
+            TrafficControl::default(),
+        );
 
         let doc_1 = Document::new(1, clock.get_time().await, b"Hello, world 1".to_vec());
         let doc_2 = Document::new(2, clock.get_time().await, b"Hello, world 2".to_vec());
@@ -385,7 +437,11 @@ mod tests {
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
         let storage = group.storage();
-        let service = ConsistencyService::new(group.clone(), RpcNetwork::default());
+        let service = ConsistencyService::new(
+            group.clone(),
+            RpcNetwork::default(),
+            TrafficControl::default(),
+        );
 
         let mut doc =
             Document::new(1, clock.get_time().await, b"Hello, world 1".to_vec());
@@ -434,7 +490,11 @@ mod tests {
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
         let storage = group.storage();
-        let service = ConsistencyService::new(group.clone(), RpcNetwork::default());
+        let service = ConsistencyService::new(
+            group.clone(),
+            RpcNetwork::default(),
+            TrafficControl::default(),
+        );
 
         let mut doc_1 =
             Document::new(1, clock.get_time().await, b"Hello, world 1".to_vec());
@@ -1,8 +1,10 @@
 use datacake_crdt::{HLCTimestamp, Key};
 use datacake_rpc::{Handler, Request, RpcService, ServiceRegistry, Status};
+use futures::TryStreamExt;
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::keyspace::{KeyspaceGroup, KeyspaceInfo, LastUpdated};
+use crate::traffic::TrafficControl;
 use crate::{Document, Storage};
 
 pub struct ReplicationService<S>
@@ -10,14 +12,15 @@ where
     S: Storage,
 {
     group: KeyspaceGroup<S>,
+    traffic: TrafficControl,
 }
 
 impl<S> ReplicationService<S>
 where
     S: Storage,
 {
-    pub fn new(group: KeyspaceGroup<S>) -> Self {
-        Self { group }
+    pub fn new(group: KeyspaceGroup<S>, traffic: TrafficControl) -> Self {
+        Self { group, traffic }
     }
 }
 
@@ -91,6 +94,9 @@ where
         let clock = self.group.clock();
         clock.register_ts(msg.timestamp).await;
 
+        // Serving repairs is background traffic, client writes should be handled first.
+        self.traffic.priority.background().await;
+
         let storage = self.group.storage();
 
         if msg.doc_ids.len() == 1 {
@@ -108,13 +114,18 @@ where
             });
         }
 
-        let documents = self
-            .group
-            .storage()
-            .multi_get(&msg.keyspace, msg.doc_ids.into_iter())
+        let mut documents = Vec::with_capacity(msg.doc_ids.len());
+        let mut stream = storage
+            .stream_multi_get(&msg.keyspace, msg.doc_ids.into_iter())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        while let Some(chunk) = stream
+            .try_next()
             .await
             .map_err(|e| Status::internal(e.to_string()))?
-            .collect();
+        {
+            documents.extend(chunk);
+        }
 
         let timestamp = self.group.clock().get_time().await;
         Ok(FetchedDocs {
@@ -180,7 +191,7 @@ mod tests {
         static KEYSPACE: &str = "poll-keyspace";
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
-        let service = ReplicationService::new(group.clone());
+        let service = ReplicationService::new(group.clone(), TrafficControl::default());
 
         let timestamp = clock.get_time().await;
         let poll_req = Request::using_owned(PollKeyspace(timestamp)).await;
@@ -217,7 +228,7 @@ mod tests {
         static KEYSPACE: &str = "get-keyspace";
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
-        let service = ReplicationService::new(group.clone());
+        let service = ReplicationService::new(group.clone(), TrafficControl::default());
 
         let keyspace = group.get_or_create_keyspace(KEYSPACE).await;
         keyspace
@@ -225,7 +236,7 @@ mod tests {
                 source: READ_REPAIR_SOURCE_ID,
                 doc: Document::new(1, clock.get_time().await, Vec::new()),
                 ctx: None,
-                _marker: PhantomData::<MemStore>::default(),
+                _marker: PhantomData::<MemStore>,
             })
             .await
             .expect("Set value in store.");
@@ -261,7 +272,7 @@ mod tests {
         static KEYSPACE: &str = "fetch-keyspace";
         let group = KeyspaceGroup::<MemStore>::new_for_test().await;
         let clock = group.clock();
-        let service = ReplicationService::new(group.clone());
+        let service = ReplicationService::new(group.clone(), TrafficControl::default());
 
         let keyspace = group.get_or_create_keyspace(KEYSPACE).await;
 
@@ -271,7 +282,7 @@ mod tests {
                 source: READ_REPAIR_SOURCE_ID,
                 doc: doc.clone(),
                 ctx: None,
-                _marker: PhantomData::<MemStore>::default(),
+                _marker: PhantomData::<MemStore>,
             })
             .await
             .expect("Set value in store.");
@@ -0,0 +1,483 @@
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+use async_trait::async_trait;
+use datacake_crdt::{HLCTimestamp, Key};
+use futures::future::{join_all, try_join_all};
+use futures::StreamExt;
+
+use crate::storage::{DocsStream, MetadataStream, StateSnapshot};
+use crate::{BulkMutationError, Document, DocumentMetadata, PutContext, Storage};
+
+/// Selects which shard a given document belongs to.
+///
+/// The hash must be stable across restarts, otherwise existing documents
+/// will no longer be found within their shard.
+pub trait ShardHasher: Send + Sync + 'static {
+    /// Produces the hash of the given document ID.
+    fn hash_key(&self, key: Key) -> u64;
+}
+
+impl<F> ShardHasher for F
+where
+    F: Fn(Key) -> u64 + Send + Sync + 'static,
+{
+    fn hash_key(&self, key: Key) -> u64 {
+        (self)(key)
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+/// The default [ShardHasher], hashing document IDs with CRC32.
+pub struct Crc32ShardHasher;
+
+impl ShardHasher for Crc32ShardHasher {
+    fn hash_key(&self, key: Key) -> u64 {
+        let mut hasher = crc32fast::Hasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// A [Storage] combinator which spreads documents across several stores.
+///
+/// Each document is assigned to a single shard by its ID, operations covering
+/// several documents are split up and executed on each shard concurrently.
+///
+/// Keyspace level data such as state snapshots are kept in the first shard.
+pub struct Sharded<S, H = Crc32ShardHasher>
+where
+    S: Storage,
+    H: ShardHasher,
+{
+    shards: Vec<S>,
+    hasher: H,
+}
+
+impl<S> Sharded<S>
+where
+    S: Storage,
+{
+    /// Creates a new sharded store using the default [Crc32ShardHasher].
+    ///
+    /// # Panics
+    ///
+    /// If no shards are provided.
+    pub fn new(shards: Vec<S>) -> Self {
+        Self::with_hasher(shards, Crc32ShardHasher)
+    }
+}
+
+impl<S, H> Sharded<S, H>
+where
+    S: Storage,
+    H: ShardHasher,
+{
+    /// Creates a new sharded store using a custom [ShardHasher].
+    ///
+    /// # Panics
+    ///
+    /// If no shards are provided.
+    pub fn with_hasher(shards: Vec<S>, hasher: H) -> Self {
+        assert!(!shards.is_empty(), "At least one shard must be provided.");
+        Self { shards, hasher }
+    }
+
+    #[inline]
+    /// The number of shards documents are spread across.
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    #[inline]
+    /// The underlying shards.
+    pub fn shards(&self) -> &[S] {
+        &self.shards
+    }
+
+    #[inline]
+    /// The index of the shard the given document belongs to.
+    pub fn shard_for(&self, key: Key) -> usize {
+        (self.hasher.hash_key(key) % self.shards.len() as u64) as usize
+    }
+
+    #[inline]
+    /// The shard keyspace level data is stored in.
+    fn primary(&self) -> &S {
+        &self.shards[0]
+    }
+
+    /// Groups the given items by the shard they belong to.
+    fn group_by_shard<T>(
+        &self,
+        items: impl Iterator<Item = T>,
+        key: impl Fn(&T) -> Key,
+    ) -> Vec<Vec<T>> {
+        let mut groups = Vec::new();
+        groups.resize_with(self.shards.len(), Vec::new);
+        for item in items {
+            groups[self.shard_for(key(&item))].push(item);
+        }
+        groups
+    }
+
+    /// Executes a bulk mutation on every shard with at least one item concurrently.
+    ///
+    /// If any shard fails, the first error is returned along with the IDs of every
+    /// document that was successfully mutated across all shards.
+    async fn execute_bulk<'a, T, F, Fut>(
+        &'a self,
+        items: impl Iterator<Item = T>,
+        key: impl Fn(&T) -> Key,
+        op: F,
+    ) -> Result<(), BulkMutationError<S::Error>>
+    where
+        T: Send + 'a,
+        F: Fn(&'a S, Vec<T>) -> Fut,
+        Fut: std::future::Future<Output = Result<(), BulkMutationError<S::Error>>>
+            + Send
+            + 'a,
+    {
+        let groups = self.group_by_shard(items, &key);
+        let tasks = self
+            .shards
+            .iter()
+            .zip(groups)
+            .filter(|(_, items)| !items.is_empty())
+            .map(|(shard, items)| {
+                let ids = items.iter().map(&key).collect::<Vec<_>>();
+                let fut = op(shard, items);
+                async move { (fut.await, ids) }
+            });
+
+        aggregate_bulk_results(join_all(tasks).await)
+    }
+}
+
+/// The result of a bulk mutation on a single shard along with the IDs it was given.
+type ShardResult<E> = (Result<(), BulkMutationError<E>>, Vec<Key>);
+
+/// Merges the results of a bulk mutation executed across several shards.
+fn aggregate_bulk_results<E>(
+    results: Vec<ShardResult<E>>,
+) -> Result<(), BulkMutationError<E>>
+where
+    E: std::error::Error + Send + 'static,
+{
+    let mut error = None;
+    let mut successful_doc_ids = Vec::new();
+    for (res, ids) in results {
+        match res {
+            Ok(()) => successful_doc_ids.extend(ids),
+            Err(e) => {
+                successful_doc_ids.extend_from_slice(e.successful_doc_ids());
+                if error.is_none() {
+                    error = Some(e.into_inner());
+                }
+            },
+        }
+    }
+
+    match error {
+        None => Ok(()),
+        Some(error) => Err(BulkMutationError::new(error, successful_doc_ids)),
+    }
+}
+
+#[async_trait]
+impl<S, H> Storage for Sharded<S, H>
+where
+    S: Storage,
+    H: ShardHasher,
+{
+    type Error = S::Error;
+    type DocsIter = std::vec::IntoIter<Document>;
+    type MetadataIter = std::vec::IntoIter<(Key, HLCTimestamp, bool)>;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        let lists =
+            try_join_all(self.shards.iter().map(|shard| shard.get_keyspace_list()))
+                .await?;
+        let keyspace_set = lists.into_iter().flatten().collect::<BTreeSet<_>>();
+        Ok(keyspace_set.into_iter().collect())
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        // The shard iterators are not required to be `Send`, so they're drained
+        // before being held across any await points.
+        let tasks = self.shards.iter().map(|shard| async move {
+            let iter = shard.iter_metadata(keyspace).await?;
+            Ok::<_, S::Error>(iter.collect::<Vec<_>>())
+        });
+
+        let metadata = try_join_all(tasks)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        Ok(metadata.into_iter())
+    }
+
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let streams = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.stream_metadata(keyspace)),
+        )
+        .await?;
+        Ok(futures::stream::iter(streams).flatten().boxed())
+    }
+
+    async fn stream_metadata_since(
+        &self,
+        keyspace: &str,
+        watermark: HLCTimestamp,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let streams = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.stream_metadata_since(keyspace, watermark)),
+        )
+        .await?;
+        Ok(futures::stream::iter(streams).flatten().boxed())
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.execute_bulk(
+            keys,
+            |key| *key,
+            |shard, keys| shard.remove_tombstones(keyspace, keys.into_iter()),
+        )
+        .await
+    }
+
+    async fn put_with_ctx(
+        &self,
+        keyspace: &str,
+        document: Document,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), Self::Error> {
+        let shard = &self.shards[self.shard_for(document.id())];
+        shard.put_with_ctx(keyspace, document, ctx).await
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        let shard = &self.shards[self.shard_for(document.id())];
+        shard.put(keyspace, document).await
+    }
+
+    async fn multi_put_with_ctx(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.execute_bulk(
+            documents,
+            |doc| doc.id(),
+            |shard, docs| shard.multi_put_with_ctx(keyspace, docs.into_iter(), ctx),
+        )
+        .await
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.execute_bulk(
+            documents,
+            |doc| doc.id(),
+            |shard, docs| shard.multi_put(keyspace, docs.into_iter()),
+        )
+        .await
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        let shard = &self.shards[self.shard_for(doc_id)];
+        shard.mark_as_tombstone(keyspace, doc_id, timestamp).await
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.execute_bulk(
+            documents,
+            |doc| doc.id,
+            |shard, docs| shard.mark_many_as_tombstone(keyspace, docs.into_iter()),
+        )
+        .await
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        let shard = &self.shards[self.shard_for(doc_id)];
+        shard.get(keyspace, doc_id).await
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        let groups = self.group_by_shard(doc_ids, |key| *key);
+        let tasks = self
+            .shards
+            .iter()
+            .zip(groups)
+            .filter(|(_, doc_ids)| !doc_ids.is_empty())
+            .map(|(shard, doc_ids)| async move {
+                let iter = shard.multi_get(keyspace, doc_ids.into_iter()).await?;
+                Ok::<_, S::Error>(iter.collect::<Vec<_>>())
+            });
+
+        let docs = try_join_all(tasks)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        Ok(docs.into_iter())
+    }
+
+    async fn stream_multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<DocsStream<Self::Error>, Self::Error> {
+        let groups = self.group_by_shard(doc_ids, |key| *key);
+        let tasks = self
+            .shards
+            .iter()
+            .zip(groups)
+            .filter(|(_, doc_ids)| !doc_ids.is_empty())
+            .map(|(shard, doc_ids)| {
+                shard.stream_multi_get(keyspace, doc_ids.into_iter())
+            });
+
+        let streams = try_join_all(tasks).await?;
+        Ok(futures::stream::iter(streams).flatten().boxed())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.primary().put_state_snapshot(keyspace, snapshot).await
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        self.primary().get_state_snapshot(keyspace).await
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.primary().remove_state_snapshot(keyspace).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::test_suite::run_test_suite;
+    use crate::test_utils::{MemStore, MockError, MockStorage};
+
+    #[tokio::test]
+    async fn test_sharded_storage_suite() {
+        let shards = (0..4).map(|_| MemStore::default()).collect();
+        run_test_suite(Sharded::new(shards)).await;
+    }
+
+    #[tokio::test]
+    async fn test_sharded_storage_single_shard_suite() {
+        run_test_suite(Sharded::new(vec![MemStore::default()])).await;
+    }
+
+    #[tokio::test]
+    async fn test_custom_hasher() {
+        let shards = (0..3).map(|_| MemStore::default()).collect();
+        let storage = Sharded::with_hasher(shards, |key: Key| key);
+        assert_eq!(storage.shard_for(0), 0);
+        assert_eq!(storage.shard_for(4), 1);
+        assert_eq!(storage.shard_for(5), 2);
+
+        let docs =
+            (0..6).map(|id| Document::new(id, HLCTimestamp::now(0, 0), Vec::new()));
+        storage.multi_put("my-keyspace", docs).await.unwrap();
+
+        for (shard_id, shard) in storage.shards().iter().enumerate() {
+            let ids = shard
+                .iter_metadata("my-keyspace")
+                .await
+                .unwrap()
+                .map(|(id, _, _)| id)
+                .collect::<HashSet<_>>();
+            let expected = (0..6)
+                .filter(|id| *id as usize % 3 == shard_id)
+                .collect::<HashSet<_>>();
+            assert_eq!(
+                ids, expected,
+                "Shard {shard_id} should only hold its documents."
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_error_aggregation() {
+        let failing = MockStorage::default().expect_multi_put(1, |_, docs| {
+            // Only the first document of this shard is persisted.
+            let first = docs.map(|doc| doc.id()).min().unwrap();
+            Err(BulkMutationError::new(
+                MockError("boom".into()),
+                vec![first],
+            ))
+        });
+        let healthy = MockStorage::default().expect_multi_put(1, |_, _| Ok(()));
+
+        // Even IDs are routed to the failing shard.
+        let storage = Sharded::with_hasher(vec![failing, healthy], |key: Key| key);
+
+        let docs =
+            (0..6).map(|id| Document::new(id, HLCTimestamp::now(0, 0), Vec::new()));
+        let err = storage
+            .multi_put("my-keyspace", docs)
+            .await
+            .expect_err("Failing shard should produce an error.");
+
+        assert_eq!(err.cause().0, "boom");
+        let successful = err
+            .successful_doc_ids()
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            successful,
+            HashSet::from_iter([0, 1, 3, 5]),
+            "Successful IDs should include the healthy shard and the failing shard's partial success.",
+        );
+    }
+}
@@ -8,9 +8,24 @@ use async_trait::async_trait;
 use datacake_crdt::{HLCTimestamp, Key};
 use datacake_node::NodeId;
 use datacake_rpc::Channel;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
 
 use crate::core::{Document, DocumentMetadata};
 
+/// The maximum number of entries yielded in a single chunk by the default
+/// streaming implementations.
+pub const STREAM_CHUNK_SIZE: usize = 10_000;
+
+/// A stream producing chunks of document metadata entries.
+///
+/// Each entry contains the document ID, when it was last updated and if it's a tombstone or not.
+pub type MetadataStream<E> =
+    BoxStream<'static, Result<Vec<(Key, HLCTimestamp, bool)>, E>>;
+
+/// A stream producing chunks of documents.
+pub type DocsStream<E> = BoxStream<'static, Result<Vec<Document>, E>>;
+
 /// A utility for tracking the progress a task has made.
 pub struct ProgressWatcher {
     inner: ProgressTracker,
@@ -190,6 +205,18 @@ where
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A persisted snapshot of a keyspace's CRDT state.
+///
+/// Snapshots allow a node to skip rebuilding its state from every metadata entry
+/// on startup, instead only the entries newer than the watermark are replayed.
+pub struct StateSnapshot {
+    /// The newest timestamp applied to the state at the time the snapshot was taken.
+    pub watermark: HLCTimestamp,
+    /// The serialized state, as produced by `OrSWotSet::as_bytes`.
+    pub state: Vec<u8>,
+}
+
 // TODO: Add default methods with more complicated handlers in order to allow room for lnx stuff.
 #[async_trait]
 /// The generic storage trait which encapsulates all the required persistence logic.
@@ -211,6 +238,21 @@ pub trait Storage: Send + Sync + 'static {
         keyspace: &str,
     ) -> Result<Self::MetadataIter, Self::Error>;
 
+    /// Retrieves a stream producing chunks of all values contained within the store.
+    ///
+    /// This should contain the document ID, when it was last updated and if it's a tombstone or not.
+    ///
+    /// By default this collects the entries returned by [Storage::iter_metadata] and yields them
+    /// in chunks of [STREAM_CHUNK_SIZE]. Backends which can page through their entries should
+    /// override this so large keyspaces are never fully held in memory by the store.
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let entries = self.iter_metadata(keyspace).await?.collect::<Vec<_>>();
+        Ok(chunked_stream(entries))
+    }
+
     /// Remove a set of keys which are marked as tombstones store.
     ///
     /// If the given `keyspace` does not exist, it should be created. A new keyspace name should
@@ -340,18 +382,116 @@ pub trait Storage: Send + Sync + 'static {
         keyspace: &str,
         doc_ids: impl Iterator<Item = Key> + Send,
     ) -> Result<Self::DocsIter, Self::Error>;
+
+    /// Retrieves a stream producing chunks of documents belonging to a given keyspace from the store.
+    ///
+    /// No error should be returned if a document id cannot be found, instead it should
+    /// just be ignored.
+    ///
+    /// By default this collects the documents returned by [Storage::multi_get] and yields them
+    /// in chunks of [STREAM_CHUNK_SIZE]. Backends which can fetch documents lazily should
+    /// override this so large requests are never fully held in memory by the store.
+    async fn stream_multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<DocsStream<Self::Error>, Self::Error> {
+        let docs = self.multi_get(keyspace, doc_ids).await?.collect::<Vec<_>>();
+        Ok(chunked_stream(docs))
+    }
+
+    /// Retrieves a stream producing chunks of the metadata entries which were
+    /// last updated after the given timestamp.
+    ///
+    /// By default this filters the entries produced by [Storage::stream_metadata].
+    /// Backends which index their entries by timestamp should override this.
+    async fn stream_metadata_since(
+        &self,
+        keyspace: &str,
+        watermark: HLCTimestamp,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let stream = self
+            .stream_metadata(keyspace)
+            .await?
+            .map_ok(move |chunk| {
+                chunk
+                    .into_iter()
+                    .filter(|entry| entry.1 > watermark)
+                    .collect::<Vec<_>>()
+            })
+            .try_filter(|chunk| futures::future::ready(!chunk.is_empty()));
+
+        Ok(stream.boxed())
+    }
+
+    /// Persists a snapshot of the given keyspace's state, replacing any existing snapshot.
+    ///
+    /// Snapshots are optional, by default they are discarded.
+    async fn put_state_snapshot(
+        &self,
+        _keyspace: &str,
+        _snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Retrieves the last persisted snapshot of the given keyspace's state, if any.
+    ///
+    /// Returning `None` causes the state to be rebuilt from all metadata entries.
+    async fn get_state_snapshot(
+        &self,
+        _keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Removes the persisted snapshot of the given keyspace's state, if any.
+    ///
+    /// This is called before any change is made which the snapshot could not be
+    /// brought up to date with, a snapshot must not be returned after this completes.
+    async fn remove_state_snapshot(&self, _keyspace: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Produces a stream yielding the given entries in chunks of [STREAM_CHUNK_SIZE].
+fn chunked_stream<T, E>(entries: Vec<T>) -> BoxStream<'static, Result<Vec<T>, E>>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    let mut entries = entries.into_iter();
+    let chunks = std::iter::from_fn(move || {
+        let chunk = entries.by_ref().take(STREAM_CHUNK_SIZE).collect::<Vec<_>>();
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    });
+
+    futures::stream::iter(chunks).boxed()
 }
 
 #[cfg(any(test, feature = "test-utils", feature = "test-suite"))]
 pub mod test_suite {
     use std::any::type_name;
     use std::collections::HashSet;
+    use std::future::Future;
     use std::hash::Hash;
 
     use datacake_crdt::{HLCTimestamp, Key};
+    use futures::future::join_all;
+    use futures::TryStreamExt;
 
     use crate::core::Document;
-    use crate::storage::Storage;
+    use crate::storage::{
+        DocsStream,
+        MetadataStream,
+        StateSnapshot,
+        Storage,
+        STREAM_CHUNK_SIZE,
+    };
     use crate::{BulkMutationError, DocumentMetadata, PutContext};
 
     /// A wrapping type around another `Storage` implementation that
@@ -385,6 +525,14 @@ pub mod test_suite {
             self.0.iter_metadata(keyspace).await
         }
 
+        async fn stream_metadata(
+            &self,
+            keyspace: &str,
+        ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+            info!(keyspace = keyspace, "stream_metadata");
+            self.0.stream_metadata(keyspace).await
+        }
+
         async fn remove_tombstones(
             &self,
             keyspace: &str,
@@ -477,6 +625,59 @@ pub mod test_suite {
             info!(keyspace = keyspace, doc_ids = ?doc_ids, "multi_get");
             self.0.multi_get(keyspace, doc_ids.into_iter()).await
         }
+
+        async fn stream_multi_get(
+            &self,
+            keyspace: &str,
+            doc_ids: impl Iterator<Item = Key> + Send,
+        ) -> Result<DocsStream<Self::Error>, Self::Error> {
+            let doc_ids = doc_ids.collect::<Vec<_>>();
+            info!(
+                keyspace = keyspace,
+                num_doc_ids = doc_ids.len(),
+                "stream_multi_get"
+            );
+            self.0.stream_multi_get(keyspace, doc_ids.into_iter()).await
+        }
+
+        async fn stream_metadata_since(
+            &self,
+            keyspace: &str,
+            watermark: HLCTimestamp,
+        ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+            info!(keyspace = keyspace, watermark = %watermark, "stream_metadata_since");
+            self.0.stream_metadata_since(keyspace, watermark).await
+        }
+
+        async fn put_state_snapshot(
+            &self,
+            keyspace: &str,
+            snapshot: StateSnapshot,
+        ) -> Result<(), Self::Error> {
+            info!(
+                keyspace = keyspace,
+                watermark = %snapshot.watermark,
+                num_bytes = snapshot.state.len(),
+                "put_state_snapshot"
+            );
+            self.0.put_state_snapshot(keyspace, snapshot).await
+        }
+
+        async fn get_state_snapshot(
+            &self,
+            keyspace: &str,
+        ) -> Result<Option<StateSnapshot>, Self::Error> {
+            info!(keyspace = keyspace, "get_state_snapshot");
+            self.0.get_state_snapshot(keyspace).await
+        }
+
+        async fn remove_state_snapshot(
+            &self,
+            keyspace: &str,
+        ) -> Result<(), Self::Error> {
+            info!(keyspace = keyspace, "remove_state_snapshot");
+            self.0.remove_state_snapshot(keyspace).await
+        }
     }
 
     #[tokio::test]
@@ -500,6 +701,42 @@ pub mod test_suite {
 
         test_basic_metadata_test(&storage, &mut clock).await;
         info!("test_basic_metadata_test OK");
+
+        test_stream_semantics(&storage, &mut clock).await;
+        info!("test_stream_semantics OK");
+
+        test_snapshot_semantics(&storage, &mut clock).await;
+        info!("test_snapshot_semantics OK");
+
+        test_concurrent_writes(&storage, &mut clock).await;
+        info!("test_concurrent_writes OK");
+
+        test_tombstone_then_put(&storage, &mut clock).await;
+        info!("test_tombstone_then_put OK");
+
+        test_large_batches(&storage, &mut clock).await;
+        info!("test_large_batches OK");
+    }
+
+    /// Checks that a persistent store keeps its contents after being dropped
+    /// and opened again.
+    ///
+    /// The `open` closure is called each time the store should be (re)opened
+    /// and must return a store backed by the same location every time.
+    pub async fn run_persistence_test_suite<S, F, Fut>(mut open: F)
+    where
+        S: Storage,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = S>,
+    {
+        let mut clock = HLCTimestamp::now(0, 0);
+        info!(
+            "Starting persistence test suite for storage: {}",
+            type_name::<S>()
+        );
+
+        test_reopen_durability(&mut open, &mut clock).await;
+        info!("test_reopen_durability OK");
     }
 
     #[instrument(name = "test_keyspace_semantics", skip(storage))]
@@ -877,6 +1114,763 @@ pub mod test_suite {
         assert!(res.is_empty(), "Expected no documents to be returned.");
     }
 
+    #[instrument(name = "test_stream_semantics", skip(storage))]
+    async fn test_stream_semantics<S: Storage>(storage: &S, clock: &mut HLCTimestamp) {
+        info!("Starting test");
+
+        static KEYSPACE: &str = "stream-test-keyspace";
+
+        let chunks = storage
+            .stream_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata stream.")
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("Consume metadata stream.");
+        assert!(
+            chunks.iter().all(|chunk| chunk.is_empty()),
+            "New keyspace stream should be empty."
+        );
+
+        // Enough documents to span several chunks.
+        let num_docs = (STREAM_CHUNK_SIZE * 2 + 10) as Key;
+        let docs = (0..num_docs)
+            .map(|id| Document::new(id, clock.send().unwrap(), id.to_le_bytes()))
+            .collect::<Vec<_>>();
+        storage
+            .multi_put(KEYSPACE, docs.clone().into_iter())
+            .await
+            .expect("Put documents");
+
+        let mut removed = DocumentMetadata::new(3, clock.send().unwrap());
+        storage
+            .mark_as_tombstone(KEYSPACE, removed.id, removed.last_updated)
+            .await
+            .expect("Mark document as tombstone.");
+
+        let expected = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(expected.len(), num_docs as usize);
+
+        let chunks = storage
+            .stream_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata stream.")
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("Consume metadata stream.");
+        assert!(
+            chunks.len() > 1,
+            "Stream should deliver large keyspaces in several chunks."
+        );
+        let num_entries = chunks.iter().map(|chunk| chunk.len()).sum::<usize>();
+        assert_eq!(
+            num_entries, num_docs as usize,
+            "Stream should yield each entry exactly once."
+        );
+        let metadata = chunks
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(
+            metadata, expected,
+            "Streamed metadata entries should match iterated entries."
+        );
+
+        let doc_ids = [1, 2, 3, 4, num_docs + 1];
+        let expected = storage
+            .multi_get(KEYSPACE, doc_ids.into_iter())
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            expected,
+            to_hashset([docs[1].clone(), docs[2].clone(), docs[4].clone(),]),
+            "Expected returned documents to match.",
+        );
+        let streamed = storage
+            .stream_multi_get(KEYSPACE, doc_ids.into_iter())
+            .await
+            .expect("Produce docs stream.")
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("Consume docs stream.")
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            streamed, expected,
+            "Streamed documents should match fetched documents."
+        );
+
+        let streamed = storage
+            .stream_multi_get(KEYSPACE, 0..num_docs)
+            .await
+            .expect("Produce docs stream.")
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("Consume docs stream.");
+        let num_streamed = streamed.iter().map(|chunk| chunk.len()).sum::<usize>();
+        assert_eq!(
+            num_streamed,
+            num_docs as usize - 1,
+            "All documents apart from the tombstone should be streamed."
+        );
+
+        removed.last_updated = clock.send().unwrap();
+        let keys = (0..num_docs)
+            .map(|id| DocumentMetadata::new(id, removed.last_updated))
+            .collect::<Vec<_>>();
+        storage
+            .mark_many_as_tombstone(KEYSPACE, keys.into_iter())
+            .await
+            .expect("Mark documents as tombstones.");
+        storage
+            .remove_tombstones(KEYSPACE, 0..num_docs)
+            .await
+            .expect("Remove tombstone entries.");
+    }
+
+    #[instrument(name = "test_snapshot_semantics", skip(storage))]
+    async fn test_snapshot_semantics<S: Storage>(storage: &S, clock: &mut HLCTimestamp) {
+        info!("Starting test");
+
+        static KEYSPACE: &str = "snapshot-test-keyspace";
+
+        let snapshot = storage
+            .get_state_snapshot(KEYSPACE)
+            .await
+            .expect("Get state snapshot.");
+        assert!(
+            snapshot.is_none(),
+            "New keyspace should not have a snapshot."
+        );
+
+        let doc_1 = Document::new(1, clock.send().unwrap(), b"Hello, world 1".to_vec());
+        let doc_2 = Document::new(2, clock.send().unwrap(), b"Hello, world 2".to_vec());
+        storage
+            .multi_put(KEYSPACE, [doc_1.clone(), doc_2.clone()].into_iter())
+            .await
+            .expect("Put documents");
+
+        let watermark = doc_2.last_updated();
+        let snapshot = StateSnapshot {
+            watermark,
+            state: b"some-state".to_vec(),
+        };
+        storage
+            .put_state_snapshot(KEYSPACE, snapshot.clone())
+            .await
+            .expect("Put state snapshot.");
+
+        // Snapshots are optional, but a store which keeps them must return them unchanged.
+        let supports_snapshots = match storage
+            .get_state_snapshot(KEYSPACE)
+            .await
+            .expect("Get state snapshot.")
+        {
+            None => false,
+            Some(loaded) => {
+                assert_eq!(loaded, snapshot, "Loaded snapshot should match persisted.");
+                true
+            },
+        };
+
+        let doc_3 = Document::new(3, clock.send().unwrap(), b"Hello, world 3".to_vec());
+        storage
+            .put(KEYSPACE, doc_3.clone())
+            .await
+            .expect("Put document");
+        let removed = DocumentMetadata::new(1, clock.send().unwrap());
+        storage
+            .mark_as_tombstone(KEYSPACE, removed.id, removed.last_updated)
+            .await
+            .expect("Mark document as tombstone.");
+
+        let entries = storage
+            .stream_metadata_since(KEYSPACE, watermark)
+            .await
+            .expect("Produce metadata stream.")
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("Consume metadata stream.")
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            entries,
+            to_hashset([
+                (doc_3.id(), doc_3.last_updated(), false),
+                (removed.id, removed.last_updated, true),
+            ]),
+            "Only entries newer than the watermark should be returned."
+        );
+
+        if supports_snapshots {
+            let snapshot = StateSnapshot {
+                watermark: removed.last_updated,
+                state: b"some-newer-state".to_vec(),
+            };
+            storage
+                .put_state_snapshot(KEYSPACE, snapshot.clone())
+                .await
+                .expect("Put state snapshot.");
+            let loaded = storage
+                .get_state_snapshot(KEYSPACE)
+                .await
+                .expect("Get state snapshot.");
+            assert_eq!(
+                loaded,
+                Some(snapshot),
+                "Snapshot should replace the existing snapshot."
+            );
+        }
+
+        storage
+            .remove_state_snapshot(KEYSPACE)
+            .await
+            .expect("Remove state snapshot.");
+        let snapshot = storage
+            .get_state_snapshot(KEYSPACE)
+            .await
+            .expect("Get state snapshot.");
+        assert!(snapshot.is_none(), "Snapshot should be removed.");
+
+        let last_updated = clock.send().unwrap();
+        storage
+            .mark_many_as_tombstone(
+                KEYSPACE,
+                [1, 2, 3]
+                    .into_iter()
+                    .map(|id| DocumentMetadata::new(id, last_updated)),
+            )
+            .await
+            .expect("Mark documents as tombstones.");
+        storage
+            .remove_tombstones(KEYSPACE, [1, 2, 3].into_iter())
+            .await
+            .expect("Remove tombstone entries.");
+    }
+
+    #[instrument(name = "test_concurrent_writes", skip(storage))]
+    async fn test_concurrent_writes<S: Storage>(storage: &S, clock: &mut HLCTimestamp) {
+        info!("Starting test");
+
+        static KEYSPACE: &str = "concurrent-test-keyspace";
+        const NUM_WRITERS: Key = 8;
+        const NUM_WRITES: usize = 25;
+
+        // Each writer updates its own document in timestamp order while the
+        // other writers are doing the same, the newest update must be kept.
+        let updates = (0..NUM_WRITERS)
+            .map(|id| {
+                (0..NUM_WRITES)
+                    .map(|n| {
+                        Document::new(
+                            id,
+                            clock.send().unwrap(),
+                            format!("writer {id} update {n}").into_bytes(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        join_all(updates.iter().map(|docs| async move {
+            for doc in docs {
+                storage
+                    .put(KEYSPACE, doc.clone())
+                    .await
+                    .expect("Put document");
+            }
+        }))
+        .await;
+
+        let newest = updates
+            .iter()
+            .map(|docs| docs.last().unwrap().clone())
+            .collect::<Vec<_>>();
+        let res = storage
+            .multi_get(KEYSPACE, 0..NUM_WRITERS)
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset(newest.iter().cloned()),
+            "Each document should hold its newest update."
+        );
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(
+            metadata,
+            to_hashset(
+                newest
+                    .iter()
+                    .map(|doc| (doc.id(), doc.last_updated(), false))
+            ),
+            "Metadata should hold the newest timestamp of each document."
+        );
+
+        // The keyspace orders conflicting writes, so stores are not required to pick
+        // a winner between racing writers. Each write must be applied atomically
+        // however, leaving the document and its metadata in agreement.
+        let id = NUM_WRITERS;
+        let racing = (0..NUM_WRITERS)
+            .map(|n| {
+                Document::new(
+                    id,
+                    clock.send().unwrap(),
+                    format!("racer {n}").into_bytes(),
+                )
+            })
+            .collect::<Vec<_>>();
+        join_all(racing.iter().enumerate().map(|(n, doc)| async move {
+            if n % 2 == 0 {
+                storage
+                    .put(KEYSPACE, doc.clone())
+                    .await
+                    .expect("Put document");
+            } else {
+                storage
+                    .mark_as_tombstone(KEYSPACE, doc.id(), doc.last_updated())
+                    .await
+                    .expect("Mark document as tombstone.");
+            }
+        }))
+        .await;
+
+        let (_, last_updated, is_tombstone) = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .find(|(key, _, _)| *key == id)
+            .expect("Metadata should exist for the raced document.");
+        let position = racing
+            .iter()
+            .position(|doc| doc.last_updated() == last_updated)
+            .expect("Metadata should match one of the racing writes.");
+        let doc = storage.get(KEYSPACE, id).await.expect("Get document.");
+        if is_tombstone {
+            assert_eq!(position % 2, 1, "Only deletes should leave a tombstone.");
+            assert!(doc.is_none(), "Tombstoned document should not be returned.");
+        } else {
+            assert_eq!(position % 2, 0, "Only puts should leave a live document.");
+            assert_eq!(
+                doc,
+                Some(racing[position].clone()),
+                "Returned document should match its metadata."
+            );
+        }
+
+        let last_updated = clock.send().unwrap();
+        let ids = 0..NUM_WRITERS + 1;
+        storage
+            .mark_many_as_tombstone(
+                KEYSPACE,
+                ids.clone()
+                    .map(|id| DocumentMetadata::new(id, last_updated)),
+            )
+            .await
+            .expect("Mark documents as tombstones.");
+        storage
+            .remove_tombstones(KEYSPACE, ids)
+            .await
+            .expect("Remove tombstone entries.");
+    }
+
+    #[instrument(name = "test_tombstone_then_put", skip(storage))]
+    async fn test_tombstone_then_put<S: Storage>(storage: &S, clock: &mut HLCTimestamp) {
+        info!("Starting test");
+
+        static KEYSPACE: &str = "tombstone-put-test-keyspace";
+
+        let doc_1 = Document::new(1, clock.send().unwrap(), b"Hello, world".to_vec());
+        storage
+            .put(KEYSPACE, doc_1.clone())
+            .await
+            .expect("Put document");
+        storage
+            .mark_as_tombstone(KEYSPACE, doc_1.id(), clock.send().unwrap())
+            .await
+            .expect("Mark document as tombstone.");
+
+        // A tombstone for a document the store has never seen must still be
+        // replaced by a later put.
+        storage
+            .mark_as_tombstone(KEYSPACE, 2, clock.send().unwrap())
+            .await
+            .expect("Mark document as tombstone.");
+
+        let doc_1 = Document::new(1, clock.send().unwrap(), b"Back again".to_vec());
+        let doc_2 = Document::new(2, clock.send().unwrap(), b"New document".to_vec());
+        storage
+            .put(KEYSPACE, doc_1.clone())
+            .await
+            .expect("Put document");
+        storage
+            .put(KEYSPACE, doc_2.clone())
+            .await
+            .expect("Put document");
+
+        let res = storage
+            .multi_get(KEYSPACE, [1, 2].into_iter())
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset([doc_1.clone(), doc_2.clone()]),
+            "Documents put after a tombstone should be returned."
+        );
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(
+            metadata,
+            to_hashset([
+                (doc_1.id(), doc_1.last_updated(), false),
+                (doc_2.id(), doc_2.last_updated(), false),
+            ]),
+            "Documents put after a tombstone should no longer be tombstones."
+        );
+
+        let last_updated = clock.send().unwrap();
+        storage
+            .mark_many_as_tombstone(
+                KEYSPACE,
+                [1, 2, 3]
+                    .into_iter()
+                    .map(|id| DocumentMetadata::new(id, last_updated)),
+            )
+            .await
+            .expect("Mark documents as tombstones.");
+        let docs = [1, 2, 3]
+            .into_iter()
+            .map(|id| Document::new(id, clock.send().unwrap(), id.to_le_bytes()))
+            .collect::<Vec<_>>();
+        storage
+            .multi_put(KEYSPACE, docs.clone().into_iter())
+            .await
+            .expect("Put documents");
+
+        let res = storage
+            .multi_get(KEYSPACE, [1, 2, 3].into_iter())
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset(docs.iter().cloned()),
+            "Documents multi-put after tombstones should be returned."
+        );
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(
+            metadata,
+            to_hashset(docs.iter().map(|doc| (doc.id(), doc.last_updated(), false))),
+            "Documents multi-put after tombstones should no longer be tombstones."
+        );
+
+        // Purging the tombstone must not prevent the document being written again.
+        let last_updated = clock.send().unwrap();
+        storage
+            .mark_as_tombstone(KEYSPACE, 1, last_updated)
+            .await
+            .expect("Mark document as tombstone.");
+        storage
+            .remove_tombstones(KEYSPACE, [1].into_iter())
+            .await
+            .expect("Remove tombstone entries.");
+        let doc_1 = Document::new(1, clock.send().unwrap(), b"Once more".to_vec());
+        storage
+            .put(KEYSPACE, doc_1.clone())
+            .await
+            .expect("Put document");
+        let doc = storage.get(KEYSPACE, 1).await.expect("Get document.");
+        assert_eq!(
+            doc,
+            Some(doc_1),
+            "Document put after its tombstone was purged should be returned."
+        );
+
+        let last_updated = clock.send().unwrap();
+        storage
+            .mark_many_as_tombstone(
+                KEYSPACE,
+                [1, 2, 3]
+                    .into_iter()
+                    .map(|id| DocumentMetadata::new(id, last_updated)),
+            )
+            .await
+            .expect("Mark documents as tombstones.");
+        storage
+            .remove_tombstones(KEYSPACE, [1, 2, 3].into_iter())
+            .await
+            .expect("Remove tombstone entries.");
+    }
+
+    #[instrument(name = "test_large_batches", skip(storage))]
+    async fn test_large_batches<S: Storage>(storage: &S, clock: &mut HLCTimestamp) {
+        info!("Starting test");
+
+        static KEYSPACE: &str = "large-batch-test-keyspace";
+        let num_docs = (STREAM_CHUNK_SIZE * 3) as Key;
+
+        let docs = (0..num_docs)
+            .map(|id| {
+                Document::new(id, clock.send().unwrap(), vec![id as u8; id as usize % 8])
+            })
+            .collect::<Vec<_>>();
+        storage
+            .multi_put(KEYSPACE, docs.clone().into_iter())
+            .await
+            .expect("Put documents");
+
+        let res = storage
+            .multi_get(KEYSPACE, 0..num_docs)
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset(docs),
+            "Every document of a large batch should be persisted."
+        );
+
+        // Update every other document in a second batch, overlapping the first.
+        let updated = (0..num_docs)
+            .step_by(2)
+            .map(|id| Document::new(id, clock.send().unwrap(), b"updated".to_vec()))
+            .collect::<Vec<_>>();
+        storage
+            .multi_put(KEYSPACE, updated.clone().into_iter())
+            .await
+            .expect("Put documents");
+
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            metadata.len(),
+            num_docs as usize,
+            "Updating documents should not create new entries."
+        );
+        let res = storage
+            .multi_get(KEYSPACE, (0..num_docs).step_by(2))
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset(updated),
+            "Documents updated by a large batch should be returned."
+        );
+
+        let last_updated = clock.send().unwrap();
+        storage
+            .mark_many_as_tombstone(
+                KEYSPACE,
+                (0..num_docs).map(|id| DocumentMetadata::new(id, last_updated)),
+            )
+            .await
+            .expect("Mark documents as tombstones.");
+        #[allow(clippy::needless_collect)]
+        let res = storage
+            .multi_get(KEYSPACE, 0..num_docs)
+            .await
+            .expect("Expected successful get request.")
+            .collect::<Vec<_>>();
+        assert!(res.is_empty(), "Expected no documents to be returned.");
+
+        storage
+            .remove_tombstones(KEYSPACE, 0..num_docs)
+            .await
+            .expect("Remove tombstone entries.");
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .count();
+        assert_eq!(
+            metadata, 0,
+            "Persisted metadata entries should be empty after tombstone purge."
+        );
+    }
+
+    #[instrument(name = "test_reopen_durability", skip(open))]
+    async fn test_reopen_durability<S, F, Fut>(open: &mut F, clock: &mut HLCTimestamp)
+    where
+        S: Storage,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = S>,
+    {
+        info!("Starting test");
+
+        static KEYSPACE: &str = "durability-test-keyspace";
+        static OTHER_KEYSPACE: &str = "durability-test-other-keyspace";
+
+        let storage = InstrumentedStorage(open().await);
+
+        let doc_1 = Document::new(1, clock.send().unwrap(), b"Hello, world".to_vec());
+        let doc_2 = Document::new(2, clock.send().unwrap(), b"Goodbye".to_vec());
+        storage
+            .put(KEYSPACE, doc_1.clone())
+            .await
+            .expect("Put document");
+        storage
+            .put(KEYSPACE, doc_2.clone())
+            .await
+            .expect("Put document");
+        let removed = DocumentMetadata::new(2, clock.send().unwrap());
+        storage
+            .mark_as_tombstone(KEYSPACE, removed.id, removed.last_updated)
+            .await
+            .expect("Mark document as tombstone.");
+
+        let batch = (0..100)
+            .map(|id| Document::new(id, clock.send().unwrap(), id.to_le_bytes()))
+            .collect::<Vec<_>>();
+        storage
+            .multi_put(OTHER_KEYSPACE, batch.clone().into_iter())
+            .await
+            .expect("Put documents");
+
+        let snapshot = StateSnapshot {
+            watermark: clock.send().unwrap(),
+            state: b"some-state".to_vec(),
+        };
+        storage
+            .put_state_snapshot(KEYSPACE, snapshot.clone())
+            .await
+            .expect("Put state snapshot.");
+        let snapshot = storage
+            .get_state_snapshot(KEYSPACE)
+            .await
+            .expect("Get state snapshot.");
+
+        let mut expected_metadata = to_hashset([
+            (doc_1.id(), doc_1.last_updated(), false),
+            (removed.id, removed.last_updated, true),
+        ]);
+        drop(storage);
+
+        let storage = InstrumentedStorage(open().await);
+
+        let mut keyspace_list = storage
+            .get_keyspace_list()
+            .await
+            .expect("Get keyspace list");
+        keyspace_list.sort();
+        assert_eq!(
+            keyspace_list,
+            vec![KEYSPACE.to_string(), OTHER_KEYSPACE.to_string()],
+            "Keyspaces should be kept after reopening the store."
+        );
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(
+            metadata, expected_metadata,
+            "Metadata and tombstones should be kept after reopening the store."
+        );
+        let doc = storage.get(KEYSPACE, 1).await.expect("Get document.");
+        assert_eq!(
+            doc,
+            Some(doc_1.clone()),
+            "Document should be kept after reopening the store."
+        );
+        let doc = storage.get(KEYSPACE, 2).await.expect("Get document.");
+        assert!(
+            doc.is_none(),
+            "Tombstoned document should not be returned after reopening the store."
+        );
+        let res = storage
+            .multi_get(OTHER_KEYSPACE, 0..100)
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset(batch.iter().cloned()),
+            "Batched documents should be kept after reopening the store."
+        );
+        let loaded = storage
+            .get_state_snapshot(KEYSPACE)
+            .await
+            .expect("Get state snapshot.");
+        assert_eq!(
+            loaded, snapshot,
+            "State snapshot should be kept after reopening the store."
+        );
+
+        // Changes made after reopening must be durable too.
+        let doc_2 = Document::new(2, clock.send().unwrap(), b"Hello again".to_vec());
+        storage
+            .put(KEYSPACE, doc_2.clone())
+            .await
+            .expect("Put document");
+        let last_updated = clock.send().unwrap();
+        storage
+            .mark_many_as_tombstone(
+                OTHER_KEYSPACE,
+                (0..50).map(|id| DocumentMetadata::new(id, last_updated)),
+            )
+            .await
+            .expect("Mark documents as tombstones.");
+        expected_metadata = to_hashset([
+            (doc_1.id(), doc_1.last_updated(), false),
+            (doc_2.id(), doc_2.last_updated(), false),
+        ]);
+        drop(storage);
+
+        let storage = InstrumentedStorage(open().await);
+
+        let metadata = storage
+            .iter_metadata(KEYSPACE)
+            .await
+            .expect("Produce metadata iterator.")
+            .collect::<HashSet<(Key, HLCTimestamp, bool)>>();
+        assert_eq!(
+            metadata, expected_metadata,
+            "Updates made after reopening should be kept."
+        );
+        let res = storage
+            .multi_get(KEYSPACE, [1, 2].into_iter())
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset([doc_1, doc_2]),
+            "Updates made after reopening should be kept."
+        );
+        let res = storage
+            .multi_get(OTHER_KEYSPACE, 0..100)
+            .await
+            .expect("Expected successful get request.")
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            res,
+            to_hashset(batch.into_iter().skip(50)),
+            "Tombstones made after reopening should be kept."
+        );
+    }
+
     fn to_hashset<T: Hash + Eq>(iter: impl IntoIterator<Item = T>) -> HashSet<T> {
         iter.into_iter().collect()
     }
@@ -2,12 +2,16 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::mem;
 use std::ops::AddAssign;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use datacake_crdt::{HLCTimestamp, Key};
 use parking_lot::{Mutex, RwLock};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use crate::core::DocumentMetadata;
-use crate::storage::BulkMutationError;
+use crate::storage::{BulkMutationError, StateSnapshot};
 use crate::{Document, PutContext, Storage};
 
 #[derive(Debug, thiserror::Error)]
@@ -281,6 +285,7 @@ pub struct MemStore {
     #[allow(clippy::complexity)]
     metadata: RwLock<HashMap<String, HashMap<Key, (HLCTimestamp, bool)>>>,
     data: RwLock<HashMap<String, HashMap<Key, Document>>>,
+    snapshots: RwLock<HashMap<String, StateSnapshot>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -402,14 +407,11 @@ impl Storage for MemStore {
                     entries.remove(&doc.id);
                 }
             });
-        self.metadata
-            .write()
-            .entry(keyspace.to_string())
-            .and_modify(|entries| {
-                for doc in docs {
-                    entries.insert(doc.id, (doc.last_updated, true));
-                }
-            });
+        let mut metadata = self.metadata.write();
+        let entries = metadata.entry(keyspace.to_string()).or_default();
+        for doc in docs {
+            entries.insert(doc.id, (doc.last_updated, true));
+        }
 
         Ok(())
     }
@@ -443,4 +445,632 @@ impl Storage for MemStore {
 
         Ok(docs.into_iter())
     }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.snapshots
+            .write()
+            .insert(keyspace.to_string(), snapshot);
+        Ok(())
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        Ok(self.snapshots.read().get(keyspace).cloned())
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.snapshots.write().remove(keyspace);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// A method of the [Storage] trait which faults can be injected into.
+///
+/// The `*_with_ctx` variants share the same method as their plain counterparts,
+/// and the streaming methods are served by [StorageMethod::IterMetadata] and
+/// [StorageMethod::MultiGet].
+pub enum StorageMethod {
+    GetKeyspaceList,
+    IterMetadata,
+    RemoveTombstones,
+    Put,
+    MultiPut,
+    MarkAsTombstone,
+    MarkManyAsTombstone,
+    Get,
+    MultiGet,
+    PutStateSnapshot,
+    GetStateSnapshot,
+    RemoveStateSnapshot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The misbehaviour injected into a storage call.
+pub enum Fault {
+    /// The call returns an error without reaching the inner store.
+    Error,
+    /// The call is delayed by the given duration before reaching the inner store.
+    Latency(Duration),
+    /// Bulk mutations only apply the first `n` entries, returning an error listing
+    /// the entries which were applied. Any other method returns an error.
+    Partial(usize),
+    /// The call panics.
+    Panic,
+}
+
+#[derive(Debug, Clone)]
+/// A rule describing when a [Fault] is injected into a [FaultyStorage].
+///
+/// By default a rule applies to every call of every method in every keyspace.
+pub struct FaultRule {
+    fault: Fault,
+    methods: Vec<StorageMethod>,
+    keyspace: Option<String>,
+    skip: usize,
+    limit: Option<usize>,
+    probability: f64,
+}
+
+impl FaultRule {
+    /// Creates a new rule injecting the given fault.
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            methods: Vec::new(),
+            keyspace: None,
+            skip: 0,
+            limit: None,
+            probability: 1.0,
+        }
+    }
+
+    /// Only apply the rule to the given method.
+    ///
+    /// This can be called several times to apply the rule to several methods.
+    pub fn with_method(mut self, method: StorageMethod) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Only apply the rule to calls operating on the given keyspace.
+    pub fn with_keyspace(mut self, keyspace: impl Into<String>) -> Self {
+        self.keyspace = Some(keyspace.into());
+        self
+    }
+
+    /// Let the first `n` matching calls through before the rule applies.
+    pub fn with_skip(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Inject the fault at most `n` times.
+    pub fn with_limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Inject the fault into matching calls with the given probability.
+    pub fn with_probability(mut self, probability: f64) -> Self {
+        self.probability = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    fn matches(&self, method: StorageMethod, keyspace: Option<&str>) -> bool {
+        let method_matches = self.methods.is_empty() || self.methods.contains(&method);
+        let keyspace_matches = match self.keyspace.as_deref() {
+            None => true,
+            Some(expected) => keyspace == Some(expected),
+        };
+        method_matches && keyspace_matches
+    }
+}
+
+struct RuleState {
+    rule: FaultRule,
+    num_matched: usize,
+    num_injected: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// An error returned by a [FaultyStorage].
+pub enum FaultyStorageError<E>
+where
+    E: Error + Send + 'static,
+{
+    #[error("{0}")]
+    /// The inner store failed to complete the operation.
+    Store(E),
+    #[error("Injected fault in {0:?}")]
+    /// A fault was injected into the call.
+    Injected(StorageMethod),
+}
+
+impl<E> FaultyStorageError<E>
+where
+    E: Error + Send + 'static,
+{
+    fn from_bulk(error: BulkMutationError<E>) -> BulkMutationError<Self> {
+        let successful_doc_ids = error.successful_doc_ids().to_vec();
+        BulkMutationError::new(Self::Store(error.into_inner()), successful_doc_ids)
+    }
+}
+
+/// A wrapping type around another `Storage` implementation which injects
+/// errors, latency, partial successes and panics into its calls.
+///
+/// Faults are described by [FaultRule]s which can be added and removed while the
+/// store is in use, allowing tests to check the system still converges once
+/// the store starts behaving again.
+pub struct FaultyStorage<S: Storage> {
+    inner: S,
+    rules: Mutex<Vec<RuleState>>,
+    rng: Mutex<StdRng>,
+    num_injected: AtomicUsize,
+}
+
+impl<S: Storage> FaultyStorage<S> {
+    /// Wraps the given store without any faults.
+    pub fn new(inner: S) -> Self {
+        Self::with_seed(inner, rand::random())
+    }
+
+    /// Wraps the given store, deciding probabilistic faults with a RNG
+    /// using the given seed so failures can be reproduced.
+    pub fn with_seed(inner: S, seed: u64) -> Self {
+        Self {
+            inner,
+            rules: Mutex::new(Vec::new()),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            num_injected: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    /// The wrapped store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Adds a rule, rules are checked in the order they were added.
+    pub fn inject(&self, rule: FaultRule) {
+        self.rules.lock().push(RuleState {
+            rule,
+            num_matched: 0,
+            num_injected: 0,
+        });
+    }
+
+    /// Removes every rule, returning the store to normal behaviour.
+    pub fn clear_faults(&self) {
+        self.rules.lock().clear();
+    }
+
+    #[inline]
+    /// The total number of faults injected so far.
+    pub fn num_injected(&self) -> usize {
+        self.num_injected.load(Ordering::Relaxed)
+    }
+
+    /// Applies any matching rules to the call.
+    ///
+    /// Latency is applied and panics are raised immediately, the first matching
+    /// error fault is returned for the caller to apply.
+    async fn apply_faults(
+        &self,
+        method: StorageMethod,
+        keyspace: Option<&str>,
+    ) -> Option<Fault> {
+        let faults = {
+            let mut rules = self.rules.lock();
+            let mut rng = self.rng.lock();
+
+            let mut faults = Vec::new();
+            for state in rules.iter_mut() {
+                if !state.rule.matches(method, keyspace) {
+                    continue;
+                }
+
+                state.num_matched += 1;
+                if state.num_matched <= state.rule.skip
+                    || state
+                        .rule
+                        .limit
+                        .is_some_and(|limit| state.num_injected >= limit)
+                    || !rng.gen_bool(state.rule.probability)
+                {
+                    continue;
+                }
+
+                state.num_injected += 1;
+                faults.push(state.rule.fault.clone());
+            }
+            faults
+        };
+
+        self.num_injected.fetch_add(faults.len(), Ordering::Relaxed);
+
+        let mut error = None;
+        for fault in faults {
+            match fault {
+                Fault::Latency(duration) => tokio::time::sleep(duration).await,
+                Fault::Panic => panic!("Injected panic in {method:?}"),
+                fault => {
+                    error.get_or_insert(fault);
+                },
+            }
+        }
+        error
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Storage> Storage for FaultyStorage<S> {
+    type Error = FaultyStorageError<S::Error>;
+    type DocsIter = S::DocsIter;
+    type MetadataIter = S::MetadataIter;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        let method = StorageMethod::GetKeyspaceList;
+        if self.apply_faults(method, None).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .get_keyspace_list()
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        let method = StorageMethod::IterMetadata;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .iter_metadata(keyspace)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let method = StorageMethod::RemoveTombstones;
+        match self.apply_faults(method, Some(keyspace)).await {
+            None => self
+                .inner
+                .remove_tombstones(keyspace, keys)
+                .await
+                .map_err(FaultyStorageError::from_bulk),
+            Some(Fault::Partial(n)) => {
+                let applied = keys.take(n).collect::<Vec<_>>();
+                self.inner
+                    .remove_tombstones(keyspace, applied.iter().copied())
+                    .await
+                    .map_err(FaultyStorageError::from_bulk)?;
+                Err(BulkMutationError::new(
+                    FaultyStorageError::Injected(method),
+                    applied,
+                ))
+            },
+            Some(_) => Err(BulkMutationError::empty_with_error(
+                FaultyStorageError::Injected(method),
+            )),
+        }
+    }
+
+    async fn put_with_ctx(
+        &self,
+        keyspace: &str,
+        document: Document,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), Self::Error> {
+        let method = StorageMethod::Put;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .put_with_ctx(keyspace, document, ctx)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        self.put_with_ctx(keyspace, document, None).await
+    }
+
+    async fn multi_put_with_ctx(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+        ctx: Option<&PutContext>,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let method = StorageMethod::MultiPut;
+        match self.apply_faults(method, Some(keyspace)).await {
+            None => self
+                .inner
+                .multi_put_with_ctx(keyspace, documents, ctx)
+                .await
+                .map_err(FaultyStorageError::from_bulk),
+            Some(Fault::Partial(n)) => {
+                let applied = documents.take(n).collect::<Vec<_>>();
+                let doc_ids = applied.iter().map(|doc| doc.id()).collect();
+                self.inner
+                    .multi_put_with_ctx(keyspace, applied.into_iter(), ctx)
+                    .await
+                    .map_err(FaultyStorageError::from_bulk)?;
+                Err(BulkMutationError::new(
+                    FaultyStorageError::Injected(method),
+                    doc_ids,
+                ))
+            },
+            Some(_) => Err(BulkMutationError::empty_with_error(
+                FaultyStorageError::Injected(method),
+            )),
+        }
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.multi_put_with_ctx(keyspace, documents, None).await
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        let method = StorageMethod::MarkAsTombstone;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .mark_as_tombstone(keyspace, doc_id, timestamp)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        let method = StorageMethod::MarkManyAsTombstone;
+        match self.apply_faults(method, Some(keyspace)).await {
+            None => self
+                .inner
+                .mark_many_as_tombstone(keyspace, documents)
+                .await
+                .map_err(FaultyStorageError::from_bulk),
+            Some(Fault::Partial(n)) => {
+                let applied = documents.take(n).collect::<Vec<_>>();
+                let doc_ids = applied.iter().map(|doc| doc.id).collect();
+                self.inner
+                    .mark_many_as_tombstone(keyspace, applied.into_iter())
+                    .await
+                    .map_err(FaultyStorageError::from_bulk)?;
+                Err(BulkMutationError::new(
+                    FaultyStorageError::Injected(method),
+                    doc_ids,
+                ))
+            },
+            Some(_) => Err(BulkMutationError::empty_with_error(
+                FaultyStorageError::Injected(method),
+            )),
+        }
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        let method = StorageMethod::Get;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .get(keyspace, doc_id)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        let method = StorageMethod::MultiGet;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .multi_get(keyspace, doc_ids)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        let method = StorageMethod::PutStateSnapshot;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .put_state_snapshot(keyspace, snapshot)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        let method = StorageMethod::GetStateSnapshot;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .get_state_snapshot(keyspace)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        let method = StorageMethod::RemoveStateSnapshot;
+        if self.apply_faults(method, Some(keyspace)).await.is_some() {
+            return Err(FaultyStorageError::Injected(method));
+        }
+        self.inner
+            .remove_state_snapshot(keyspace)
+            .await
+            .map_err(FaultyStorageError::Store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::test_suite::run_test_suite;
+
+    static KEYSPACE: &str = "my-keyspace";
+
+    fn docs(ids: impl Iterator<Item = Key>) -> Vec<Document> {
+        ids.map(|id| Document::new(id, HLCTimestamp::from_u64(id), Vec::new()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_faulty_storage_without_faults() {
+        run_test_suite(FaultyStorage::new(MemStore::default())).await;
+    }
+
+    #[tokio::test]
+    async fn test_scripted_faults() {
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(
+            FaultRule::new(Fault::Error)
+                .with_method(StorageMethod::Put)
+                .with_skip(1)
+                .with_limit(2),
+        );
+
+        let results = futures::future::join_all(
+            docs(0..5).into_iter().map(|doc| storage.put(KEYSPACE, doc)),
+        )
+        .await;
+        let failed = results.iter().filter(|res| res.is_err()).count();
+        assert!(results[0].is_ok(), "The first call should be skipped.");
+        assert_eq!(failed, 2);
+        assert_eq!(storage.num_injected(), 2);
+
+        storage
+            .get(KEYSPACE, 0)
+            .await
+            .expect("Other methods should be unaffected.");
+    }
+
+    #[tokio::test]
+    async fn test_keyspace_faults() {
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(FaultRule::new(Fault::Error).with_keyspace("broken"));
+
+        storage
+            .put(KEYSPACE, docs(0..1).remove(0))
+            .await
+            .expect("Other keyspaces should be unaffected.");
+        let err = storage
+            .get("broken", 0)
+            .await
+            .expect_err("Fault should be injected.");
+        assert!(matches!(
+            err,
+            FaultyStorageError::Injected(StorageMethod::Get)
+        ));
+
+        storage.clear_faults();
+        storage.get("broken", 0).await.expect("Faults are cleared.");
+    }
+
+    #[tokio::test]
+    async fn test_partial_faults() {
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(FaultRule::new(Fault::Partial(2)).with_limit(1));
+
+        let err = storage
+            .multi_put(KEYSPACE, docs(0..5).into_iter())
+            .await
+            .expect_err("Fault should be injected.");
+        assert_eq!(err.successful_doc_ids(), [0, 1]);
+
+        let stored = storage
+            .multi_get(KEYSPACE, 0..5)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(stored, docs(0..2));
+    }
+
+    #[tokio::test]
+    async fn test_probabilistic_faults() {
+        let run = |seed| async move {
+            let storage = FaultyStorage::with_seed(MemStore::default(), seed);
+            storage.inject(FaultRule::new(Fault::Error).with_probability(0.5));
+
+            let mut failed = Vec::new();
+            for doc in docs(0..100) {
+                let doc_id = doc.id();
+                if storage.put(KEYSPACE, doc).await.is_err() {
+                    failed.push(doc_id);
+                }
+            }
+            failed
+        };
+
+        let failed = run(42).await;
+        assert!(failed.len() > 10 && failed.len() < 90, "{}", failed.len());
+        assert_eq!(failed, run(42).await, "Faults should be reproducible.");
+    }
+
+    #[tokio::test]
+    async fn test_latency_faults() {
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(FaultRule::new(Fault::Latency(Duration::from_millis(50))));
+
+        let start = Instant::now();
+        storage
+            .get(KEYSPACE, 0)
+            .await
+            .expect("Latency is not an error.");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Injected panic in Get")]
+    async fn test_panic_faults() {
+        let storage = FaultyStorage::new(MemStore::default());
+        storage.inject(FaultRule::new(Fault::Panic).with_method(StorageMethod::Get));
+        let _ = storage.get(KEYSPACE, 0).await;
+    }
 }
@@ -0,0 +1,378 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use crate::ProgressTracker;
+
+/// The maximum amount of time background traffic will yield to client traffic
+/// before it is allowed to continue regardless.
+///
+/// This prevents a constant stream of client writes starving replication entirely.
+const MAX_BACKGROUND_DELAY: Duration = Duration::from_millis(250);
+/// The maximum amount of time a rate limited task will sleep before
+/// registering progress with its tracker.
+const PROGRESS_TICK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+/// A throughput limit applied to a class of background traffic.
+///
+/// By default no limits are applied.
+pub struct RateLimit {
+    docs_per_sec: Option<u64>,
+    bytes_per_sec: Option<u64>,
+}
+
+impl RateLimit {
+    /// Creates a new limit which does not restrict traffic.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of documents which can be processed per second.
+    pub fn with_docs_per_sec(mut self, docs_per_sec: u64) -> Self {
+        self.docs_per_sec = Some(docs_per_sec.max(1));
+        self
+    }
+
+    /// Limits the number of document bytes which can be processed per second.
+    pub fn with_bytes_per_sec(mut self, bytes_per_sec: u64) -> Self {
+        self.bytes_per_sec = Some(bytes_per_sec.max(1));
+        self
+    }
+
+    #[inline]
+    /// The maximum number of documents which can be processed per second.
+    pub fn docs_per_sec(&self) -> Option<u64> {
+        self.docs_per_sec
+    }
+
+    #[inline]
+    /// The maximum number of document bytes which can be processed per second.
+    pub fn bytes_per_sec(&self) -> Option<u64> {
+        self.bytes_per_sec
+    }
+
+    #[inline]
+    /// Returns if the limit does not restrict traffic at all.
+    pub fn is_unlimited(&self) -> bool {
+        self.docs_per_sec.is_none() && self.bytes_per_sec.is_none()
+    }
+}
+
+#[derive(Clone, Default)]
+/// The traffic controls shared by the client path and background services.
+pub(crate) struct TrafficControl {
+    /// The gate prioritising client traffic over background traffic.
+    pub(crate) priority: PriorityGate,
+    /// The limiter applied to documents fetched by the replication cycle.
+    pub(crate) repair: RateLimiter,
+    /// The limiter applied to batches sent by the task distributor.
+    pub(crate) distributor: RateLimiter,
+}
+
+impl TrafficControl {
+    pub(crate) fn new(repair: RateLimit, distributor: RateLimit) -> Self {
+        Self {
+            priority: PriorityGate::default(),
+            repair: RateLimiter::new(repair),
+            distributor: RateLimiter::new(distributor),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+/// A token bucket limiter over both documents and bytes.
+///
+/// Acquiring more than is currently available puts the bucket into debt,
+/// which must be paid back before the next caller may continue. This allows
+/// requests larger than the per-second limit without stalling forever.
+pub(crate) struct RateLimiter {
+    inner: Option<Arc<Mutex<Buckets>>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        if limit.is_unlimited() {
+            return Self::default();
+        }
+
+        let now = Instant::now();
+        let buckets = Buckets {
+            docs: limit.docs_per_sec.map(|rate| TokenBucket::new(rate, now)),
+            bytes: limit.bytes_per_sec.map(|rate| TokenBucket::new(rate, now)),
+        };
+
+        Self {
+            inner: Some(Arc::new(Mutex::new(buckets))),
+        }
+    }
+
+    /// The maximum number of documents which should be requested at once,
+    /// given the desired number of documents.
+    ///
+    /// This keeps a single request from putting the limiter into more than
+    /// a second of debt.
+    pub(crate) fn max_docs_per_request(&self, desired: usize) -> usize {
+        let limit = self
+            .inner
+            .as_ref()
+            .and_then(|inner| inner.lock().docs.as_ref().map(|bucket| bucket.rate));
+
+        match limit {
+            Some(rate) => desired.min(rate as usize).max(1),
+            None => desired,
+        }
+    }
+
+    /// Waits until the given number of documents and bytes can be processed.
+    pub(crate) async fn acquire(&self, num_docs: usize, num_bytes: usize) {
+        let wait = self.reserve(num_docs, num_bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Waits until the given number of documents and bytes can be processed,
+    /// registering progress with the tracker while waiting.
+    ///
+    /// This stops supervisors from treating a throttled task as a stalled one.
+    pub(crate) async fn acquire_with_progress(
+        &self,
+        num_docs: usize,
+        num_bytes: usize,
+        progress: &ProgressTracker,
+    ) {
+        let mut wait = self.reserve(num_docs, num_bytes);
+        while !wait.is_zero() {
+            let step = wait.min(PROGRESS_TICK);
+            tokio::time::sleep(step).await;
+            progress.register_progress();
+            wait -= step;
+        }
+    }
+
+    fn reserve(&self, num_docs: usize, num_bytes: usize) -> Duration {
+        let inner = match self.inner.as_ref() {
+            None => return Duration::ZERO,
+            Some(inner) => inner,
+        };
+
+        let now = Instant::now();
+        let mut lock = inner.lock();
+        let docs_wait = lock
+            .docs
+            .as_mut()
+            .map(|bucket| bucket.reserve(now, num_docs as u64))
+            .unwrap_or_default();
+        let bytes_wait = lock
+            .bytes
+            .as_mut()
+            .map(|bucket| bucket.reserve(now, num_bytes as u64))
+            .unwrap_or_default();
+
+        docs_wait.max(bytes_wait)
+    }
+}
+
+struct Buckets {
+    docs: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+struct TokenBucket {
+    rate: u64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Takes the given amount of tokens from the bucket, returning how long
+    /// the caller must wait for the bucket to no longer be in debt.
+    fn reserve(&mut self, now: Instant, amount: u64) -> Duration {
+        let rate = self.rate as f64;
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        self.last_refill = now;
+
+        self.tokens -= amount as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+/// A gate which lets client traffic take priority over background traffic.
+///
+/// Client operations hold a [ForegroundGuard] while they are in progress,
+/// background operations wait for the gate to clear before continuing.
+pub(crate) struct PriorityGate {
+    inner: Arc<GateInner>,
+}
+
+#[derive(Default)]
+struct GateInner {
+    in_flight: AtomicUsize,
+    notify: Notify,
+}
+
+impl PriorityGate {
+    /// Marks a client operation as in progress until the guard is dropped.
+    pub(crate) fn foreground(&self) -> ForegroundGuard {
+        self.inner.in_flight.fetch_add(1, Ordering::AcqRel);
+        ForegroundGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// The number of client operations currently in progress.
+    pub(crate) fn num_in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Waits for any in progress client operations to complete.
+    ///
+    /// Background traffic is never held back for longer than [MAX_BACKGROUND_DELAY].
+    pub(crate) async fn background(&self) {
+        let deadline = tokio::time::Instant::now() + MAX_BACKGROUND_DELAY;
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.num_in_flight() == 0 {
+                return;
+            }
+
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// A guard marking a client operation as in progress.
+pub(crate) struct ForegroundGuard {
+    inner: Arc<GateInner>,
+}
+
+impl Drop for ForegroundGuard {
+    fn drop(&mut self) {
+        if self.inner.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.notify.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unlimited_rate_limiter() {
+        let limiter = RateLimiter::new(RateLimit::unlimited());
+        assert!(limiter.inner.is_none());
+        assert_eq!(limiter.reserve(1_000_000, 1_000_000), Duration::ZERO);
+        assert_eq!(limiter.max_docs_per_request(50_000), 50_000);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_debt() {
+        let limit = RateLimit::unlimited()
+            .with_docs_per_sec(100)
+            .with_bytes_per_sec(1_000);
+        let limiter = RateLimiter::new(limit);
+
+        assert_eq!(limiter.reserve(100, 500), Duration::ZERO);
+
+        // The bytes bucket has 500 left, but the docs bucket is now empty.
+        let wait = limiter.reserve(50, 0);
+        assert!(
+            wait > Duration::from_millis(400) && wait <= Duration::from_millis(500),
+            "Expected roughly half a second of docs debt. Got {:?}",
+            wait,
+        );
+
+        let wait = limiter.reserve(0, 2_500);
+        assert!(
+            wait > Duration::from_millis(1_900) && wait <= Duration::from_secs(2),
+            "Expected roughly two seconds of bytes debt. Got {:?}",
+            wait,
+        );
+
+        assert_eq!(limiter.max_docs_per_request(50_000), 100);
+        assert_eq!(limiter.max_docs_per_request(10), 10);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_acquire() {
+        let limiter = RateLimiter::new(RateLimit::unlimited().with_docs_per_sec(20));
+
+        let start = Instant::now();
+        limiter.acquire(20, 0).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        limiter.acquire(5, 0).await;
+        assert!(
+            start.elapsed() >= Duration::from_millis(200),
+            "Limiter should have waited for the bucket to refill.",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_priority_gate_yields_to_foreground() {
+        let gate = PriorityGate::default();
+
+        let start = Instant::now();
+        gate.background().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        let guard = gate.foreground();
+        assert_eq!(gate.num_in_flight(), 1);
+
+        let background = tokio::spawn({
+            let gate = gate.clone();
+            async move {
+                let start = Instant::now();
+                gate.background().await;
+                start.elapsed()
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(guard);
+        assert_eq!(gate.num_in_flight(), 0);
+
+        let waited = background.await.unwrap();
+        assert!(
+            waited >= Duration::from_millis(40) && waited < MAX_BACKGROUND_DELAY,
+            "Background task should wait for the foreground guard. Got {:?}",
+            waited,
+        );
+    }
+
+    #[tokio::test]
+    async fn test_priority_gate_does_not_starve_background() {
+        let gate = PriorityGate::default();
+        let _guard = gate.foreground();
+
+        let start = Instant::now();
+        gate.background().await;
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= MAX_BACKGROUND_DELAY,
+            "Background task should be held back while client traffic is in flight.",
+        );
+        assert!(elapsed < MAX_BACKGROUND_DELAY * 2);
+    }
+}
@@ -188,7 +188,7 @@ async fn test_keyspace_list() -> anyhow::Result<()> {
         .expect("Put doc.");
 
     let doc = handle.get_keyspace_list().await.unwrap();
-    let result = vec![KEYSPACE_1.to_string()];
+    let result = [KEYSPACE_1.to_string()];
     assert!(doc.iter().all(|item| result.contains(item)));
 
     handle
@@ -202,7 +202,7 @@ async fn test_keyspace_list() -> anyhow::Result<()> {
         .expect("Put doc.");
 
     let doc = handle.get_keyspace_list().await.unwrap();
-    let result = vec![KEYSPACE_1.to_string(), KEYSPACE_2.to_string()];
+    let result = [KEYSPACE_1.to_string(), KEYSPACE_2.to_string()];
     assert!(doc.iter().all(|item| result.contains(item)));
 
     handle
@@ -216,7 +216,7 @@ async fn test_keyspace_list() -> anyhow::Result<()> {
         .expect("Put doc.");
 
     let doc = handle.get_keyspace_list().await.unwrap();
-    let result = vec![
+    let result = [
         KEYSPACE_1.to_string(),
         KEYSPACE_2.to_string(),
         KEYSPACE_3.to_string(),
@@ -1,25 +1,124 @@
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 use datacake_crdt::{HLCTimestamp, Key};
-use datacake_eventual_consistency::{Document, DocumentMetadata};
+use datacake_eventual_consistency::{Document, DocumentMetadata, StateSnapshot};
 use flume::{self, Receiver, Sender};
 use futures::channel::oneshot;
 use heed::byteorder::LittleEndian;
-use heed::types::{ByteSlice, Str, Unit, U64};
-use heed::{Database, Env, EnvOpenOptions};
+use heed::types::{Bytes, Str, Unit, U64};
+use heed::{Database, Env, EnvFlags, EnvOpenOptions, MdbError, RoTxn};
 
-type KvDB = Database<U64<LittleEndian>, ByteSlice>;
+type KvDB = Database<U64<LittleEndian>, Bytes>;
 type MetaDB = Database<U64<LittleEndian>, U64<LittleEndian>>;
 type KeyspaceDB = Database<Str, Unit>;
+type SnapshotDB = Database<Str, Bytes>;
 type DatabaseKeyspace = BTreeMap<String, (KvDB, MetaDB)>;
-type Task = Box<dyn FnOnce(&Env, &KeyspaceDB, &mut DatabaseKeyspace) + Send + 'static>;
-
-const DEFAULT_MAP_SIZE: usize = 10 << 20;
-const MAX_NUM_DBS: u32 = 250;
+type Task = Box<dyn FnOnce(&mut Writer) + Send + 'static>;
+type ReadTask = Box<dyn FnOnce(&Reader) + Send + 'static>;
+
+/// The initial size of the memory map.
+pub const DEFAULT_MAP_SIZE: usize = 10 << 20;
+/// The maximum number of named databases, each keyspace uses two databases.
+pub const DEFAULT_MAX_DBS: u32 = 250;
+/// Map sizes are rounded up to a multiple of this, which is a multiple
+/// of every common OS page size.
+const MAP_SIZE_ALIGNMENT: usize = 64 << 10;
+/// The default number of reader threads.
+pub const DEFAULT_NUM_READERS: usize = 4;
 const CAPACITY: usize = 10;
 
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// When LMDB flushes committed transactions to disk.
+pub enum SyncMode {
+    #[default]
+    /// Flush data and metadata on every commit.
+    Full,
+    /// Flush data on every commit, but leave flushing the metadata to the OS.
+    ///
+    /// The last transaction may be lost after a system crash, but the
+    /// database remains intact.
+    NoMetaSync,
+    /// Leave all flushing to the OS.
+    ///
+    /// Any number of recent transactions may be lost after a system crash
+    /// and, depending on the file system, the database may be corrupted.
+    NoSync,
+}
+
+#[derive(Debug, Clone)]
+/// Options for opening the LMDB environment.
+pub struct LmdbOptions {
+    map_size: usize,
+    max_map_size: Option<usize>,
+    max_dbs: u32,
+    sync_mode: SyncMode,
+    num_readers: usize,
+}
+
+impl Default for LmdbOptions {
+    fn default() -> Self {
+        Self {
+            map_size: DEFAULT_MAP_SIZE,
+            max_map_size: None,
+            max_dbs: DEFAULT_MAX_DBS,
+            sync_mode: SyncMode::default(),
+            num_readers: DEFAULT_NUM_READERS,
+        }
+    }
+}
+
+impl LmdbOptions {
+    /// Set the initial size of the memory map in bytes.
+    ///
+    /// By default this is [DEFAULT_MAP_SIZE]. The map is doubled in size
+    /// whenever it becomes full, up to the limit set by [LmdbOptions::with_max_map_size].
+    pub fn with_map_size(mut self, map_size: usize) -> Self {
+        self.map_size = map_size;
+        self
+    }
+
+    /// Set the size in bytes the memory map may grow to.
+    ///
+    /// Once this is reached writes fail with [MdbError::MapFull].
+    /// By default the map grows without limit.
+    pub fn with_max_map_size(mut self, max_map_size: usize) -> Self {
+        self.max_map_size = Some(max_map_size);
+        self
+    }
+
+    /// Set the maximum number of named databases.
+    ///
+    /// By default this is [DEFAULT_MAX_DBS]. Each keyspace uses two databases and
+    /// two are reserved for internal use, so this limits the number of keyspaces
+    /// to `(max_dbs - 2) / 2`.
+    pub fn with_max_dbs(mut self, max_dbs: u32) -> Self {
+        self.max_dbs = max_dbs;
+        self
+    }
+
+    /// Set when committed transactions are flushed to disk.
+    ///
+    /// By default this is [SyncMode::Full].
+    pub fn with_sync_mode(mut self, sync_mode: SyncMode) -> Self {
+        self.sync_mode = sync_mode;
+        self
+    }
+
+    /// Set the number of reader threads.
+    ///
+    /// By default this is [DEFAULT_NUM_READERS]. Each reader thread serves reads from
+    /// its own read transactions, which LMDB executes concurrently with each other and
+    /// with the writer. If `0`, reads are executed on the writer thread.
+    pub fn with_num_readers(mut self, num_readers: usize) -> Self {
+        self.num_readers = num_readers;
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 /// A asynchronous wrapper around a LMDB database.
 ///
@@ -27,13 +126,17 @@ const CAPACITY: usize = 10;
 /// any IO operations from blocking the async context.
 pub struct StorageHandle {
     tx: Sender<Task>,
+    readers: Option<Sender<ReadTask>>,
+    num_readers: usize,
     env: Env,
+    snapshots: SnapshotDB,
 }
 
 impl StorageHandle {
     /// Connects to the LMDB database.
     ///
-    /// This spawns 1 background threads with actions being executed within that thread.
+    /// This spawns 1 background writer thread and [DEFAULT_NUM_READERS] reader threads
+    /// with actions being executed within those threads.
     ///
     /// This approach reduces the affect of writes blocking reads and vice-versa.
     ///
@@ -50,33 +153,63 @@ impl StorageHandle {
     /// # }
     /// ```
     pub async fn open(path: impl AsRef<Path>) -> heed::Result<Self> {
-        let (tx, env) = setup_database(path).await?;
-        Ok(Self { tx, env })
+        Self::open_with_options(path, LmdbOptions::default()).await
+    }
+
+    /// Connects to the LMDB database using the given options.
+    ///
+    /// ```rust
+    /// use datacake_lmdb::{LmdbOptions, StorageHandle, SyncMode};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let options = LmdbOptions::default()
+    ///     .with_map_size(64 << 20)
+    ///     .with_max_dbs(1024)
+    ///     .with_sync_mode(SyncMode::NoMetaSync);
+    /// let storage = StorageHandle::open_with_options("./my-lmdb-options", options)
+    ///     .await
+    ///     .expect("Create database");
+    /// # drop(storage);
+    /// # let _ = std::fs::remove_dir_all("./my-lmdb-options");
+    /// # }
+    /// ```
+    pub async fn open_with_options(
+        path: impl AsRef<Path>,
+        options: LmdbOptions,
+    ) -> heed::Result<Self> {
+        let num_readers = options.num_readers;
+        let (tx, readers, env, snapshots) = setup_database(path, options).await?;
+        Ok(Self {
+            tx,
+            readers,
+            num_readers,
+            env,
+            snapshots,
+        })
+    }
+
+    #[inline]
+    /// The number of reader threads.
+    pub fn num_readers(&self) -> usize {
+        self.num_readers
     }
 
     #[inline]
     /// Get the current heed environment.
+    ///
+    /// WARNING:
+    /// The memory map is resized when it becomes full, which requires that no
+    /// transactions are open, transactions created from this environment
+    /// should be kept short.
     pub fn env(&self) -> &Env {
         &self.env
     }
 
     /// Get the current keyspace list.
     pub(crate) async fn keyspace_list(&self) -> heed::Result<Vec<String>> {
-        let (tx, rx) = oneshot::channel();
-
-        let cb = move |env: &Env,
-                       keyspace_list: &KeyspaceDB,
-                       _databases: &mut DatabaseKeyspace| {
-            let res = read_keyspace_list(env, keyspace_list);
-            let _ = tx.send(res);
-        };
-
-        self.tx
-            .send_async(Box::new(cb))
+        self.submit(|writer| read_keyspace_list(&writer.env, &writer.keyspace_list))
             .await
-            .expect("send message");
-
-        rx.await.unwrap()
     }
 
     /// Execute a PUT operation on the DB.
@@ -105,7 +238,7 @@ impl StorageHandle {
 
         self.submit_task(keyspace, move |env: &Env, kv: &KvDB, meta: &MetaDB| {
             let mut txn = env.write_txn()?;
-            for doc in docs {
+            for doc in docs.iter() {
                 kv.put(&mut txn, &doc.id(), doc.data())?;
                 meta.put(&mut txn, &doc.id(), &doc.last_updated().as_u64())?;
             }
@@ -120,20 +253,50 @@ impl StorageHandle {
         &self,
         keyspace: &str,
     ) -> heed::Result<Vec<(Key, HLCTimestamp, bool)>> {
-        self.submit_task(keyspace, move |env: &Env, kv: &KvDB, meta: &MetaDB| {
+        self.submit_read_task(keyspace, move |txn: &RoTxn, kv: &KvDB, meta: &MetaDB| {
             let mut entries = Vec::new();
-            let txn = env.read_txn()?;
 
-            for pair in meta.iter(&txn)? {
+            for pair in meta.iter(txn)? {
+                let (id, ts) = pair?;
+
+                let is_tombstone = kv.get(txn, &id)?.is_none();
+                entries.push((id, HLCTimestamp::from_u64(ts), is_tombstone));
+            }
+
+            Ok(entries)
+        })
+        .await
+        .map(Option::unwrap_or_default)
+    }
+
+    /// Get a page of the metadata list from the DB.
+    ///
+    /// Entries are returned in key order starting after the given key,
+    /// or from the start of the keyspace if no key is given.
+    pub(crate) async fn get_metadata_page(
+        &self,
+        keyspace: &str,
+        after: Option<Key>,
+        limit: usize,
+    ) -> heed::Result<Vec<(Key, HLCTimestamp, bool)>> {
+        self.submit_read_task(keyspace, move |txn: &RoTxn, kv: &KvDB, meta: &MetaDB| {
+            let mut entries = Vec::with_capacity(limit);
+
+            let start = match after {
+                None => Bound::Unbounded,
+                Some(key) => Bound::Excluded(key),
+            };
+            for pair in meta.range(txn, &(start, Bound::Unbounded))?.take(limit) {
                 let (id, ts) = pair?;
 
-                let is_tombstone = kv.get(&txn, &id)?.is_none();
+                let is_tombstone = kv.get(txn, &id)?.is_none();
                 entries.push((id, HLCTimestamp::from_u64(ts), is_tombstone));
             }
 
             Ok(entries)
         })
         .await
+        .map(Option::unwrap_or_default)
     }
 
     /// Mark an entry as a tombstone.
@@ -163,7 +326,7 @@ impl StorageHandle {
 
         self.submit_task(keyspace, move |env: &Env, kv: &KvDB, meta: &MetaDB| {
             let mut txn = env.write_txn()?;
-            for doc in docs {
+            for doc in docs.iter() {
                 kv.delete(&mut txn, &doc.id)?;
                 meta.put(&mut txn, &doc.id, &doc.last_updated.as_u64())?;
             }
@@ -174,19 +337,39 @@ impl StorageHandle {
     }
 
     /// Clear a tombstone entry.
+    ///
+    /// If the keyspace is left empty it is removed from the keyspace list
+    /// until a new entry is written to it.
     pub(crate) async fn remove_tombstones(
         &self,
         keyspace: &str,
         keys: impl Iterator<Item = Key>,
     ) -> heed::Result<()> {
+        let keyspace = keyspace.to_owned();
         let keys = Vec::from_iter(keys);
 
-        self.submit_task(keyspace, move |env: &Env, _kv: &KvDB, meta: &MetaDB| {
-            let mut txn = env.write_txn()?;
-            for key in keys {
-                meta.delete(&mut txn, &key)?; // Our entry will already be removed.
+        self.submit(move |writer| {
+            let (kv, meta) = match writer.keyspace_dbs(&keyspace, false)? {
+                None => return Ok(()),
+                Some(dbs) => dbs,
+            };
+
+            let mut txn = writer.env.write_txn()?;
+            for key in keys.iter() {
+                meta.delete(&mut txn, key)?; // Our entry will already be removed.
+            }
+
+            let is_empty = meta.is_empty(&txn)?;
+            if is_empty {
+                kv.clear(&mut txn)?;
+                writer.keyspace_list.delete(&mut txn, &keyspace)?;
             }
             txn.commit()?;
+
+            if is_empty {
+                writer.shared.databases.write().unwrap().remove(&keyspace);
+            }
+
             Ok(())
         })
         .await
@@ -198,16 +381,16 @@ impl StorageHandle {
         keyspace: &str,
         key: u64,
     ) -> heed::Result<Option<Document>> {
-        self.submit_task(keyspace, move |env: &Env, kv: &KvDB, meta: &MetaDB| {
-            let txn = env.read_txn()?;
-            if let Some(doc) = kv.get(&txn, &key)? {
-                let ts = meta.get(&txn, &key)?.unwrap();
+        self.submit_read_task(keyspace, move |txn: &RoTxn, kv: &KvDB, meta: &MetaDB| {
+            if let Some(doc) = kv.get(txn, &key)? {
+                let ts = meta.get(txn, &key)?.unwrap();
                 Ok(Some(Document::new(key, HLCTimestamp::from_u64(ts), doc)))
             } else {
                 Ok(None)
             }
         })
         .await
+        .map(Option::flatten)
     }
 
     /// Execute a PUT operation on the DB.
@@ -218,12 +401,11 @@ impl StorageHandle {
     ) -> heed::Result<Vec<Document>> {
         let keys = Vec::from_iter(keys);
 
-        self.submit_task(keyspace, move |env: &Env, kv: &KvDB, meta: &MetaDB| {
+        self.submit_read_task(keyspace, move |txn: &RoTxn, kv: &KvDB, meta: &MetaDB| {
             let mut docs = Vec::with_capacity(keys.len());
-            let txn = env.read_txn()?;
-            for key in keys {
-                if let Some(doc) = kv.get(&txn, &key)? {
-                    let ts = meta.get(&txn, &key)?.unwrap();
+            for &key in keys.iter() {
+                if let Some(doc) = kv.get(txn, &key)? {
+                    let ts = meta.get(txn, &key)?.unwrap();
                     docs.push(Document::new(key, HLCTimestamp::from_u64(ts), doc));
                 }
             }
@@ -231,6 +413,79 @@ impl StorageHandle {
             Ok(docs)
         })
         .await
+        .map(Option::unwrap_or_default)
+    }
+
+    /// Persists a keyspace state snapshot, replacing any existing snapshot.
+    ///
+    /// The snapshot is stored as its little endian watermark followed by the state.
+    pub(crate) async fn put_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> heed::Result<()> {
+        let keyspace = keyspace.to_owned();
+        let snapshots = self.snapshots;
+
+        self.submit_env_task(move |env: &Env| {
+            let mut value = Vec::with_capacity(8 + snapshot.state.len());
+            value.extend_from_slice(&snapshot.watermark.as_u64().to_le_bytes());
+            value.extend_from_slice(&snapshot.state);
+
+            let mut txn = env.write_txn()?;
+            snapshots.put(&mut txn, &keyspace, &value)?;
+            txn.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the persisted keyspace state snapshot, if any.
+    pub(crate) async fn get_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> heed::Result<Option<StateSnapshot>> {
+        let keyspace = keyspace.to_owned();
+        let snapshots = self.snapshots;
+
+        self.submit_env_task(move |env: &Env| {
+            let txn = env.read_txn()?;
+            let value = match snapshots.get(&txn, &keyspace)? {
+                Some(value) if value.len() >= 8 => value,
+                _ => return Ok(None),
+            };
+
+            let (watermark, state) = value.split_at(8);
+            let watermark = u64::from_le_bytes(watermark.try_into().unwrap());
+            Ok(Some(StateSnapshot {
+                watermark: HLCTimestamp::from_u64(watermark),
+                state: state.to_vec(),
+            }))
+        })
+        .await
+    }
+
+    /// Removes the persisted keyspace state snapshot.
+    pub(crate) async fn remove_snapshot(&self, keyspace: &str) -> heed::Result<()> {
+        let keyspace = keyspace.to_owned();
+        let snapshots = self.snapshots;
+
+        self.submit_env_task(move |env: &Env| {
+            let mut txn = env.write_txn()?;
+            snapshots.delete(&mut txn, &keyspace)?;
+            txn.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Submits a task which is not bound to a specific keyspace.
+    async fn submit_env_task<CB, T>(&self, inner: CB) -> heed::Result<T>
+    where
+        T: Send + 'static,
+        CB: Fn(&Env) -> heed::Result<T> + Send + 'static,
+    {
+        self.submit(move |writer| inner(&writer.env)).await
     }
 
     /// Submits a writer task to execute on the KV store.
@@ -240,28 +495,88 @@ impl StorageHandle {
     async fn submit_task<CB, T>(&self, keyspace: &str, inner: CB) -> heed::Result<T>
     where
         T: Send + 'static,
-        CB: FnOnce(&Env, &KvDB, &MetaDB) -> heed::Result<T> + Send + 'static,
+        CB: Fn(&Env, &KvDB, &MetaDB) -> heed::Result<T> + Send + 'static,
+    {
+        let keyspace = keyspace.to_owned();
+
+        self.submit(move |writer| {
+            let (kv, meta) = writer
+                .keyspace_dbs(&keyspace, true)?
+                .expect("Databases should be created");
+            inner(&writer.env, &kv, &meta)
+        })
+        .await
+    }
+
+    /// Submits a read task to execute on one of the reader threads.
+    ///
+    /// If there are no reader threads the task is executed on the writer thread instead.
+    /// Returns `None` if the keyspace does not exist.
+    async fn submit_read_task<CB, T>(
+        &self,
+        keyspace: &str,
+        inner: CB,
+    ) -> heed::Result<Option<T>>
+    where
+        T: Send + 'static,
+        CB: Fn(&RoTxn, &KvDB, &MetaDB) -> heed::Result<T> + Send + 'static,
     {
-        let (tx, rx) = oneshot::channel();
         let keyspace = keyspace.to_owned();
 
-        let cb = move |env: &Env,
-                       keyspace_list: &KeyspaceDB,
-                       databases: &mut DatabaseKeyspace| {
-            let res = match databases.entry(keyspace) {
-                Entry::Vacant(entry) => {
-                    match try_create_dbs(env, keyspace_list, entry.key()) {
-                        Ok(dbs) => {
-                            let (kv, meta) = entry.insert(dbs);
-                            inner(env, kv, meta)
-                        },
-                        Err(e) => Err(e),
-                    }
-                },
-                Entry::Occupied(existing) => {
-                    let (kv, meta) = existing.get();
-                    inner(env, kv, meta)
-                },
+        let readers = match self.readers.as_ref() {
+            None => {
+                return self
+                    .submit(move |writer| {
+                        let dbs = writer.keyspace_dbs(&keyspace, false)?;
+                        read_keyspace(&writer.env, &writer.shared, dbs, &inner)
+                    })
+                    .await
+            },
+            Some(readers) => readers,
+        };
+
+        let (tx, rx) = oneshot::channel();
+
+        let cb = move |reader: &Reader| {
+            let dbs = reader
+                .shared
+                .databases
+                .read()
+                .unwrap()
+                .get(&keyspace)
+                .copied();
+            let _ = tx.send(read_keyspace(&reader.env, &reader.shared, dbs, &inner));
+        };
+
+        readers
+            .send_async(Box::new(cb))
+            .await
+            .expect("send message");
+
+        rx.await.unwrap()
+    }
+
+    /// Submits a task to the background thread.
+    ///
+    /// If the task fails because the memory map is full, the map is grown
+    /// and the task is retried.
+    async fn submit<CB, T>(&self, inner: CB) -> heed::Result<T>
+    where
+        T: Send + 'static,
+        CB: Fn(&mut Writer) -> heed::Result<T> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        let cb = move |writer: &mut Writer| {
+            let res = loop {
+                match inner(writer) {
+                    Err(heed::Error::Mdb(MdbError::MapFull)) => {
+                        if let Err(e) = writer.grow_map() {
+                            break Err(e);
+                        }
+                    },
+                    res => break res,
+                }
             };
 
             let _ = tx.send(res);
@@ -276,23 +591,144 @@ impl StorageHandle {
     }
 }
 
+/// The state shared between the writer and reader threads.
+#[derive(Default)]
+struct Shared {
+    /// The databases of every keyspace.
+    ///
+    /// LMDB does not allow databases to be opened by multiple transactions at once,
+    /// so only the writer opens databases and readers only use those already opened.
+    databases: RwLock<DatabaseKeyspace>,
+    /// Held by readers for the lifetime of their transactions, as the map can only
+    /// be resized while no transactions are open.
+    resize_lock: RwLock<()>,
+}
+
+/// The state owned by a reader thread.
+struct Reader {
+    env: Env,
+    shared: Arc<Shared>,
+}
+
+/// The state owned by the background writer thread.
+struct Writer {
+    env: Env,
+    keyspace_list: KeyspaceDB,
+    shared: Arc<Shared>,
+    max_map_size: Option<usize>,
+}
+
+impl Writer {
+    /// Gets the databases of the keyspace.
+    ///
+    /// If they do not already exist they are created when `create` is `true`,
+    /// otherwise `None` is returned.
+    fn keyspace_dbs(
+        &mut self,
+        keyspace: &str,
+        create: bool,
+    ) -> heed::Result<Option<(KvDB, MetaDB)>> {
+        let mut databases = self.shared.databases.write().unwrap();
+        let dbs = match databases.entry(keyspace.to_owned()) {
+            Entry::Occupied(existing) => Some(*existing.get()),
+            Entry::Vacant(entry) => {
+                let dbs = if create {
+                    Some(try_create_dbs(&self.env, &self.keyspace_list, entry.key())?)
+                } else {
+                    try_open_dbs(&self.env, entry.key())?
+                };
+
+                if let Some(dbs) = dbs {
+                    entry.insert(dbs);
+                }
+                dbs
+            },
+        };
+
+        Ok(dbs)
+    }
+
+    /// Doubles the size of the memory map, up to the maximum map size.
+    fn grow_map(&self) -> heed::Result<()> {
+        let current = self.env.info().map_size;
+        let mut new_size = align_map_size(current.saturating_mul(2));
+        if let Some(max_map_size) = self.max_map_size {
+            new_size = new_size.min(max_map_size);
+        }
+
+        if new_size <= current {
+            return Err(heed::Error::Mdb(MdbError::MapFull));
+        }
+
+        info!(
+            current_size = current,
+            new_size = new_size,
+            "LMDB map is full, growing map."
+        );
+
+        // SAFETY: Writer tasks are executed one at a time on this thread and every
+        //         transaction is closed by the time a task returns, readers hold the
+        //         resize lock while their transactions are open.
+        let _guard = self.shared.resize_lock.write().unwrap();
+        unsafe { self.env.resize(new_size) }
+    }
+}
+
+/// Executes the read within a new read transaction, returning `None` if the
+/// keyspace does not exist.
+fn read_keyspace<T>(
+    env: &Env,
+    shared: &Shared,
+    dbs: Option<(KvDB, MetaDB)>,
+    inner: impl Fn(&RoTxn, &KvDB, &MetaDB) -> heed::Result<T>,
+) -> heed::Result<Option<T>> {
+    let (kv, meta) = match dbs {
+        None => return Ok(None),
+        Some(dbs) => dbs,
+    };
+
+    let _guard = shared.resize_lock.read().unwrap();
+    let txn = env.read_txn()?;
+    inner(&txn, &kv, &meta).map(Some)
+}
+
+#[inline]
+fn align_map_size(size: usize) -> usize {
+    size.div_ceil(MAP_SIZE_ALIGNMENT)
+        .saturating_mul(MAP_SIZE_ALIGNMENT)
+}
+
+fn kv_db_name(keyspace: &str) -> String {
+    format!("datacake-{keyspace}-kv")
+}
+
+fn meta_db_name(keyspace: &str) -> String {
+    format!("datacake-{keyspace}-meta")
+}
+
 fn try_create_dbs(
     env: &Env,
     keyspace_list: &KeyspaceDB,
     keyspace: &str,
 ) -> heed::Result<(KvDB, MetaDB)> {
-    let kv_name = format!("datacake-{keyspace}-kv");
-    let meta_name = format!("datacake-{keyspace}-meta");
-
     let mut txn = env.write_txn()?;
     keyspace_list.put(&mut txn, keyspace, &())?;
-    let kv_db = env.create_database(&mut txn, Some(&kv_name))?;
-    let meta_db = env.create_database(&mut txn, Some(&meta_name))?;
+    let kv_db = env.create_database(&mut txn, Some(&kv_db_name(keyspace)))?;
+    let meta_db = env.create_database(&mut txn, Some(&meta_db_name(keyspace)))?;
     txn.commit()?;
 
     Ok((kv_db, meta_db))
 }
 
+fn try_open_dbs(env: &Env, keyspace: &str) -> heed::Result<Option<(KvDB, MetaDB)>> {
+    let txn = env.read_txn()?;
+    let kv_db = env.open_database(&txn, Some(&kv_db_name(keyspace)))?;
+    let meta_db = env.open_database(&txn, Some(&meta_db_name(keyspace)))?;
+    txn.commit()?;
+
+    Ok(kv_db.zip(meta_db))
+}
+
 fn read_keyspace_list(
     env: &Env,
     keyspace_list: &KeyspaceDB,
@@ -308,42 +744,98 @@ fn read_keyspace_list(
     Ok(list)
 }
 
-async fn setup_database(path: impl AsRef<Path>) -> heed::Result<(Sender<Task>, Env)> {
+type Handles = (Sender<Task>, Option<Sender<ReadTask>>, Env, SnapshotDB);
+
+async fn setup_database(
+    path: impl AsRef<Path>,
+    options: LmdbOptions,
+) -> heed::Result<Handles> {
     let path = path.as_ref().to_path_buf();
     let (tx, rx) = flume::bounded(CAPACITY);
+    let num_readers = options.num_readers;
 
-    let env = tokio::task::spawn_blocking(move || setup_disk_handle(&path, rx))
-        .await
-        .expect("spawn background runner")?;
+    let (env, shared, snapshots) =
+        tokio::task::spawn_blocking(move || setup_disk_handle(&path, options, rx))
+            .await
+            .expect("spawn background runner")?;
+
+    let readers = if num_readers == 0 {
+        None
+    } else {
+        let (readers_tx, readers_rx) = flume::bounded(CAPACITY * num_readers);
+        for _ in 0..num_readers {
+            let reader = Reader {
+                env: env.clone(),
+                shared: shared.clone(),
+            };
+            let tasks = readers_rx.clone();
+            std::thread::spawn(move || run_read_tasks(reader, tasks));
+        }
+        Some(readers_tx)
+    };
 
-    Ok((tx, env))
+    Ok((tx, readers, env, snapshots))
 }
 
-fn setup_disk_handle(path: &Path, tasks: Receiver<Task>) -> heed::Result<Env> {
+fn setup_disk_handle(
+    path: &Path,
+    options: LmdbOptions,
+    tasks: Receiver<Task>,
+) -> heed::Result<(Env, Arc<Shared>, SnapshotDB)> {
     if !path.exists() {
         let _ = std::fs::create_dir_all(path); // Attempt to create the directory.
     }
 
-    let env = EnvOpenOptions::new()
-        .map_size(DEFAULT_MAP_SIZE)
-        .max_dbs(MAX_NUM_DBS)
-        .open(path)?;
+    let flags = match options.sync_mode {
+        SyncMode::Full => EnvFlags::empty(),
+        SyncMode::NoMetaSync => EnvFlags::NO_META_SYNC,
+        SyncMode::NoSync => EnvFlags::NO_SYNC,
+    };
+
+    // SAFETY: The environment is only opened once per path by this process and
+    //         the sync flags only affect durability after a system crash, which
+    //         is documented by `SyncMode`.
+    let env = unsafe {
+        EnvOpenOptions::new()
+            .map_size(align_map_size(options.map_size))
+            .max_dbs(options.max_dbs)
+            .flags(flags)
+            .open(path)?
+    };
 
     let mut txn = env.write_txn()?;
     let keyspace_list = env.create_database(&mut txn, Some("datacake-keyspace"))?;
+    let snapshots = env.create_database(&mut txn, Some("datacake-snapshots"))?;
     txn.commit()?;
 
-    let env2 = env.clone();
-    std::thread::spawn(move || run_tasks(env, tasks, keyspace_list));
+    // Existing keyspaces are opened upfront as readers cannot open databases.
+    let mut writer = Writer {
+        env: env.clone(),
+        keyspace_list,
+        shared: Arc::default(),
+        max_map_size: options.max_map_size,
+    };
+    for keyspace in read_keyspace_list(&env, &keyspace_list)? {
+        writer.keyspace_dbs(&keyspace, false)?;
+    }
+
+    let shared = writer.shared.clone();
+    std::thread::spawn(move || run_tasks(writer, tasks));
 
-    Ok(env2)
+    Ok((env, shared, snapshots))
 }
 
-/// Runs all tasks received with a mutable reference to the given connection.
-fn run_tasks(env: Env, tasks: Receiver<Task>, keyspace_list: KeyspaceDB) {
-    let mut dbs = DatabaseKeyspace::new();
+/// Runs all tasks received with a mutable reference to the writer state.
+fn run_tasks(mut writer: Writer, tasks: Receiver<Task>) {
     while let Ok(task) = tasks.recv() {
-        (task)(&env, &keyspace_list, &mut dbs);
+        (task)(&mut writer);
+    }
+}
+
+/// Runs all read tasks received with a reference to the reader state.
+fn run_read_tasks(reader: Reader, tasks: Receiver<ReadTask>) {
+    while let Ok(task) = tasks.recv() {
+        (task)(&reader);
     }
 }
 
@@ -477,4 +969,208 @@ mod tests {
             "Document should exist"
         );
     }
+    fn large_docs(num_docs: u64) -> impl Iterator<Item = Document> {
+        (0..num_docs)
+            .map(|id| Document::new(id, HLCTimestamp::from_u64(id), vec![0; 16 << 10]))
+    }
+
+    #[tokio::test]
+    async fn test_map_growth() {
+        let options = LmdbOptions::default().with_map_size(256 << 10);
+        let handle = StorageHandle::open_with_options(get_path(), options)
+            .await
+            .expect("Database should open OK.");
+        let initial_size = handle.env().info().map_size;
+
+        handle
+            .put_many_kv("test", large_docs(128))
+            .await
+            .expect("Map should grow to fit documents");
+        assert!(handle.env().info().map_size > initial_size);
+
+        let docs = handle.get_many("test", 0..128).await.expect("Get docs");
+        assert_eq!(docs, large_docs(128).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_max_map_size() {
+        let options = LmdbOptions::default()
+            .with_map_size(256 << 10)
+            .with_max_map_size(512 << 10);
+        let handle = StorageHandle::open_with_options(get_path(), options)
+            .await
+            .expect("Database should open OK.");
+
+        let err = handle
+            .put_many_kv("test", large_docs(128))
+            .await
+            .expect_err("Map should not grow past the max size");
+        assert!(matches!(err, heed::Error::Mdb(MdbError::MapFull)));
+        assert_eq!(handle.env().info().map_size, 512 << 10);
+
+        handle
+            .put_many_kv("test", large_docs(4))
+            .await
+            .expect("Smaller writes should still succeed");
+    }
+
+    #[tokio::test]
+    async fn test_max_dbs() {
+        let options = LmdbOptions::default().with_max_dbs(1024);
+        let handle = StorageHandle::open_with_options(get_path(), options)
+            .await
+            .expect("Database should open OK.");
+
+        for i in 0..500 {
+            let doc = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+            handle
+                .put_kv(&format!("keyspace-{i}"), doc)
+                .await
+                .expect("Put new doc");
+        }
+        assert_eq!(handle.keyspace_list().await.unwrap().len(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_empty_keyspace_removed() {
+        let path = get_path();
+        let handle = StorageHandle::open(&path)
+            .await
+            .expect("Database should open OK.");
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        handle
+            .put_kv("test", doc.clone())
+            .await
+            .expect("Put new doc");
+        handle
+            .put_kv("test2", doc.clone())
+            .await
+            .expect("Put new doc");
+        handle
+            .mark_tombstone("test", 1, HLCTimestamp::from_u64(1))
+            .await
+            .expect("Mark tombstone");
+
+        handle
+            .remove_tombstones("missing", [1].into_iter())
+            .await
+            .expect("Missing keyspaces should be ignored");
+        assert_eq!(handle.keyspace_list().await.unwrap(), ["test", "test2"]);
+
+        handle
+            .remove_tombstones("test", [1].into_iter())
+            .await
+            .expect("Remove tombstones");
+        assert_eq!(handle.keyspace_list().await.unwrap(), ["test2"]);
+
+        handle
+            .put_kv("test", doc.clone())
+            .await
+            .expect("Put new doc");
+        assert_eq!(handle.keyspace_list().await.unwrap(), ["test", "test2"]);
+        assert_eq!(handle.get("test", 1).await.unwrap(), Some(doc));
+    }
+
+    #[tokio::test]
+    async fn test_reads_not_blocked_by_writer() {
+        let options = LmdbOptions::default().with_num_readers(2);
+        let handle = StorageHandle::open_with_options(get_path(), options)
+            .await
+            .expect("Database should open OK.");
+        assert_eq!(handle.num_readers(), 2);
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        handle
+            .put_kv("test", doc.clone())
+            .await
+            .expect("Put new doc");
+
+        // Occupy the writer until the read has completed.
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let writer = {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                handle
+                    .submit_env_task(move |_env| {
+                        let _ = release_rx.recv();
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+
+        let res = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            handle.get("test", 1),
+        )
+        .await;
+        release_tx.send(()).unwrap();
+        writer.await.unwrap().expect("Writer task");
+
+        let fetched = res
+            .expect("Read should not wait on the writer")
+            .expect("Get doc");
+        assert_eq!(fetched, Some(doc));
+    }
+
+    #[tokio::test]
+    async fn test_reads_without_readers() {
+        let path = get_path();
+        let options = LmdbOptions::default().with_num_readers(0);
+        let handle = StorageHandle::open_with_options(&path, options)
+            .await
+            .expect("Database should open OK.");
+        assert_eq!(handle.num_readers(), 0);
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        handle
+            .put_kv("test", doc.clone())
+            .await
+            .expect("Put new doc");
+        assert_eq!(handle.get("test", 1).await.unwrap(), Some(doc));
+        assert_eq!(handle.get_metadata("test").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reads_missing_keyspace() {
+        let handle = StorageHandle::open(get_path())
+            .await
+            .expect("Database should open OK.");
+
+        assert_eq!(handle.get("missing", 1).await.unwrap(), None);
+        assert!(handle
+            .get_many("missing", [1].into_iter())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(handle.get_metadata("missing").await.unwrap().is_empty());
+        assert!(
+            handle.keyspace_list().await.unwrap().is_empty(),
+            "Reads should not create keyspaces"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_existing_keyspaces_readable_after_reopen() {
+        let path = get_path();
+        let doc = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        {
+            let handle = StorageHandle::open(&path)
+                .await
+                .expect("Database should open OK.");
+            handle
+                .put_kv("test", doc.clone())
+                .await
+                .expect("Put new doc");
+            let env = handle.env().clone();
+            drop(handle);
+            env.prepare_for_closing().wait();
+        }
+
+        let handle = StorageHandle::open(&path)
+            .await
+            .expect("Database should open OK.");
+        assert_eq!(handle.get("test", 1).await.unwrap(), Some(doc));
+    }
 }
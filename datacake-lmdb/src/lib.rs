@@ -66,6 +66,9 @@
 //! }
 //! ```
 
+#[macro_use]
+extern crate tracing;
+
 mod db;
 
 use std::path::Path;
@@ -74,11 +77,23 @@ use async_trait::async_trait;
 use datacake_crdt::{HLCTimestamp, Key};
 use datacake_eventual_consistency::{
     BulkMutationError,
+    DocsStream,
     Document,
     DocumentMetadata,
+    MetadataStream,
+    StateSnapshot,
     Storage,
+    STREAM_CHUNK_SIZE,
+};
+pub use db::{
+    LmdbOptions,
+    StorageHandle,
+    SyncMode,
+    DEFAULT_MAP_SIZE,
+    DEFAULT_MAX_DBS,
+    DEFAULT_NUM_READERS,
 };
-pub use db::StorageHandle;
+use futures::StreamExt;
 pub use heed;
 pub use heed::Error;
 
@@ -88,7 +103,8 @@ pub struct LmdbStorage {
 
 impl LmdbStorage {
     /// Connects to the LMDB database.
-    /// This spawns 1 background threads with actions being executed within that thread.
+    /// This spawns 1 background writer thread and a pool of reader threads with actions
+    /// being executed within those threads.
     /// This approach reduces the affect of writes blocking reads and vice-versa.
     pub async fn open(path: impl AsRef<Path>) -> heed::Result<Self> {
         let db = StorageHandle::open(path).await?;
@@ -96,6 +112,18 @@ impl LmdbStorage {
         Ok(Self { db })
     }
 
+    /// Connects to the LMDB database using the given options.
+    ///
+    /// See [StorageHandle::open_with_options].
+    pub async fn open_with_options(
+        path: impl AsRef<Path>,
+        options: LmdbOptions,
+    ) -> heed::Result<Self> {
+        let db = StorageHandle::open_with_options(path, options).await?;
+
+        Ok(Self { db })
+    }
+
     /// Access to the LMDB storage handle.
     ///
     /// This allows you to access the LMDB db directly
@@ -127,6 +155,44 @@ impl Storage for LmdbStorage {
             .map(|v| Box::new(v.into_iter()) as Self::MetadataIter)
     }
 
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let handle = self.db.clone();
+        let keyspace = keyspace.to_string();
+
+        // Pages through the keyspace by key, each page is only read once the
+        // previous one has been consumed.
+        let stream = futures::stream::try_unfold(Some(None), move |cursor| {
+            let handle = handle.clone();
+            let keyspace = keyspace.clone();
+            async move {
+                let after = match cursor {
+                    None => return Ok(None),
+                    Some(after) => after,
+                };
+
+                let page = handle
+                    .get_metadata_page(&keyspace, after, STREAM_CHUNK_SIZE)
+                    .await?;
+                if page.is_empty() {
+                    return Ok(None);
+                }
+
+                let next_cursor = if page.len() < STREAM_CHUNK_SIZE {
+                    None
+                } else {
+                    page.last().map(|entry| Some(entry.0))
+                };
+
+                Ok(Some((page, next_cursor)))
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
     async fn remove_tombstones(
         &self,
         keyspace: &str,
@@ -193,6 +259,48 @@ impl Storage for LmdbStorage {
             .await
             .map(|v| Box::new(v.into_iter()) as Self::DocsIter)
     }
+
+    async fn stream_multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<DocsStream<Self::Error>, Self::Error> {
+        let handle = self.db.clone();
+        let keyspace = keyspace.to_string();
+        let doc_ids = doc_ids.collect::<Vec<_>>();
+
+        // Each chunk of documents is only read once the previous one has been consumed.
+        let chunks = doc_ids
+            .chunks(STREAM_CHUNK_SIZE)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+        let stream = futures::stream::iter(chunks).then(move |doc_ids| {
+            let handle = handle.clone();
+            let keyspace = keyspace.clone();
+            async move { handle.get_many(&keyspace, doc_ids.into_iter()).await }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.db.put_snapshot(keyspace, snapshot).await
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        self.db.get_snapshot(keyspace).await
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.db.remove_snapshot(keyspace).await
+    }
 }
 
 #[cfg(test)]
@@ -202,7 +310,7 @@ mod tests {
     use datacake_eventual_consistency::test_suite;
     use uuid::Uuid;
 
-    use crate::LmdbStorage;
+    use crate::{LmdbOptions, LmdbStorage};
 
     #[tokio::test]
     async fn test_storage_logic() {
@@ -212,4 +320,27 @@ mod tests {
         let storage = LmdbStorage::open(path).await.expect("Open DB");
         test_suite::run_test_suite(storage).await;
     }
+
+    #[tokio::test]
+    async fn test_storage_logic_without_readers() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&path).unwrap();
+
+        let options = LmdbOptions::default().with_num_readers(0);
+        let storage = LmdbStorage::open_with_options(path, options)
+            .await
+            .expect("Open DB");
+        test_suite::run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_storage_durability() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&path).unwrap();
+
+        test_suite::run_persistence_test_suite(|| async {
+            LmdbStorage::open(&path).await.expect("Open DB")
+        })
+        .await;
+    }
 }
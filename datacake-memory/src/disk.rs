@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+use datacake_crdt::HLCTimestamp;
+use datacake_eventual_consistency::Document;
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
+
+use crate::keyspace::Entry;
+use crate::MemoryStorageError;
+
+#[derive(Archive, Serialize, Deserialize, Default)]
+#[archive(check_bytes)]
+/// A point in time copy of every keyspace within the store.
+pub(crate) struct DiskSnapshot {
+    pub(crate) keyspaces: Vec<KeyspaceDump>,
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+pub(crate) struct KeyspaceDump {
+    pub(crate) name: String,
+    pub(crate) entries: Vec<EntryDump>,
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+pub(crate) struct EntryDump {
+    id: u64,
+    ts: u64,
+    /// The document data, or `None` if the entry is a tombstone.
+    data: Option<Vec<u8>>,
+}
+
+impl From<(u64, Entry)> for EntryDump {
+    fn from((id, entry): (u64, Entry)) -> Self {
+        let ts = entry.last_updated().as_u64();
+        let data = match entry {
+            Entry::Live(doc) => Some(doc.data().to_vec()),
+            Entry::Tombstone(_) => None,
+        };
+
+        Self { id, ts, data }
+    }
+}
+
+impl EntryDump {
+    pub(crate) fn into_entry(self) -> (u64, Entry) {
+        let ts = HLCTimestamp::from_u64(self.ts);
+        let entry = match self.data {
+            Some(data) => Entry::Live(Document::new(self.id, ts, data)),
+            None => Entry::Tombstone(ts),
+        };
+
+        (self.id, entry)
+    }
+}
+
+/// Writes the snapshot to the given path.
+///
+/// The snapshot is written to a temporary file first and then renamed
+/// so a crash part way through never leaves a partially written snapshot.
+pub(crate) fn write_snapshot(
+    path: &Path,
+    snapshot: &DiskSnapshot,
+) -> Result<(), MemoryStorageError> {
+    let buffer = rkyv::to_bytes::<_, 4096>(snapshot)
+        .map_err(|_| MemoryStorageError::Corrupted)?;
+
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Reads a snapshot from the given path.
+///
+/// If no snapshot exists, `None` is returned.
+pub(crate) fn read_snapshot(
+    path: &Path,
+) -> Result<Option<DiskSnapshot>, MemoryStorageError> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    // The archive must be read into an aligned buffer before it can be validated.
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    let mut aligned = AlignedVec::with_capacity(buffer.len());
+    aligned.extend_from_slice(&buffer);
+
+    let snapshot = rkyv::from_bytes::<DiskSnapshot>(&aligned)
+        .map_err(|_| MemoryStorageError::Corrupted)?;
+
+    Ok(Some(snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+
+        assert!(
+            read_snapshot(&path).unwrap().is_none(),
+            "Missing snapshots should be treated as empty."
+        );
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(5), b"Hello, world".to_vec());
+        let snapshot = DiskSnapshot {
+            keyspaces: vec![KeyspaceDump {
+                name: "my-keyspace".to_string(),
+                entries: vec![
+                    EntryDump::from((1, Entry::Live(doc.clone()))),
+                    EntryDump::from((2, Entry::Tombstone(HLCTimestamp::from_u64(6)))),
+                ],
+            }],
+        };
+        write_snapshot(&path, &snapshot).expect("Write snapshot.");
+
+        let mut loaded = read_snapshot(&path)
+            .expect("Read snapshot.")
+            .expect("Snapshot should exist.");
+        assert_eq!(loaded.keyspaces.len(), 1);
+        let keyspace = loaded.keyspaces.remove(0);
+        assert_eq!(keyspace.name, "my-keyspace");
+
+        let entries = keyspace
+            .entries
+            .into_iter()
+            .map(EntryDump::into_entry)
+            .collect::<Vec<_>>();
+        assert!(matches!(&entries[0], (1, Entry::Live(loaded)) if loaded == &doc));
+        assert!(
+            matches!(entries[1], (2, Entry::Tombstone(ts)) if ts == HLCTimestamp::from_u64(6))
+        );
+
+        std::fs::write(&path, b"not-a-snapshot").unwrap();
+        assert!(matches!(
+            read_snapshot(&path),
+            Err(MemoryStorageError::Corrupted)
+        ));
+        let _ = std::fs::remove_file(&path);
+    }
+}
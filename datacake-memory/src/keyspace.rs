@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use datacake_crdt::{HLCTimestamp, Key};
+use datacake_eventual_consistency::{Document, DocumentMetadata};
+use parking_lot::RwLock;
+
+type Shard = RwLock<HashMap<Key, Entry>>;
+
+#[derive(Clone)]
+/// A single entry within the keyspace.
+pub(crate) enum Entry {
+    /// A document which currently exists.
+    Live(Document),
+    /// A document which has been deleted but not yet purged.
+    Tombstone(HLCTimestamp),
+}
+
+impl Entry {
+    #[inline]
+    pub(crate) fn last_updated(&self) -> HLCTimestamp {
+        match self {
+            Entry::Live(doc) => doc.last_updated(),
+            Entry::Tombstone(ts) => *ts,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_tombstone(&self) -> bool {
+        matches!(self, Entry::Tombstone(_))
+    }
+}
+
+/// A keyspace split across several independently locked shards.
+///
+/// Keys are spread across the shards by hash so concurrent operations
+/// on different documents rarely contend on the same lock.
+pub(crate) struct Keyspace {
+    shards: Box<[Shard]>,
+}
+
+impl Keyspace {
+    pub(crate) fn new(num_shards: usize) -> Self {
+        let shards = (0..num_shards.max(1))
+            .map(|_| RwLock::new(HashMap::new()))
+            .collect();
+
+        Self { shards }
+    }
+
+    #[inline]
+    pub(crate) fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    #[inline]
+    fn shard_index(&self, key: Key) -> usize {
+        // Document IDs are frequently sequential, so they're mixed before
+        // selecting a shard to avoid clustering.
+        let hash = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        ((hash >> 32) as usize) % self.shards.len()
+    }
+
+    #[inline]
+    fn shard(&self, key: Key) -> &Shard {
+        &self.shards[self.shard_index(key)]
+    }
+
+    /// Groups the given items by the shard they belong to.
+    fn group_by_shard<T>(
+        &self,
+        items: impl Iterator<Item = T>,
+        key: impl Fn(&T) -> Key,
+    ) -> Vec<Vec<T>> {
+        let mut groups = (0..self.shards.len())
+            .map(|_| Vec::new())
+            .collect::<Vec<_>>();
+        for item in items {
+            groups[self.shard_index(key(&item))].push(item);
+        }
+        groups
+    }
+
+    pub(crate) fn put(&self, doc: Document) {
+        self.shard(doc.id())
+            .write()
+            .insert(doc.id(), Entry::Live(doc));
+    }
+
+    pub(crate) fn put_many(&self, docs: impl Iterator<Item = Document>) {
+        let groups = self.group_by_shard(docs, |doc| doc.id());
+        for (shard, docs) in self.shards.iter().zip(groups) {
+            if docs.is_empty() {
+                continue;
+            }
+
+            let mut lock = shard.write();
+            for doc in docs {
+                lock.insert(doc.id(), Entry::Live(doc));
+            }
+        }
+    }
+
+    pub(crate) fn mark_tombstone(&self, key: Key, ts: HLCTimestamp) {
+        self.shard(key).write().insert(key, Entry::Tombstone(ts));
+    }
+
+    pub(crate) fn mark_many_as_tombstone(
+        &self,
+        docs: impl Iterator<Item = DocumentMetadata>,
+    ) {
+        let groups = self.group_by_shard(docs, |doc| doc.id);
+        for (shard, docs) in self.shards.iter().zip(groups) {
+            if docs.is_empty() {
+                continue;
+            }
+
+            let mut lock = shard.write();
+            for doc in docs {
+                lock.insert(doc.id, Entry::Tombstone(doc.last_updated));
+            }
+        }
+    }
+
+    /// Removes the given keys if they are tombstones.
+    ///
+    /// Live documents are left untouched as they may have been updated
+    /// since the tombstone was marked as safe to remove.
+    pub(crate) fn remove_tombstones(&self, keys: impl Iterator<Item = Key>) {
+        let groups = self.group_by_shard(keys, |key| *key);
+        for (shard, keys) in self.shards.iter().zip(groups) {
+            if keys.is_empty() {
+                continue;
+            }
+
+            let mut lock = shard.write();
+            for key in keys {
+                if lock.get(&key).map(Entry::is_tombstone).unwrap_or_default() {
+                    lock.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, key: Key) -> Option<Document> {
+        match self.shard(key).read().get(&key) {
+            Some(Entry::Live(doc)) => Some(doc.clone()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_many(&self, keys: impl Iterator<Item = Key>) -> Vec<Document> {
+        let groups = self.group_by_shard(keys, |key| *key);
+        let mut docs = Vec::new();
+        for (shard, keys) in self.shards.iter().zip(groups) {
+            if keys.is_empty() {
+                continue;
+            }
+
+            let lock = shard.read();
+            for key in keys {
+                if let Some(Entry::Live(doc)) = lock.get(&key) {
+                    docs.push(doc.clone());
+                }
+            }
+        }
+        docs
+    }
+
+    /// Gets the metadata of every entry within the given shard.
+    pub(crate) fn shard_metadata(&self, shard: usize) -> Vec<(Key, HLCTimestamp, bool)> {
+        self.shards[shard]
+            .read()
+            .iter()
+            .map(|(key, entry)| (*key, entry.last_updated(), entry.is_tombstone()))
+            .collect()
+    }
+
+    /// Gets a copy of every entry within the given shard.
+    pub(crate) fn shard_entries(&self, shard: usize) -> Vec<(Key, Entry)> {
+        self.shards[shard]
+            .read()
+            .iter()
+            .map(|(key, entry)| (*key, entry.clone()))
+            .collect()
+    }
+
+    pub(crate) fn metadata(&self) -> Vec<(Key, HLCTimestamp, bool)> {
+        (0..self.shards.len())
+            .flat_map(|shard| self.shard_metadata(shard))
+            .collect()
+    }
+
+    /// Inserts an entry loaded from disk.
+    pub(crate) fn restore(&self, key: Key, entry: Entry) {
+        self.shard(key).write().insert(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_spread_across_shards() {
+        let keyspace = Keyspace::new(8);
+        keyspace.put_many(
+            (0..1_000)
+                .map(|id| Document::new(id, HLCTimestamp::from_u64(id), Vec::new())),
+        );
+
+        for shard in 0..keyspace.num_shards() {
+            let num_entries = keyspace.shard_metadata(shard).len();
+            assert!(
+                num_entries > 50,
+                "Sequential keys should be spread across shards. Shard {shard} has {num_entries}",
+            );
+        }
+        assert_eq!(keyspace.metadata().len(), 1_000);
+    }
+
+    #[test]
+    fn test_remove_tombstones_keeps_live_docs() {
+        let keyspace = Keyspace::new(4);
+        keyspace.put(Document::new(
+            1,
+            HLCTimestamp::from_u64(1),
+            b"hello".to_vec(),
+        ));
+        keyspace.mark_tombstone(2, HLCTimestamp::from_u64(2));
+
+        keyspace.remove_tombstones([1, 2].into_iter());
+
+        assert!(
+            keyspace.get(1).is_some(),
+            "Live documents should not be removed."
+        );
+        assert_eq!(
+            keyspace.metadata(),
+            vec![(1, HLCTimestamp::from_u64(1), false)],
+        );
+    }
+}
@@ -0,0 +1,560 @@
+//! # Datacake Memory
+//!
+//! A pre-built in-memory implementation of the datacake-eventual-consistency `Storage` trait,
+//! this is ideal for ephemeral clusters such as caches where persistence is not required.
+//!
+//! Each keyspace is split across several independently locked shards so concurrent reads and
+//! writes rarely contend with one another. The store can optionally write a snapshot of its
+//! contents to disk periodically, which is loaded again when the store is next built.
+//!
+//! For more info see <https://github.com/lnx-search/datacake>
+//!
+//! ## Example
+//!
+//! ```rust
+//! use anyhow::Result;
+//! use datacake_eventual_consistency::EventuallyConsistentStoreExtension;
+//! use datacake_node::{
+//!     ConnectionConfig,
+//!     Consistency,
+//!     DCAwareSelector,
+//!     DatacakeNodeBuilder,
+//! };
+//! use datacake_memory::MemoryStorage;
+//!
+//! static KEYSPACE: &str = "memory-store";
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     tracing_subscriber::fmt::init();
+//!
+//!     let store = MemoryStorage::new();
+//!
+//!     let addr = test_helper::get_unused_addr();
+//!     let connection_cfg = ConnectionConfig::new(addr, addr, Vec::<String>::new());
+//!
+//!     let node = DatacakeNodeBuilder::<DCAwareSelector>::new(1, connection_cfg)
+//!         .connect()
+//!         .await?;
+//!     let store = node
+//!         .add_extension(EventuallyConsistentStoreExtension::new(store))
+//!         .await?;
+//!
+//!     let handle = store.handle();
+//!
+//!     handle
+//!         .put(KEYSPACE, 1, b"Hello, world".to_vec(), Consistency::All)
+//!         .await
+//!         .expect("Put value.");
+//!
+//!     let doc = handle
+//!         .get(KEYSPACE, 1)
+//!         .await
+//!         .expect("Get value.")
+//!         .expect("Document should not be none");
+//!     assert_eq!(doc.id(), 1);
+//!     assert_eq!(doc.data(), b"Hello, world");
+//!
+//!     handle
+//!         .del(KEYSPACE, 1, Consistency::All)
+//!         .await
+//!         .expect("Del value.");
+//!     let doc = handle.get(KEYSPACE, 1).await.expect("Get value.");
+//!     assert!(doc.is_none(), "No document should not exist!");
+//!
+//!     node.shutdown().await;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+#[macro_use]
+extern crate tracing;
+
+mod disk;
+mod keyspace;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use datacake_crdt::{HLCTimestamp, Key};
+use datacake_eventual_consistency::{
+    BulkMutationError,
+    DocsStream,
+    Document,
+    DocumentMetadata,
+    MetadataStream,
+    Storage,
+    STREAM_CHUNK_SIZE,
+};
+use futures::StreamExt;
+use parking_lot::RwLock;
+
+use crate::disk::{DiskSnapshot, EntryDump, KeyspaceDump};
+use crate::keyspace::Keyspace;
+
+/// The number of shards each keyspace is split into per available CPU core.
+const SHARDS_PER_CORE: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+/// An error which can occur while loading or persisting a disk snapshot.
+///
+/// In-memory operations themselves never fail.
+pub enum MemoryStorageError {
+    #[error("IO Error: {0}")]
+    /// An IO error occurred while reading or writing the snapshot file.
+    Io(#[from] io::Error),
+    #[error("The snapshot file could not be (de)serialized.")]
+    /// The snapshot file is corrupted or was written by an incompatible version.
+    Corrupted,
+}
+
+#[derive(Debug, Clone)]
+/// A builder for configuring a [MemoryStorage] instance.
+pub struct MemoryStorageBuilder {
+    num_shards: usize,
+    snapshot_path: Option<PathBuf>,
+    snapshot_interval: Option<Duration>,
+}
+
+impl Default for MemoryStorageBuilder {
+    fn default() -> Self {
+        let num_cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        Self {
+            num_shards: num_cores * SHARDS_PER_CORE,
+            snapshot_path: None,
+            snapshot_interval: None,
+        }
+    }
+}
+
+impl MemoryStorageBuilder {
+    /// Set the number of shards each keyspace is split into.
+    ///
+    /// By default this is `4` shards per available CPU core.
+    pub fn with_num_shards(mut self, num_shards: usize) -> Self {
+        self.num_shards = num_shards.max(1);
+        self
+    }
+
+    /// Load the store from a snapshot at the given path when built, writing a
+    /// new snapshot to the path every `interval`.
+    ///
+    /// If no snapshot exists at the path, the store starts empty.
+    /// Snapshots can also be written on demand with [MemoryStorage::persist],
+    /// which is useful when shutting down.
+    pub fn with_disk_snapshots(
+        mut self,
+        path: impl AsRef<Path>,
+        interval: Duration,
+    ) -> Self {
+        self.snapshot_path = Some(path.as_ref().to_path_buf());
+        self.snapshot_interval = Some(interval);
+        self
+    }
+
+    /// Creates the storage, loading any existing disk snapshot.
+    ///
+    /// This must be called from within a tokio runtime if disk snapshots are enabled.
+    pub async fn build(self) -> Result<MemoryStorage, MemoryStorageError> {
+        let storage = MemoryStorage::create(self.num_shards, self.snapshot_path.clone());
+
+        if let Some(path) = self.snapshot_path {
+            storage.load_snapshot(path).await?;
+        }
+
+        if let Some(interval) = self.snapshot_interval {
+            tokio::spawn(snapshot_task(Arc::downgrade(&storage.inner), interval));
+        }
+
+        Ok(storage)
+    }
+}
+
+#[derive(Clone)]
+/// A [Storage] implementation which keeps all documents in memory.
+///
+/// Cloning the storage is cheap and produces another handle to the same data.
+///
+/// State snapshots (see [Storage::put_state_snapshot]) are not supported as
+/// rebuilding the state from memory is already fast.
+pub struct MemoryStorage {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    num_shards: usize,
+    keyspaces: RwLock<BTreeMap<String, Arc<Keyspace>>>,
+    snapshot_path: Option<PathBuf>,
+    /// Prevents concurrent snapshots racing on the same file.
+    persist_lock: tokio::sync::Mutex<()>,
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStorage {
+    /// Creates a new, empty store with the default number of shards and
+    /// no disk snapshots.
+    pub fn new() -> Self {
+        Self::create(MemoryStorageBuilder::default().num_shards, None)
+    }
+
+    fn create(num_shards: usize, snapshot_path: Option<PathBuf>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                num_shards,
+                keyspaces: RwLock::default(),
+                snapshot_path,
+                persist_lock: tokio::sync::Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Creates a new builder for configuring the store.
+    pub fn builder() -> MemoryStorageBuilder {
+        MemoryStorageBuilder::default()
+    }
+
+    #[inline]
+    /// The number of shards each keyspace is split into.
+    pub fn num_shards(&self) -> usize {
+        self.inner.num_shards
+    }
+
+    /// Writes a snapshot of the store to the configured disk snapshot path.
+    ///
+    /// Returns `false` if disk snapshots are not enabled.
+    pub async fn persist(&self) -> Result<bool, MemoryStorageError> {
+        match self.inner.snapshot_path.clone() {
+            None => Ok(false),
+            Some(path) => {
+                self.inner.persist(path).await?;
+                Ok(true)
+            },
+        }
+    }
+
+    async fn load_snapshot(&self, path: PathBuf) -> Result<(), MemoryStorageError> {
+        let snapshot = tokio::task::spawn_blocking(move || disk::read_snapshot(&path))
+            .await
+            .expect("Spawn background thread")?;
+
+        let snapshot = match snapshot {
+            None => return Ok(()),
+            Some(snapshot) => snapshot,
+        };
+
+        let mut num_entries = 0;
+        for keyspace in snapshot.keyspaces {
+            let target = self.inner.get_or_create_keyspace(&keyspace.name);
+            num_entries += keyspace.entries.len();
+            for entry in keyspace.entries {
+                let (key, entry) = entry.into_entry();
+                target.restore(key, entry);
+            }
+        }
+
+        info!(
+            num_entries = num_entries,
+            "Loaded memory store from disk snapshot."
+        );
+
+        Ok(())
+    }
+
+    #[inline]
+    fn keyspace(&self, keyspace: &str) -> Option<Arc<Keyspace>> {
+        self.inner.keyspaces.read().get(keyspace).cloned()
+    }
+
+    #[inline]
+    fn get_or_create_keyspace(&self, keyspace: &str) -> Arc<Keyspace> {
+        self.inner.get_or_create_keyspace(keyspace)
+    }
+}
+
+impl Inner {
+    fn get_or_create_keyspace(&self, keyspace: &str) -> Arc<Keyspace> {
+        if let Some(existing) = self.keyspaces.read().get(keyspace) {
+            return existing.clone();
+        }
+
+        self.keyspaces
+            .write()
+            .entry(keyspace.to_string())
+            .or_insert_with(|| Arc::new(Keyspace::new(self.num_shards)))
+            .clone()
+    }
+
+    /// Copies the current contents of the store and writes them to disk.
+    ///
+    /// Each shard is copied in turn, so writes are only blocked for the time
+    /// it takes to copy a single shard.
+    async fn persist(&self, path: PathBuf) -> Result<(), MemoryStorageError> {
+        let _guard = self.persist_lock.lock().await;
+
+        let keyspaces = self
+            .keyspaces
+            .read()
+            .iter()
+            .map(|(name, keyspace)| (name.clone(), keyspace.clone()))
+            .collect::<Vec<_>>();
+
+        let mut snapshot = DiskSnapshot::default();
+        for (name, keyspace) in keyspaces {
+            let entries = (0..keyspace.num_shards())
+                .flat_map(|shard| keyspace.shard_entries(shard))
+                .map(EntryDump::from)
+                .collect();
+            snapshot.keyspaces.push(KeyspaceDump { name, entries });
+        }
+
+        tokio::task::spawn_blocking(move || disk::write_snapshot(&path, &snapshot))
+            .await
+            .expect("Spawn background thread")
+    }
+}
+
+/// Periodically writes a disk snapshot until the storage is dropped.
+async fn snapshot_task(inner: Weak<Inner>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    interval.tick().await; // The first tick completes immediately.
+
+    loop {
+        interval.tick().await;
+
+        let inner = match inner.upgrade() {
+            None => break,
+            Some(inner) => inner,
+        };
+        let path = match inner.snapshot_path.clone() {
+            None => break,
+            Some(path) => path,
+        };
+
+        if let Err(e) = inner.persist(path).await {
+            error!(error = ?e, "Failed to write memory store disk snapshot.");
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    type Error = MemoryStorageError;
+    type DocsIter = std::vec::IntoIter<Document>;
+    type MetadataIter = std::vec::IntoIter<(Key, HLCTimestamp, bool)>;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(self.inner.keyspaces.read().keys().cloned().collect())
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        let metadata = self
+            .keyspace(keyspace)
+            .map(|keyspace| keyspace.metadata())
+            .unwrap_or_default();
+        Ok(metadata.into_iter())
+    }
+
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let keyspace = match self.keyspace(keyspace) {
+            None => return Ok(futures::stream::empty().boxed()),
+            Some(keyspace) => keyspace,
+        };
+
+        // Shards are only copied once the previous shard has been consumed.
+        let chunks = (0..keyspace.num_shards()).flat_map(move |shard| {
+            let metadata = keyspace.shard_metadata(shard);
+            metadata
+                .chunks(STREAM_CHUNK_SIZE)
+                .map(|chunk| Ok(chunk.to_vec()))
+                .collect::<Vec<_>>()
+        });
+
+        Ok(futures::stream::iter(chunks).boxed())
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        if let Some(keyspace) = self.keyspace(keyspace) {
+            keyspace.remove_tombstones(keys);
+        }
+        Ok(())
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        self.get_or_create_keyspace(keyspace).put(document);
+        Ok(())
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.get_or_create_keyspace(keyspace).put_many(documents);
+        Ok(())
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        self.get_or_create_keyspace(keyspace)
+            .mark_tombstone(doc_id, timestamp);
+        Ok(())
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.get_or_create_keyspace(keyspace)
+            .mark_many_as_tombstone(documents);
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        Ok(self
+            .keyspace(keyspace)
+            .and_then(|keyspace| keyspace.get(doc_id)))
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        let docs = self
+            .keyspace(keyspace)
+            .map(|keyspace| keyspace.get_many(doc_ids))
+            .unwrap_or_default();
+        Ok(docs.into_iter())
+    }
+
+    async fn stream_multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<DocsStream<Self::Error>, Self::Error> {
+        let keyspace = match self.keyspace(keyspace) {
+            None => return Ok(futures::stream::empty().boxed()),
+            Some(keyspace) => keyspace,
+        };
+
+        // Each chunk of documents is only copied once the previous one has been consumed.
+        let doc_ids = doc_ids.collect::<Vec<_>>();
+        let chunks = doc_ids
+            .chunks(STREAM_CHUNK_SIZE)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+        let stream = futures::stream::iter(chunks)
+            .map(move |chunk| Ok(keyspace.get_many(chunk.into_iter())));
+
+        Ok(stream.boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use datacake_eventual_consistency::test_suite;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_storage_logic() {
+        let storage = MemoryStorage::new();
+        test_suite::run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_storage_logic_single_shard() {
+        let storage = MemoryStorage::builder()
+            .with_num_shards(1)
+            .build()
+            .await
+            .expect("Build storage.");
+        test_suite::run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_disk_snapshot_restore() {
+        let path = temp_dir().join(Uuid::new_v4().to_string());
+
+        let storage = MemoryStorage::builder()
+            .with_disk_snapshots(&path, Duration::from_secs(60))
+            .build()
+            .await
+            .expect("Build storage.");
+
+        let doc_1 = Document::new(1, HLCTimestamp::now(0, 0), b"Hello, world".to_vec());
+        let doc_2 = Document::new(2, HLCTimestamp::now(0, 0), b"Hello, world".to_vec());
+        storage
+            .multi_put("my-keyspace", [doc_1.clone(), doc_2.clone()].into_iter())
+            .await
+            .unwrap();
+        storage
+            .mark_as_tombstone("my-keyspace", 2, HLCTimestamp::now(1, 0))
+            .await
+            .unwrap();
+        assert!(storage.persist().await.expect("Persist storage."));
+        drop(storage);
+
+        let storage = MemoryStorage::builder()
+            .with_disk_snapshots(&path, Duration::from_secs(60))
+            .build()
+            .await
+            .expect("Build storage.");
+        assert_eq!(
+            storage.get_keyspace_list().await.unwrap(),
+            vec!["my-keyspace".to_string()],
+        );
+        assert_eq!(storage.get("my-keyspace", 1).await.unwrap(), Some(doc_1));
+        assert_eq!(storage.get("my-keyspace", 2).await.unwrap(), None);
+
+        let metadata = storage
+            .iter_metadata("my-keyspace")
+            .await
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(metadata.len(), 2, "Tombstones should be restored.");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_persist_without_disk_snapshots() {
+        let storage = MemoryStorage::new();
+        assert!(!storage.persist().await.expect("Persist storage."));
+    }
+}
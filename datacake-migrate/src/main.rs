@@ -0,0 +1,299 @@
+#[macro_use]
+extern crate tracing;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use datacake_eventual_consistency::{migrate, verify, MigrationOptions, Storage};
+use datacake_lmdb::LmdbStorage;
+use datacake_memory::MemoryStorage;
+use datacake_redb::RedbStorage;
+use datacake_sqlite::SqliteStorage;
+
+/// How often the memory backend writes its disk snapshot in the background.
+///
+/// The snapshot is always written explicitly once the migration completes,
+/// so this only needs to be long enough to never trigger.
+const MEMORY_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let args: Args = Args::parse();
+    run(args).await
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+/// Copies every keyspace, document and tombstone from one datacake storage
+/// backend into another, preserving timestamps exactly.
+///
+/// Stores are given as `<backend>:<path>` where the backend is one of
+/// `sqlite`, `lmdb`, `redb` or `memory`.
+pub struct Args {
+    #[arg(long)]
+    /// The store to read from, i.e. `sqlite:./data/store.db`.
+    from: BackendSpec,
+
+    #[arg(long)]
+    /// The store to write to, i.e. `lmdb:./data/lmdb`.
+    to: BackendSpec,
+
+    #[arg(long = "keyspace")]
+    /// Only migrate the given keyspaces.
+    ///
+    /// If not provided every keyspace within the source store is migrated.
+    keyspaces: Vec<String>,
+
+    #[arg(long)]
+    /// Skip entries already present in the destination, resuming an interrupted migration.
+    resume: bool,
+
+    #[arg(long)]
+    /// Check the destination matches the source once the migration completes.
+    verify: bool,
+
+    #[arg(long, conflicts_with_all = ["resume", "verify"])]
+    /// Only check the destination matches the source without migrating anything.
+    verify_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The location of a store and the backend it uses.
+enum BackendSpec {
+    Sqlite(PathBuf),
+    Lmdb(PathBuf),
+    Redb(PathBuf),
+    Memory(PathBuf),
+}
+
+impl FromStr for BackendSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (backend, path) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected `<backend>:<path>` but got {s:?}"))?;
+        if path.is_empty() {
+            return Err(format!("No path provided for the {backend:?} backend"));
+        }
+
+        let path = PathBuf::from(path);
+        match backend {
+            "sqlite" => Ok(Self::Sqlite(path)),
+            "lmdb" => Ok(Self::Lmdb(path)),
+            "redb" => Ok(Self::Redb(path)),
+            "memory" => Ok(Self::Memory(path)),
+            other => Err(format!(
+                "Unknown backend {other:?}, expected one of `sqlite`, `lmdb`, `redb` or `memory`"
+            )),
+        }
+    }
+}
+
+impl BackendSpec {
+    async fn open(&self) -> Result<Backend> {
+        let backend = match self {
+            Self::Sqlite(path) => Backend::Sqlite(SqliteStorage::open(path).await?),
+            Self::Lmdb(path) => Backend::Lmdb(LmdbStorage::open(path).await?),
+            Self::Redb(path) => Backend::Redb(RedbStorage::open(path).await?),
+            Self::Memory(path) => {
+                let store = MemoryStorage::builder()
+                    .with_disk_snapshots(path, MEMORY_SNAPSHOT_INTERVAL)
+                    .build()
+                    .await?;
+                Backend::Memory(store)
+            },
+        };
+
+        Ok(backend)
+    }
+}
+
+/// An opened store.
+enum Backend {
+    Sqlite(SqliteStorage),
+    Lmdb(LmdbStorage),
+    Redb(RedbStorage),
+    Memory(MemoryStorage),
+}
+
+impl Backend {
+    /// Ensures any buffered changes are written to disk.
+    async fn flush(&self) -> Result<()> {
+        if let Self::Memory(store) = self {
+            store.persist().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Calls the given expression with the concrete store wrapped by the backend.
+macro_rules! with_store {
+    ($backend:expr, | $store:ident | $body:expr) => {
+        match $backend {
+            Backend::Sqlite($store) => $body,
+            Backend::Lmdb($store) => $body,
+            Backend::Redb($store) => $body,
+            Backend::Memory($store) => $body,
+        }
+    };
+}
+
+async fn run(args: Args) -> Result<()> {
+    let source = args.from.open().await?;
+    let destination = args.to.open().await?;
+
+    with_store!(&source, |source| {
+        with_store!(&destination, |destination| {
+            execute(source, destination, &args).await?
+        })
+    });
+
+    if !args.verify_only {
+        destination.flush().await?;
+    }
+
+    Ok(())
+}
+
+async fn execute<S, D>(source: &S, destination: &D, args: &Args) -> Result<()>
+where
+    S: Storage,
+    D: Storage,
+{
+    let mut options = MigrationOptions::default().with_resume(args.resume);
+    if !args.keyspaces.is_empty() {
+        options = options.with_keyspaces(args.keyspaces.iter().cloned());
+    }
+
+    if !args.verify_only {
+        let report = migrate(source, destination, &options).await?;
+        info!(
+            keyspaces = report.keyspaces,
+            documents = report.documents,
+            tombstones = report.tombstones,
+            skipped = report.skipped,
+            "Migration complete.",
+        );
+    }
+
+    if args.verify || args.verify_only {
+        let report = verify(source, destination, &options).await?;
+        for mismatch in report.mismatches.iter() {
+            warn!(
+                keyspace = mismatch.keyspace,
+                doc_id = mismatch.doc_id,
+                kind = ?mismatch.kind,
+                "Entry does not match the source store.",
+            );
+        }
+
+        if !report.is_ok() {
+            bail!(
+                "Verification failed, {} of {} entries do not match",
+                report.mismatches.len(),
+                report.checked,
+            );
+        }
+        info!(checked = report.checked, "Verification complete.");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use datacake_crdt::HLCTimestamp;
+    use datacake_eventual_consistency::Document;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_backend_spec() {
+        assert_eq!(
+            "sqlite:./data/store.db".parse::<BackendSpec>(),
+            Ok(BackendSpec::Sqlite(PathBuf::from("./data/store.db"))),
+        );
+        assert_eq!(
+            "lmdb:/tmp/lmdb".parse::<BackendSpec>(),
+            Ok(BackendSpec::Lmdb(PathBuf::from("/tmp/lmdb"))),
+        );
+        assert_eq!(
+            "redb:store.redb".parse::<BackendSpec>(),
+            Ok(BackendSpec::Redb(PathBuf::from("store.redb"))),
+        );
+        assert_eq!(
+            "memory:snapshot.bin".parse::<BackendSpec>(),
+            Ok(BackendSpec::Memory(PathBuf::from("snapshot.bin"))),
+        );
+        assert!("rocksdb:./data".parse::<BackendSpec>().is_err());
+        assert!("sqlite:".parse::<BackendSpec>().is_err());
+        assert!("./data/store.db".parse::<BackendSpec>().is_err());
+    }
+
+    fn args(extra: &[&str]) -> Args {
+        let mut args = vec![
+            "datacake-migrate",
+            "--from",
+            "sqlite:source.db",
+            "--to",
+            "lmdb:destination",
+        ];
+        args.extend_from_slice(extra);
+        Args::try_parse_from(args).expect("Parse args")
+    }
+
+    #[tokio::test]
+    async fn test_migrate_sqlite_to_lmdb() -> Result<()> {
+        let dir = temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir)?;
+
+        let source = SqliteStorage::open(dir.join("source.db")).await?;
+        let destination = LmdbStorage::open(dir.join("lmdb")).await?;
+
+        let docs = (0..100).map(|id| {
+            Document::new(id, HLCTimestamp::from_u64(id), id.to_le_bytes().to_vec())
+        });
+        source.multi_put("my-keyspace", docs).await?;
+        source
+            .mark_as_tombstone("my-keyspace", 5, HLCTimestamp::from_u64(500))
+            .await?;
+
+        execute(&source, &destination, &args(&["--verify"])).await?;
+
+        let doc = destination
+            .get("my-keyspace", 7)
+            .await?
+            .expect("Document should be migrated");
+        assert_eq!(doc.last_updated(), HLCTimestamp::from_u64(7));
+        assert_eq!(doc.data(), 7u64.to_le_bytes());
+        assert!(destination.get("my-keyspace", 5).await?.is_none());
+
+        source
+            .put(
+                "my-keyspace",
+                Document::new(200, HLCTimestamp::from_u64(600), Vec::new()),
+            )
+            .await?;
+        let err = execute(&source, &destination, &args(&["--verify-only"]))
+            .await
+            .expect_err("Verification should fail for un-migrated documents");
+        assert!(
+            err.to_string().contains("1 of 101"),
+            "Unexpected error: {err}"
+        );
+
+        execute(&source, &destination, &args(&["--resume", "--verify"])).await?;
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
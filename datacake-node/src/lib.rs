@@ -98,7 +98,9 @@ use std::time::Duration;
 use chitchat::transport::Transport;
 use chitchat::FailureDetectorConfig;
 pub use clock::Clock;
-use datacake_rpc::{RpcService, Server};
+use datacake_rpc::{CircuitBreakerConfig, RpcService, Server};
+#[cfg(feature = "tls")]
+use datacake_rpc::{ClientTls, ServerTls};
 pub use error::NodeError;
 pub use extension::ClusterExtension;
 use futures::StreamExt;
@@ -132,6 +134,9 @@ pub struct DatacakeNodeBuilder<S = DCAwareSelector> {
     cluster_id: String,
     data_center: Cow<'static, str>,
     node_selector: S,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    #[cfg(feature = "tls")]
+    tls: Option<(ServerTls, ClientTls)>,
 }
 
 impl<S> DatacakeNodeBuilder<S>
@@ -148,7 +153,10 @@ where
             connection_cfg,
             cluster_id: DEFAULT_CLUSTER_ID.to_string(),
             data_center: Cow::Borrowed(DEFAULT_DATA_CENTER),
-            node_selector: DCAwareSelector::default(),
+            node_selector: DCAwareSelector,
+            circuit_breaker: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
@@ -163,6 +171,9 @@ where
             cluster_id: self.cluster_id,
             data_center: self.data_center,
             node_selector: selector,
+            circuit_breaker: self.circuit_breaker,
+            #[cfg(feature = "tls")]
+            tls: self.tls,
         }
     }
 
@@ -178,6 +189,26 @@ where
         self
     }
 
+    /// Enables a circuit breaker on the node's connections to other nodes.
+    ///
+    /// The state of each connection's breaker can be inspected via
+    /// [RpcNetwork::circuit_state].
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    #[cfg(feature = "tls")]
+    /// Use TLS for all RPC traffic of the node.
+    ///
+    /// The server configuration is used to accept connections from other nodes,
+    /// and the client configuration to connect to them. Every node in the cluster
+    /// must use TLS for them to be able to communicate.
+    pub fn with_tls(mut self, server: ServerTls, client: ClientTls) -> Self {
+        self.tls = Some((server, client));
+        self
+    }
+
     /// Starts the Datacake cluster, connecting to the targeted seed nodes.
     ///
     /// When connecting to the cluster, the `node_id` **must be unique** otherwise
@@ -195,9 +226,28 @@ where
         let clock = Clock::new(self.node_id);
 
         let statistics = ClusterStatistics::default();
-        let network = RpcNetwork::default();
+        #[cfg(not(feature = "tls"))]
+        let (network, rpc_server) = (
+            RpcNetwork::default(),
+            Server::listen(self.connection_cfg.listen_addr).await?,
+        );
+        #[cfg(feature = "tls")]
+        let (network, rpc_server) = match self.tls {
+            None => (
+                RpcNetwork::default(),
+                Server::listen(self.connection_cfg.listen_addr).await?,
+            ),
+            Some((server, client)) => (
+                RpcNetwork::with_tls(client),
+                Server::listen_with_tls(self.connection_cfg.listen_addr, server).await?,
+            ),
+        };
+
+        let network = match self.circuit_breaker {
+            None => network,
+            Some(config) => network.with_circuit_breaker(config),
+        };
 
-        let rpc_server = Server::listen(self.connection_cfg.listen_addr).await?;
         let selector = nodes_selector::start_node_selector(
             self.connection_cfg.public_addr,
             self.data_center.clone(),
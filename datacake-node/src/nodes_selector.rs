@@ -263,8 +263,8 @@ impl NodeSelector for DCAwareSelector {
                 let majority = total_nodes / 2;
 
                 let mut dcs_iterators = data_centers
-                    .iter()
-                    .map(|(_, nodes)| {
+                    .values()
+                    .map(|nodes| {
                         nodes
                             .get_nodes()
                             .iter()
@@ -541,7 +541,7 @@ mod tests {
 
     #[test]
     fn test_dc_aware_selector() {
-        let mut selector = DCAwareSelector::default();
+        let mut selector = DCAwareSelector;
 
         let nodes = selector
             .select_nodes(
@@ -2,7 +2,9 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use datacake_rpc::Channel;
+#[cfg(feature = "tls")]
+use datacake_rpc::ClientTls;
+use datacake_rpc::{Channel, CircuitBreakerConfig, CircuitState};
 use parking_lot::RwLock;
 use tracing::trace;
 
@@ -10,9 +12,49 @@ use tracing::trace;
 /// A collection of RPC client connections which can be reused and multiplexed.
 pub struct RpcNetwork {
     clients: Arc<RwLock<HashMap<SocketAddr, Channel>>>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    #[cfg(feature = "tls")]
+    tls: Option<ClientTls>,
 }
 
 impl RpcNetwork {
+    #[cfg(feature = "tls")]
+    /// Creates a new network which connects to every node using TLS.
+    pub fn with_tls(tls: ClientTls) -> Self {
+        Self {
+            clients: Arc::default(),
+            circuit_breaker: None,
+            tls: Some(tls),
+        }
+    }
+
+    /// Enables a circuit breaker on every connection created by the network.
+    ///
+    /// Requests to nodes which are known to be dead fail immediately rather
+    /// than waiting for the connection attempt to fail.
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// The state of the circuit breaker for the connection to the given address.
+    ///
+    /// Returns `None` if there is no connection to the address.
+    pub fn circuit_state(&self, addr: SocketAddr) -> Option<CircuitState> {
+        let guard = self.clients.read();
+        guard.get(&addr).map(|channel| channel.circuit_state())
+    }
+
+    /// The addresses of all connections with a circuit which is not closed.
+    pub fn open_circuits(&self) -> Vec<SocketAddr> {
+        let guard = self.clients.read();
+        guard
+            .iter()
+            .filter(|(_, channel)| channel.circuit_state() != CircuitState::Closed)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
     /// Attempts to get an already existing connection or creates a new connection.
     pub fn get_or_connect(&self, addr: SocketAddr) -> Channel {
         {
@@ -28,8 +70,19 @@ impl RpcNetwork {
 
     /// Connects to a given address and adds it to the clients.
     pub fn connect(&self, addr: SocketAddr) -> Channel {
+        #[cfg(feature = "tls")]
+        let channel = match self.tls.clone() {
+            None => Channel::connect(addr),
+            Some(tls) => Channel::connect_with_tls(addr, tls),
+        };
+        #[cfg(not(feature = "tls"))]
         let channel = Channel::connect(addr);
 
+        let channel = match self.circuit_breaker {
+            None => channel,
+            Some(config) => channel.with_circuit_breaker(config),
+        };
+
         {
             let mut guard = self.clients.write();
             guard.insert(addr, channel.clone());
@@ -44,3 +97,23 @@ impl RpcNetwork {
         guard.remove(&addr);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_state() {
+        let addr = "127.0.0.1:8000".parse().unwrap();
+        let network =
+            RpcNetwork::default().with_circuit_breaker(CircuitBreakerConfig::default());
+        assert_eq!(network.circuit_state(addr), None);
+
+        network.get_or_connect(addr);
+        assert_eq!(network.circuit_state(addr), Some(CircuitState::Closed));
+        assert!(network.open_circuits().is_empty());
+
+        network.disconnect(addr);
+        assert_eq!(network.circuit_state(addr), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use datacake_node::{ConnectionConfig, DCAwareSelector, DatacakeNodeBuilder};
+use datacake_rpc::{
+    Certificate,
+    ClientTls,
+    ClientTlsConfig,
+    Identity,
+    ServerTls,
+    ServerTlsConfig,
+};
+use test_helper::TestCertificates;
+
+fn tls_configs(certs: &TestCertificates) -> (ServerTls, ClientTls) {
+    let identity =
+        Identity::from_pem(&certs.server_cert_pem, &certs.server_key_pem).unwrap();
+    let ca = Certificate::from_pem(&certs.ca_pem).unwrap();
+
+    let server = ServerTlsConfig::new(identity.clone())
+        .with_client_ca_certificate(ca.clone())
+        .build()
+        .unwrap();
+    let client = ClientTlsConfig::new()
+        .with_ca_certificate(ca)
+        .with_identity(identity)
+        .build()
+        .unwrap();
+
+    (server, client)
+}
+
+#[tokio::test]
+pub async fn test_member_join_with_mutual_tls() -> anyhow::Result<()> {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let certs = TestCertificates::generate();
+
+    let node_1_addr = test_helper::get_unused_addr();
+    let node_2_addr = test_helper::get_unused_addr();
+    let node_3_addr = test_helper::get_unused_addr();
+    let node_1_connection_cfg =
+        ConnectionConfig::new(node_1_addr, node_1_addr, [node_2_addr.to_string()]);
+    let node_2_connection_cfg =
+        ConnectionConfig::new(node_2_addr, node_2_addr, [node_1_addr.to_string()]);
+    let node_3_connection_cfg =
+        ConnectionConfig::new(node_3_addr, node_3_addr, [node_1_addr.to_string()]);
+
+    let (server, client) = tls_configs(&certs);
+    let node_1 = DatacakeNodeBuilder::<DCAwareSelector>::new(1, node_1_connection_cfg)
+        .with_tls(server, client)
+        .connect()
+        .await?;
+    let (server, client) = tls_configs(&certs);
+    let node_2 = DatacakeNodeBuilder::<DCAwareSelector>::new(2, node_2_connection_cfg)
+        .with_tls(server, client)
+        .connect()
+        .await?;
+
+    node_1
+        .wait_for_nodes(&[2], Duration::from_secs(30))
+        .await
+        .expect("Nodes should connect within timeout.");
+    node_2
+        .wait_for_nodes(&[1], Duration::from_secs(30))
+        .await
+        .expect("Nodes should connect within timeout.");
+
+    let stats = node_1.statistics();
+    assert_eq!(stats.num_live_members(), 2);
+    let stats = node_2.statistics();
+    assert_eq!(stats.num_live_members(), 2);
+
+    // A node which does not use TLS cannot join the cluster.
+    let node_3 = DatacakeNodeBuilder::<DCAwareSelector>::new(3, node_3_connection_cfg)
+        .connect()
+        .await?;
+    let res = node_3.wait_for_nodes(&[1], Duration::from_secs(5)).await;
+    assert!(
+        res.is_err(),
+        "Node without TLS should not join the cluster."
+    );
+
+    let stats = node_1.statistics();
+    assert_eq!(stats.num_live_members(), 2);
+
+    node_1.shutdown().await;
+    node_2.shutdown().await;
+    node_3.shutdown().await;
+
+    Ok(())
+}
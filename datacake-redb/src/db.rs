@@ -0,0 +1,652 @@
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+
+use datacake_crdt::{HLCTimestamp, Key};
+use datacake_eventual_consistency::{Document, DocumentMetadata, StateSnapshot};
+use flume::{self, Receiver, Sender};
+use futures::channel::oneshot;
+use redb::{
+    Database,
+    ReadOnlyTable,
+    ReadTransaction,
+    ReadableTable,
+    ReadableTableMetadata,
+    Table,
+    TableDefinition,
+    TableError,
+    WriteTransaction,
+};
+
+type KvTable<'txn> = Table<'txn, u64, &'static [u8]>;
+type MetaTable<'txn> = Table<'txn, u64, u64>;
+type ReadOnlyKvTable = ReadOnlyTable<u64, &'static [u8]>;
+type ReadOnlyMetaTable = ReadOnlyTable<u64, u64>;
+type Task = Box<dyn FnOnce(&Database) -> Completion + Send + 'static>;
+type Completion = Box<dyn FnOnce() + Send + 'static>;
+
+const KEYSPACE_LIST: TableDefinition<&str, ()> =
+    TableDefinition::new("datacake-keyspace");
+const SNAPSHOTS: TableDefinition<&str, &[u8]> =
+    TableDefinition::new("datacake-snapshots");
+const CAPACITY: usize = 10;
+
+#[derive(Clone)]
+/// A asynchronous wrapper around a redb database.
+///
+/// Writes are executed in a background thread preventing any IO operations
+/// from blocking the async context, while reads are executed on the blocking
+/// thread pool as redb allows reads to run concurrently with each other and
+/// with the writer.
+pub struct StorageHandle {
+    tx: Sender<Task>,
+    db: Arc<Database>,
+}
+
+impl StorageHandle {
+    /// Opens the redb database file at the given path.
+    ///
+    /// This spawns 1 background thread which executes every write.
+    ///
+    /// If the database does not already exist it will be created.
+    ///
+    /// ```rust
+    /// use datacake_redb::StorageHandle;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let storage = StorageHandle::open("./my-redb-data.redb").await.expect("Create database");
+    /// # drop(storage);
+    /// # let _ = std::fs::remove_file("./my-redb-data.redb");
+    /// # }
+    /// ```
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, redb::Error> {
+        let path = path.as_ref().to_path_buf();
+        let db = tokio::task::spawn_blocking(move || setup_database(&path))
+            .await
+            .expect("spawn background runner")?;
+
+        let db = Arc::new(db);
+        let (tx, rx) = flume::bounded(CAPACITY);
+
+        let writer = Arc::downgrade(&db);
+        std::thread::spawn(move || run_tasks(writer, rx));
+
+        Ok(Self { tx, db })
+    }
+
+    #[inline]
+    /// Get the underlying redb database.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Get the current keyspace list.
+    pub(crate) async fn keyspace_list(&self) -> Result<Vec<String>, redb::Error> {
+        self.submit_read_task(|txn| {
+            let keyspace_list = match open_read_table(txn, KEYSPACE_LIST)? {
+                None => return Ok(Vec::new()),
+                Some(table) => table,
+            };
+
+            let mut list = Vec::new();
+            for entry in keyspace_list.iter()? {
+                let (keyspace, _) = entry?;
+                list.push(keyspace.value().to_owned());
+            }
+
+            Ok(list)
+        })
+        .await
+    }
+
+    /// Execute a PUT operation on the DB.
+    pub(crate) async fn put_kv(
+        &self,
+        keyspace: &str,
+        doc: Document,
+    ) -> Result<(), redb::Error> {
+        self.submit_task(keyspace, move |kv, meta| {
+            kv.insert(doc.id(), doc.data())?;
+            meta.insert(doc.id(), doc.last_updated().as_u64())?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Execute a many PUT operations on the DB.
+    pub(crate) async fn put_many_kv(
+        &self,
+        keyspace: &str,
+        docs: impl Iterator<Item = Document>,
+    ) -> Result<(), redb::Error> {
+        let docs = Vec::from_iter(docs);
+
+        self.submit_task(keyspace, move |kv, meta| {
+            for doc in docs {
+                kv.insert(doc.id(), doc.data())?;
+                meta.insert(doc.id(), doc.last_updated().as_u64())?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the metadata list from the DB.
+    pub(crate) async fn get_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Vec<(Key, HLCTimestamp, bool)>, redb::Error> {
+        self.get_metadata_range(keyspace, Bound::Unbounded, usize::MAX)
+            .await
+    }
+
+    /// Get a page of the metadata list from the DB.
+    ///
+    /// Entries are returned in key order starting after the given key,
+    /// or from the start of the keyspace if no key is given.
+    pub(crate) async fn get_metadata_page(
+        &self,
+        keyspace: &str,
+        after: Option<Key>,
+        limit: usize,
+    ) -> Result<Vec<(Key, HLCTimestamp, bool)>, redb::Error> {
+        let start = match after {
+            None => Bound::Unbounded,
+            Some(key) => Bound::Excluded(key),
+        };
+        self.get_metadata_range(keyspace, start, limit).await
+    }
+
+    async fn get_metadata_range(
+        &self,
+        keyspace: &str,
+        start: Bound<Key>,
+        limit: usize,
+    ) -> Result<Vec<(Key, HLCTimestamp, bool)>, redb::Error> {
+        let keyspace = keyspace.to_owned();
+
+        self.submit_read_task(move |txn| {
+            let (kv, meta) = match open_read_tables(txn, &keyspace)? {
+                None => return Ok(Vec::new()),
+                Some(tables) => tables,
+            };
+
+            let mut entries = Vec::new();
+            for pair in meta.range::<Key>((start, Bound::Unbounded))?.take(limit) {
+                let (id, ts) = pair?;
+                let id = id.value();
+
+                let is_tombstone = kv.get(id)?.is_none();
+                entries.push((id, HLCTimestamp::from_u64(ts.value()), is_tombstone));
+            }
+
+            Ok(entries)
+        })
+        .await
+    }
+
+    /// Mark an entry as a tombstone.
+    pub(crate) async fn mark_tombstone(
+        &self,
+        keyspace: &str,
+        key: Key,
+        ts: HLCTimestamp,
+    ) -> Result<(), redb::Error> {
+        self.submit_task(keyspace, move |kv, meta| {
+            kv.remove(key)?;
+            meta.insert(key, ts.as_u64())?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Mark many entries as tombstones.
+    pub(crate) async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        docs: impl Iterator<Item = DocumentMetadata>,
+    ) -> Result<(), redb::Error> {
+        let docs = Vec::from_iter(docs);
+
+        self.submit_task(keyspace, move |kv, meta| {
+            for doc in docs {
+                kv.remove(doc.id)?;
+                meta.insert(doc.id, doc.last_updated.as_u64())?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Clear a tombstone entry.
+    ///
+    /// If the keyspace is left empty its tables are deleted and it is removed
+    /// from the keyspace list until a new entry is written to it.
+    pub(crate) async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key>,
+    ) -> Result<(), redb::Error> {
+        let keyspace = keyspace.to_owned();
+        let keys = Vec::from_iter(keys);
+
+        self.submit_write_task(move |txn| {
+            let (kv_name, meta_name) =
+                (kv_table_name(&keyspace), meta_table_name(&keyspace));
+            let kv_table = TableDefinition::<u64, &[u8]>::new(&kv_name);
+            let meta_table = TableDefinition::<u64, u64>::new(&meta_name);
+
+            let is_empty = {
+                let mut meta = match txn.open_table(meta_table) {
+                    Err(TableError::TableDoesNotExist(_)) => return Ok(()),
+                    other => other?,
+                };
+                for key in keys {
+                    meta.remove(key)?; // Our entry will already be removed.
+                }
+                meta.is_empty()?
+            };
+
+            if is_empty {
+                txn.delete_table(kv_table)?;
+                txn.delete_table(meta_table)?;
+                txn.open_table(KEYSPACE_LIST)?.remove(keyspace.as_str())?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get a document from the DB.
+    pub(crate) async fn get(
+        &self,
+        keyspace: &str,
+        key: Key,
+    ) -> Result<Option<Document>, redb::Error> {
+        let keyspace = keyspace.to_owned();
+
+        self.submit_read_task(move |txn| {
+            let (kv, meta) = match open_read_tables(txn, &keyspace)? {
+                None => return Ok(None),
+                Some(tables) => tables,
+            };
+
+            read_doc(&kv, &meta, key)
+        })
+        .await
+    }
+
+    /// Get many documents from the DB.
+    pub(crate) async fn get_many(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key>,
+    ) -> Result<Vec<Document>, redb::Error> {
+        let keyspace = keyspace.to_owned();
+        let keys = Vec::from_iter(keys);
+
+        self.submit_read_task(move |txn| {
+            let (kv, meta) = match open_read_tables(txn, &keyspace)? {
+                None => return Ok(Vec::new()),
+                Some(tables) => tables,
+            };
+
+            let mut docs = Vec::with_capacity(keys.len());
+            for key in keys {
+                if let Some(doc) = read_doc(&kv, &meta, key)? {
+                    docs.push(doc);
+                }
+            }
+
+            Ok(docs)
+        })
+        .await
+    }
+
+    /// Persists a keyspace state snapshot, replacing any existing snapshot.
+    ///
+    /// The snapshot is stored as its little endian watermark followed by the state.
+    pub(crate) async fn put_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), redb::Error> {
+        let keyspace = keyspace.to_owned();
+
+        self.submit_write_task(move |txn| {
+            let mut value = Vec::with_capacity(8 + snapshot.state.len());
+            value.extend_from_slice(&snapshot.watermark.as_u64().to_le_bytes());
+            value.extend_from_slice(&snapshot.state);
+
+            txn.open_table(SNAPSHOTS)?
+                .insert(keyspace.as_str(), value.as_slice())?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the persisted keyspace state snapshot, if any.
+    pub(crate) async fn get_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, redb::Error> {
+        let keyspace = keyspace.to_owned();
+
+        self.submit_read_task(move |txn| {
+            let snapshots = match open_read_table(txn, SNAPSHOTS)? {
+                None => return Ok(None),
+                Some(table) => table,
+            };
+
+            let value = match snapshots.get(keyspace.as_str())? {
+                Some(value) if value.value().len() >= 8 => value,
+                _ => return Ok(None),
+            };
+
+            let (watermark, state) = value.value().split_at(8);
+            let watermark = u64::from_le_bytes(watermark.try_into().unwrap());
+            Ok(Some(StateSnapshot {
+                watermark: HLCTimestamp::from_u64(watermark),
+                state: state.to_vec(),
+            }))
+        })
+        .await
+    }
+
+    /// Removes the persisted keyspace state snapshot.
+    pub(crate) async fn remove_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<(), redb::Error> {
+        let keyspace = keyspace.to_owned();
+
+        self.submit_write_task(move |txn| {
+            txn.open_table(SNAPSHOTS)?.remove(keyspace.as_str())?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Submits a write task to execute on the keyspace's tables.
+    ///
+    /// The tables are created if they do not already exist.
+    async fn submit_task<CB, T>(
+        &self,
+        keyspace: &str,
+        inner: CB,
+    ) -> Result<T, redb::Error>
+    where
+        T: Send + 'static,
+        CB: FnOnce(&mut KvTable, &mut MetaTable) -> Result<T, redb::Error>
+            + Send
+            + 'static,
+    {
+        let keyspace = keyspace.to_owned();
+
+        self.submit_write_task(move |txn| {
+            txn.open_table(KEYSPACE_LIST)?
+                .insert(keyspace.as_str(), ())?;
+
+            let (kv_name, meta_name) =
+                (kv_table_name(&keyspace), meta_table_name(&keyspace));
+            let mut kv = txn.open_table(TableDefinition::new(&kv_name))?;
+            let mut meta = txn.open_table(TableDefinition::new(&meta_name))?;
+            inner(&mut kv, &mut meta)
+        })
+        .await
+    }
+
+    /// Submits a task to execute within a write transaction on the background thread.
+    ///
+    /// The transaction is committed if the task succeeds.
+    async fn submit_write_task<CB, T>(&self, inner: CB) -> Result<T, redb::Error>
+    where
+        T: Send + 'static,
+        CB: FnOnce(&WriteTransaction) -> Result<T, redb::Error> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        let cb = move |db: &Database| {
+            let res = db.begin_write().map_err(redb::Error::from).and_then(|txn| {
+                let res = inner(&txn)?;
+                txn.commit()?;
+                Ok(res)
+            });
+            Box::new(move || {
+                let _ = tx.send(res);
+            }) as Completion
+        };
+
+        self.tx
+            .send_async(Box::new(cb))
+            .await
+            .expect("send message");
+
+        rx.await.unwrap()
+    }
+
+    /// Submits a task to execute within a read transaction on the blocking thread pool.
+    async fn submit_read_task<CB, T>(&self, inner: CB) -> Result<T, redb::Error>
+    where
+        T: Send + 'static,
+        CB: FnOnce(&ReadTransaction) -> Result<T, redb::Error> + Send + 'static,
+    {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || {
+            let txn = db.begin_read()?;
+            inner(&txn)
+        })
+        .await
+        .expect("spawn blocking read")
+    }
+}
+
+fn kv_table_name(keyspace: &str) -> String {
+    format!("datacake-{keyspace}-kv")
+}
+
+fn meta_table_name(keyspace: &str) -> String {
+    format!("datacake-{keyspace}-meta")
+}
+
+/// Opens a table for reading, returning `None` if it does not exist.
+fn open_read_table<K, V>(
+    txn: &ReadTransaction,
+    table: TableDefinition<K, V>,
+) -> Result<Option<ReadOnlyTable<K, V>>, redb::Error>
+where
+    K: redb::Key + 'static,
+    V: redb::Value + 'static,
+{
+    match txn.open_table(table) {
+        Ok(table) => Ok(Some(table)),
+        Err(TableError::TableDoesNotExist(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Opens the keyspace's tables for reading, returning `None` if the keyspace does not exist.
+fn open_read_tables(
+    txn: &ReadTransaction,
+    keyspace: &str,
+) -> Result<Option<(ReadOnlyKvTable, ReadOnlyMetaTable)>, redb::Error> {
+    let (kv_name, meta_name) = (kv_table_name(keyspace), meta_table_name(keyspace));
+    let kv = open_read_table(txn, TableDefinition::new(&kv_name))?;
+    let meta = open_read_table(txn, TableDefinition::new(&meta_name))?;
+    Ok(kv.zip(meta))
+}
+
+fn read_doc(
+    kv: &ReadOnlyKvTable,
+    meta: &ReadOnlyMetaTable,
+    key: Key,
+) -> Result<Option<Document>, redb::Error> {
+    let data = match kv.get(key)? {
+        None => return Ok(None),
+        Some(data) => data,
+    };
+    let ts = meta
+        .get(key)?
+        .expect("Document should have metadata")
+        .value();
+
+    Ok(Some(Document::new(
+        key,
+        HLCTimestamp::from_u64(ts),
+        data.value().to_vec(),
+    )))
+}
+
+fn setup_database(path: &Path) -> Result<Database, redb::Error> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            let _ = std::fs::create_dir_all(parent); // Attempt to create the directory.
+        }
+    }
+
+    Ok(Database::create(path)?)
+}
+
+/// Runs all tasks received with a reference to the given database.
+/// Executes write tasks until every handle has been dropped.
+///
+/// The writer only holds a weak reference so the database file is closed as soon
+/// as the last handle is dropped, allowing it to be reopened straight away.
+/// Each task's result is delivered once the reference has been released.
+fn run_tasks(db: Weak<Database>, tasks: Receiver<Task>) {
+    while let Ok(task) = tasks.recv() {
+        let Some(database) = db.upgrade() else { break };
+        let complete = (task)(&database);
+        drop(database);
+        complete();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn get_path() -> PathBuf {
+        temp_dir()
+            .join(Uuid::new_v4().to_string())
+            .join("store.redb")
+    }
+
+    #[tokio::test]
+    async fn test_db_creation() {
+        StorageHandle::open(get_path())
+            .await
+            .expect("Database should open OK.");
+    }
+
+    #[tokio::test]
+    async fn test_db_put_and_get() {
+        let handle = StorageHandle::open(get_path())
+            .await
+            .expect("Database should open OK.");
+
+        let doc1 = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        handle
+            .put_kv("test", doc1.clone())
+            .await
+            .expect("Put new doc");
+
+        // Test keyspace dont overlap
+        let doc2 = Document::new(1, HLCTimestamp::from_u64(2), b"Hello 2".as_ref());
+        handle
+            .put_kv("test2", doc2.clone())
+            .await
+            .expect("Put new doc");
+
+        let fetched_doc_1 = handle
+            .get("test", 1)
+            .await
+            .expect("Get doc")
+            .expect("Doc exists");
+        let fetched_doc_2 = handle
+            .get("test2", 1)
+            .await
+            .expect("Get doc")
+            .expect("Doc exists");
+
+        assert_eq!(
+            [doc1, doc2],
+            [fetched_doc_1, fetched_doc_2],
+            "Documents returned should not overlap and match."
+        );
+        assert!(handle.get("missing", 1).await.expect("Get doc").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_db_mark_tombstone() {
+        let handle = StorageHandle::open(get_path())
+            .await
+            .expect("Database should open OK.");
+
+        let doc1 = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        handle
+            .put_kv("test", doc1.clone())
+            .await
+            .expect("Put new doc");
+
+        handle
+            .mark_tombstone("test", doc1.id(), HLCTimestamp::from_u64(1))
+            .await
+            .expect("Mark tombstone");
+        assert!(
+            handle.get("test", 1).await.expect("Get doc").is_none(),
+            "Document should not exist"
+        );
+        assert_eq!(
+            handle.get_metadata("test").await.expect("Get metadata"),
+            vec![(1, HLCTimestamp::from_u64(1), true)],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_keyspace_removed() {
+        let handle = StorageHandle::open(get_path())
+            .await
+            .expect("Database should open OK.");
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(0), b"Hello".as_ref());
+        handle
+            .put_kv("test", doc.clone())
+            .await
+            .expect("Put new doc");
+        handle
+            .put_kv("test2", doc.clone())
+            .await
+            .expect("Put new doc");
+        handle
+            .mark_tombstone("test", 1, HLCTimestamp::from_u64(1))
+            .await
+            .expect("Mark tombstone");
+
+        handle
+            .remove_tombstones("missing", [1].into_iter())
+            .await
+            .expect("Missing keyspaces should be ignored");
+        assert_eq!(handle.keyspace_list().await.unwrap(), ["test", "test2"]);
+
+        handle
+            .remove_tombstones("test", [1].into_iter())
+            .await
+            .expect("Remove tombstones");
+        assert_eq!(handle.keyspace_list().await.unwrap(), ["test2"]);
+
+        handle
+            .put_kv("test", doc.clone())
+            .await
+            .expect("Put new doc");
+        assert_eq!(handle.keyspace_list().await.unwrap(), ["test", "test2"]);
+        assert_eq!(handle.get("test", 1).await.unwrap(), Some(doc));
+    }
+}
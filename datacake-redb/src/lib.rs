@@ -0,0 +1,318 @@
+//! # Datacake redb
+//!
+//! A pre-built implementation of the datacake-eventual-consistency `Storage` trait, this allows you to set up
+//! a persistent cluster immediately without any hassle of implementing a correct store.
+//!
+//! For more info see <https://github.com/lnx-search/datacake>
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::env::temp_dir;
+//! use anyhow::Result;
+//! use uuid::Uuid;
+//! use datacake_eventual_consistency::EventuallyConsistentStoreExtension;
+//! use datacake_node::{
+//!     ConnectionConfig,
+//!     Consistency,
+//!     DCAwareSelector,
+//!     DatacakeNodeBuilder,
+//! };
+//! use datacake_redb::RedbStorage;
+//!
+//! static KEYSPACE: &str = "redb-store";
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     tracing_subscriber::fmt::init();
+//!
+//!     let temp_dir = temp_dir().join(Uuid::new_v4().to_string());
+//!     std::fs::create_dir_all(&temp_dir)?;
+//!
+//!     let store = RedbStorage::open(temp_dir.join("store.redb")).await?;
+//!
+//!     let addr = test_helper::get_unused_addr();
+//!     let connection_cfg = ConnectionConfig::new(addr, addr, Vec::<String>::new());
+//!
+//!     let node = DatacakeNodeBuilder::<DCAwareSelector>::new(1, connection_cfg)
+//!         .connect()
+//!         .await?;
+//!     let store = node
+//!         .add_extension(EventuallyConsistentStoreExtension::new(store))
+//!         .await?;
+//!
+//!     let handle = store.handle();
+//!
+//!     handle.put(KEYSPACE, 1, b"Hello, world".to_vec(), Consistency::All).await?;
+//!
+//!     let doc = handle
+//!         .get(KEYSPACE, 1)
+//!         .await?
+//!         .expect("Document should not be none");
+//!     assert_eq!(doc.id(), 1);
+//!     assert_eq!(doc.data(), b"Hello, world");
+//!
+//!     handle.del(KEYSPACE, 1, Consistency::All).await?;
+//!     let doc = handle.get(KEYSPACE, 1).await?;
+//!     assert!(doc.is_none(), "No document should not exist!");
+//!
+//!     handle.del(KEYSPACE, 2, Consistency::All).await?;
+//!     let doc = handle.get(KEYSPACE, 2).await?;
+//!     assert!(doc.is_none(), "No document should not exist!");
+//!
+//!     node.shutdown().await;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+// `redb::Error` is returned as is, in the same way the other backends return their database's error.
+#![allow(clippy::result_large_err)]
+
+mod db;
+
+use std::path::Path;
+
+use async_trait::async_trait;
+use datacake_crdt::{HLCTimestamp, Key};
+use datacake_eventual_consistency::{
+    BulkMutationError,
+    DocsStream,
+    Document,
+    DocumentMetadata,
+    MetadataStream,
+    StateSnapshot,
+    Storage,
+    STREAM_CHUNK_SIZE,
+};
+pub use db::StorageHandle;
+use futures::StreamExt;
+pub use redb::{self, Error};
+
+/// A [Storage] implementation based on a redb database.
+///
+/// redb is written entirely in Rust, so unlike the SQLite and LMDB backends
+/// it does not require a C toolchain to build or cross-compile.
+pub struct RedbStorage {
+    db: StorageHandle,
+}
+
+impl RedbStorage {
+    /// Opens the redb database file at the given path.
+    /// This spawns 1 background thread which executes every write, reads are executed
+    /// concurrently on the blocking thread pool.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, redb::Error> {
+        let db = StorageHandle::open(path).await?;
+
+        Ok(Self { db })
+    }
+
+    /// Access to the redb storage handle.
+    ///
+    /// This allows you to access the redb database directly,
+    /// but it does not provide any access to the tables used
+    /// by the datacake storage layer.
+    pub fn handle(&self) -> &StorageHandle {
+        &self.db
+    }
+}
+
+#[async_trait]
+impl Storage for RedbStorage {
+    type Error = redb::Error;
+    type DocsIter = Box<dyn Iterator<Item = Document>>;
+    type MetadataIter = Box<dyn Iterator<Item = (Key, HLCTimestamp, bool)>>;
+
+    async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        self.handle().keyspace_list().await
+    }
+
+    async fn iter_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<Self::MetadataIter, Self::Error> {
+        self.handle()
+            .get_metadata(keyspace)
+            .await
+            .map(|v| Box::new(v.into_iter()) as Self::MetadataIter)
+    }
+
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let handle = self.db.clone();
+        let keyspace = keyspace.to_string();
+
+        // Pages through the keyspace by key, each page is only read once the
+        // previous one has been consumed.
+        let stream = futures::stream::try_unfold(Some(None), move |cursor| {
+            let handle = handle.clone();
+            let keyspace = keyspace.clone();
+            async move {
+                let after = match cursor {
+                    None => return Ok(None),
+                    Some(after) => after,
+                };
+
+                let page = handle
+                    .get_metadata_page(&keyspace, after, STREAM_CHUNK_SIZE)
+                    .await?;
+                if page.is_empty() {
+                    return Ok(None);
+                }
+
+                let next_cursor = if page.len() < STREAM_CHUNK_SIZE {
+                    None
+                } else {
+                    page.last().map(|entry| Some(entry.0))
+                };
+
+                Ok(Some((page, next_cursor)))
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn remove_tombstones(
+        &self,
+        keyspace: &str,
+        keys: impl Iterator<Item = Key> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.handle()
+            .remove_tombstones(keyspace, keys)
+            .await
+            .map_err(BulkMutationError::empty_with_error)
+    }
+
+    async fn put(&self, keyspace: &str, document: Document) -> Result<(), Self::Error> {
+        self.handle().put_kv(keyspace, document).await
+    }
+
+    async fn multi_put(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = Document> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.handle()
+            .put_many_kv(keyspace, documents)
+            .await
+            .map_err(BulkMutationError::empty_with_error)
+    }
+
+    async fn mark_as_tombstone(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+        timestamp: HLCTimestamp,
+    ) -> Result<(), Self::Error> {
+        self.handle()
+            .mark_tombstone(keyspace, doc_id, timestamp)
+            .await
+    }
+
+    async fn mark_many_as_tombstone(
+        &self,
+        keyspace: &str,
+        documents: impl Iterator<Item = DocumentMetadata> + Send,
+    ) -> Result<(), BulkMutationError<Self::Error>> {
+        self.handle()
+            .mark_many_as_tombstone(keyspace, documents)
+            .await
+            .map_err(BulkMutationError::empty_with_error)
+    }
+
+    async fn get(
+        &self,
+        keyspace: &str,
+        doc_id: Key,
+    ) -> Result<Option<Document>, Self::Error> {
+        self.handle().get(keyspace, doc_id).await
+    }
+
+    async fn multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<Self::DocsIter, Self::Error> {
+        self.handle()
+            .get_many(keyspace, doc_ids)
+            .await
+            .map(|v| Box::new(v.into_iter()) as Self::DocsIter)
+    }
+
+    async fn stream_multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<DocsStream<Self::Error>, Self::Error> {
+        let handle = self.db.clone();
+        let keyspace = keyspace.to_string();
+        let doc_ids = doc_ids.collect::<Vec<_>>();
+
+        // Each chunk of documents is only read once the previous one has been consumed.
+        let chunks = doc_ids
+            .chunks(STREAM_CHUNK_SIZE)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+        let stream = futures::stream::iter(chunks).then(move |doc_ids| {
+            let handle = handle.clone();
+            let keyspace = keyspace.clone();
+            async move { handle.get_many(&keyspace, doc_ids.into_iter()).await }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.db.put_snapshot(keyspace, snapshot).await
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        self.db.get_snapshot(keyspace).await
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.db.remove_snapshot(keyspace).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use datacake_eventual_consistency::test_suite;
+    use uuid::Uuid;
+
+    use crate::RedbStorage;
+
+    #[tokio::test]
+    async fn test_storage_logic() {
+        let path = temp_dir()
+            .join(Uuid::new_v4().to_string())
+            .join("store.redb");
+
+        let storage = RedbStorage::open(path).await.expect("Open DB");
+        test_suite::run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_storage_durability() {
+        let path = temp_dir()
+            .join(Uuid::new_v4().to_string())
+            .join("store.redb");
+
+        test_suite::run_persistence_test_suite(|| async {
+            RedbStorage::open(&path).await.expect("Open DB")
+        })
+        .await;
+    }
+}
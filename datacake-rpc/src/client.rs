@@ -1,11 +1,17 @@
 use std::borrow::Cow;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::time::Duration;
 
+use http::HeaderMap;
+
 use crate::handler::{Handler, RpcService, TryAsBody, TryIntoBody};
+use crate::interceptor::{self, CallContext, Interceptor};
+use crate::metadata::{Metadata, Response};
 use crate::net::{Channel, Status};
 use crate::request::{MessageMetadata, RequestContents};
-use crate::Body;
+use crate::retry::RetryPolicy;
+use crate::{deadline, Body};
 
 /// A type alias for the returned data view of the RPC message reply.
 pub type MessageReply<Svc, Msg> =
@@ -71,6 +77,8 @@ where
 {
     channel: Channel,
     timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
     _p: PhantomData<Svc>,
 }
 
@@ -82,6 +90,8 @@ where
         Self {
             channel: self.channel.clone(),
             timeout: self.timeout,
+            retry_policy: self.retry_policy.clone(),
+            interceptors: self.interceptors.clone(),
             _p: PhantomData,
         }
     }
@@ -98,6 +108,8 @@ where
         Self {
             channel,
             timeout: None,
+            retry_policy: None,
+            interceptors: Vec::new(),
             _p: PhantomData,
         }
     }
@@ -105,10 +117,31 @@ where
     /// Sets a timeout of a given amount of time.
     ///
     /// If any requests exceed this amount of time `Status::timeout` is returned.
+    ///
+    /// The timeout is sent to the server which cancels the handler once it
+    /// is exceeded. Calls made from within a handler use the remaining time
+    /// of the request being handled if it is shorter than this timeout.
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = Some(timeout);
     }
 
+    /// Sets the policy used for retrying failed requests.
+    ///
+    /// Retries only apply to messages sent with [Self::send], as messages sent
+    /// with [Self::send_owned] are consumed by the first attempt.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = Some(policy);
+    }
+
+    /// Adds an interceptor which runs around every message sent by the client.
+    ///
+    /// Interceptors are run in the order they are added and are carried over
+    /// to clients created with [Self::new_client], see [Interceptor] for
+    /// more information.
+    pub fn add_interceptor(&mut self, interceptor: impl Interceptor) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
     /// Creates a new RPC client which can handle a new service type.
     ///
     /// [RpcClient]'s are cheap to create and should be preferred over
@@ -120,6 +153,8 @@ where
         RpcClient {
             channel: self.channel.clone(),
             timeout: None,
+            retry_policy: None,
+            interceptors: self.interceptors.clone(),
             _p: PhantomData,
         }
     }
@@ -139,13 +174,64 @@ where
         // this on the trait side, which is a shame.
         <Svc as Handler<Msg>>::Reply: RequestContents + TryIntoBody,
     {
-        let metadata = MessageMetadata {
+        self.send_with_metadata(msg, Metadata::default())
+            .await
+            .map(Response::into_inner)
+    }
+
+    /// Sends a message to the server along with the given metadata and
+    /// wait for a reply.
+    ///
+    /// The returned [Response] contains the metadata attached to the reply
+    /// by the handler.
+    pub async fn send_with_metadata<Msg>(
+        &self,
+        msg: &Msg,
+        metadata: Metadata,
+    ) -> Result<Response<MessageReply<Svc, Msg>>, Status>
+    where
+        Msg: RequestContents + TryAsBody,
+        Svc: Handler<Msg>,
+        // Due to some interesting compiler errors, we couldn't use GATs here to enforce
+        // this on the trait side, which is a shame.
+        <Svc as Handler<Msg>>::Reply: RequestContents + TryIntoBody,
+    {
+        let msg_metadata = MessageMetadata {
             service_name: Cow::Borrowed(<Svc as RpcService>::service_name()),
             path: Cow::Borrowed(<Svc as Handler<Msg>>::path()),
         };
 
-        let body = msg.try_as_body()?;
-        self.send_body(body, metadata).await
+        let mut attempt = 0;
+        loop {
+            let body = msg.try_as_body()?;
+            let msg_metadata = MessageMetadata {
+                service_name: msg_metadata.service_name.clone(),
+                path: msg_metadata.path.clone(),
+            };
+
+            let status = match self.send_body(body, msg_metadata, &metadata).await {
+                Err(status) => status,
+                reply => return reply,
+            };
+
+            match self.retry_policy.as_ref() {
+                Some(policy)
+                    if attempt < policy.max_retries()
+                        && policy.is_retryable(status.code) =>
+                {
+                    let backoff = policy.backoff(attempt);
+                    debug!(
+                        attempt = attempt,
+                        backoff = ?backoff,
+                        error = %status,
+                        "Retrying failed request.",
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                },
+                _ => return Err(status),
+            }
+        }
     }
 
     /// Sends a message to the server and wait for a reply using an owned
@@ -163,20 +249,43 @@ where
         // this on the trait side, which is a shame.
         <Svc as Handler<Msg>>::Reply: RequestContents + TryIntoBody,
     {
-        let metadata = MessageMetadata {
+        self.send_owned_with_metadata(msg, Metadata::default())
+            .await
+            .map(Response::into_inner)
+    }
+
+    /// Sends a message to the server along with the given metadata and
+    /// wait for a reply using an owned message value.
+    ///
+    /// The returned [Response] contains the metadata attached to the reply
+    /// by the handler.
+    pub async fn send_owned_with_metadata<Msg>(
+        &self,
+        msg: Msg,
+        metadata: Metadata,
+    ) -> Result<Response<MessageReply<Svc, Msg>>, Status>
+    where
+        Msg: RequestContents + TryIntoBody,
+        Svc: Handler<Msg>,
+        // Due to some interesting compiler errors, we couldn't use GATs here to enforce
+        // this on the trait side, which is a shame.
+        <Svc as Handler<Msg>>::Reply: RequestContents + TryIntoBody,
+    {
+        let msg_metadata = MessageMetadata {
             service_name: Cow::Borrowed(<Svc as RpcService>::service_name()),
             path: Cow::Borrowed(<Svc as Handler<Msg>>::path()),
         };
 
         let body = msg.try_into_body()?;
-        self.send_body(body, metadata).await
+        self.send_body(body, msg_metadata, &metadata).await
     }
 
     async fn send_body<Msg>(
         &self,
         body: Body,
-        metadata: MessageMetadata,
-    ) -> Result<MessageReply<Svc, Msg>, Status>
+        msg_metadata: MessageMetadata,
+        metadata: &Metadata,
+    ) -> Result<Response<MessageReply<Svc, Msg>>, Status>
     where
         Msg: RequestContents,
         Svc: Handler<Msg>,
@@ -184,9 +293,51 @@ where
         // this on the trait side, which is a shame.
         <Svc as Handler<Msg>>::Reply: RequestContents + TryIntoBody,
     {
-        let future = self.channel.send_msg(metadata, body);
+        let mut headers = HeaderMap::new();
+        metadata.write_headers(&mut headers);
+
+        let mut call = CallContext::new(
+            crate::to_uri_path(&msg_metadata.service_name, &msg_metadata.path),
+            self.channel.remote_addr(),
+            None,
+            headers,
+        );
+
+        let result = match interceptor::run_on_request(&self.interceptors, &mut call)
+            .await
+        {
+            Ok(()) => {
+                self.send_body_inner::<Msg>(body, msg_metadata, call.headers().clone())
+                    .await
+            },
+            Err(status) => Err(status),
+        };
+
+        interceptor::run_on_response(&self.interceptors, &call, result.as_ref().err())
+            .await;
+
+        result
+    }
+
+    async fn send_body_inner<Msg>(
+        &self,
+        body: Body,
+        msg_metadata: MessageMetadata,
+        mut headers: HeaderMap,
+    ) -> Result<Response<MessageReply<Svc, Msg>>, Status>
+    where
+        Msg: RequestContents,
+        Svc: Handler<Msg>,
+        <Svc as Handler<Msg>>::Reply: RequestContents + TryIntoBody,
+    {
+        let timeout = deadline::effective_timeout(self.timeout)?;
+        if let Some(timeout) = timeout {
+            deadline::set_header(&mut headers, timeout);
+        }
+
+        let future = self.channel.send_msg(msg_metadata, headers, body);
 
-        let result = match self.timeout {
+        let (headers, result) = match timeout {
             Some(duration) => tokio::time::timeout(duration, future)
                 .await
                 .map_err(|_| Status::timeout())?
@@ -195,7 +346,10 @@ where
         };
 
         match result {
-            Ok(body) => <<Svc as Handler<Msg>>::Reply>::from_body(body).await,
+            Ok(body) => {
+                let reply = <<Svc as Handler<Msg>>::Reply>::from_body(body).await?;
+                Ok(Response::new(Metadata::from_headers(&headers), reply))
+            },
             Err(buffer) => {
                 let status = rkyv::from_bytes(&buffer).map_err(|_| Status::invalid())?;
                 Err(status)
@@ -0,0 +1,212 @@
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::handler::TryAsBody;
+use crate::request::RequestContents;
+use crate::{Body, Status};
+
+/// A serde based format used to encode messages which cannot use [rkyv].
+///
+/// Codecs are selected per message by wrapping it in [Encoded], the
+/// [Json] and [Bincode] codecs are provided behind the `json` and
+/// `bincode` features respectively.
+pub trait Codec: Send + Sync + 'static {
+    /// Encodes the value into a buffer.
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Status>;
+
+    /// Decodes a value from the buffer.
+    fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T, Status>;
+}
+
+/// A message or reply encoded with the codec `C` rather than [rkyv].
+///
+/// This allows services to exchange types which cannot derive the rkyv
+/// traits, for example types from third-party crates. Unlike rkyv messages,
+/// the receiver gets the fully deserialized value rather than a zero-copy
+/// view, so a `Request<Json<T>>` dereferences to `T`.
+///
+/// ```rust
+/// use datacake_rpc::{Handler, Json, Request, RpcService, ServiceRegistry, Status};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Greet {
+///     name: String,
+/// }
+///
+/// pub struct GreeterService;
+///
+/// impl RpcService for GreeterService {
+///     fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+///         registry.add_handler::<Json<Greet>>();
+///     }
+/// }
+///
+/// #[datacake_rpc::async_trait]
+/// impl Handler<Json<Greet>> for GreeterService {
+///     type Reply = Json<String>;
+///
+///     async fn on_message(&self, msg: Request<Json<Greet>>) -> Result<Self::Reply, Status> {
+///         Ok(Json::new(format!("Hello, {}!", msg.name)))
+///     }
+/// }
+/// ```
+pub struct Encoded<C, T> {
+    value: T,
+    _codec: PhantomData<fn() -> C>,
+}
+
+impl<C, T> Encoded<C, T> {
+    /// Wraps the value to be encoded with the codec.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            _codec: PhantomData,
+        }
+    }
+
+    #[inline]
+    /// Consumes the wrapper returning the inner value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<C, T> From<T> for Encoded<C, T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<C, T: Clone> Clone for Encoded<C, T> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<C, T: Debug> Debug for Encoded<C, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<C, T> Deref for Encoded<C, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<C, T> DerefMut for Encoded<C, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<C, T> TryAsBody for Encoded<C, T>
+where
+    C: Codec,
+    T: Serialize,
+{
+    fn try_as_body(&self) -> Result<Body, Status> {
+        C::encode(&self.value).map(Body::from)
+    }
+}
+
+#[async_trait]
+impl<C, T> RequestContents for Encoded<C, T>
+where
+    C: Codec,
+    T: DeserializeOwned + Send + 'static,
+{
+    type Content = T;
+
+    async fn from_body(body: Body) -> Result<Self::Content, Status> {
+        let bytes = hyper::body::to_bytes(body.into_inner())
+            .await
+            .map_err(Status::internal)?;
+
+        C::decode(&bytes)
+    }
+}
+
+#[cfg(feature = "json")]
+/// A [Codec] encoding messages as JSON using [serde_json].
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Status> {
+        serde_json::to_vec(value).map_err(Status::internal)
+    }
+
+    fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T, Status> {
+        serde_json::from_slice(buf).map_err(|_| Status::invalid())
+    }
+}
+
+#[cfg(feature = "json")]
+/// A message or reply encoded as JSON.
+pub type Json<T> = Encoded<JsonCodec, T>;
+
+#[cfg(feature = "bincode")]
+/// A [Codec] encoding messages using [bincode].
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl Codec for BincodeCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Status> {
+        bincode::serialize(value).map_err(Status::internal)
+    }
+
+    fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T, Status> {
+        bincode::deserialize(buf).map_err(|_| Status::invalid())
+    }
+}
+
+#[cfg(feature = "bincode")]
+/// A message or reply encoded using bincode.
+pub type Bincode<T> = Encoded<BincodeCodec, T>;
+
+#[cfg(all(test, feature = "json", feature = "bincode"))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::handler::TryIntoBody;
+
+    async fn roundtrip<C: Codec>(value: HashMap<String, Vec<u32>>) {
+        let body = Encoded::<C, _>::new(value.clone()).try_into_body().unwrap();
+        let decoded = Encoded::<C, HashMap<String, Vec<u32>>>::from_body(body)
+            .await
+            .unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[tokio::test]
+    async fn test_codec_roundtrip() {
+        let mut value = HashMap::new();
+        value.insert("a".to_string(), vec![1, 2, 3]);
+        value.insert("b".to_string(), Vec::new());
+
+        roundtrip::<JsonCodec>(value.clone()).await;
+        roundtrip::<BincodeCodec>(value).await;
+    }
+
+    #[tokio::test]
+    async fn test_invalid_payload() {
+        let body = Body::from(b"not json".to_vec());
+        let res = Json::<u64>::from_body(body).await;
+        assert_eq!(res.err(), Some(Status::invalid()));
+
+        let body = Body::from(vec![1u8]);
+        let res = Bincode::<u64>::from_body(body).await;
+        assert_eq!(res.err(), Some(Status::invalid()));
+    }
+}
@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::time::Duration;
+
+use http::{HeaderMap, HeaderValue};
+use tokio::time::Instant;
+
+use crate::Status;
+
+/// The header carrying the time the caller is willing to wait for a reply,
+/// in milliseconds.
+///
+/// A relative timeout is sent rather than an absolute deadline so the
+/// clocks of the two nodes do not need to be in sync.
+pub(crate) const DEADLINE_HEADER: &str = "x-datacake-timeout";
+
+tokio::task_local! {
+    /// The deadline of the request currently being handled by the task.
+    static DEADLINE: Instant;
+}
+
+/// The deadline of the request currently being handled, if any.
+///
+/// This allows calls made by a handler to inherit the deadline of the
+/// request it is handling.
+pub(crate) fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Runs the future with the given deadline, returning [Status::timeout]
+/// if it is not completed in time.
+pub(crate) async fn scope<T>(
+    deadline: Instant,
+    fut: impl Future<Output = Result<T, Status>>,
+) -> Result<T, Status> {
+    tokio::time::timeout_at(deadline, DEADLINE.scope(deadline, fut))
+        .await
+        .unwrap_or_else(|_| Err(Status::timeout()))
+}
+
+/// Works out the timeout of an outgoing call from the client's own timeout
+/// and the deadline of the request currently being handled.
+///
+/// Returns [Status::timeout] if the inherited deadline has already passed.
+pub(crate) fn effective_timeout(
+    timeout: Option<Duration>,
+) -> Result<Option<Duration>, Status> {
+    let inherited = match current() {
+        None => return Ok(timeout),
+        Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+    };
+
+    if inherited.is_zero() {
+        return Err(Status::timeout());
+    }
+
+    Ok(Some(
+        timeout.map_or(inherited, |timeout| timeout.min(inherited)),
+    ))
+}
+
+/// Reads the deadline of an incoming request from its headers.
+pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Instant> {
+    let millis = headers
+        .get(DEADLINE_HEADER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()?;
+    Some(Instant::now() + Duration::from_millis(millis))
+}
+
+/// Sets the timeout of an outgoing request on its headers.
+pub(crate) fn set_header(headers: &mut HeaderMap, timeout: Duration) {
+    let millis = timeout.as_millis().min(u64::MAX as u128) as u64;
+    headers.insert(DEADLINE_HEADER, HeaderValue::from(millis));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_effective_timeout() {
+        let timeout = Duration::from_secs(1);
+        assert_eq!(effective_timeout(None), Ok(None));
+        assert_eq!(effective_timeout(Some(timeout)), Ok(Some(timeout)));
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        DEADLINE
+            .scope(deadline, async {
+                let inherited = effective_timeout(None).unwrap().unwrap();
+                assert!(inherited <= Duration::from_millis(500));
+
+                let inherited = effective_timeout(Some(timeout)).unwrap().unwrap();
+                assert!(
+                    inherited <= Duration::from_millis(500),
+                    "The shorter timeout should be used."
+                );
+
+                let inherited = effective_timeout(Some(Duration::from_millis(10)))
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(inherited, Duration::from_millis(10));
+            })
+            .await;
+
+        DEADLINE
+            .scope(Instant::now(), async {
+                assert_eq!(effective_timeout(None), Err(Status::timeout()));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(from_headers(&headers).is_none());
+
+        set_header(&mut headers, Duration::from_millis(250));
+        assert_eq!(headers.get(DEADLINE_HEADER).unwrap(), "250");
+        let deadline = from_headers(&headers).unwrap();
+        assert!(deadline <= Instant::now() + Duration::from_millis(250));
+
+        headers.insert(DEADLINE_HEADER, HeaderValue::from_static("invalid"));
+        assert!(from_headers(&headers).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let res = scope(deadline, async {
+            assert_eq!(current(), Some(deadline));
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await;
+        assert_eq!(res, Err(Status::timeout()));
+        assert!(current().is_none());
+    }
+}
@@ -4,11 +4,14 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use parking_lot::Mutex;
 use rkyv::ser::serializers::AllocSerializer;
-use rkyv::{AlignedVec, Archive, Serialize};
+use rkyv::{Archive, Serialize};
+use tokio::time::Instant;
 
+use crate::metadata::Metadata;
 use crate::net::Status;
-use crate::request::{Request, RequestContents};
+use crate::request::{PeerIdentity, Request, RequestContents};
 use crate::{Body, SCRATCH_SPACE};
 
 /// A specific handler key.
@@ -111,12 +114,12 @@ where
     /// by the service via the generic.
     pub fn add_handler<Msg>(&mut self)
     where
-        Msg: RequestContents + Sync + Send + 'static,
+        Msg: RequestContents + Send + 'static,
         Svc: Handler<Msg>,
     {
         let phantom = PhantomHandler {
             handler: self.service.clone(),
-            _msg: PhantomData::<Msg>::default(),
+            _msg: PhantomData,
         };
 
         let uri = crate::to_uri_path(Svc::service_name(), <Svc as Handler<Msg>>::path());
@@ -235,8 +238,12 @@ pub(crate) trait OpaqueMessageHandler: Send + Sync {
     async fn try_handle(
         &self,
         remote_addr: SocketAddr,
+        peer_identity: Option<PeerIdentity>,
+        deadline: Option<Instant>,
+        metadata: Metadata,
+        response_metadata: Arc<Mutex<Metadata>>,
         data: Body,
-    ) -> Result<Body, AlignedVec>;
+    ) -> Result<Body, Status>;
 }
 
 struct PhantomHandler<H, Msg>
@@ -245,39 +252,33 @@ where
     Msg: Send + 'static,
 {
     handler: Arc<H>,
-    _msg: PhantomData<Msg>,
+    _msg: PhantomData<fn() -> Msg>,
 }
 
 #[async_trait]
 impl<H, Msg> OpaqueMessageHandler for PhantomHandler<H, Msg>
 where
-    Msg: RequestContents + Send + Sync + 'static,
+    Msg: RequestContents + Send + 'static,
     H: Handler<Msg> + Send + Sync + 'static,
 {
     async fn try_handle(
         &self,
         remote_addr: SocketAddr,
+        peer_identity: Option<PeerIdentity>,
+        deadline: Option<Instant>,
+        metadata: Metadata,
+        response_metadata: Arc<Mutex<Metadata>>,
         data: Body,
-    ) -> Result<Body, AlignedVec> {
-        let view = match Msg::from_body(data).await {
-            Ok(view) => view,
-            Err(status) => {
-                let error = rkyv::to_bytes::<_, SCRATCH_SPACE>(&status)
-                    .unwrap_or_else(|_| AlignedVec::new());
-                return Err(error);
-            },
-        };
-
-        let msg = Request::new(remote_addr, view);
+    ) -> Result<Body, Status> {
+        let view = Msg::from_body(data).await?;
+        let msg = Request::<Msg>::new(remote_addr, peer_identity, view)
+            .with_deadline(deadline)
+            .with_metadata(metadata, response_metadata);
 
         self.handler
             .on_message(msg)
             .await
             .and_then(|reply| reply.try_into_body())
-            .map_err(|status| {
-                rkyv::to_bytes::<_, SCRATCH_SPACE>(&status)
-                    .unwrap_or_else(|_| AlignedVec::new())
-            })
     }
 }
 
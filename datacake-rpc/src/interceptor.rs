@@ -0,0 +1,193 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use http::HeaderMap;
+
+use crate::request::PeerIdentity;
+use crate::{Metadata, Status};
+
+#[async_trait]
+/// A hook which runs around every RPC call made by a [RpcClient](crate::RpcClient)
+/// or handled by a [Server](crate::Server).
+///
+/// Interceptors can inspect and modify the headers of a call, reject it
+/// by returning a [Status], and observe how and when the call completed.
+/// This makes them suitable for cross-cutting logic like authentication,
+/// quotas and audit logging.
+///
+/// ```rust
+/// use datacake_rpc::{CallContext, Interceptor, Status};
+///
+/// pub struct RequireToken(&'static str);
+///
+/// #[datacake_rpc::async_trait]
+/// impl Interceptor for RequireToken {
+///     async fn on_request(&self, call: &mut CallContext) -> Result<(), Status> {
+///         match call.headers().get("authorization") {
+///             Some(token) if token == self.0 => Ok(()),
+///             _ => Err(Status::permission_denied("Missing or invalid token.")),
+///         }
+///     }
+///
+///     async fn on_response(&self, call: &CallContext, status: Option<&Status>) {
+///         println!(
+///             "{}/{} from {} took {:?}, error: {:?}",
+///             call.service(),
+///             call.path(),
+///             call.remote_addr(),
+///             call.elapsed(),
+///             status,
+///         );
+///     }
+/// }
+/// ```
+pub trait Interceptor: Send + Sync + 'static {
+    /// Called before the call is sent by the client or handled by the server.
+    ///
+    /// Returning an error rejects the call, the status is returned to the
+    /// caller and no further interceptors are run.
+    async fn on_request(&self, _call: &mut CallContext) -> Result<(), Status> {
+        Ok(())
+    }
+
+    /// Called once the call has completed, including calls which were rejected.
+    ///
+    /// `status` contains the error the call failed with, if any.
+    async fn on_response(&self, _call: &CallContext, _status: Option<&Status>) {}
+}
+
+/// The details of a single RPC call passed to an [Interceptor].
+pub struct CallContext {
+    uri_path: String,
+    remote_addr: SocketAddr,
+    peer_identity: Option<PeerIdentity>,
+    headers: HeaderMap,
+    started_at: Instant,
+}
+
+impl CallContext {
+    pub(crate) fn new(
+        uri_path: String,
+        remote_addr: SocketAddr,
+        peer_identity: Option<PeerIdentity>,
+        headers: HeaderMap,
+    ) -> Self {
+        Self {
+            uri_path,
+            remote_addr,
+            peer_identity,
+            headers,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// The name of the service being called.
+    pub fn service(&self) -> &str {
+        self.uri_path
+            .trim_start_matches('/')
+            .split_once('/')
+            .map(|(service, _)| service)
+            .unwrap_or_default()
+    }
+
+    /// The message name/path of the handler being called.
+    pub fn path(&self) -> &str {
+        self.uri_path
+            .trim_start_matches('/')
+            .split_once('/')
+            .map(|(_, path)| path)
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    /// The address of the remote peer.
+    ///
+    /// For servers this is the client, for clients this is the server.
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    #[inline]
+    /// The identity the client established with its TLS certificate.
+    ///
+    /// This is always `None` for calls made by a client.
+    pub fn peer_identity(&self) -> Option<&PeerIdentity> {
+        self.peer_identity.as_ref()
+    }
+
+    #[inline]
+    /// The headers of the call.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    #[inline]
+    /// A mutable reference to the headers of the call.
+    ///
+    /// Headers added by client interceptors are sent to the server.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// The metadata of the call.
+    ///
+    /// Metadata is carried in the headers of the call, so entries added
+    /// to the headers by earlier interceptors are included.
+    pub fn metadata(&self) -> Metadata {
+        Metadata::from_headers(&self.headers)
+    }
+
+    #[inline]
+    /// The time elapsed since the call started.
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+/// Runs the `on_request` hook of each interceptor in order, stopping at the
+/// first rejection.
+pub(crate) async fn run_on_request(
+    interceptors: &[Arc<dyn Interceptor>],
+    call: &mut CallContext,
+) -> Result<(), Status> {
+    for interceptor in interceptors {
+        interceptor.on_request(call).await?;
+    }
+    Ok(())
+}
+
+/// Runs the `on_response` hook of each interceptor in order.
+pub(crate) async fn run_on_response(
+    interceptors: &[Arc<dyn Interceptor>],
+    call: &CallContext,
+    status: Option<&Status>,
+) {
+    for interceptor in interceptors {
+        interceptor.on_response(call, status).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_context() {
+        let addr = "127.0.0.1:8000".parse().unwrap();
+        let call = CallContext::new(
+            crate::to_uri_path("my-service", "my-message"),
+            addr,
+            None,
+            HeaderMap::new(),
+        );
+        assert_eq!(call.service(), "my-service");
+        assert_eq!(call.path(), "my-message");
+        assert_eq!(call.remote_addr(), addr);
+
+        let call = CallContext::new("/".to_string(), addr, None, HeaderMap::new());
+        assert_eq!(call.service(), "");
+        assert_eq!(call.path(), "");
+    }
+}
@@ -10,6 +10,13 @@
 //! - Fast (de)serialization of owned types.
 //! - True zero-copy deserialization avoiding heavy allocations.
 //! - Dynamic adding and removing of message handlers/services.
+//! - Optional TLS and mutual TLS using rustls, behind the `tls` feature.
+//! - Server and client interceptors for authentication, logging and metrics.
+//! - Client retries with exponential backoff and per-channel circuit breaking.
+//! - Server, client and bidirectional streaming of messages.
+//! - Deadline propagation from clients to servers and their downstream calls.
+//! - Per-call request metadata and response metadata.
+//! - Optional serde based JSON and bincode codecs, behind the `json` and `bincode` features.
 //!
 //! ### Basic example
 //! ```rust
@@ -97,10 +104,17 @@ extern crate tracing;
 
 mod body;
 mod client;
+#[cfg(feature = "serde")]
+mod codec;
+mod deadline;
 mod handler;
+mod interceptor;
+mod metadata;
 mod net;
 mod request;
+mod retry;
 mod server;
+mod stream;
 mod utils;
 mod view;
 
@@ -113,10 +127,39 @@ use std::hash::{Hash, Hasher};
 pub use async_trait::async_trait;
 pub use body::Body;
 pub use client::{MessageReply, RpcClient};
+#[cfg(feature = "bincode")]
+pub use codec::{Bincode, BincodeCodec};
+#[cfg(feature = "serde")]
+pub use codec::{Codec, Encoded};
+#[cfg(feature = "json")]
+pub use codec::{Json, JsonCodec};
 pub use handler::{Handler, RpcService, ServiceRegistry, TryAsBody, TryIntoBody};
-pub use net::{ArchivedErrorCode, ArchivedStatus, Channel, Error, ErrorCode, Status};
-pub use request::{Request, RequestContents};
+pub use interceptor::{CallContext, Interceptor};
+pub use metadata::{InvalidMetadata, Metadata, Response};
+pub use net::{
+    ArchivedErrorCode,
+    ArchivedStatus,
+    Channel,
+    CircuitBreakerConfig,
+    CircuitState,
+    Error,
+    ErrorCode,
+    Status,
+};
+#[cfg(feature = "tls")]
+pub use net::{
+    Certificate,
+    ClientTls,
+    ClientTlsConfig,
+    Identity,
+    ServerTls,
+    ServerTlsConfig,
+    TlsError,
+};
+pub use request::{PeerIdentity, Request, RequestContents};
+pub use retry::RetryPolicy;
 pub use server::Server;
+pub use stream::{MessageStream, Streaming};
 pub use view::{DataView, InvalidView};
 
 pub(crate) fn hash<H: Hash + ?Sized>(v: &H) -> u64 {
@@ -130,5 +173,5 @@ pub(crate) fn to_uri_path(service: &str, path: &str) -> String {
 }
 
 fn sanitise(parameter: &str) -> String {
-    parameter.replace(['<', '>'], "-")
+    parameter.replace(['<', '>'], "-").replace(' ', "")
 }
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use http::header::HeaderName;
+use http::{HeaderMap, HeaderValue};
+
+/// The prefix of the headers used to carry metadata entries.
+const METADATA_PREFIX: &str = "x-datacake-meta-";
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid metadata entry {key:?}, keys must be valid header names and values visible ASCII.")]
+/// The metadata entry cannot be sent as a header.
+pub struct InvalidMetadata {
+    /// The key of the invalid entry.
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A set of key-value pairs sent alongside a message or its reply.
+///
+/// This can be used to carry information like request ids, tenant ids
+/// or auth tokens without changing the messages themselves. Entries are
+/// sent as HTTP headers, keys are case-insensitive and are stored in
+/// lowercase.
+///
+/// ```rust
+/// use datacake_rpc::Metadata;
+///
+/// let mut metadata = Metadata::new();
+/// metadata.insert("Request-Id", "1234").unwrap();
+/// assert_eq!(metadata.get("request-id"), Some("1234"));
+/// assert!(metadata.insert("tenant", "über").is_err());
+/// ```
+pub struct Metadata {
+    entries: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// Creates a new, empty set of metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a new entry, returning the previous value of the key if any.
+    ///
+    /// Keys must only contain characters valid in a HTTP header name and
+    /// values must be visible ASCII.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Option<String>, InvalidMetadata> {
+        let key = key.into().to_ascii_lowercase();
+        let value = value.into();
+
+        let header = format!("{METADATA_PREFIX}{key}");
+        if key.is_empty()
+            || HeaderName::from_bytes(header.as_bytes()).is_err()
+            || HeaderValue::from_str(&value).map_or(true, |v| v.to_str().is_err())
+        {
+            return Err(InvalidMetadata { key });
+        }
+
+        Ok(self.entries.insert(key, value))
+    }
+
+    /// Gets the value of the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(&key.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Removes the given key, returning its value if it existed.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.entries.remove(&key.to_ascii_lowercase())
+    }
+
+    /// An iterator over all entries ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    #[inline]
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    /// Returns if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Reads all metadata entries from the given headers.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let entries = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let key = name.as_str().strip_prefix(METADATA_PREFIX)?;
+                let value = value.to_str().ok()?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Writes all metadata entries to the given headers.
+    pub(crate) fn write_headers(&self, headers: &mut HeaderMap) {
+        for (key, value) in self.entries.iter() {
+            // Entries are validated on insert.
+            let name =
+                HeaderName::from_bytes(format!("{METADATA_PREFIX}{key}").as_bytes())
+                    .expect("Validated header name");
+            let value = HeaderValue::from_str(value).expect("Validated header value");
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// A reply from the server along with the metadata it attached.
+///
+/// See [RpcClient::send_with_metadata](crate::RpcClient::send_with_metadata).
+pub struct Response<T> {
+    metadata: Metadata,
+    inner: T,
+}
+
+impl<T> Response<T> {
+    pub(crate) fn new(metadata: Metadata, inner: T) -> Self {
+        Self { metadata, inner }
+    }
+
+    #[inline]
+    /// The metadata attached to the reply by the handler.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    #[inline]
+    /// Consumes the response into the reply.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    #[inline]
+    /// Consumes the response into the metadata and the reply.
+    pub fn into_parts(self) -> (Metadata, T) {
+        (self.metadata, self.inner)
+    }
+}
+
+impl<T> Deref for Response<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let mut metadata = Metadata::new();
+        assert!(metadata.is_empty());
+        assert_eq!(metadata.insert("Request-Id", "1234").unwrap(), None);
+        assert_eq!(
+            metadata.insert("request-id", "5678").unwrap(),
+            Some("1234".to_string())
+        );
+        metadata.insert("tenant", "tenant-a").unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata.get("REQUEST-ID"), Some("5678"));
+        assert_eq!(
+            metadata.iter().collect::<Vec<_>>(),
+            vec![("request-id", "5678"), ("tenant", "tenant-a")],
+        );
+
+        assert!(metadata.insert("", "value").is_err());
+        assert!(metadata.insert("bad key", "value").is_err());
+        assert!(metadata.insert("key", "bad\nvalue").is_err());
+
+        assert_eq!(metadata.remove("tenant"), Some("tenant-a".to_string()));
+        assert_eq!(metadata.get("tenant"), None);
+    }
+
+    #[test]
+    fn test_headers() {
+        let mut metadata = Metadata::new();
+        metadata.insert("request-id", "1234").unwrap();
+        metadata.insert("auth-token", "secret").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-other", HeaderValue::from_static("ignored"));
+        metadata.write_headers(&mut headers);
+        assert_eq!(headers.get("x-datacake-meta-request-id").unwrap(), "1234");
+
+        let copy = Metadata::from_headers(&headers);
+        assert_eq!(copy, metadata);
+    }
+}
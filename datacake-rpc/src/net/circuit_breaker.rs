@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+#[derive(Debug, Copy, Clone)]
+/// Configuration of the circuit breaker of a [Channel](crate::Channel).
+///
+/// Once `failure_threshold` consecutive requests fail to reach the peer, the
+/// circuit is opened and requests fail immediately without touching the
+/// network. After `reset_timeout` has elapsed a single probe request is let
+/// through, closing the circuit again if it succeeds.
+pub struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Sets the number of consecutive failures before the circuit is opened.
+    ///
+    /// Defaults to `5`.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// Sets how long the circuit stays open before a probe request is allowed.
+    ///
+    /// Defaults to `5s`.
+    pub fn with_reset_timeout(mut self, timeout: Duration) -> Self {
+        self.reset_timeout = timeout;
+        self
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The current state of a [Channel](crate::Channel)'s circuit breaker.
+pub enum CircuitState {
+    /// The peer is considered healthy and requests are sent as normal.
+    Closed,
+    /// The peer is considered dead and requests fail immediately.
+    Open,
+    /// The reset timeout has elapsed and a probe request is allowed
+    /// to check if the peer has recovered.
+    HalfOpen,
+}
+
+/// Tracks the failures of requests to a single peer.
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// The current state of the circuit.
+    pub(crate) fn state(&self) -> CircuitState {
+        let lock = self.inner.lock();
+        match lock.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() >= self.config.reset_timeout => {
+                CircuitState::HalfOpen
+            },
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    /// Attempts to acquire a permit for sending a request.
+    ///
+    /// Returns `None` if the circuit is open, or if it is half-open and
+    /// another request is already probing the peer.
+    pub(crate) fn try_acquire(&self) -> Option<BreakerPermit<'_>> {
+        let mut lock = self.inner.lock();
+        if let Some(opened_at) = lock.opened_at {
+            if lock.probing || opened_at.elapsed() < self.config.reset_timeout {
+                return None;
+            }
+            lock.probing = true;
+        }
+
+        Some(BreakerPermit {
+            breaker: self,
+            completed: false,
+        })
+    }
+
+    fn record_success(&self) {
+        let mut lock = self.inner.lock();
+        *lock = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut lock = self.inner.lock();
+        lock.consecutive_failures = lock.consecutive_failures.saturating_add(1);
+        if lock.probing || lock.consecutive_failures >= self.config.failure_threshold {
+            lock.opened_at = Some(Instant::now());
+            lock.probing = false;
+        }
+    }
+}
+
+/// A permit to send a single request through the circuit breaker.
+///
+/// If the permit is dropped without recording a result, for example
+/// because the request timed out, it counts as a failure.
+pub(crate) struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    completed: bool,
+}
+
+impl<'a> BreakerPermit<'a> {
+    /// Records the outcome of the request.
+    pub(crate) fn record(mut self, success: bool) {
+        self.completed = true;
+        if success {
+            self.breaker.record_success();
+        } else {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+impl<'a> Drop for BreakerPermit<'a> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let config = CircuitBreakerConfig::default()
+            .with_failure_threshold(2)
+            .with_reset_timeout(Duration::from_millis(50));
+        let breaker = CircuitBreaker::new(config);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.try_acquire().unwrap().record(false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.try_acquire().unwrap().record(true);
+        breaker.try_acquire().unwrap().record(false);
+        assert_eq!(
+            breaker.state(),
+            CircuitState::Closed,
+            "Successes should reset the failure count."
+        );
+
+        drop(breaker.try_acquire().unwrap());
+        assert_eq!(
+            breaker.state(),
+            CircuitState::Open,
+            "Dropped permits should count as failures."
+        );
+        assert!(breaker.try_acquire().is_none());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        let probe = breaker.try_acquire().unwrap();
+        assert!(
+            breaker.try_acquire().is_none(),
+            "Only a single probe should be allowed."
+        );
+        probe.record(false);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.try_acquire().unwrap().record(true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
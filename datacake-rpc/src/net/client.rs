@@ -1,45 +1,79 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 
-use http::{Method, Request};
+use http::{HeaderMap, Method, Request};
 use hyper::StatusCode;
 use rkyv::AlignedVec;
 
+use super::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
 #[cfg(feature = "simulation")]
 use super::simulation::LazyClient;
+#[cfg(all(feature = "tls", not(feature = "simulation")))]
+use super::tls::RpcConnector;
+#[cfg(feature = "tls")]
+use super::ClientTls;
 use crate::body::Body;
 use crate::net::Error;
 use crate::request::MessageMetadata;
 
+#[cfg(all(not(feature = "tls"), not(feature = "simulation")))]
+type Connector = hyper::client::HttpConnector;
+#[cfg(all(feature = "tls", not(feature = "simulation")))]
+type Connector = RpcConnector;
+
 #[derive(Clone)]
 /// A raw client connection which can produce multiplexed streams.
 pub struct Channel {
     #[cfg(not(feature = "simulation"))]
-    connection: hyper::Client<hyper::client::HttpConnector, hyper::Body>,
+    connection: hyper::Client<Connector, hyper::Body>,
 
     #[cfg(feature = "simulation")]
     connection: LazyClient,
 
     remote_addr: SocketAddr,
+    breaker: Option<Arc<CircuitBreaker>>,
 }
 
 impl Channel {
     #[cfg(not(feature = "simulation"))]
     /// Connects to a remote RPC server.
     pub fn connect(remote_addr: SocketAddr) -> Self {
+        #[cfg(not(feature = "tls"))]
+        let connector = Self::http_connector();
+        #[cfg(feature = "tls")]
+        let connector = RpcConnector::new(Self::http_connector(), None);
+
+        Self::with_connector(remote_addr, connector)
+    }
+
+    #[cfg(all(feature = "tls", not(feature = "simulation")))]
+    /// Connects to a remote RPC server using TLS.
+    pub fn connect_with_tls(remote_addr: SocketAddr, tls: ClientTls) -> Self {
+        let connector = RpcConnector::new(Self::http_connector(), Some(tls));
+        Self::with_connector(remote_addr, connector)
+    }
+
+    #[cfg(not(feature = "simulation"))]
+    fn http_connector() -> hyper::client::HttpConnector {
         let mut http = hyper::client::HttpConnector::new();
         http.enforce_http(false);
         http.set_nodelay(true);
         http.set_connect_timeout(Some(std::time::Duration::from_secs(2)));
+        http
+    }
 
+    #[cfg(not(feature = "simulation"))]
+    fn with_connector(remote_addr: SocketAddr, connector: Connector) -> Self {
         let client = hyper::Client::builder()
             .http2_keep_alive_while_idle(true)
             .http2_only(true)
             .http2_adaptive_window(true)
-            .build(http);
+            .build(connector);
 
         Self {
             connection: client,
             remote_addr,
+            breaker: None,
         }
     }
 
@@ -51,26 +85,87 @@ impl Channel {
         Self {
             connection: client,
             remote_addr,
+            breaker: None,
+        }
+    }
+
+    #[cfg(all(feature = "tls", feature = "simulation"))]
+    /// Connects to a remote RPC server using TLS with turmoil simulation enabled.
+    pub fn connect_with_tls(remote_addr: SocketAddr, tls: ClientTls) -> Self {
+        let client = LazyClient::connect_with_tls(remote_addr, tls);
+
+        Self {
+            connection: client,
+            remote_addr,
+            breaker: None,
         }
     }
 
+    /// Enables a circuit breaker for the channel.
+    ///
+    /// Once the peer is considered dead, requests fail immediately with
+    /// [Error::CircuitOpen] rather than attempting to connect to it.
+    /// The breaker is shared by all clones of the returned channel.
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.breaker = Some(Arc::new(CircuitBreaker::new(config)));
+        self
+    }
+
+    /// The current state of the channel's circuit breaker.
+    ///
+    /// Channels without a circuit breaker are always [CircuitState::Closed].
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker
+            .as_ref()
+            .map(|breaker| breaker.state())
+            .unwrap_or(CircuitState::Closed)
+    }
+
     /// Sends a message payload the remote server and gets the response
     /// data back.
+    ///
+    /// The given headers are attached to the outgoing request and the
+    /// headers of the response are returned alongside its data.
     pub(crate) async fn send_msg(
         &self,
         metadata: MessageMetadata,
+        headers: HeaderMap,
+        msg: Body,
+    ) -> Result<(HeaderMap, Result<Body, AlignedVec>), Error> {
+        let permit = match self.breaker.as_ref() {
+            None => None,
+            Some(breaker) => match breaker.try_acquire() {
+                None => return Err(Error::CircuitOpen(self.remote_addr)),
+                permit => permit,
+            },
+        };
+
+        let result = self.send_request(metadata, headers, msg).await;
+
+        if let Some(permit) = permit {
+            permit.record(result.is_ok());
+        }
+
+        result
+    }
+
+    async fn send_request(
+        &self,
+        metadata: MessageMetadata,
+        headers: HeaderMap,
         msg: Body,
-    ) -> Result<Result<Body, AlignedVec>, Error> {
+    ) -> Result<(HeaderMap, Result<Body, AlignedVec>), Error> {
         let uri = format!(
             "http://{}{}",
             self.remote_addr,
             crate::to_uri_path(&metadata.service_name, &metadata.path),
         );
-        let request = Request::builder()
+        let mut request = Request::builder()
             .method(Method::POST)
             .uri(uri)
             .body(msg.into_inner())
             .unwrap();
+        request.headers_mut().extend(headers);
 
         #[cfg(not(feature = "simulation"))]
         let resp = self.connection.request(request).await?;
@@ -83,10 +178,10 @@ impl Channel {
         let (req, body) = resp.into_parts();
 
         if req.status == StatusCode::OK {
-            Ok(Ok(Body::new(body)))
+            Ok((req.headers, Ok(Body::new(body))))
         } else {
             let buffer = crate::utils::to_aligned(body).await?;
-            Ok(Err(buffer))
+            Ok((req.headers, Err(buffer)))
         }
     }
 
@@ -1,15 +1,29 @@
+mod circuit_breaker;
 mod client;
 mod server;
 mod status;
 
 #[cfg(feature = "simulation")]
 mod simulation;
+#[cfg(feature = "tls")]
+mod tls;
 
 use std::io;
 
+pub use circuit_breaker::{CircuitBreakerConfig, CircuitState};
 pub use client::Channel;
 pub(crate) use server::start_rpc_server;
 pub use status::{ArchivedErrorCode, ArchivedStatus, ErrorCode, Status};
+#[cfg(feature = "tls")]
+pub use tls::{
+    Certificate,
+    ClientTls,
+    ClientTlsConfig,
+    Identity,
+    ServerTls,
+    ServerTlsConfig,
+    TlsError,
+};
 
 #[derive(Debug, thiserror::Error)]
 /// A failure in an RPC operation.
@@ -20,4 +34,7 @@ pub enum Error {
     #[error("Hyper Error: {0}")]
     /// The operation failed due an error originating in hyper.
     Hyper(#[from] hyper::Error),
+    #[error("Circuit breaker is open for peer {0}")]
+    /// The circuit breaker of the channel is open and the request was not sent.
+    CircuitOpen(std::net::SocketAddr),
 }
@@ -1,25 +1,35 @@
 use std::convert::Infallible;
 use std::io;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use http::{Request, Response, StatusCode};
 use hyper::server::conn::Http;
 use hyper::service::service_fn;
+use parking_lot::Mutex;
 use rkyv::AlignedVec;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 
+#[cfg(feature = "tls")]
+use super::ServerTls;
 use crate::body::Body;
+use crate::interceptor::{self, CallContext};
+use crate::metadata::Metadata;
+use crate::request::PeerIdentity;
 use crate::server::ServerState;
-use crate::{Status, SCRATCH_SPACE};
+use crate::{deadline, Status, SCRATCH_SPACE};
 
 /// Starts the RPC server.
 ///
-/// This takes a binding socket address and server state.
+/// This takes a binding socket address and server state, connections are
+/// encrypted if a TLS configuration is provided.
 pub(crate) async fn start_rpc_server(
     bind_addr: SocketAddr,
     state: ServerState,
+    #[cfg(feature = "tls")] tls: Option<ServerTls>,
 ) -> io::Result<JoinHandle<()>> {
     #[cfg(not(feature = "simulation"))]
     let listener = tokio::net::TcpListener::bind(bind_addr).await?;
@@ -40,21 +50,24 @@ pub(crate) async fn start_rpc_server(
             };
 
             let state = state.clone();
+            #[cfg(feature = "tls")]
+            let tls = tls.clone();
             tokio::task::spawn(async move {
-                let state = state.clone();
-                let handler = service_fn(move |req| {
-                    handle_connection(req, state.clone(), remote_addr)
-                });
-
-                let connection = Http::new()
-                    .http2_only(true)
-                    .http2_adaptive_window(true)
-                    .http2_keep_alive_timeout(Duration::from_secs(10))
-                    .serve_connection(io, handler);
-
-                if let Err(e) = connection.await {
-                    error!(error = ?e, "Error while serving HTTP connection.");
+                #[cfg(feature = "tls")]
+                if let Some(tls) = tls {
+                    match tls.accept(io).await {
+                        Ok((stream, peer_identity)) => {
+                            serve_connection(stream, state, remote_addr, peer_identity)
+                                .await
+                        },
+                        Err(e) => {
+                            warn!(error = ?e, remote_addr = %remote_addr, "TLS handshake failed.");
+                        },
+                    }
+                    return;
                 }
+
+                serve_connection(io, state, remote_addr, None).await;
             });
         }
     });
@@ -64,6 +77,30 @@ pub(crate) async fn start_rpc_server(
     Ok(handle)
 }
 
+/// Serves the HTTP connection of a single client.
+async fn serve_connection<IO>(
+    io: IO,
+    state: ServerState,
+    remote_addr: SocketAddr,
+    peer_identity: Option<PeerIdentity>,
+) where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let handler = service_fn(move |req| {
+        handle_connection(req, state.clone(), remote_addr, peer_identity.clone())
+    });
+
+    let connection = Http::new()
+        .http2_only(true)
+        .http2_adaptive_window(true)
+        .http2_keep_alive_timeout(Duration::from_secs(10))
+        .serve_connection(io, handler);
+
+    if let Err(e) = connection.await {
+        error!(error = ?e, "Error while serving HTTP connection.");
+    }
+}
+
 /// A single connection handler.
 ///
 /// This accepts new streams being created and spawns concurrent tasks to handle
@@ -72,8 +109,9 @@ async fn handle_connection(
     req: Request<hyper::Body>,
     state: ServerState,
     remote_addr: SocketAddr,
+    peer_identity: Option<PeerIdentity>,
 ) -> Result<Response<hyper::Body>, Infallible> {
-    match handle_message(req, state, remote_addr).await {
+    match handle_message(req, state, remote_addr, peer_identity).await {
         Ok(r) => Ok(r),
         Err(e) => {
             let mut response = Response::new(e.to_string().into());
@@ -87,12 +125,53 @@ async fn handle_message(
     req: Request<hyper::Body>,
     state: ServerState,
     remote_addr: SocketAddr,
+    peer_identity: Option<PeerIdentity>,
 ) -> anyhow::Result<Response<hyper::Body>> {
     let (req, body) = req.into_parts();
-    let uri = req.uri.path();
-    match state.get_handler(uri) {
-        None => {
-            let status = Status::unavailable(format!("Unknown service {uri}"));
+    let interceptors = state.interceptors();
+    let mut call = CallContext::new(
+        req.uri.path().to_string(),
+        remote_addr,
+        peer_identity.clone(),
+        req.headers,
+    );
+
+    let response_metadata = Arc::new(Mutex::new(Metadata::default()));
+    let reply = match interceptor::run_on_request(&interceptors, &mut call).await {
+        Err(status) => Err(status),
+        Ok(()) => {
+            let uri = req.uri.path();
+            match state.get_handler(uri) {
+                None => Err(Status::unavailable(format!("Unknown service {uri}"))),
+                Some(handler) => {
+                    let deadline = deadline::from_headers(call.headers());
+                    let reply = handler.try_handle(
+                        remote_addr,
+                        peer_identity,
+                        deadline,
+                        Metadata::from_headers(call.headers()),
+                        response_metadata.clone(),
+                        Body::new(body),
+                    );
+
+                    match deadline {
+                        None => reply.await,
+                        Some(deadline) => deadline::scope(deadline, reply).await,
+                    }
+                },
+            }
+        },
+    };
+
+    interceptor::run_on_response(&interceptors, &call, reply.as_ref().err()).await;
+
+    let mut response = match reply {
+        Ok(body) => {
+            let mut response = Response::new(body.into_inner());
+            (*response.status_mut()) = StatusCode::OK;
+            response
+        },
+        Err(status) => {
             let buffer =
                 rkyv::to_bytes::<_, SCRATCH_SPACE>(&status).unwrap_or_else(|e| {
                     warn!(error = ?e, "Failed to serialize error message.");
@@ -101,24 +180,13 @@ async fn handle_message(
 
             let mut response = Response::new(buffer.to_vec().into());
             (*response.status_mut()) = StatusCode::BAD_REQUEST;
-
-            Ok(response)
+            response
         },
-        Some(handler) => {
-            let reply = handler.try_handle(remote_addr, Body::new(body)).await;
-
-            match reply {
-                Ok(body) => {
-                    let mut response = Response::new(body.into_inner());
-                    (*response.status_mut()) = StatusCode::OK;
-                    Ok(response)
-                },
-                Err(buffer) => {
-                    let mut response = Response::new(buffer.to_vec().into());
-                    (*response.status_mut()) = StatusCode::BAD_REQUEST;
-                    Ok(response)
-                },
-            }
-        },
-    }
+    };
+
+    response_metadata
+        .lock()
+        .write_headers(response.headers_mut());
+
+    Ok(response)
 }
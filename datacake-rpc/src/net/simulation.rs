@@ -9,6 +9,10 @@ use hyper::Body;
 use tokio::sync::{Mutex, OnceCell};
 use tokio::time::timeout;
 
+#[cfg(feature = "tls")]
+use super::tls::RpcStream;
+#[cfg(feature = "tls")]
+use super::ClientTls;
 use crate::net::Error;
 
 #[derive(Clone)]
@@ -19,6 +23,8 @@ use crate::net::Error;
 pub struct LazyClient {
     addr: SocketAddr,
     client: Arc<OnceCell<Mutex<SendRequest<Body>>>>,
+    #[cfg(feature = "tls")]
+    tls: Option<ClientTls>,
 }
 
 impl LazyClient {
@@ -27,6 +33,18 @@ impl LazyClient {
         Self {
             addr: socket,
             client: Arc::new(OnceCell::new()),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    /// Creates a new lazy client which connects using TLS.
+    pub fn connect_with_tls(socket: SocketAddr, tls: ClientTls) -> Self {
+        Self {
+            addr: socket,
+            client: Arc::new(OnceCell::new()),
+            tls: Some(tls),
         }
     }
 
@@ -48,6 +66,12 @@ impl LazyClient {
             ))
         })??;
 
+        #[cfg(feature = "tls")]
+        let io = match self.tls.as_ref() {
+            None => RpcStream::Plain(io),
+            Some(tls) => RpcStream::Tls(Box::new(tls.connect(self.addr, io).await?)),
+        };
+
         let (sender, connection) = hyper::client::conn::Builder::new()
             .http2_keep_alive_while_idle(true)
             .http2_only(true)
@@ -4,7 +4,7 @@ use std::fmt::{Debug, Display, Formatter};
 use rkyv::{Archive, Deserialize, Serialize};
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Archive, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Archive, PartialEq, Eq, Clone)]
 #[archive(compare(PartialEq), check_bytes)]
 #[archive_attr(derive(PartialEq, Eq, Debug))]
 /// Status information around the cause of a message request failing.
@@ -60,6 +60,22 @@ impl Status {
             message: "The operation took to long to be completed.".to_string(),
         }
     }
+
+    /// The caller is not permitted to perform the operation.
+    pub fn permission_denied(msg: impl Display) -> Self {
+        Self {
+            code: ErrorCode::PermissionDenied,
+            message: msg.to_string(),
+        }
+    }
+
+    /// A limit or quota was exceeded while handling the operation.
+    pub fn resource_exhausted(msg: impl Display) -> Self {
+        Self {
+            code: ErrorCode::ResourceExhausted,
+            message: msg.to_string(),
+        }
+    }
 }
 
 impl Display for Status {
@@ -80,7 +96,7 @@ impl Debug for Status {
 impl Error for Status {}
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Archive, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Archive, PartialEq, Eq, Debug, Clone, Copy)]
 #[archive(compare(PartialEq), check_bytes)]
 #[archive_attr(derive(Debug, PartialEq, Eq))]
 /// A generic error code describing the high level reason why the request failed.
@@ -97,6 +113,10 @@ pub enum ErrorCode {
     ConnectionError,
     /// The operation took too long to be completed and was aborted.
     Timeout,
+    /// The caller is not permitted to perform the operation.
+    PermissionDenied,
+    /// A limit or quota was exceeded while handling the operation.
+    ResourceExhausted,
 }
 
 #[cfg(test)]
@@ -125,5 +145,8 @@ mod tests {
         test_status_variant(Status::connection("Test connection failed."));
         test_status_variant(Status::unavailable("Test unavailable."));
         test_status_variant(Status::internal("Test internal error."));
+        test_status_variant(Status::timeout());
+        test_status_variant(Status::permission_denied("Test permission denied."));
+        test_status_variant(Status::resource_exhausted("Test resource exhausted."));
     }
 }
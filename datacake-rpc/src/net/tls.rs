@@ -0,0 +1,506 @@
+#[cfg(not(feature = "simulation"))]
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+#[cfg(not(feature = "simulation"))]
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+#[cfg(not(feature = "simulation"))]
+use hyper::client::HttpConnector;
+#[cfg(not(feature = "simulation"))]
+use hyper::service::Service;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{
+    CertificateDer,
+    InvalidDnsNameError,
+    PrivateKeyDer,
+    ServerName,
+};
+use rustls::server::{VerifierBuilderError, WebPkiClientVerifier};
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::request::PeerIdentity;
+
+/// The ALPN protocol used by the RPC system.
+const ALPN_H2: &[u8] = b"h2";
+
+#[derive(Debug, thiserror::Error)]
+/// An error which occurred while building a TLS configuration.
+pub enum TlsError {
+    #[error("Invalid PEM data: {0}")]
+    /// The provided PEM data could not be parsed.
+    Pem(#[from] rustls::pki_types::pem::Error),
+    #[error("No certificates were found in the provided PEM data.")]
+    /// The provided PEM data did not contain any certificates.
+    NoCertificates,
+    #[error("Invalid server name: {0}")]
+    /// The server name used to verify the server's certificate is invalid.
+    InvalidServerName(#[from] InvalidDnsNameError),
+    #[error("Invalid client certificate verifier: {0}")]
+    /// The client certificate verifier could not be created.
+    Verifier(#[from] VerifierBuilderError),
+    #[error("Rustls Error: {0}")]
+    /// The configuration was rejected by rustls.
+    Rustls(#[from] rustls::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A DER encoded X.509 certificate.
+pub struct Certificate(CertificateDer<'static>);
+
+impl Certificate {
+    /// Creates a certificate from its DER encoding.
+    pub fn from_der(der: impl Into<Vec<u8>>) -> Self {
+        Self(CertificateDer::from(der.into()))
+    }
+
+    /// Parses the first certificate contained in the given PEM data.
+    pub fn from_pem(pem: impl AsRef<[u8]>) -> Result<Self, TlsError> {
+        Self::all_from_pem(pem)?
+            .into_iter()
+            .next()
+            .ok_or(TlsError::NoCertificates)
+    }
+
+    /// Parses every certificate contained in the given PEM data.
+    ///
+    /// This is useful for loading a bundle of CA certificates.
+    pub fn all_from_pem(pem: impl AsRef<[u8]>) -> Result<Vec<Self>, TlsError> {
+        CertificateDer::pem_slice_iter(pem.as_ref())
+            .map(|cert| cert.map(Self).map_err(TlsError::from))
+            .collect()
+    }
+
+    #[inline]
+    /// The DER encoding of the certificate.
+    pub fn as_der(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A certificate chain and the private key belonging to it.
+///
+/// This is used by servers to identify themselves to clients and by clients
+/// to identify themselves to servers requiring client certificates.
+pub struct Identity {
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl Clone for Identity {
+    fn clone(&self) -> Self {
+        Self {
+            chain: self.chain.clone(),
+            key: self.key.clone_key(),
+        }
+    }
+}
+
+impl Identity {
+    /// Parses the identity from a PEM encoded certificate chain and private key.
+    ///
+    /// The end-entity certificate must be the first certificate of the chain.
+    pub fn from_pem(
+        cert_chain: impl AsRef<[u8]>,
+        key: impl AsRef<[u8]>,
+    ) -> Result<Self, TlsError> {
+        let chain = Certificate::all_from_pem(cert_chain)?
+            .into_iter()
+            .map(|cert| cert.0)
+            .collect::<Vec<_>>();
+        if chain.is_empty() {
+            return Err(TlsError::NoCertificates);
+        }
+
+        let key = PrivateKeyDer::from_pem_slice(key.as_ref())?;
+
+        Ok(Self { chain, key })
+    }
+}
+
+#[derive(Clone, Default)]
+/// The TLS settings used by a [Channel](crate::Channel) when connecting to a server.
+///
+/// ```rust
+/// use datacake_rpc::{Certificate, ClientTlsConfig};
+///
+/// # fn build(ca_pem: &[u8]) -> Result<(), datacake_rpc::TlsError> {
+/// let tls = ClientTlsConfig::new()
+///     .with_ca_certificate(Certificate::from_pem(ca_pem)?)
+///     .with_server_name("datacake.local")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ClientTlsConfig {
+    ca_certificates: Vec<Certificate>,
+    identity: Option<Identity>,
+    server_name: Option<String>,
+}
+
+impl ClientTlsConfig {
+    /// Creates a new, empty, client configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust server certificates signed by the given CA certificate.
+    pub fn with_ca_certificate(mut self, certificate: Certificate) -> Self {
+        self.ca_certificates.push(certificate);
+        self
+    }
+
+    /// Present the given identity to servers which require client certificates.
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Set the name the server's certificate is verified against.
+    ///
+    /// By default the server's certificate must be valid for the IP address
+    /// being connected to. When nodes share a certificate issued for a single
+    /// name, that name should be set here.
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Validates the configuration and creates the client TLS handle.
+    pub fn build(self) -> Result<ClientTls, TlsError> {
+        let mut roots = RootCertStore::empty();
+        for certificate in self.ca_certificates {
+            roots.add(certificate.0)?;
+        }
+
+        let builder = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots);
+        let mut config = match self.identity {
+            None => builder.with_no_client_auth(),
+            Some(identity) => {
+                builder.with_client_auth_cert(identity.chain, identity.key)?
+            },
+        };
+        config.alpn_protocols = vec![ALPN_H2.to_vec()];
+
+        let server_name = self.server_name.map(ServerName::try_from).transpose()?;
+
+        Ok(ClientTls {
+            connector: tokio_rustls::TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+}
+
+#[derive(Clone)]
+/// The TLS settings used by a [Server](crate::Server) when accepting connections.
+///
+/// ```rust
+/// use datacake_rpc::{Certificate, Identity, ServerTlsConfig};
+///
+/// # fn build(cert_pem: &[u8], key_pem: &[u8], ca_pem: &[u8]) -> Result<(), datacake_rpc::TlsError> {
+/// let identity = Identity::from_pem(cert_pem, key_pem)?;
+/// let tls = ServerTlsConfig::new(identity)
+///     // Require clients to present a certificate signed by our CA.
+///     .with_client_ca_certificate(Certificate::from_pem(ca_pem)?)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ServerTlsConfig {
+    identity: Identity,
+    client_ca_certificates: Vec<Certificate>,
+    client_auth_optional: bool,
+}
+
+impl ServerTlsConfig {
+    /// Creates a new server configuration using the given identity.
+    ///
+    /// By default client certificates are not requested.
+    pub fn new(identity: Identity) -> Self {
+        Self {
+            identity,
+            client_ca_certificates: Vec::new(),
+            client_auth_optional: false,
+        }
+    }
+
+    /// Require clients to present a certificate signed by the given CA certificate.
+    pub fn with_client_ca_certificate(mut self, certificate: Certificate) -> Self {
+        self.client_ca_certificates.push(certificate);
+        self
+    }
+
+    /// Accept clients which do not present a certificate.
+    ///
+    /// Certificates which are presented must still be signed by one of the
+    /// client CA certificates. This has no effect if no client CA certificate is set.
+    pub fn with_client_auth_optional(mut self, optional: bool) -> Self {
+        self.client_auth_optional = optional;
+        self
+    }
+
+    /// Validates the configuration and creates the server TLS handle.
+    pub fn build(self) -> Result<ServerTls, TlsError> {
+        let provider = crypto_provider();
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        let builder = if self.client_ca_certificates.is_empty() {
+            builder.with_no_client_auth()
+        } else {
+            let mut roots = RootCertStore::empty();
+            for certificate in self.client_ca_certificates {
+                roots.add(certificate.0)?;
+            }
+
+            let mut verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            if self.client_auth_optional {
+                verifier = verifier.allow_unauthenticated();
+            }
+            builder.with_client_cert_verifier(verifier.build()?)
+        };
+
+        let mut config =
+            builder.with_single_cert(self.identity.chain, self.identity.key)?;
+        config.alpn_protocols = vec![ALPN_H2.to_vec()];
+
+        Ok(ServerTls {
+            acceptor: tokio_rustls::TlsAcceptor::from(Arc::new(config)),
+        })
+    }
+}
+
+#[derive(Clone)]
+/// A validated client TLS configuration.
+///
+/// This is cheap to clone and can be shared between channels.
+pub struct ClientTls {
+    connector: tokio_rustls::TlsConnector,
+    server_name: Option<ServerName<'static>>,
+}
+
+impl ClientTls {
+    /// Performs the TLS handshake with the server at the given address.
+    pub(crate) async fn connect<IO>(
+        &self,
+        remote_addr: SocketAddr,
+        io: IO,
+    ) -> io::Result<tokio_rustls::client::TlsStream<IO>>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let server_name = self
+            .server_name
+            .clone()
+            .unwrap_or_else(|| ServerName::IpAddress(remote_addr.ip().into()));
+        self.connector.connect(server_name, io).await
+    }
+}
+
+#[derive(Clone)]
+/// A validated server TLS configuration.
+pub struct ServerTls {
+    acceptor: tokio_rustls::TlsAcceptor,
+}
+
+impl ServerTls {
+    /// Performs the TLS handshake with a client, returning the client's
+    /// identity if it presented a certificate.
+    pub(crate) async fn accept<IO>(
+        &self,
+        io: IO,
+    ) -> io::Result<(tokio_rustls::server::TlsStream<IO>, Option<PeerIdentity>)>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let stream = self.acceptor.accept(io).await?;
+        let peer_identity = stream.get_ref().1.peer_certificates().map(|chain| {
+            PeerIdentity::new(chain.iter().map(|cert| cert.to_vec()).collect())
+        });
+        Ok((stream, peer_identity))
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+#[cfg(not(feature = "simulation"))]
+#[derive(Clone)]
+/// A HTTP connector which optionally wraps connections in TLS.
+pub(crate) struct RpcConnector {
+    http: HttpConnector,
+    tls: Option<ClientTls>,
+}
+
+#[cfg(not(feature = "simulation"))]
+impl RpcConnector {
+    pub(crate) fn new(http: HttpConnector, tls: Option<ClientTls>) -> Self {
+        Self { http, tls }
+    }
+}
+
+#[cfg(not(feature = "simulation"))]
+impl Service<Uri> for RpcConnector {
+    type Response = RpcStream<tokio::net::TcpStream>;
+    type Error = io::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(io::Error::other)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connecting = self.http.call(uri);
+        let tls = self.tls.clone();
+
+        Box::pin(async move {
+            let io = connecting.await.map_err(io::Error::other)?;
+            match tls {
+                None => Ok(RpcStream::Plain(io)),
+                Some(tls) => {
+                    let remote_addr = io.peer_addr()?;
+                    let stream = tls.connect(remote_addr, io).await?;
+                    Ok(RpcStream::Tls(Box::new(stream)))
+                },
+            }
+        })
+    }
+}
+
+/// A client connection which may or may not be using TLS.
+pub(crate) enum RpcStream<IO> {
+    Plain(IO),
+    Tls(Box<tokio_rustls::client::TlsStream<IO>>),
+}
+
+impl<IO> Connection for RpcStream<IO>
+where
+    IO: Connection + AsyncRead + AsyncWrite + Unpin,
+{
+    fn connected(&self) -> Connected {
+        match self {
+            Self::Plain(io) => io.connected(),
+            Self::Tls(stream) => stream.get_ref().0.connected().negotiated_h2(),
+        }
+    }
+}
+
+impl<IO> AsyncRead for RpcStream<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(io) => Pin::new(io).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<IO> AsyncWrite for RpcStream<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(io) => Pin::new(io).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(io) => Pin::new(io).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(io) => Pin::new(io).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pem() {
+        let certs = test_helper::TestCertificates::generate();
+
+        let ca = Certificate::from_pem(&certs.ca_pem).expect("Parse CA certificate");
+        assert!(!ca.as_der().is_empty());
+        assert_eq!(
+            Certificate::all_from_pem(&certs.ca_pem).unwrap(),
+            vec![ca],
+            "A single certificate should be parsed."
+        );
+
+        assert!(matches!(
+            Certificate::from_pem(b""),
+            Err(TlsError::NoCertificates)
+        ));
+        assert!(matches!(
+            Identity::from_pem(b"", &certs.server_key_pem),
+            Err(TlsError::NoCertificates)
+        ));
+        assert!(Identity::from_pem(&certs.server_cert_pem, b"").is_err());
+        Identity::from_pem(&certs.server_cert_pem, &certs.server_key_pem)
+            .expect("Parse identity");
+    }
+
+    #[test]
+    fn test_build_configs() {
+        let certs = test_helper::TestCertificates::generate();
+        let ca = Certificate::from_pem(&certs.ca_pem).unwrap();
+        let identity =
+            Identity::from_pem(&certs.server_cert_pem, &certs.server_key_pem).unwrap();
+
+        ServerTlsConfig::new(identity.clone())
+            .build()
+            .expect("Build server config");
+        ServerTlsConfig::new(identity.clone())
+            .with_client_ca_certificate(ca.clone())
+            .with_client_auth_optional(true)
+            .build()
+            .expect("Build server config with client auth");
+        ClientTlsConfig::new()
+            .with_ca_certificate(ca)
+            .with_identity(identity)
+            .with_server_name("localhost")
+            .build()
+            .expect("Build client config");
+
+        assert!(matches!(
+            ClientTlsConfig::new()
+                .with_server_name("not a name")
+                .build(),
+            Err(TlsError::InvalidServerName(_))
+        ));
+    }
+}
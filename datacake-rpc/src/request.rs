@@ -2,11 +2,16 @@ use std::borrow::Cow;
 use std::fmt::{Debug, Formatter};
 use std::net::SocketAddr;
 use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use parking_lot::Mutex;
 use rkyv::validation::validators::DefaultValidator;
 use rkyv::{Archive, CheckBytes, Deserialize, Serialize};
+use tokio::time::Instant;
 
+use crate::metadata::{InvalidMetadata, Metadata};
 use crate::view::DataView;
 use crate::{Body, Status};
 
@@ -62,6 +67,32 @@ pub struct MessageMetadata {
     pub(crate) path: Cow<'static, str>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The identity of a remote peer, as established by its TLS client certificate.
+pub struct PeerIdentity {
+    chain: Arc<Vec<Vec<u8>>>,
+}
+
+impl PeerIdentity {
+    #[cfg(feature = "tls")]
+    pub(crate) fn new(chain: Vec<Vec<u8>>) -> Self {
+        Self {
+            chain: Arc::new(chain),
+        }
+    }
+
+    /// The DER encoding of the peer's end-entity certificate.
+    pub fn certificate(&self) -> &[u8] {
+        self.chain.first().map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The DER encoding of every certificate the peer presented,
+    /// starting with the end-entity certificate.
+    pub fn chain(&self) -> &[Vec<u8>] {
+        &self.chain
+    }
+}
+
 /// A zero-copy view of the message data and any additional metadata provided
 /// by the RPC system.
 ///
@@ -72,6 +103,10 @@ where
     Msg: RequestContents,
 {
     pub(crate) remote_addr: SocketAddr,
+    pub(crate) peer_identity: Option<PeerIdentity>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) metadata: Metadata,
+    pub(crate) response_metadata: Arc<Mutex<Metadata>>,
 
     // A small hack to stop linters miss-guiding users
     // into thinking their messages are `!Sized` when in fact they are.
@@ -91,6 +126,9 @@ where
         f.debug_struct("Request")
             .field("view", &self.view)
             .field("remote_addr", &self.remote_addr)
+            .field("peer_identity", &self.peer_identity)
+            .field("deadline", &self.deadline)
+            .field("metadata", &self.metadata)
             .finish()
     }
 }
@@ -110,9 +148,17 @@ impl<Msg> Request<Msg>
 where
     Msg: RequestContents,
 {
-    pub(crate) fn new(remote_addr: SocketAddr, view: Msg::Content) -> Self {
+    pub(crate) fn new(
+        remote_addr: SocketAddr,
+        peer_identity: Option<PeerIdentity>,
+        view: Msg::Content,
+    ) -> Self {
         Self {
             remote_addr,
+            peer_identity,
+            deadline: None,
+            metadata: Metadata::default(),
+            response_metadata: Arc::default(),
             #[cfg(debug_assertions)]
             view: Box::new(view),
             #[cfg(not(debug_assertions))]
@@ -120,6 +166,24 @@ where
         }
     }
 
+    /// Sets the deadline the caller expects a reply by.
+    pub(crate) fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Sets the metadata sent by the caller and the shared metadata
+    /// which is sent back with the reply.
+    pub(crate) fn with_metadata(
+        mut self,
+        metadata: Metadata,
+        response_metadata: Arc<Mutex<Metadata>>,
+    ) -> Self {
+        self.metadata = metadata;
+        self.response_metadata = response_metadata;
+        self
+    }
+
     #[cfg(debug_assertions)]
     /// Consumes the request into the value of the message.
     pub fn into_inner(self) -> Msg::Content {
@@ -136,6 +200,43 @@ where
     pub fn remote_addr(&self) -> SocketAddr {
         self.remote_addr
     }
+
+    /// The identity of the remote peer.
+    ///
+    /// This is only available when the server uses TLS and the peer
+    /// presented a client certificate.
+    pub fn peer_identity(&self) -> Option<&PeerIdentity> {
+        self.peer_identity.as_ref()
+    }
+
+    /// The metadata sent by the caller alongside the message.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Attaches a metadata entry to the reply sent back to the caller.
+    ///
+    /// The metadata can be read by the caller via
+    /// [RpcClient::send_with_metadata](crate::RpcClient::send_with_metadata).
+    pub fn set_response_metadata(
+        &self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), InvalidMetadata> {
+        self.response_metadata.lock().insert(key, value)?;
+        Ok(())
+    }
+
+    /// The time remaining before the caller's deadline is reached.
+    ///
+    /// This is `None` if the caller did not set a timeout. Once the deadline
+    /// is reached the handler is cancelled and the caller is sent
+    /// [Status::timeout], any RPC calls made by the handler inherit
+    /// the deadline.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
 }
 
 #[cfg(feature = "test-utils")]
@@ -158,7 +259,7 @@ where
         use std::net::{Ipv4Addr, SocketAddrV4};
 
         let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from([127, 0, 0, 1]), 80));
-        Self::new(addr, contents)
+        Self::new(addr, None, contents)
     }
 }
 
@@ -189,8 +290,20 @@ mod tests {
         let bytes = rkyv::to_bytes::<_, 1024>(&msg).expect("Serialize");
         let view: DataView<MessageMetadata, _> =
             DataView::using(bytes).expect("Create view");
-        let req = Request::<MessageMetadata>::new(addr, view);
+        let req = Request::<MessageMetadata>::new(addr, None, view);
         assert_eq!(req.remote_addr(), addr, "Remote addr should match.");
+        assert!(
+            req.peer_identity().is_none(),
+            "No peer identity should be set."
+        );
+        assert!(req.remaining_time().is_none(), "No deadline should be set.");
+        assert!(req.metadata().is_empty(), "No metadata should be set.");
+        req.set_response_metadata("request-id", "1234").unwrap();
+        assert_eq!(req.response_metadata.lock().get("request-id"), Some("1234"));
+
+        let req = req.with_deadline(Some(Instant::now() + Duration::from_secs(5)));
+        let remaining = req.remaining_time().unwrap();
+        assert!(remaining <= Duration::from_secs(5));
         assert_eq!(
             req.to_owned().unwrap(),
             msg,
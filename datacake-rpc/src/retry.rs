@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::ErrorCode;
+
+#[derive(Debug, Clone)]
+/// The policy used by a [RpcClient](crate::RpcClient) for retrying failed requests.
+///
+/// Requests are only retried if they fail with an error code marked as
+/// idempotent, by default these are [ErrorCode::ConnectionError] and
+/// [ErrorCode::ServiceUnavailable], as neither of them can be caused by a
+/// handler having already processed the message.
+///
+/// Between attempts the client waits with an exponential backoff, doubling
+/// the delay with every attempt up to a maximum. Jitter is applied to the
+/// delay to avoid many clients retrying in lockstep.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use datacake_rpc::{ErrorCode, RetryPolicy};
+///
+/// let policy = RetryPolicy::new(5)
+///     .with_initial_backoff(Duration::from_millis(20))
+///     .with_max_backoff(Duration::from_secs(1))
+///     .with_idempotent_codes([ErrorCode::ConnectionError, ErrorCode::Timeout]);
+///
+/// assert!(policy.is_retryable(ErrorCode::Timeout));
+/// assert!(!policy.is_retryable(ErrorCode::InternalError));
+/// ```
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    idempotent_codes: Vec<ErrorCode>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            jitter: true,
+            idempotent_codes: vec![
+                ErrorCode::ConnectionError,
+                ErrorCode::ServiceUnavailable,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy which retries a request up to `max_retries` times.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the delay before the first retry.
+    ///
+    /// Defaults to `50ms`.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the maximum delay between two attempts.
+    ///
+    /// Defaults to `2s`.
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Enables or disables jitter being applied to the backoff.
+    ///
+    /// Defaults to `true`.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the error codes which are safe to retry.
+    pub fn with_idempotent_codes(
+        mut self,
+        codes: impl IntoIterator<Item = ErrorCode>,
+    ) -> Self {
+        self.idempotent_codes = codes.into_iter().collect();
+        self
+    }
+
+    #[inline]
+    /// The maximum number of times a request is retried.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns if a request which failed with the given error code can be retried.
+    pub fn is_retryable(&self, code: ErrorCode) -> bool {
+        self.idempotent_codes.contains(&code)
+    }
+
+    /// The delay to wait before the given retry attempt, starting at `0`.
+    ///
+    /// With jitter enabled the delay is picked randomly between half and
+    /// the full backoff.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+
+        if !self.jitter || backoff.is_zero() {
+            return backoff;
+        }
+
+        let half = backoff / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(10)
+            .with_initial_backoff(Duration::from_millis(10))
+            .with_max_backoff(Duration::from_millis(100))
+            .with_jitter(false);
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(10));
+        assert_eq!(policy.backoff(1), Duration::from_millis(20));
+        assert_eq!(policy.backoff(3), Duration::from_millis(80));
+        assert_eq!(policy.backoff(4), Duration::from_millis(100));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(100));
+
+        let policy = policy.with_jitter(true);
+        for attempt in 0..10 {
+            let backoff = policy.backoff(attempt);
+            let expected = policy.clone().with_jitter(false).backoff(attempt);
+            assert!(backoff >= expected / 2 && backoff <= expected);
+        }
+    }
+
+    #[test]
+    fn test_retryable_codes() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(ErrorCode::ConnectionError));
+        assert!(policy.is_retryable(ErrorCode::ServiceUnavailable));
+        assert!(!policy.is_retryable(ErrorCode::Timeout));
+        assert!(!policy.is_retryable(ErrorCode::InternalError));
+        assert!(!policy.is_retryable(ErrorCode::PermissionDenied));
+    }
+}
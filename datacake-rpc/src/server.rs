@@ -7,6 +7,9 @@ use parking_lot::{Mutex, RwLock};
 use tokio::task::JoinHandle;
 
 use crate::handler::{HandlerKey, OpaqueMessageHandler, RpcService, ServiceRegistry};
+use crate::interceptor::Interceptor;
+#[cfg(feature = "tls")]
+use crate::net::ServerTls;
 
 /// A RPC server instance.
 ///
@@ -72,7 +75,27 @@ impl Server {
     /// Spawns the RPC server task and returns the server handle.
     pub async fn listen(addr: SocketAddr) -> io::Result<Self> {
         let state = ServerState::default();
-        let handle = crate::net::start_rpc_server(addr, state.clone()).await?;
+        let handle = crate::net::start_rpc_server(
+            addr,
+            state.clone(),
+            #[cfg(feature = "tls")]
+            None,
+        )
+        .await?;
+
+        Ok(Self { state, handle })
+    }
+
+    #[cfg(feature = "tls")]
+    /// Spawns the RPC server task, accepting only TLS connections,
+    /// and returns the server handle.
+    ///
+    /// If the TLS configuration requires client certificates, the identity of
+    /// each client is available via [Request::peer_identity](crate::Request::peer_identity).
+    pub async fn listen_with_tls(addr: SocketAddr, tls: ServerTls) -> io::Result<Self> {
+        let state = ServerState::default();
+        let handle =
+            crate::net::start_rpc_server(addr, state.clone(), Some(tls)).await?;
 
         Ok(Self { state, handle })
     }
@@ -88,6 +111,14 @@ impl Server {
         self.state.add_handlers(Svc::service_name(), handlers);
     }
 
+    /// Adds an interceptor which runs around every message handled by the server.
+    ///
+    /// Interceptors are run in the order they are added, see [Interceptor]
+    /// for more information.
+    pub fn add_interceptor(&self, interceptor: impl Interceptor) {
+        self.state.add_interceptor(Arc::new(interceptor));
+    }
+
     /// Removes all handlers linked with the given service name.
     pub fn remove_service(&self, service_name: &str) {
         self.state.remove_handlers(service_name);
@@ -112,6 +143,7 @@ impl Server {
 pub(crate) struct ServerState {
     services: Arc<Mutex<BTreeMap<String, BTreeSet<HandlerKey>>>>,
     handlers: Arc<RwLock<BTreeMap<HandlerKey, Arc<dyn OpaqueMessageHandler>>>>,
+    interceptors: Arc<RwLock<Vec<Arc<dyn Interceptor>>>>,
 }
 
 impl ServerState {
@@ -158,4 +190,14 @@ impl ServerState {
         let lock = self.handlers.read();
         lock.get(&crate::hash(uri)).cloned()
     }
+
+    /// Adds a new interceptor to the server state.
+    pub(crate) fn add_interceptor(&self, interceptor: Arc<dyn Interceptor>) {
+        self.interceptors.write().push(interceptor);
+    }
+
+    /// Gets the currently registered interceptors.
+    pub(crate) fn interceptors(&self) -> Vec<Arc<dyn Interceptor>> {
+        self.interceptors.read().clone()
+    }
 }
@@ -0,0 +1,305 @@
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use async_trait::async_trait;
+use bytes::{Buf, BytesMut};
+use futures::{Stream, StreamExt};
+use hyper::body::HttpBody;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{AlignedVec, Archive, CheckBytes, Serialize};
+
+use crate::handler::TryIntoBody;
+use crate::request::RequestContents;
+use crate::view::DataView;
+use crate::{Body, Status, SCRATCH_SPACE};
+
+/// The frame contains a serialized message.
+const MESSAGE_FRAME: u8 = 0;
+/// The frame contains a serialized [Status] and is the last frame of the stream.
+const ERROR_FRAME: u8 = 1;
+/// The size of the frame header, made up of the frame kind and payload length.
+const HEADER_SIZE: usize = 5;
+
+/// A stream of messages sent over the RPC system.
+///
+/// This can be used as the `Reply` of a [Handler](crate::Handler) to stream
+/// messages back to the client, or as the message itself to stream messages
+/// to the server using [RpcClient::send_owned](crate::RpcClient::send_owned).
+/// Each message is serialized and sent as it is produced, the receiving side
+/// gets a [MessageStream] yielding a zero-copy view of each message.
+///
+/// If the stream yields an error, the [Status] is sent to the receiver
+/// and the stream ends.
+///
+/// ```rust
+/// use datacake_rpc::{Handler, Request, RpcService, ServiceRegistry, Status, Streaming};
+/// use rkyv::{Archive, Deserialize, Serialize};
+///
+/// #[repr(C)]
+/// #[derive(Serialize, Deserialize, Archive, Debug)]
+/// #[archive(check_bytes)]
+/// #[archive_attr(derive(Debug))]
+/// pub struct Count(u32);
+///
+/// pub struct CounterService;
+///
+/// impl RpcService for CounterService {
+///     fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+///         registry.add_handler::<Count>();
+///     }
+/// }
+///
+/// #[datacake_rpc::async_trait]
+/// impl Handler<Count> for CounterService {
+///     type Reply = Streaming<u32>;
+///
+///     async fn on_message(&self, msg: Request<Count>) -> Result<Self::Reply, Status> {
+///         let count = msg.0;
+///         Ok((0..count).collect())
+///     }
+/// }
+/// ```
+pub struct Streaming<T> {
+    inner: Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>,
+}
+
+impl<T> Streaming<T>
+where
+    T: Archive + Serialize<AllocSerializer<SCRATCH_SPACE>> + Send + 'static,
+{
+    /// Creates a new message stream from the given stream.
+    pub fn new(stream: impl Stream<Item = Result<T, Status>> + Send + 'static) -> Self {
+        Self {
+            inner: Box::pin(stream),
+        }
+    }
+}
+
+impl<T> FromIterator<T> for Streaming<T>
+where
+    T: Archive + Serialize<AllocSerializer<SCRATCH_SPACE>> + Send + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let messages = iter.into_iter().map(Ok).collect::<Vec<_>>();
+        Self::new(futures::stream::iter(messages))
+    }
+}
+
+impl<T> TryIntoBody for Streaming<T>
+where
+    T: Archive + Serialize<AllocSerializer<SCRATCH_SPACE>> + Send + 'static,
+{
+    fn try_into_body(self) -> Result<Body, Status> {
+        let frames = futures::stream::unfold(Some(self.inner), |state| async move {
+            let mut stream = state?;
+            let message = stream.next().await?;
+
+            let frame = message.and_then(|msg| {
+                rkyv::to_bytes::<_, SCRATCH_SPACE>(&msg)
+                    .map_err(|e| Status::internal(e.to_string()))
+            });
+
+            let (frame, state) = match frame {
+                Ok(buffer) => (encode_frame(MESSAGE_FRAME, &buffer), Some(stream)),
+                Err(status) => {
+                    let buffer = rkyv::to_bytes::<_, SCRATCH_SPACE>(&status)
+                        .unwrap_or_else(|e| {
+                            warn!(error = ?e, "Failed to serialize error message.");
+                            AlignedVec::new()
+                        });
+                    (encode_frame(ERROR_FRAME, &buffer), None)
+                },
+            };
+
+            Some((Ok::<_, Infallible>(frame), state))
+        });
+
+        Ok(Body::new(hyper::Body::wrap_stream(frames)))
+    }
+}
+
+#[async_trait]
+impl<T> RequestContents for Streaming<T>
+where
+    T: Archive + Send + Sync + 'static,
+    T::Archived: CheckBytes<DefaultValidator<'static>> + Send + Sync + 'static,
+{
+    type Content = MessageStream<T>;
+
+    async fn from_body(body: Body) -> Result<Self::Content, Status> {
+        Ok(MessageStream::new(body))
+    }
+}
+
+fn encode_frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// A stream of messages received from a [Streaming] sender.
+///
+/// Each item is a zero-copy view of the message, if the sender failed,
+/// the stream yields the [Status] it failed with and then ends.
+pub struct MessageStream<T> {
+    body: hyper::Body,
+    buffer: BytesMut,
+    done: bool,
+    _msg: PhantomData<fn() -> T>,
+}
+
+impl<T> MessageStream<T> {
+    fn new(body: Body) -> Self {
+        Self {
+            body: body.into_inner(),
+            buffer: BytesMut::new(),
+            done: false,
+            _msg: PhantomData,
+        }
+    }
+}
+
+impl<T> MessageStream<T>
+where
+    T: Archive,
+    T::Archived: CheckBytes<DefaultValidator<'static>> + 'static,
+{
+    /// Attempts to decode the next frame in the buffer.
+    fn try_decode(&mut self) -> Option<Result<DataView<T>, Status>> {
+        if self.buffer.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let kind = self.buffer[0];
+        let len = u32::from_le_bytes(self.buffer[1..HEADER_SIZE].try_into().unwrap());
+        if self.buffer.len() < HEADER_SIZE + len as usize {
+            return None;
+        }
+
+        self.buffer.advance(HEADER_SIZE);
+        let payload = self.buffer.split_to(len as usize);
+        let mut data = AlignedVec::with_capacity(payload.len());
+        data.extend_from_slice(&payload);
+
+        match kind {
+            MESSAGE_FRAME => Some(DataView::using(data).map_err(|_| Status::invalid())),
+            ERROR_FRAME => {
+                self.done = true;
+                let status =
+                    rkyv::from_bytes(&data).unwrap_or_else(|_| Status::invalid());
+                Some(Err(status))
+            },
+            _ => {
+                self.done = true;
+                Some(Err(Status::invalid()))
+            },
+        }
+    }
+}
+
+impl<T> Stream for MessageStream<T>
+where
+    T: Archive,
+    T::Archived: CheckBytes<DefaultValidator<'static>> + 'static,
+{
+    type Item = Result<DataView<T>, Status>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            if let Some(message) = this.try_decode() {
+                return Poll::Ready(Some(message));
+            }
+
+            match ready!(Pin::new(&mut this.body).poll_data(cx)) {
+                Some(Ok(chunk)) => this.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(Status::connection(e))));
+                },
+                None => {
+                    this.done = true;
+                    if this.buffer.is_empty() {
+                        return Poll::Ready(None);
+                    }
+
+                    // The sender stopped part way through a frame.
+                    return Poll::Ready(Some(Err(Status::invalid())));
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(
+        stream: Streaming<String>,
+    ) -> Vec<Result<DataView<String>, Status>> {
+        let body = stream.try_into_body().unwrap();
+        let stream = Streaming::<String>::from_body(body).await.unwrap();
+        stream.collect().await
+    }
+
+    #[tokio::test]
+    async fn test_stream_roundtrip() {
+        let messages = vec!["hello".to_string(), String::new(), "world".repeat(1024)];
+        let received = collect(messages.clone().into_iter().collect()).await;
+        assert_eq!(received.len(), messages.len());
+        for (view, expected) in received.into_iter().zip(messages) {
+            assert_eq!(view.unwrap().to_owned().unwrap(), expected);
+        }
+
+        let received = collect(Vec::new().into_iter().collect()).await;
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_error() {
+        let stream = Streaming::new(futures::stream::iter(vec![
+            Ok("hello".to_string()),
+            Err(Status::internal("Oops!")),
+            Ok("world".to_string()),
+        ]));
+
+        let mut received = collect(stream).await.into_iter();
+        assert_eq!(
+            received.next().unwrap().unwrap().to_owned().unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            received.next().unwrap().err(),
+            Some(Status::internal("Oops!"))
+        );
+        assert!(
+            received.next().is_none(),
+            "Stream should end after an error."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_stream() {
+        let mut frame = encode_frame(MESSAGE_FRAME, b"abcdefgh");
+        frame.truncate(frame.len() - 2);
+
+        let stream = MessageStream::<String>::new(Body::from(frame));
+        let received = stream.collect::<Vec<_>>().await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].as_ref().err(), Some(&Status::invalid()));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use datacake_rpc::{
+    Bincode,
+    Channel,
+    Handler,
+    Json,
+    Request,
+    RpcClient,
+    RpcService,
+    Server,
+    ServiceRegistry,
+    Status,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Inventory {
+    items: HashMap<String, u32>,
+    restock_after: Duration,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Lookup {
+    item: String,
+}
+
+#[repr(C)]
+#[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Ping;
+
+#[derive(Default)]
+pub struct InventoryService {
+    inventory: parking_lot::Mutex<Option<Inventory>>,
+}
+
+impl RpcService for InventoryService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<Json<Inventory>>();
+        registry.add_handler::<Bincode<Lookup>>();
+        registry.add_handler::<Ping>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Json<Inventory>> for InventoryService {
+    type Reply = Json<usize>;
+
+    async fn on_message(
+        &self,
+        msg: Request<Json<Inventory>>,
+    ) -> Result<Self::Reply, Status> {
+        let inventory = msg.into_inner();
+        let num_items = inventory.items.len();
+        *self.inventory.lock() = Some(inventory);
+        Ok(Json::new(num_items))
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Bincode<Lookup>> for InventoryService {
+    type Reply = Bincode<Option<u32>>;
+
+    async fn on_message(
+        &self,
+        msg: Request<Bincode<Lookup>>,
+    ) -> Result<Self::Reply, Status> {
+        let inventory = self.inventory.lock();
+        let count = inventory
+            .as_ref()
+            .and_then(|inventory| inventory.items.get(&msg.item).copied());
+        Ok(Bincode::new(count))
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Ping> for InventoryService {
+    type Reply = bool;
+
+    async fn on_message(&self, _msg: Request<Ping>) -> Result<Self::Reply, Status> {
+        Ok(self.inventory.lock().is_some())
+    }
+}
+
+#[tokio::test]
+async fn test_serde_codecs() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(InventoryService::default());
+
+    let rpc_client = RpcClient::<InventoryService>::new(Channel::connect(addr));
+
+    let has_inventory = *rpc_client.send(&Ping).await.unwrap();
+    assert!(!has_inventory);
+
+    let lookup = Bincode::new(Lookup {
+        item: "apples".to_string(),
+    });
+    let count = rpc_client.send(&lookup).await.unwrap();
+    assert_eq!(count, None);
+
+    let mut items = HashMap::new();
+    items.insert("apples".to_string(), 12);
+    items.insert("pears".to_string(), 3);
+    let inventory = Inventory {
+        items,
+        restock_after: Duration::from_secs(60),
+    };
+    let num_items = rpc_client.send(&Json::new(inventory)).await.unwrap();
+    assert_eq!(num_items, 2);
+
+    let count = rpc_client.send(&lookup).await.unwrap();
+    assert_eq!(count, Some(12));
+
+    let has_inventory = *rpc_client.send(&Ping).await.unwrap();
+    assert!(
+        has_inventory,
+        "rkyv messages should work alongside serde messages."
+    );
+
+    server.shutdown();
+}
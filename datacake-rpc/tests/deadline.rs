@@ -0,0 +1,181 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use datacake_rpc::{
+    Channel,
+    Handler,
+    Request,
+    RpcClient,
+    RpcService,
+    Server,
+    ServiceRegistry,
+    Status,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Remaining;
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Sleep(u64);
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Forward;
+
+#[derive(Default)]
+pub struct DeadlineService {
+    cancelled: Arc<AtomicUsize>,
+}
+
+impl RpcService for DeadlineService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<Remaining>();
+        registry.add_handler::<Sleep>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Remaining> for DeadlineService {
+    type Reply = Option<u64>;
+
+    async fn on_message(&self, msg: Request<Remaining>) -> Result<Self::Reply, Status> {
+        Ok(msg.remaining_time().map(|t| t.as_millis() as u64))
+    }
+}
+
+/// Counts the handlers which were dropped before completing.
+struct CancelGuard(Arc<AtomicUsize>, bool);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if !self.1 {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Sleep> for DeadlineService {
+    type Reply = ();
+
+    async fn on_message(&self, msg: Request<Sleep>) -> Result<Self::Reply, Status> {
+        let mut guard = CancelGuard(self.cancelled.clone(), false);
+        tokio::time::sleep(Duration::from_millis(msg.0)).await;
+        guard.1 = true;
+        Ok(())
+    }
+}
+
+pub struct ProxyService {
+    client: RpcClient<DeadlineService>,
+}
+
+impl RpcService for ProxyService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<Forward>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Forward> for ProxyService {
+    type Reply = Option<u64>;
+
+    async fn on_message(&self, _msg: Request<Forward>) -> Result<Self::Reply, Status> {
+        let remaining = self.client.send(&Remaining).await?;
+        Ok(remaining.to_owned().unwrap())
+    }
+}
+
+#[tokio::test]
+async fn test_deadline_is_sent() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(DeadlineService::default());
+
+    let mut rpc_client = RpcClient::<DeadlineService>::new(Channel::connect(addr));
+    let remaining = rpc_client.send(&Remaining).await.unwrap();
+    assert_eq!(
+        remaining.to_owned().unwrap(),
+        None,
+        "No deadline should be set without a timeout."
+    );
+
+    rpc_client.set_timeout(Duration::from_secs(2));
+    let remaining = rpc_client.send(&Remaining).await.unwrap();
+    let remaining = remaining.to_owned().unwrap().unwrap();
+    assert!(
+        remaining > 0 && remaining <= 2_000,
+        "Remaining {remaining}ms"
+    );
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_handler_is_cancelled() {
+    let addr = test_helper::get_unused_addr();
+
+    let cancelled = Arc::new(AtomicUsize::new(0));
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(DeadlineService {
+        cancelled: cancelled.clone(),
+    });
+
+    let mut rpc_client = RpcClient::<DeadlineService>::new(Channel::connect(addr));
+    rpc_client.set_timeout(Duration::from_millis(100));
+
+    let resp = rpc_client.send(&Sleep(5_000)).await;
+    assert_eq!(resp, Err(Status::timeout()));
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        cancelled.load(Ordering::SeqCst),
+        1,
+        "Handler should be cancelled once the deadline is reached."
+    );
+
+    let resp = rpc_client.send(&Sleep(10)).await;
+    assert!(resp.is_ok(), "Handler should complete within the deadline.");
+    assert_eq!(cancelled.load(Ordering::SeqCst), 1);
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_deadline_is_inherited() {
+    let downstream_addr = test_helper::get_unused_addr();
+    let downstream = Server::listen(downstream_addr).await.unwrap();
+    downstream.add_service(DeadlineService::default());
+
+    let proxy_addr = test_helper::get_unused_addr();
+    let proxy = Server::listen(proxy_addr).await.unwrap();
+    proxy.add_service(ProxyService {
+        client: RpcClient::new(Channel::connect(downstream_addr)),
+    });
+
+    let mut rpc_client = RpcClient::<ProxyService>::new(Channel::connect(proxy_addr));
+    let remaining = rpc_client.send(&Forward).await.unwrap();
+    assert_eq!(remaining.to_owned().unwrap(), None);
+
+    rpc_client.set_timeout(Duration::from_millis(500));
+    let remaining = rpc_client.send(&Forward).await.unwrap();
+    let remaining = remaining.to_owned().unwrap().unwrap();
+    assert!(
+        remaining > 0 && remaining <= 500,
+        "Downstream call should inherit the deadline, remaining {remaining}ms"
+    );
+
+    proxy.shutdown();
+    downstream.shutdown();
+}
@@ -0,0 +1,196 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use datacake_rpc::{
+    CallContext,
+    Channel,
+    Handler,
+    Interceptor,
+    Request,
+    RpcClient,
+    RpcService,
+    Server,
+    ServiceRegistry,
+    Status,
+};
+use http::HeaderValue;
+use parking_lot::Mutex;
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct MyMessage {
+    name: String,
+}
+
+pub struct MyService;
+
+impl RpcService for MyService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<MyMessage>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<MyMessage> for MyService {
+    type Reply = String;
+
+    async fn on_message(&self, msg: Request<MyMessage>) -> Result<Self::Reply, Status> {
+        Ok(msg.to_owned().unwrap().name)
+    }
+}
+
+/// Attaches an auth token to every outgoing call.
+pub struct AttachToken(&'static str);
+
+#[datacake_rpc::async_trait]
+impl Interceptor for AttachToken {
+    async fn on_request(&self, call: &mut CallContext) -> Result<(), Status> {
+        call.headers_mut()
+            .insert("authorization", HeaderValue::from_static(self.0));
+        Ok(())
+    }
+}
+
+/// Rejects every call without the expected auth token.
+pub struct RequireToken(&'static str);
+
+#[datacake_rpc::async_trait]
+impl Interceptor for RequireToken {
+    async fn on_request(&self, call: &mut CallContext) -> Result<(), Status> {
+        match call.headers().get("authorization") {
+            Some(token) if token == self.0 => Ok(()),
+            _ => Err(Status::permission_denied("Missing or invalid token.")),
+        }
+    }
+}
+
+/// The service, path and error of a completed call.
+type CallEntry = (String, String, Option<Status>);
+
+#[derive(Clone, Default)]
+/// Records every completed call.
+pub struct CallLog {
+    requests: Arc<AtomicUsize>,
+    calls: Arc<Mutex<Vec<CallEntry>>>,
+}
+
+#[datacake_rpc::async_trait]
+impl Interceptor for CallLog {
+    async fn on_request(&self, _call: &mut CallContext) -> Result<(), Status> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn on_response(&self, call: &CallContext, status: Option<&Status>) {
+        self.calls.lock().push((
+            call.service().to_string(),
+            call.path().to_string(),
+            status.cloned(),
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_interceptor_auth() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(MyService);
+    server.add_interceptor(RequireToken("secret"));
+
+    let log = CallLog::default();
+    server.add_interceptor(log.clone());
+
+    let client = Channel::connect(addr);
+
+    let msg = MyMessage {
+        name: "Bobby".to_string(),
+    };
+
+    let rpc_client = RpcClient::<MyService>::new(client.clone());
+    let resp = rpc_client.send(&msg).await;
+    assert_eq!(
+        resp,
+        Err(Status::permission_denied("Missing or invalid token.")),
+        "Call without a token should be rejected."
+    );
+
+    let mut rpc_client = RpcClient::<MyService>::new(client.clone());
+    rpc_client.add_interceptor(AttachToken("wrong"));
+    let resp = rpc_client.send(&msg).await;
+    assert_eq!(
+        resp,
+        Err(Status::permission_denied("Missing or invalid token.")),
+        "Call with the wrong token should be rejected."
+    );
+
+    let mut rpc_client = RpcClient::<MyService>::new(client);
+    rpc_client.add_interceptor(AttachToken("secret"));
+    let resp = rpc_client.send(&msg).await.unwrap();
+    assert_eq!(resp, msg.name);
+
+    assert_eq!(
+        log.requests.load(Ordering::SeqCst),
+        1,
+        "Interceptors after a rejection should not be run."
+    );
+
+    let calls = log.calls.lock().clone();
+    assert_eq!(calls.len(), 3, "Every call should be observed.");
+    assert_eq!(calls[0].0, MyService::service_name());
+    assert_eq!(calls[0].1, <MyService as Handler<MyMessage>>::path());
+    assert_eq!(
+        calls[0].2,
+        Some(Status::permission_denied("Missing or invalid token.")),
+    );
+    assert_eq!(
+        calls[1].2,
+        Some(Status::permission_denied("Missing or invalid token.")),
+    );
+    assert_eq!(calls[2].2, None);
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_client_interceptor_rejection() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(MyService);
+
+    let server_log = CallLog::default();
+    server.add_interceptor(server_log.clone());
+
+    let client = Channel::connect(addr);
+    let client_log = CallLog::default();
+    let mut rpc_client = RpcClient::<MyService>::new(client);
+    rpc_client.add_interceptor(client_log.clone());
+    rpc_client.add_interceptor(RequireToken("secret"));
+
+    let msg = MyMessage {
+        name: "Bobby".to_string(),
+    };
+    let resp = rpc_client.send(&msg).await;
+    assert_eq!(
+        resp,
+        Err(Status::permission_denied("Missing or invalid token.")),
+        "Call should be rejected before being sent."
+    );
+    assert_eq!(client_log.calls.lock().len(), 1);
+    assert!(
+        server_log.calls.lock().is_empty(),
+        "Rejected call should never reach the server."
+    );
+
+    // Interceptors are carried over to new clients.
+    let rpc_client = rpc_client.new_client::<MyService>();
+    let resp = rpc_client.send(&msg).await;
+    assert!(resp.is_err());
+    assert_eq!(client_log.calls.lock().len(), 2);
+
+    server.shutdown();
+}
@@ -0,0 +1,124 @@
+use datacake_rpc::{
+    CallContext,
+    Channel,
+    Handler,
+    Interceptor,
+    Metadata,
+    Request,
+    RpcClient,
+    RpcService,
+    Server,
+    ServiceRegistry,
+    Status,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct MyMessage {
+    name: String,
+}
+
+pub struct MyService;
+
+impl RpcService for MyService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<MyMessage>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<MyMessage> for MyService {
+    type Reply = String;
+
+    async fn on_message(&self, msg: Request<MyMessage>) -> Result<Self::Reply, Status> {
+        let tenant = msg
+            .metadata()
+            .get("tenant")
+            .ok_or_else(|| Status::permission_denied("Missing tenant."))?
+            .to_string();
+
+        if let Some(request_id) = msg.metadata().get("request-id") {
+            msg.set_response_metadata("request-id", request_id).unwrap();
+        }
+        msg.set_response_metadata("served-by", "server-1").unwrap();
+
+        Ok(format!("{}:{}", tenant, msg.to_owned().unwrap().name))
+    }
+}
+
+/// Rejects calls for tenants other than the allowed one.
+pub struct TenantFilter(&'static str);
+
+#[datacake_rpc::async_trait]
+impl Interceptor for TenantFilter {
+    async fn on_request(&self, call: &mut CallContext) -> Result<(), Status> {
+        match call.metadata().get("tenant") {
+            Some(tenant) if tenant != self.0 => {
+                Err(Status::permission_denied("Unknown tenant."))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_metadata() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(MyService);
+    server.add_interceptor(TenantFilter("tenant-a"));
+
+    let rpc_client = RpcClient::<MyService>::new(Channel::connect(addr));
+
+    let msg = MyMessage {
+        name: "Bobby".to_string(),
+    };
+
+    let resp = rpc_client.send(&msg).await;
+    assert_eq!(
+        resp,
+        Err(Status::permission_denied("Missing tenant.")),
+        "Handler should see that no metadata was sent."
+    );
+
+    let mut metadata = Metadata::new();
+    metadata.insert("tenant", "tenant-a").unwrap();
+    metadata.insert("Request-Id", "1234").unwrap();
+
+    let resp = rpc_client
+        .send_with_metadata(&msg, metadata.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.metadata().get("request-id"), Some("1234"));
+    assert_eq!(resp.metadata().get("served-by"), Some("server-1"));
+    assert_eq!(resp.metadata().len(), 2);
+    assert_eq!(resp.into_inner().as_str(), "tenant-a:Bobby");
+
+    let resp = rpc_client
+        .send_owned_with_metadata(
+            MyMessage {
+                name: "Jimmy".to_string(),
+            },
+            metadata,
+        )
+        .await
+        .unwrap();
+    let (metadata, reply) = resp.into_parts();
+    assert_eq!(metadata.get("request-id"), Some("1234"));
+    assert_eq!(reply.as_str(), "tenant-a:Jimmy");
+
+    let mut metadata = Metadata::new();
+    metadata.insert("tenant", "tenant-b").unwrap();
+    let resp = rpc_client.send_with_metadata(&msg, metadata).await;
+    assert_eq!(
+        resp.err(),
+        Some(Status::permission_denied("Unknown tenant.")),
+        "Interceptors should be able to read the metadata."
+    );
+
+    server.shutdown();
+}
@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use datacake_rpc::{
+    Channel,
+    CircuitBreakerConfig,
+    CircuitState,
+    ErrorCode,
+    Handler,
+    Request,
+    RetryPolicy,
+    RpcClient,
+    RpcService,
+    Server,
+    ServiceRegistry,
+    Status,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Unavailable;
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Internal;
+
+#[derive(Default)]
+pub struct FlakyService {
+    calls: Arc<AtomicUsize>,
+}
+
+impl RpcService for FlakyService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<Unavailable>();
+        registry.add_handler::<Internal>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Unavailable> for FlakyService {
+    type Reply = ();
+
+    async fn on_message(
+        &self,
+        _msg: Request<Unavailable>,
+    ) -> Result<Self::Reply, Status> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Err(Status::unavailable("Try again later."))
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Internal> for FlakyService {
+    type Reply = ();
+
+    async fn on_message(&self, _msg: Request<Internal>) -> Result<Self::Reply, Status> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Err(Status::internal("Oops! Something went wrong!"))
+    }
+}
+
+fn test_policy() -> RetryPolicy {
+    RetryPolicy::new(3)
+        .with_initial_backoff(Duration::from_millis(10))
+        .with_max_backoff(Duration::from_millis(50))
+}
+
+#[tokio::test]
+async fn test_retry_idempotent_codes() {
+    let addr = test_helper::get_unused_addr();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(FlakyService {
+        calls: calls.clone(),
+    });
+
+    let mut rpc_client = RpcClient::<FlakyService>::new(Channel::connect(addr));
+    rpc_client.set_retry_policy(test_policy());
+
+    let resp = rpc_client.send(&Unavailable).await;
+    assert_eq!(resp, Err(Status::unavailable("Try again later.")));
+    assert_eq!(
+        calls.swap(0, Ordering::SeqCst),
+        4,
+        "Idempotent errors should be retried."
+    );
+
+    let resp = rpc_client.send(&Internal).await;
+    assert_eq!(resp, Err(Status::internal("Oops! Something went wrong!")));
+    assert_eq!(
+        calls.swap(0, Ordering::SeqCst),
+        1,
+        "Non-idempotent errors should not be retried."
+    );
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_retry_until_server_is_live() {
+    let addr = test_helper::get_unused_addr();
+
+    let mut rpc_client = RpcClient::<FlakyService>::new(Channel::connect(addr));
+    rpc_client.set_retry_policy(
+        RetryPolicy::new(20)
+            .with_initial_backoff(Duration::from_millis(25))
+            .with_max_backoff(Duration::from_millis(25)),
+    );
+
+    let handle = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let server = Server::listen(addr).await.unwrap();
+        server.add_service(FlakyService::default());
+        server
+    });
+
+    let resp = rpc_client.send(&Internal).await;
+    assert_eq!(
+        resp,
+        Err(Status::internal("Oops! Something went wrong!")),
+        "Request should be retried until the server is reachable."
+    );
+
+    handle.await.unwrap().shutdown();
+}
+
+#[tokio::test]
+async fn test_circuit_breaker() {
+    let addr = test_helper::get_unused_addr();
+
+    let channel = Channel::connect(addr).with_circuit_breaker(
+        CircuitBreakerConfig::default()
+            .with_failure_threshold(2)
+            .with_reset_timeout(Duration::from_millis(200)),
+    );
+    let rpc_client = RpcClient::<FlakyService>::new(channel.clone());
+    assert_eq!(channel.circuit_state(), CircuitState::Closed);
+
+    for _ in 0..2 {
+        let err = rpc_client.send(&Internal).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::ConnectionError);
+    }
+    assert_eq!(channel.circuit_state(), CircuitState::Open);
+
+    let err = rpc_client.send(&Internal).await.unwrap_err();
+    assert_eq!(err.code, ErrorCode::ConnectionError);
+    assert!(
+        err.message.contains("Circuit breaker is open"),
+        "Request should fail fast while the circuit is open: {err}"
+    );
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(FlakyService::default());
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(channel.circuit_state(), CircuitState::HalfOpen);
+
+    let resp = rpc_client.send(&Internal).await;
+    assert_eq!(resp, Err(Status::internal("Oops! Something went wrong!")));
+    assert_eq!(
+        channel.circuit_state(),
+        CircuitState::Closed,
+        "Reaching the peer should close the circuit."
+    );
+
+    server.shutdown();
+}
@@ -0,0 +1,172 @@
+use datacake_rpc::{
+    Channel,
+    Handler,
+    Request,
+    RpcClient,
+    RpcService,
+    Server,
+    ServiceRegistry,
+    Status,
+    Streaming,
+};
+use futures::{SinkExt, StreamExt};
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Count {
+    count: u32,
+    fail_at: Option<u32>,
+}
+
+pub struct StreamingService;
+
+impl RpcService for StreamingService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<Count>();
+        registry.add_handler::<Streaming<u64>>();
+        registry.add_handler::<Streaming<String>>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Count> for StreamingService {
+    type Reply = Streaming<u32>;
+
+    async fn on_message(&self, msg: Request<Count>) -> Result<Self::Reply, Status> {
+        let msg = msg.to_owned().unwrap();
+        let stream = futures::stream::iter(0..msg.count).map(move |n| {
+            if Some(n) == msg.fail_at {
+                Err(Status::internal("Oops! Something went wrong!"))
+            } else {
+                Ok(n)
+            }
+        });
+        Ok(Streaming::new(stream))
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Streaming<u64>> for StreamingService {
+    type Reply = u64;
+
+    async fn on_message(
+        &self,
+        msg: Request<Streaming<u64>>,
+    ) -> Result<Self::Reply, Status> {
+        let mut stream = msg.into_inner();
+        let mut total = 0;
+        while let Some(value) = stream.next().await {
+            total += value?.to_owned().map_err(Status::internal)?;
+        }
+        Ok(total)
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<Streaming<String>> for StreamingService {
+    type Reply = Streaming<String>;
+
+    async fn on_message(
+        &self,
+        msg: Request<Streaming<String>>,
+    ) -> Result<Self::Reply, Status> {
+        let stream = msg
+            .into_inner()
+            .map(|msg| msg.map(|view| view.as_str().to_uppercase()));
+        Ok(Streaming::new(stream))
+    }
+}
+
+#[tokio::test]
+async fn test_server_streaming() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(StreamingService);
+
+    let rpc_client = RpcClient::<StreamingService>::new(Channel::connect(addr));
+
+    let msg = Count {
+        count: 1_000,
+        fail_at: None,
+    };
+    let stream = rpc_client.send(&msg).await.unwrap();
+    let values = stream
+        .map(|view| view.unwrap().to_owned().unwrap())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(values, (0..1_000).collect::<Vec<_>>());
+
+    let msg = Count {
+        count: 10,
+        fail_at: Some(3),
+    };
+    let stream = rpc_client.send(&msg).await.unwrap();
+    let values = stream.collect::<Vec<_>>().await;
+    assert_eq!(values.len(), 4, "Stream should end after an error.");
+    assert!(values[..3].iter().all(|v| v.is_ok()));
+    assert_eq!(
+        values[3].as_ref().err(),
+        Some(&Status::internal("Oops! Something went wrong!")),
+    );
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_client_streaming() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(StreamingService);
+
+    let rpc_client = RpcClient::<StreamingService>::new(Channel::connect(addr));
+
+    let upload = (1..=10_000u64).collect::<Streaming<_>>();
+    let total = rpc_client.send_owned(upload).await.unwrap();
+    assert_eq!(total, (1..=10_000u64).sum::<u64>());
+
+    let upload = Streaming::new(futures::stream::iter(vec![
+        Ok(1u64),
+        Err(Status::internal("Upload failed.")),
+    ]));
+    let resp = rpc_client.send_owned(upload).await;
+    assert_eq!(
+        resp,
+        Err(Status::internal("Upload failed.")),
+        "Errors in the upload should be passed to the handler."
+    );
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_bidirectional_streaming() {
+    let addr = test_helper::get_unused_addr();
+
+    let server = Server::listen(addr).await.unwrap();
+    server.add_service(StreamingService);
+
+    let rpc_client = RpcClient::<StreamingService>::new(Channel::connect(addr));
+
+    let (mut tx, rx) = futures::channel::mpsc::unbounded();
+    let mut replies = rpc_client
+        .send_owned(Streaming::new(rx.map(Ok)))
+        .await
+        .unwrap();
+
+    // Each reply must be received before the next message is sent.
+    for word in ["hello", "streaming", "world"] {
+        tx.send(word.to_string()).await.unwrap();
+        let reply = replies.next().await.unwrap().unwrap();
+        assert_eq!(reply.as_str(), word.to_uppercase());
+    }
+
+    drop(tx);
+    assert!(replies.next().await.is_none());
+
+    server.shutdown();
+}
@@ -0,0 +1,263 @@
+use datacake_rpc::{
+    Certificate,
+    Channel,
+    ClientTlsConfig,
+    ErrorCode,
+    Handler,
+    Identity,
+    Request,
+    RpcClient,
+    RpcService,
+    Server,
+    ServerTlsConfig,
+    ServiceRegistry,
+    Status,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+use test_helper::TestCertificates;
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Archive, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct MyMessage {
+    name: String,
+}
+
+pub struct MyService;
+
+impl RpcService for MyService {
+    fn register_handlers(registry: &mut ServiceRegistry<Self>) {
+        registry.add_handler::<MyMessage>();
+    }
+}
+
+#[datacake_rpc::async_trait]
+impl Handler<MyMessage> for MyService {
+    type Reply = Vec<u8>;
+
+    // Replies with the certificate presented by the client, if any.
+    async fn on_message(&self, msg: Request<MyMessage>) -> Result<Self::Reply, Status> {
+        Ok(msg
+            .peer_identity()
+            .map(|identity| identity.certificate().to_vec())
+            .unwrap_or_default())
+    }
+}
+
+fn server_identity(certs: &TestCertificates) -> Identity {
+    Identity::from_pem(&certs.server_cert_pem, &certs.server_key_pem).unwrap()
+}
+
+fn client_identity(certs: &TestCertificates) -> Identity {
+    Identity::from_pem(&certs.client_cert_pem, &certs.client_key_pem).unwrap()
+}
+
+fn ca_certificate(certs: &TestCertificates) -> Certificate {
+    Certificate::from_pem(&certs.ca_pem).unwrap()
+}
+
+fn message() -> MyMessage {
+    MyMessage {
+        name: "Bobby".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_tls() {
+    let certs = TestCertificates::generate();
+    let addr = test_helper::get_unused_addr();
+
+    let tls = ServerTlsConfig::new(server_identity(&certs))
+        .build()
+        .unwrap();
+    let server = Server::listen_with_tls(addr, tls).await.unwrap();
+    server.add_service(MyService);
+
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+
+    let resp = rpc_client.send(&message()).await.unwrap();
+    assert!(
+        resp.is_empty(),
+        "No peer identity should exist without a client certificate."
+    );
+
+    // The server's certificate is valid for `localhost` as well as its IP address.
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .with_server_name("localhost")
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    rpc_client.send(&message()).await.unwrap();
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_mutual_tls() {
+    let certs = TestCertificates::generate();
+    let addr = test_helper::get_unused_addr();
+
+    let tls = ServerTlsConfig::new(server_identity(&certs))
+        .with_client_ca_certificate(ca_certificate(&certs))
+        .build()
+        .unwrap();
+    let server = Server::listen_with_tls(addr, tls).await.unwrap();
+    server.add_service(MyService);
+
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .with_identity(client_identity(&certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+
+    let resp = rpc_client.send(&message()).await.unwrap();
+    let expected = Certificate::from_pem(&certs.client_cert_pem).unwrap();
+    assert_eq!(
+        resp.as_slice(),
+        expected.as_der(),
+        "Peer identity should match the client certificate."
+    );
+
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    let resp = rpc_client.send(&message()).await;
+    assert!(
+        matches!(
+            resp,
+            Err(Status {
+                code: ErrorCode::ConnectionError,
+                ..
+            })
+        ),
+        "Clients without a certificate should be rejected. Got: {resp:?}"
+    );
+
+    let other_certs = TestCertificates::generate();
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .with_identity(client_identity(&other_certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    let resp = rpc_client.send(&message()).await;
+    assert!(
+        matches!(
+            resp,
+            Err(Status {
+                code: ErrorCode::ConnectionError,
+                ..
+            })
+        ),
+        "Clients with an untrusted certificate should be rejected. Got: {resp:?}"
+    );
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_optional_client_auth() {
+    let certs = TestCertificates::generate();
+    let addr = test_helper::get_unused_addr();
+
+    let tls = ServerTlsConfig::new(server_identity(&certs))
+        .with_client_ca_certificate(ca_certificate(&certs))
+        .with_client_auth_optional(true)
+        .build()
+        .unwrap();
+    let server = Server::listen_with_tls(addr, tls).await.unwrap();
+    server.add_service(MyService);
+
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    let resp = rpc_client.send(&message()).await.unwrap();
+    assert!(resp.is_empty(), "No peer identity should exist.");
+
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .with_identity(client_identity(&certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    let resp = rpc_client.send(&message()).await.unwrap();
+    assert!(!resp.is_empty(), "Peer identity should exist.");
+
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn test_untrusted_server() {
+    let certs = TestCertificates::generate();
+    let other_certs = TestCertificates::generate();
+    let addr = test_helper::get_unused_addr();
+
+    let tls = ServerTlsConfig::new(server_identity(&certs))
+        .build()
+        .unwrap();
+    let server = Server::listen_with_tls(addr, tls).await.unwrap();
+    server.add_service(MyService);
+
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&other_certs))
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    let resp = rpc_client.send(&message()).await;
+    assert!(
+        matches!(
+            resp,
+            Err(Status {
+                code: ErrorCode::ConnectionError,
+                ..
+            })
+        ),
+        "Servers with an untrusted certificate should be rejected. Got: {resp:?}"
+    );
+
+    // The server's certificate is not valid for any other name.
+    let tls = ClientTlsConfig::new()
+        .with_ca_certificate(ca_certificate(&certs))
+        .with_server_name("datacake.local")
+        .build()
+        .unwrap();
+    let client = Channel::connect_with_tls(addr, tls);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    let resp = rpc_client.send(&message()).await;
+    assert!(
+        matches!(
+            resp,
+            Err(Status {
+                code: ErrorCode::ConnectionError,
+                ..
+            })
+        ),
+        "Servers with a certificate for another name should be rejected. Got: {resp:?}"
+    );
+
+    // Plain text clients cannot talk to TLS servers.
+    let client = Channel::connect(addr);
+    let rpc_client = RpcClient::<MyService>::new(client);
+    assert!(rpc_client.send(&message()).await.is_err());
+
+    server.shutdown();
+}
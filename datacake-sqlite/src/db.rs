@@ -1,38 +1,90 @@
 use std::path::Path;
+use std::time::Duration;
 
 use flume::{self, Receiver, Sender};
 use futures::channel::oneshot;
-use rusqlite::{Connection, OptionalExtension, Params, Row};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Params, Row};
 
 type Task = Box<dyn FnOnce(&mut Connection) + Send + 'static>;
 
 const CAPACITY: usize = 10;
 
+/// The number of reader connections opened by [StorageHandle::open].
+pub const DEFAULT_NUM_READERS: usize = 4;
+
+/// How long a reader waits for a lock before giving up.
+///
+/// In WAL mode readers are only blocked briefly during checkpoints.
+const READER_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone)]
 /// A asynchronous wrapper around a SQLite database.
 ///
 /// These operations will be ran in a background thread preventing
 /// any IO operations from blocking the async context.
+///
+/// The database is opened in WAL mode with a single writer connection and
+/// a pool of read-only connections, each running on their own thread.
+/// Statements executed via `execute` and `execute_many` run on the writer,
+/// while the `fetch_*` methods are spread across the readers so they are
+/// never queued behind large writes.
 pub struct StorageHandle {
-    tx: Sender<Task>,
+    writer: Sender<Task>,
+    readers: Option<Sender<Task>>,
+    num_readers: usize,
 }
 
 impl StorageHandle {
     /// Connects to the SQLite database.
     ///
-    /// This spawns 1 background threads with actions being executed within that thread.
+    /// This spawns 1 writer thread and [DEFAULT_NUM_READERS] reader threads
+    /// with actions being executed within those threads.
     ///
     /// This approach reduces the affect of writes blocking reads and vice-versa.
     pub async fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
-        let tx = setup_database(path).await?;
-        Ok(Self { tx })
+        Self::open_with_readers(path, DEFAULT_NUM_READERS).await
+    }
+
+    /// Connects to the SQLite database with the given number of reader connections.
+    ///
+    /// If `num_readers` is `0` all reads are executed on the writer connection.
+    pub async fn open_with_readers(
+        path: impl AsRef<Path>,
+        num_readers: usize,
+    ) -> rusqlite::Result<Self> {
+        let path = path.as_ref();
+
+        // Every connection to an in-memory database is its own separate database.
+        let num_readers = if is_in_memory(path) { 0 } else { num_readers };
+
+        let writer = setup_database(path).await?;
+        let readers = if num_readers > 0 {
+            Some(setup_readers(path, num_readers).await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            writer,
+            readers,
+            num_readers,
+        })
     }
 
     /// Connects to a new in-memory SQLite database.
+    ///
+    /// In-memory databases cannot be shared between connections, so
+    /// all reads are executed on the writer connection.
     pub async fn open_in_memory() -> rusqlite::Result<Self> {
         Self::open(":memory:").await
     }
 
+    #[inline]
+    /// The number of reader connections in the pool.
+    pub fn num_readers(&self) -> usize {
+        self.num_readers
+    }
+
     /// Execute a SQL statement with some provided parameters.
     pub async fn execute<P>(
         &self,
@@ -92,7 +144,7 @@ impl StorageHandle {
     {
         let sql = sql.as_ref().to_string();
 
-        self.submit_task(move |conn| {
+        self.submit_read_task(move |conn| {
             let mut prepared = conn.prepare_cached(&sql)?;
             prepared.query_row(params, T::from_row).optional()
         })
@@ -111,7 +163,7 @@ impl StorageHandle {
     {
         let sql = sql.as_ref().to_string();
 
-        self.submit_task(move |conn| {
+        self.submit_read_task(move |conn| {
             let mut prepared = conn.prepare_cached(&sql)?;
             let mut rows = Vec::with_capacity(param_sets.len());
 
@@ -138,7 +190,7 @@ impl StorageHandle {
     {
         let sql = sql.as_ref().to_string();
 
-        self.submit_task(move |conn| {
+        self.submit_read_task(move |conn| {
             let mut prepared = conn.prepare_cached(&sql)?;
             let mut iter = prepared.query(params)?;
 
@@ -154,27 +206,43 @@ impl StorageHandle {
 
     /// Submits a writer task to execute.
     ///
-    /// This executes the callback on the memory view connection which should be
-    /// significantly faster to modify or read.
-    async fn submit_task<CB, T>(&self, inner: CB) -> rusqlite::Result<T>
+    /// This executes the callback on the writer connection.
+    pub(crate) async fn submit_task<CB, T>(&self, inner: CB) -> rusqlite::Result<T>
     where
         T: Send + 'static,
         CB: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
     {
-        let (tx, rx) = oneshot::channel();
+        submit_to(&self.writer, inner).await
+    }
 
-        let cb = move |conn: &mut Connection| {
-            let res = inner(conn);
-            let _ = tx.send(res);
-        };
+    /// Submits a reader task to execute.
+    ///
+    /// This executes the callback on the next available reader connection, or
+    /// the writer connection if the pool has no readers.
+    pub(crate) async fn submit_read_task<CB, T>(&self, inner: CB) -> rusqlite::Result<T>
+    where
+        T: Send + 'static,
+        CB: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        submit_to(self.readers.as_ref().unwrap_or(&self.writer), inner).await
+    }
+}
 
-        self.tx
-            .send_async(Box::new(cb))
-            .await
-            .expect("send message");
+async fn submit_to<CB, T>(tasks: &Sender<Task>, inner: CB) -> rusqlite::Result<T>
+where
+    T: Send + 'static,
+    CB: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
 
-        rx.await.unwrap()
-    }
+    let cb = move |conn: &mut Connection| {
+        let res = inner(conn);
+        let _ = tx.send(res);
+    };
+
+    tasks.send_async(Box::new(cb)).await.expect("send message");
+
+    rx.await.unwrap()
 }
 
 /// A helper trait for converting between a Row reference and the given type.
@@ -185,6 +253,10 @@ pub trait FromRow: Sized {
     fn from_row(row: &Row) -> rusqlite::Result<Self>;
 }
 
+fn is_in_memory(path: &Path) -> bool {
+    path.as_os_str().is_empty() || path == Path::new(":memory:")
+}
+
 async fn setup_database(path: impl AsRef<Path>) -> rusqlite::Result<Sender<Task>> {
     let path = path.as_ref().to_path_buf();
     let (tx, rx) = flume::bounded(CAPACITY);
@@ -199,6 +271,9 @@ async fn setup_database(path: impl AsRef<Path>) -> rusqlite::Result<Sender<Task>
 fn setup_disk_handle(path: &Path, tasks: Receiver<Task>) -> rusqlite::Result<()> {
     let disk = Connection::open(path)?;
 
+    // Must be set before switching to WAL as it only takes effect on empty databases,
+    // existing databases must be converted with a full vacuum.
+    disk.execute("pragma auto_vacuum = incremental;", ())?;
     disk.query_row("pragma journal_mode = WAL;", (), |_r| Ok(()))?;
     disk.execute("pragma synchronous = normal;", ())?;
     disk.execute("pragma temp_store = memory;", ())?;
@@ -208,6 +283,47 @@ fn setup_disk_handle(path: &Path, tasks: Receiver<Task>) -> rusqlite::Result<()>
     Ok(())
 }
 
+/// Opens the reader connections, all of which share a single task queue.
+///
+/// This must be called after the writer has been set up so the database
+/// already exists and is in WAL mode.
+async fn setup_readers(
+    path: &Path,
+    num_readers: usize,
+) -> rusqlite::Result<Sender<Task>> {
+    let path = path.to_path_buf();
+    let (tx, rx) = flume::bounded(CAPACITY * num_readers);
+
+    tokio::task::spawn_blocking(move || {
+        let readers = (0..num_readers)
+            .map(|_| open_reader(&path))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for reader in readers {
+            let tasks = rx.clone();
+            std::thread::spawn(move || run_tasks(reader, tasks));
+        }
+
+        Ok::<_, rusqlite::Error>(())
+    })
+    .await
+    .expect("spawn background runner")?;
+
+    Ok(tx)
+}
+
+fn open_reader(path: &Path) -> rusqlite::Result<Connection> {
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+        | OpenFlags::SQLITE_OPEN_NO_MUTEX
+        | OpenFlags::SQLITE_OPEN_URI;
+    let conn = Connection::open_with_flags(path, flags)?;
+
+    conn.busy_timeout(READER_BUSY_TIMEOUT)?;
+    conn.execute("pragma temp_store = memory;", ())?;
+
+    Ok(conn)
+}
+
 /// Runs all tasks received with a mutable reference to the given connection.
 fn run_tasks(mut conn: Connection, tasks: Receiver<Task>) {
     while let Ok(task) = tasks.recv() {
@@ -236,6 +352,66 @@ mod tests {
         run_storage_handle_suite(handle).await;
     }
 
+    #[tokio::test]
+    async fn test_reads_not_blocked_by_writer() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let handle = StorageHandle::open_with_readers(path, 2)
+            .await
+            .expect("open DB");
+        assert_eq!(handle.num_readers(), 2);
+
+        handle
+            .execute(
+                "CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT NOT NULL, data BLOB)",
+                (),
+            )
+            .await
+            .expect("create table");
+        handle
+            .execute(
+                "INSERT INTO person (id, name, data) VALUES (1, 'cf8', 'tada');",
+                (),
+            )
+            .await
+            .expect("Insert row");
+
+        // Occupy the writer until the read has completed.
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let writer = {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                handle
+                    .submit_task(move |_conn| {
+                        let _ = release_rx.recv();
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+
+        let res = tokio::time::timeout(
+            Duration::from_secs(5),
+            handle.fetch_one::<_, Person>("SELECT id, name, data FROM person;", ()),
+        )
+        .await
+        .expect("Read should not be queued behind the writer")
+        .expect("execute statement");
+        assert!(res.is_some(), "Reader should see committed rows.");
+
+        release_tx.send(()).unwrap();
+        writer.await.unwrap().expect("Writer task should complete.");
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage_handle_has_no_readers() {
+        let handle = StorageHandle::open_in_memory().await.expect("open DB");
+        assert_eq!(
+            handle.num_readers(),
+            0,
+            "In-memory databases cannot be shared between connections."
+        );
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     struct Person {
         id: i32,
@@ -0,0 +1,316 @@
+use datacake_eventual_consistency::Document;
+use rusqlite::types::Value;
+use rusqlite::{ffi, params_from_iter, Connection};
+
+use crate::{models, SqliteStorage, TableLayout};
+
+/// Columns which cannot be used as the name of a JSON index.
+const RESERVED_COLUMNS: &[&str] =
+    &["keyspace", "doc_id", "ts", "ts_order", "data", "json"];
+
+/// The document data as JSON text, or `NULL` if it is a tombstone or not valid JSON.
+const JSON_DATA: &str =
+    "CASE WHEN json_valid(CAST(data AS TEXT)) THEN CAST(data AS TEXT) END";
+
+impl SqliteStorage {
+    /// Fetches every document in the keyspace matching the given `WHERE` clause,
+    /// ordered by document ID.
+    ///
+    /// The clause is plain SQL evaluated against each document, with the document
+    /// data available as JSON text via the `json` column so it can be used with
+    /// SQLite's JSON functions. Any columns created by [SqliteStorage::create_json_index]
+    /// can also be referenced by name to make use of their index.
+    /// Documents which are not valid JSON have a `NULL` `json` column.
+    ///
+    /// Parameters are bound to the `?` placeholders within the clause in order.
+    ///
+    /// WARNING:
+    /// The clause is inserted into the statement as is, it must never be built
+    /// from untrusted input, use parameters instead.
+    ///
+    /// ```rust
+    /// use datacake_crdt::HLCTimestamp;
+    /// use datacake_eventual_consistency::{Document, Storage};
+    /// use datacake_sqlite::SqliteStorage;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let storage = SqliteStorage::open_in_memory().await.unwrap();
+    /// let doc = Document::new(1, HLCTimestamp::from_u64(0), br#"{"name": "bobby"}"#.to_vec());
+    /// storage.put("users", doc.clone()).await.unwrap();
+    ///
+    /// let docs = storage
+    ///     .query("users", "json_extract(json, '$.name') = ?", ["bobby".to_string()])
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(docs, vec![doc]);
+    /// # }
+    /// ```
+    pub async fn query<V>(
+        &self,
+        keyspace: &str,
+        sql_where: &str,
+        params: impl IntoIterator<Item = V>,
+    ) -> rusqlite::Result<Vec<Document>>
+    where
+        V: Into<Value>,
+    {
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(Vec::new()),
+            Some(queries) => queries,
+        };
+
+        let mut values = params.into_iter().map(Into::into).collect::<Vec<_>>();
+
+        // The keyspace parameter comes after the clause so it does not affect
+        // the numbering of any parameters within it.
+        let scope = if queries.shared {
+            values.push(Value::Text(keyspace.to_string()));
+            format!("AND keyspace = ?{}", values.len())
+        } else {
+            String::new()
+        };
+
+        let table = &queries.table;
+        let sql = format!(
+            "SELECT doc_id, ts, data FROM (SELECT *, {JSON_DATA} AS json FROM {table})
+                WHERE ({sql_where}\n) AND data IS NOT NULL {scope}
+                ORDER BY doc_id ASC;"
+        );
+
+        let docs = self
+            .inner
+            .fetch_all::<_, models::Doc>(sql, params_from_iter(values))
+            .await?
+            .into_iter()
+            .map(|doc| doc.0)
+            .collect();
+        Ok(docs)
+    }
+
+    /// Creates an indexed column in the keyspace's table containing the
+    /// value at the given JSON path of each document, i.e. `$.user.name`.
+    ///
+    /// The column can then be referenced by name in [SqliteStorage::query].
+    /// If a column with the given name already exists this is a no-op.
+    ///
+    /// Indexes are only supported by databases using the [TableLayout::PerKeyspace]
+    /// layout, as each keyspace has its own table.
+    pub async fn create_json_index(
+        &self,
+        keyspace: &str,
+        column: &str,
+        path: &str,
+    ) -> rusqlite::Result<()> {
+        if self.table_layout() != TableLayout::PerKeyspace {
+            return Err(json_error(
+                "JSON indexes require the per-keyspace table layout".to_string(),
+            ));
+        }
+
+        let valid_name = column
+            .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && column
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name
+            || RESERVED_COLUMNS.contains(&column.to_ascii_lowercase().as_str())
+        {
+            return Err(json_error(format!("Invalid index column name {column:?}")));
+        }
+        if !path.starts_with('$') {
+            return Err(json_error(format!(
+                "Invalid JSON path {path:?}, paths must start with `$`"
+            )));
+        }
+
+        let queries = self
+            .queries(keyspace, true)
+            .await?
+            .expect("Keyspace table should be created");
+
+        let table = queries.table.clone();
+        let column = column.to_string();
+        let path = path.replace('\'', "''");
+        self.inner
+            .submit_task(move |conn| add_json_column(conn, &table, &column, &path))
+            .await
+    }
+}
+
+fn add_json_column(
+    conn: &mut Connection,
+    table: &str,
+    column: &str,
+    path: &str,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    let exists: bool = tx.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_xinfo('{table}') WHERE name = ? COLLATE NOCASE);"),
+        (column,),
+        |row| row.get(0),
+    )?;
+    if !exists {
+        info!(
+            table = table,
+            column = column,
+            path = path,
+            "Creating JSON index."
+        );
+        tx.execute_batch(&format!(
+            r#"
+            ALTER TABLE {table} ADD COLUMN {column} AS (json_extract({JSON_DATA}, '{path}')) VIRTUAL;
+            CREATE INDEX IF NOT EXISTS {table}_{column} ON {table} ({column});
+            "#
+        ))?;
+    }
+
+    tx.commit()
+}
+
+fn json_error(msg: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_MISUSE), Some(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use datacake_crdt::HLCTimestamp;
+    use datacake_eventual_consistency::Storage;
+
+    use super::*;
+
+    static KEYSPACE: &str = "users";
+
+    fn user(id: u64, name: &str, age: u32) -> Document {
+        let data = format!(r#"{{"name": "{name}", "age": {age}}}"#);
+        Document::new(id, HLCTimestamp::from_u64(id), data.into_bytes())
+    }
+
+    async fn populate(storage: &SqliteStorage) -> Vec<Document> {
+        let users = vec![
+            user(1, "bobby", 20),
+            user(2, "timmy", 30),
+            user(3, "tina", 40),
+        ];
+        storage
+            .multi_put(KEYSPACE, users.clone().into_iter())
+            .await
+            .unwrap();
+
+        let invalid = Document::new(4, HLCTimestamp::from_u64(4), b"not-json".to_vec());
+        storage.put(KEYSPACE, invalid).await.unwrap();
+        storage.put("other", user(5, "bobby", 50)).await.unwrap();
+        storage.put(KEYSPACE, user(6, "bobby", 60)).await.unwrap();
+        storage
+            .mark_as_tombstone(KEYSPACE, 6, HLCTimestamp::from_u64(100))
+            .await
+            .unwrap();
+
+        users
+    }
+
+    #[tokio::test]
+    async fn test_query() {
+        for layout in [TableLayout::Shared, TableLayout::PerKeyspace] {
+            let storage = SqliteStorage::builder(":memory:")
+                .with_table_layout(layout)
+                .open()
+                .await
+                .unwrap();
+            let users = populate(&storage).await;
+
+            let docs = storage
+                .query(KEYSPACE, "json_extract(json, '$.name') = ?", ["bobby".to_string()])
+                .await
+                .unwrap();
+            assert_eq!(docs, vec![users[0].clone()], "{layout:?}");
+
+            let docs = storage
+                .query(
+                    KEYSPACE,
+                    "json_extract(json, '$.age') > ?2 OR json_extract(json, '$.name') = ?1",
+                    [Value::from("bobby".to_string()), Value::from(25)],
+                )
+                .await
+                .unwrap();
+            assert_eq!(docs, users, "{layout:?}");
+
+            let docs = storage
+                .query(KEYSPACE, "json IS NULL", Vec::<Value>::new())
+                .await
+                .unwrap();
+            assert_eq!(
+                docs.len(),
+                1,
+                "Only the invalid JSON document should match."
+            );
+            assert_eq!(docs[0].id(), 4);
+
+            let docs = storage
+                .query("missing", "1 = 1", Vec::<Value>::new())
+                .await
+                .unwrap();
+            assert!(docs.is_empty(), "{layout:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_index() {
+        let storage = SqliteStorage::open_in_memory().await.unwrap();
+        let err = storage
+            .create_json_index(KEYSPACE, "name", "$.name")
+            .await
+            .expect_err("Shared tables should not support JSON indexes");
+        assert!(err.to_string().contains("per-keyspace"));
+
+        let storage = SqliteStorage::builder(":memory:")
+            .with_table_layout(TableLayout::PerKeyspace)
+            .open()
+            .await
+            .unwrap();
+        let users = populate(&storage).await;
+
+        assert!(storage
+            .create_json_index(KEYSPACE, "ts", "$.ts")
+            .await
+            .is_err());
+        assert!(storage
+            .create_json_index(KEYSPACE, "name; DROP", "$.name")
+            .await
+            .is_err());
+        assert!(storage
+            .create_json_index(KEYSPACE, "name", "name")
+            .await
+            .is_err());
+
+        storage
+            .create_json_index(KEYSPACE, "name", "$.name")
+            .await
+            .unwrap();
+        storage
+            .create_json_index(KEYSPACE, "name", "$.name")
+            .await
+            .expect("Existing indexes should be a no-op");
+
+        storage.put(KEYSPACE, user(7, "bobby", 70)).await.unwrap();
+        let docs = storage
+            .query(KEYSPACE, "name = ?", ["bobby".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(docs, vec![users[0].clone(), user(7, "bobby", 70)]);
+
+        let plan = storage
+            .handle()
+            .fetch_all::<_, (i64, i64, i64, String)>(
+                "EXPLAIN QUERY PLAN SELECT doc_id FROM keyspace_1 WHERE name = 'bobby';",
+                (),
+            )
+            .await
+            .unwrap();
+        assert!(
+            plan.iter().any(|row| row.3.contains("keyspace_1_name")),
+            "Query should use the index: {plan:?}"
+        );
+    }
+}
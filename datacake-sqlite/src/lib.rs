@@ -75,29 +75,152 @@
 //! }
 //! ```
 
+#[macro_use]
+extern crate tracing;
+
 mod db;
 mod from_row_impl;
+mod json;
+mod maintenance;
+mod schema;
 
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
 use datacake_crdt::{HLCTimestamp, Key};
 use datacake_eventual_consistency::{
     BulkMutationError,
+    DocsStream,
     Document,
     DocumentMetadata,
+    MetadataStream,
+    StateSnapshot,
     Storage,
+    STREAM_CHUNK_SIZE,
 };
-pub use db::FromRow;
+pub use db::{FromRow, DEFAULT_NUM_READERS};
+use futures::StreamExt;
+pub use maintenance::{CheckpointMode, CheckpointResult, DEFAULT_VACUUM_PAGES};
+use rusqlite::types::Value;
+pub use schema::{TableLayout, SCHEMA_VERSION};
 
 pub use crate::db::StorageHandle;
+use crate::schema::{ts_order, Queries};
+
+#[derive(Debug, Clone)]
+/// A builder for configuring and opening a [SqliteStorage] instance.
+pub struct SqliteStorageBuilder {
+    path: PathBuf,
+    num_readers: usize,
+    layout: TableLayout,
+    wal_autocheckpoint: Option<u32>,
+    vacuum_pages: Option<u32>,
+}
+
+impl SqliteStorageBuilder {
+    /// Set the number of reader connections.
+    ///
+    /// By default this is [DEFAULT_NUM_READERS], see [SqliteStorage::open_with_readers].
+    pub fn with_num_readers(mut self, num_readers: usize) -> Self {
+        self.num_readers = num_readers;
+        self
+    }
+
+    /// Set how documents are laid out across tables.
+    ///
+    /// By default every keyspace shares a single table. The layout of a database
+    /// cannot be changed once it has been created.
+    pub fn with_table_layout(mut self, layout: TableLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set the number of pages the WAL may grow to before SQLite automatically
+    /// checkpoints it back into the database file.
+    ///
+    /// By default SQLite uses `1000` pages, see [SqliteStorage::checkpoint]
+    /// for checkpointing manually.
+    pub fn with_wal_autocheckpoint(mut self, pages: u32) -> Self {
+        self.wal_autocheckpoint = Some(pages);
+        self
+    }
+
+    /// Set the maximum number of pages returned to the OS after tombstones are purged.
+    ///
+    /// By default this is [DEFAULT_VACUUM_PAGES], which keeps each vacuum short so
+    /// it does not hold up other writes. If `None` no vacuum is run after purges
+    /// and [SqliteStorage::incremental_vacuum] must be called manually.
+    pub fn with_incremental_vacuum(mut self, max_pages: Option<u32>) -> Self {
+        self.vacuum_pages = max_pages;
+        self
+    }
+
+    /// Opens the database, creating it if it does not already exist and upgrading
+    /// its schema to the latest [SCHEMA_VERSION].
+    pub async fn open(self) -> Result<SqliteStorage, rusqlite::Error> {
+        let inner =
+            StorageHandle::open_with_readers(&self.path, self.num_readers).await?;
+
+        let layout = self.layout;
+        inner
+            .submit_task(move |conn| schema::setup(conn, layout))
+            .await?;
+
+        if let Some(pages) = self.wal_autocheckpoint {
+            inner
+                .submit_task(move |conn| {
+                    conn.pragma_update(None, "wal_autocheckpoint", pages)
+                })
+                .await?;
+        }
+
+        Ok(SqliteStorage {
+            inner,
+            tables: Tables::new(layout),
+            vacuum_pages: self.vacuum_pages,
+        })
+    }
+}
 
 /// A [Storage] implementation based on an SQLite database.
 pub struct SqliteStorage {
     inner: StorageHandle,
+    tables: Tables,
+    vacuum_pages: Option<u32>,
 }
 
 impl SqliteStorage {
+    /// Creates a new builder for opening a database in the given path.
+    ///
+    /// ```rust
+    /// use datacake_sqlite::{SqliteStorage, TableLayout};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let storage = SqliteStorage::builder("./tables.db")
+    ///     .with_num_readers(2)
+    ///     .with_table_layout(TableLayout::PerKeyspace)
+    ///     .open()
+    ///     .await
+    ///     .expect("Create database");
+    /// # drop(storage);
+    /// # let _ = std::fs::remove_file("./tables.db");
+    /// # let _ = std::fs::remove_file("./tables.db-shm");
+    /// # let _ = std::fs::remove_file("./tables.db-wal");
+    /// # }
+    /// ```
+    pub fn builder(path: impl AsRef<Path>) -> SqliteStorageBuilder {
+        SqliteStorageBuilder {
+            path: path.as_ref().to_path_buf(),
+            num_readers: DEFAULT_NUM_READERS,
+            layout: TableLayout::default(),
+            wal_autocheckpoint: None,
+            vacuum_pages: Some(DEFAULT_VACUUM_PAGES),
+        }
+    }
+
     /// Opens a new SQLite database in the given path.
     ///
     /// If the database does not already exist it will be created.
@@ -115,9 +238,39 @@ impl SqliteStorage {
     /// # }
     /// ```
     pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self, rusqlite::Error> {
-        let inner = StorageHandle::open(path.as_ref()).await?;
-        setup_db(inner.clone()).await?;
-        Ok(Self { inner })
+        Self::builder(path).open().await
+    }
+
+    /// Opens a new SQLite database in the given path with `num_readers` reader connections.
+    ///
+    /// Reads such as `get`, `multi_get` and `iter_metadata` are spread across the
+    /// readers so they are not queued behind large writes, if `num_readers` is `0`
+    /// every operation runs on the single writer connection.
+    ///
+    /// If the database does not already exist it will be created.
+    ///
+    /// ```rust
+    /// use datacake_sqlite::SqliteStorage;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let storage = SqliteStorage::open_with_readers("./readers.db", 8)
+    ///     .await
+    ///     .expect("Create database");
+    /// # drop(storage);
+    /// # let _ = std::fs::remove_file("./readers.db");
+    /// # let _ = std::fs::remove_file("./readers.db-shm");
+    /// # let _ = std::fs::remove_file("./readers.db-wal");
+    /// # }
+    /// ```
+    pub async fn open_with_readers<P: AsRef<Path>>(
+        path: P,
+        num_readers: usize,
+    ) -> Result<Self, rusqlite::Error> {
+        Self::builder(path)
+            .with_num_readers(num_readers)
+            .open()
+            .await
     }
 
     /// Opens a new SQLite database in memory.
@@ -132,14 +285,19 @@ impl SqliteStorage {
     /// # }
     /// ```
     pub async fn open_in_memory() -> Result<Self, rusqlite::Error> {
-        let inner = StorageHandle::open_in_memory().await?;
-        setup_db(inner.clone()).await?;
-        Ok(Self { inner })
+        Self::builder(":memory:").open().await
     }
 
     /// Creates a new [SqliteStorage] instances from an existing storage handle.
+    ///
+    /// The database must have already been set up by [SqliteStorage::open]
+    /// using the [TableLayout::Shared] layout.
     pub fn from_handle(handle: StorageHandle) -> Self {
-        Self { inner: handle }
+        Self {
+            inner: handle,
+            tables: Tables::new(TableLayout::Shared),
+            vacuum_pages: Some(DEFAULT_VACUUM_PAGES),
+        }
     }
 
     /// Creates a copy of the storage handle to be used in other sections of code
@@ -149,10 +307,80 @@ impl SqliteStorage {
     /// Any changes made to this will not be reflected in the cluster, it is primarily
     /// only provided for ease of reading.
     ///
-    /// The table `state_entries` is already created and reserved.
+    /// The tables `state_entries`, `state_snapshots`, `datacake_settings`, `keyspace_tables`
+    /// and any tables prefixed with `keyspace_` are already created and reserved.
     pub fn handle(&self) -> StorageHandle {
         self.inner.clone()
     }
+
+    #[inline]
+    /// The table layout used by the database.
+    pub fn table_layout(&self) -> TableLayout {
+        self.tables.layout
+    }
+
+    /// Gets the queries for accessing the given keyspace.
+    ///
+    /// If the keyspace has its own table which does not exist yet, it is
+    /// created when `create` is `true`, otherwise `None` is returned.
+    async fn queries(
+        &self,
+        keyspace: &str,
+        create: bool,
+    ) -> rusqlite::Result<Option<Arc<Queries>>> {
+        if self.tables.layout == TableLayout::Shared {
+            return Ok(Some(self.tables.shared.clone()));
+        }
+
+        if let Some(queries) = self.tables.dedicated.read().unwrap().get(keyspace) {
+            return Ok(Some(queries.clone()));
+        }
+
+        let table = if create {
+            let keyspace = keyspace.to_string();
+            let table = self
+                .inner
+                .submit_task(move |conn| schema::create_keyspace_table(conn, &keyspace))
+                .await?;
+            Some(table)
+        } else {
+            self.inner
+                .fetch_one::<_, (i64,)>(
+                    queries::SELECT_KEYSPACE_TABLE,
+                    (keyspace.to_string(),),
+                )
+                .await?
+                .map(|row| schema::keyspace_table_name(row.0))
+        };
+
+        let queries = table.map(|table| Arc::new(Queries::dedicated(&table)));
+        if let Some(queries) = queries.as_ref() {
+            self.tables
+                .dedicated
+                .write()
+                .unwrap()
+                .insert(keyspace.to_string(), queries.clone());
+        }
+
+        Ok(queries)
+    }
+}
+
+/// The tables documents are stored in.
+struct Tables {
+    layout: TableLayout,
+    shared: Arc<Queries>,
+    dedicated: RwLock<HashMap<String, Arc<Queries>>>,
+}
+
+impl Tables {
+    fn new(layout: TableLayout) -> Self {
+        Self {
+            layout,
+            shared: Arc::new(Queries::shared()),
+            dedicated: RwLock::default(),
+        }
+    }
 }
 
 #[async_trait]
@@ -162,6 +390,13 @@ impl Storage for SqliteStorage {
     type MetadataIter = Box<dyn Iterator<Item = (Key, HLCTimestamp, bool)>>;
 
     async fn get_keyspace_list(&self) -> Result<Vec<String>, Self::Error> {
+        if self.tables.layout == TableLayout::PerKeyspace {
+            return self
+                .inner
+                .submit_read_task(schema::list_keyspace_tables)
+                .await;
+        }
+
         let list = self
             .inner
             .fetch_all::<_, (String,)>(queries::SELECT_KEYSPACE_LIST, ())
@@ -176,11 +411,16 @@ impl Storage for SqliteStorage {
         &self,
         keyspace: &str,
     ) -> Result<Self::MetadataIter, Self::Error> {
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(Box::new(std::iter::empty())),
+            Some(queries) => queries,
+        };
+
         let list = self
             .inner
             .fetch_all::<_, models::Metadata>(
-                queries::SELECT_METADATA_LIST,
-                (keyspace.to_string(),),
+                &queries.select_metadata_list,
+                queries.params(keyspace, []),
             )
             .await?
             .into_iter()
@@ -188,32 +428,167 @@ impl Storage for SqliteStorage {
         Ok(Box::new(list))
     }
 
+    async fn stream_metadata(
+        &self,
+        keyspace: &str,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(futures::stream::empty().boxed()),
+            Some(queries) => queries,
+        };
+        let handle = self.inner.clone();
+        let keyspace = keyspace.to_string();
+
+        // Pages through the keyspace by doc ID, each page is only fetched once the
+        // previous one has been consumed.
+        let stream = futures::stream::try_unfold(Some(None::<i64>), move |cursor| {
+            let handle = handle.clone();
+            let queries = queries.clone();
+            let keyspace = keyspace.clone();
+            async move {
+                let last_doc_id = match cursor {
+                    None => return Ok(None),
+                    Some(last_doc_id) => last_doc_id,
+                };
+
+                let limit = Value::Integer(STREAM_CHUNK_SIZE as i64);
+                let page = match last_doc_id {
+                    None => {
+                        handle
+                            .fetch_all::<_, models::Metadata>(
+                                &queries.select_metadata_page_start,
+                                queries.params(&keyspace, [limit]),
+                            )
+                            .await?
+                    },
+                    Some(last_doc_id) => {
+                        handle
+                            .fetch_all::<_, models::Metadata>(
+                                &queries.select_metadata_page,
+                                queries.params(
+                                    &keyspace,
+                                    [Value::Integer(last_doc_id), limit],
+                                ),
+                            )
+                            .await?
+                    },
+                };
+
+                if page.is_empty() {
+                    return Ok(None);
+                }
+
+                let next_cursor = if page.len() < STREAM_CHUNK_SIZE {
+                    None
+                } else {
+                    page.last().map(|metadata| Some(metadata.0 as i64))
+                };
+                let entries = page
+                    .into_iter()
+                    .map(|metadata| (metadata.0, metadata.1, metadata.2))
+                    .collect();
+
+                Ok(Some((entries, next_cursor)))
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn stream_metadata_since(
+        &self,
+        keyspace: &str,
+        watermark: HLCTimestamp,
+    ) -> Result<MetadataStream<Self::Error>, Self::Error> {
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(futures::stream::empty().boxed()),
+            Some(queries) => queries,
+        };
+        let handle = self.inner.clone();
+        let keyspace = keyspace.to_string();
+
+        // Pages through the timestamp index, starting after every entry at the watermark.
+        let start = (ts_order(watermark), i64::MAX);
+        let stream = futures::stream::try_unfold(Some(start), move |cursor| {
+            let handle = handle.clone();
+            let queries = queries.clone();
+            let keyspace = keyspace.clone();
+            async move {
+                let (last_ts, last_doc_id) = match cursor {
+                    None => return Ok(None),
+                    Some(cursor) => cursor,
+                };
+
+                let page = handle
+                    .fetch_all::<_, models::Metadata>(
+                        &queries.select_metadata_since_page,
+                        queries.params(
+                            &keyspace,
+                            [
+                                Value::Integer(last_ts),
+                                Value::Integer(last_doc_id),
+                                Value::Integer(STREAM_CHUNK_SIZE as i64),
+                            ],
+                        ),
+                    )
+                    .await?;
+
+                if page.is_empty() {
+                    return Ok(None);
+                }
+
+                let next_cursor = if page.len() < STREAM_CHUNK_SIZE {
+                    None
+                } else {
+                    page.last()
+                        .map(|metadata| (ts_order(metadata.1), metadata.0 as i64))
+                };
+                let entries = page
+                    .into_iter()
+                    .map(|metadata| (metadata.0, metadata.1, metadata.2))
+                    .collect();
+
+                Ok(Some((entries, next_cursor)))
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
     async fn remove_tombstones(
         &self,
         keyspace: &str,
         keys: impl Iterator<Item = Key> + Send,
     ) -> Result<(), BulkMutationError<Self::Error>> {
+        let queries = match self
+            .queries(keyspace, false)
+            .await
+            .map_err(BulkMutationError::empty_with_error)?
+        {
+            None => return Ok(()),
+            Some(queries) => queries,
+        };
+
         let params = keys
-            .map(|doc_id| (keyspace.to_string(), doc_id as i64))
+            .map(|doc_id| queries.params(keyspace, [Value::Integer(doc_id as i64)]))
             .collect::<Vec<_>>();
         self.inner
-            .execute_many(queries::DELETE_TOMBSTONE, params)
+            .execute_many(&queries.delete_tombstone, params)
             .await // Safe as we're in a transaction.
             .map_err(BulkMutationError::empty_with_error)?;
+
+        self.vacuum_after_purge().await;
+
         Ok(())
     }
 
     async fn put(&self, keyspace: &str, doc: Document) -> Result<(), Self::Error> {
+        let queries = self
+            .queries(keyspace, true)
+            .await?
+            .expect("Table is created");
         self.inner
-            .execute(
-                queries::INSERT,
-                (
-                    keyspace.to_string(),
-                    doc.id() as i64,
-                    doc.last_updated().to_string(),
-                    doc.data().to_vec(),
-                ),
-            )
+            .execute(&queries.insert, queries.params(keyspace, doc_values(&doc)))
             .await?;
         Ok(())
     }
@@ -223,18 +598,17 @@ impl Storage for SqliteStorage {
         keyspace: &str,
         documents: impl Iterator<Item = Document> + Send,
     ) -> Result<(), BulkMutationError<Self::Error>> {
+        let queries = self
+            .queries(keyspace, true)
+            .await
+            .map_err(BulkMutationError::empty_with_error)?
+            .expect("Table is created");
+
         let params = documents
-            .map(|doc| {
-                (
-                    keyspace.to_string(),
-                    doc.id() as i64,
-                    doc.last_updated().to_string(),
-                    doc.data().to_vec(),
-                )
-            })
+            .map(|doc| queries.params(keyspace, doc_values(&doc)))
             .collect::<Vec<_>>();
         self.inner
-            .execute_many(queries::INSERT, params)
+            .execute_many(&queries.insert, params)
             .await // Safe as we're in a transaction.
             .map_err(BulkMutationError::empty_with_error)?;
         Ok(())
@@ -246,10 +620,14 @@ impl Storage for SqliteStorage {
         doc_id: Key,
         timestamp: HLCTimestamp,
     ) -> Result<(), Self::Error> {
+        let queries = self
+            .queries(keyspace, true)
+            .await?
+            .expect("Table is created");
         self.inner
             .execute(
-                queries::SET_TOMBSTONE,
-                (keyspace.to_string(), doc_id as i64, timestamp.to_string()),
+                &queries.set_tombstone,
+                queries.params(keyspace, tombstone_values(doc_id, timestamp)),
             )
             .await?;
         Ok(())
@@ -260,17 +638,19 @@ impl Storage for SqliteStorage {
         keyspace: &str,
         documents: impl Iterator<Item = DocumentMetadata> + Send,
     ) -> Result<(), BulkMutationError<Self::Error>> {
+        let queries = self
+            .queries(keyspace, true)
+            .await
+            .map_err(BulkMutationError::empty_with_error)?
+            .expect("Table is created");
+
         let params = documents
             .map(|doc| {
-                (
-                    keyspace.to_string(),
-                    doc.id as i64,
-                    doc.last_updated.to_string(),
-                )
+                queries.params(keyspace, tombstone_values(doc.id, doc.last_updated))
             })
             .collect::<Vec<_>>();
         self.inner
-            .execute_many(queries::SET_TOMBSTONE, params)
+            .execute_many(&queries.set_tombstone, params)
             .await // Safe as we're in a transaction.
             .map_err(BulkMutationError::empty_with_error)?;
         Ok(())
@@ -281,11 +661,16 @@ impl Storage for SqliteStorage {
         keyspace: &str,
         doc_id: Key,
     ) -> Result<Option<Document>, Self::Error> {
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(None),
+            Some(queries) => queries,
+        };
+
         let entry = self
             .inner
             .fetch_one::<_, models::Doc>(
-                queries::SELECT_DOC,
-                (keyspace.to_string(), doc_id as i64),
+                &queries.select_doc,
+                queries.params(keyspace, [Value::Integer(doc_id as i64)]),
             )
             .await?;
 
@@ -297,105 +682,307 @@ impl Storage for SqliteStorage {
         keyspace: &str,
         doc_ids: impl Iterator<Item = Key> + Send,
     ) -> Result<Self::DocsIter, Self::Error> {
-        let doc_ids = doc_ids
-            .map(|id| (keyspace.to_string(), id))
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(Box::new(std::iter::empty())),
+            Some(queries) => queries,
+        };
+
+        let params = doc_ids
+            .map(|id| queries.params(keyspace, [Value::Integer(id as i64)]))
             .collect::<Vec<_>>();
         let docs = self
             .inner
-            .fetch_many::<_, models::Doc>(queries::SELECT_DOC, doc_ids)
+            .fetch_many::<_, models::Doc>(&queries.select_doc, params)
             .await?
             .into_iter()
             .map(|d| d.0);
 
         Ok(Box::new(docs))
     }
+
+    async fn stream_multi_get(
+        &self,
+        keyspace: &str,
+        doc_ids: impl Iterator<Item = Key> + Send,
+    ) -> Result<DocsStream<Self::Error>, Self::Error> {
+        let queries = match self.queries(keyspace, false).await? {
+            None => return Ok(futures::stream::empty().boxed()),
+            Some(queries) => queries,
+        };
+        let handle = self.inner.clone();
+        let doc_ids = doc_ids.collect::<Vec<_>>();
+
+        // Each chunk of documents is only fetched once the previous one has been consumed.
+        let chunks = doc_ids
+            .chunks(STREAM_CHUNK_SIZE)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|id| queries.params(keyspace, [Value::Integer(*id as i64)]))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let stream = futures::stream::iter(chunks).then(move |params| {
+            let handle = handle.clone();
+            let queries = queries.clone();
+            async move {
+                let docs = handle
+                    .fetch_many::<_, models::Doc>(&queries.select_doc, params)
+                    .await?
+                    .into_iter()
+                    .map(|d| d.0)
+                    .collect();
+                Ok(docs)
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn put_state_snapshot(
+        &self,
+        keyspace: &str,
+        snapshot: StateSnapshot,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .execute(
+                queries::INSERT_SNAPSHOT,
+                (
+                    keyspace.to_string(),
+                    snapshot.watermark.to_string(),
+                    snapshot.state,
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_state_snapshot(
+        &self,
+        keyspace: &str,
+    ) -> Result<Option<StateSnapshot>, Self::Error> {
+        let snapshot = self
+            .inner
+            .fetch_one::<_, models::Snapshot>(
+                queries::SELECT_SNAPSHOT,
+                (keyspace.to_string(),),
+            )
+            .await?;
+        Ok(snapshot.map(|snapshot| snapshot.0))
+    }
+
+    async fn remove_state_snapshot(&self, keyspace: &str) -> Result<(), Self::Error> {
+        self.inner
+            .execute(queries::DELETE_SNAPSHOT, (keyspace.to_string(),))
+            .await?;
+        Ok(())
+    }
+}
+
+/// The `doc_id, ts, ts_order, data` parameters of a document.
+fn doc_values(doc: &Document) -> [Value; 4] {
+    [
+        Value::Integer(doc.id() as i64),
+        Value::Text(doc.last_updated().to_string()),
+        Value::Integer(ts_order(doc.last_updated())),
+        Value::Blob(doc.data().to_vec()),
+    ]
+}
+
+/// The `doc_id, ts, ts_order` parameters of a tombstone.
+fn tombstone_values(doc_id: Key, ts: HLCTimestamp) -> [Value; 3] {
+    [
+        Value::Integer(doc_id as i64),
+        Value::Text(ts.to_string()),
+        Value::Integer(ts_order(ts)),
+    ]
 }
 
 mod queries {
-    pub static INSERT: &str = r#"
-        INSERT INTO state_entries (keyspace, doc_id, ts, data) VALUES (?, ?, ?, ?)
-            ON CONFLICT (keyspace, doc_id) DO UPDATE SET ts = excluded.ts, data = excluded.data;
-        "#;
-    pub static SELECT_DOC: &str = r#"
-        SELECT doc_id, ts, data FROM state_entries WHERE keyspace = ? AND doc_id = ? AND data IS NOT NULL;
-        "#;
     pub static SELECT_KEYSPACE_LIST: &str = r#"
         SELECT DISTINCT keyspace FROM state_entries GROUP BY keyspace;
         "#;
-    pub static SELECT_METADATA_LIST: &str = r#"
-        SELECT doc_id, ts, (data IS NULL) as tombstone FROM state_entries WHERE keyspace = ?;
+    pub static SELECT_KEYSPACE_TABLE: &str = r#"
+        SELECT id FROM keyspace_tables WHERE name = ?;
+        "#;
+    pub static INSERT_SNAPSHOT: &str = r#"
+        INSERT INTO state_snapshots (keyspace, watermark, state) VALUES (?, ?, ?)
+            ON CONFLICT (keyspace) DO UPDATE SET watermark = excluded.watermark, state = excluded.state;
         "#;
-    pub static SET_TOMBSTONE: &str = r#"
-        INSERT INTO state_entries (keyspace, doc_id, ts, data) VALUES (?, ?, ?, NULL)
-            ON CONFLICT (keyspace, doc_id) DO UPDATE SET ts = excluded.ts, data = NULL;
+    pub static SELECT_SNAPSHOT: &str = r#"
+        SELECT watermark, state FROM state_snapshots WHERE keyspace = ?;
         "#;
-    pub static DELETE_TOMBSTONE: &str = r#"
-        DELETE FROM state_entries WHERE keyspace = ? AND doc_id = ?;
+    pub static DELETE_SNAPSHOT: &str = r#"
+        DELETE FROM state_snapshots WHERE keyspace = ?;
         "#;
 }
 
 mod models {
-    use std::str::FromStr;
-
-    use datacake_crdt::{HLCTimestamp, Key};
-    use datacake_eventual_consistency::Document;
+    use datacake_crdt::Key;
+    use datacake_eventual_consistency::{Document, StateSnapshot};
     use rusqlite::Row;
 
+    use crate::schema::parse_timestamp;
     use crate::FromRow;
 
     pub struct Doc(pub Document);
     impl FromRow for Doc {
         fn from_row(row: &Row) -> rusqlite::Result<Self> {
             let id = row.get::<_, i64>(0)? as Key;
-            let ts = row.get::<_, String>(1)?;
+            let ts = parse_timestamp(&row.get::<_, String>(1)?)?;
             let data = row.get::<_, Vec<u8>>(2)?;
 
-            let ts = HLCTimestamp::from_str(&ts)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-
             Ok(Self(Document::new(id, ts, data)))
         }
     }
 
-    pub struct Metadata(pub Key, pub HLCTimestamp, pub bool);
+    pub struct Metadata(pub Key, pub datacake_crdt::HLCTimestamp, pub bool);
     impl FromRow for Metadata {
         fn from_row(row: &Row) -> rusqlite::Result<Self> {
             let id = row.get::<_, i64>(0)? as Key;
-            let ts = row.get::<_, String>(1)?;
+            let ts = parse_timestamp(&row.get::<_, String>(1)?)?;
             let is_tombstone = row.get::<_, bool>(2)?;
 
-            let ts = HLCTimestamp::from_str(&ts)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-
             Ok(Self(id, ts, is_tombstone))
         }
     }
-}
 
-async fn setup_db(handle: StorageHandle) -> rusqlite::Result<()> {
-    let table = r#"
-        CREATE TABLE IF NOT EXISTS state_entries (
-            keyspace TEXT,
-            doc_id BIGINT,
-            ts TEXT,
-            data BLOB,
-            PRIMARY KEY (keyspace, doc_id)
-        );
-    "#;
-    handle.execute(table, ()).await?;
+    pub struct Snapshot(pub StateSnapshot);
+    impl FromRow for Snapshot {
+        fn from_row(row: &Row) -> rusqlite::Result<Self> {
+            let watermark = parse_timestamp(&row.get::<_, String>(0)?)?;
+            let state = row.get::<_, Vec<u8>>(1)?;
 
-    Ok(())
+            Ok(Self(StateSnapshot { watermark, state }))
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use datacake_eventual_consistency::test_suite;
+    use std::env::temp_dir;
+
+    use datacake_crdt::HLCTimestamp;
+    use datacake_eventual_consistency::{test_suite, Document, Storage};
+    use futures::TryStreamExt;
 
-    use crate::SqliteStorage;
+    use crate::{SqliteStorage, TableLayout};
 
     #[tokio::test]
     async fn test_storage_logic() {
         let storage = SqliteStorage::open_in_memory().await.unwrap();
         test_suite::run_test_suite(storage).await;
     }
+
+    #[tokio::test]
+    async fn test_storage_logic_with_readers() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let storage = SqliteStorage::open_with_readers(path, 2).await.unwrap();
+        assert_eq!(storage.handle().num_readers(), 2);
+        test_suite::run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_storage_durability() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        test_suite::run_persistence_test_suite(|| async {
+            SqliteStorage::open(&path).await.expect("Open DB")
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_storage_logic_per_keyspace_tables() {
+        let storage = SqliteStorage::builder(":memory:")
+            .with_table_layout(TableLayout::PerKeyspace)
+            .open()
+            .await
+            .unwrap();
+        assert_eq!(storage.table_layout(), TableLayout::PerKeyspace);
+        test_suite::run_test_suite(storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_per_keyspace_tables_are_isolated() {
+        let storage = SqliteStorage::builder(":memory:")
+            .with_table_layout(TableLayout::PerKeyspace)
+            .open()
+            .await
+            .unwrap();
+
+        let doc = Document::new(1, HLCTimestamp::from_u64(1), b"Hello".to_vec());
+        storage.put("keyspace-1", doc.clone()).await.unwrap();
+        storage
+            .mark_as_tombstone("keyspace-2", 1, HLCTimestamp::from_u64(2))
+            .await
+            .unwrap();
+
+        assert_eq!(storage.get("keyspace-1", 1).await.unwrap(), Some(doc));
+        assert_eq!(storage.get("keyspace-2", 1).await.unwrap(), None);
+        assert_eq!(storage.get("missing", 1).await.unwrap(), None);
+        assert_eq!(storage.iter_metadata("missing").await.unwrap().count(), 0);
+
+        let tables = storage
+            .handle()
+            .fetch_all::<_, (String,)>(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name GLOB 'keyspace_[0-9]*';",
+                (),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tables.len(), 2, "Each keyspace should have its own table.");
+
+        storage
+            .remove_tombstones("keyspace-2", [1].into_iter())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_keyspace_list().await.unwrap(),
+            vec!["keyspace-1".to_string()],
+            "Empty keyspaces should not be listed."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_metadata_since_uses_index_order() {
+        for layout in [TableLayout::Shared, TableLayout::PerKeyspace] {
+            let storage = SqliteStorage::builder(":memory:")
+                .with_table_layout(layout)
+                .open()
+                .await
+                .unwrap();
+
+            // Several pages of entries, many sharing the same timestamp.
+            let num_docs = super::STREAM_CHUNK_SIZE as u64 * 2 + 50;
+            let docs = (0..num_docs).map(|id| {
+                Document::new(
+                    id,
+                    HLCTimestamp::from_u64(num_docs - (id / 3)),
+                    Vec::new(),
+                )
+            });
+            storage.multi_put("my-keyspace", docs).await.unwrap();
+
+            let watermark = HLCTimestamp::from_u64(num_docs / 2);
+            let entries = storage
+                .stream_metadata_since("my-keyspace", watermark)
+                .await
+                .unwrap()
+                .try_concat()
+                .await
+                .unwrap();
+
+            let expected = (0..num_docs)
+                .filter(|id| num_docs - (id / 3) > num_docs / 2)
+                .count();
+            assert_eq!(entries.len(), expected, "{layout:?}");
+            assert!(
+                entries
+                    .windows(2)
+                    .all(|pair| (pair[0].1, pair[0].0) < (pair[1].1, pair[1].0)),
+                "Entries should be unique and ordered by timestamp. {layout:?}"
+            );
+            assert!(entries.iter().all(|entry| entry.1 > watermark));
+        }
+    }
 }
@@ -0,0 +1,318 @@
+use std::path::Path;
+
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{ffi, Connection};
+
+use crate::SqliteStorage;
+
+/// The maximum number of pages freed by the incremental vacuum run after each
+/// tombstone purge by default.
+pub const DEFAULT_VACUUM_PAGES: u32 = 1024;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// How aggressively a WAL checkpoint copies frames back into the database file.
+///
+/// See <https://www.sqlite.org/c3ref/wal_checkpoint_v2.html> for the full details.
+pub enum CheckpointMode {
+    #[default]
+    /// Checkpoint as many frames as possible without waiting on any readers or writers.
+    Passive,
+    /// Wait for writers to finish, then checkpoint every frame.
+    Full,
+    /// Like [CheckpointMode::Full], but also waits for readers so the WAL is restarted
+    /// from the beginning on the next write.
+    Restart,
+    /// Like [CheckpointMode::Restart], but also truncates the WAL file to zero bytes.
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointMode::Passive => "PASSIVE",
+            CheckpointMode::Full => "FULL",
+            CheckpointMode::Restart => "RESTART",
+            CheckpointMode::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The outcome of a WAL checkpoint.
+pub struct CheckpointResult {
+    /// If the checkpoint could not complete because it was blocked by
+    /// another connection.
+    pub busy: bool,
+    /// The number of frames in the WAL, or `-1` if the database is not in WAL mode.
+    pub wal_frames: i64,
+    /// The number of frames copied back into the database file, or `-1`
+    /// if the database is not in WAL mode.
+    pub checkpointed_frames: i64,
+}
+
+impl SqliteStorage {
+    /// Writes a consistent copy of the database to the given path using SQLite's
+    /// online backup API, replacing any existing file at the path.
+    ///
+    /// The backup is taken from a single read transaction on one of the reader
+    /// connections, so writes continue while the backup is in progress.
+    /// Databases without readers, i.e. in-memory databases, are backed up
+    /// on the writer connection.
+    pub async fn backup_to(&self, path: impl AsRef<Path>) -> rusqlite::Result<()> {
+        let path = path.as_ref().to_path_buf();
+        self.inner
+            .submit_read_task(move |conn| backup(conn, &path))
+            .await
+    }
+
+    /// Runs a WAL checkpoint, copying changes from the WAL back into the database file.
+    ///
+    /// SQLite already checkpoints automatically once the WAL reaches the size set by
+    /// [crate::SqliteStorageBuilder::with_wal_autocheckpoint], this can be used to
+    /// checkpoint at a more convenient time or to truncate the WAL file.
+    pub async fn checkpoint(
+        &self,
+        mode: CheckpointMode,
+    ) -> rusqlite::Result<CheckpointResult> {
+        let sql = format!("PRAGMA wal_checkpoint({});", mode.as_str());
+        self.inner
+            .submit_task(move |conn| {
+                conn.query_row(&sql, (), |row| {
+                    Ok(CheckpointResult {
+                        busy: row.get::<_, i64>(0)? != 0,
+                        wal_frames: row.get(1)?,
+                        checkpointed_frames: row.get(2)?,
+                    })
+                })
+            })
+            .await
+    }
+
+    /// Returns up to `max_pages` free pages to the OS, or every free page if `None`.
+    ///
+    /// Returns the number of pages which were freed.
+    ///
+    /// This only has an effect on databases in incremental auto-vacuum mode, which
+    /// is the case for any database created by this crate. Older databases must be
+    /// converted once with [SqliteStorage::vacuum].
+    pub async fn incremental_vacuum(
+        &self,
+        max_pages: Option<u32>,
+    ) -> rusqlite::Result<u64> {
+        self.inner
+            .submit_task(move |conn| incremental_vacuum(conn, max_pages))
+            .await
+    }
+
+    /// Rebuilds the entire database file, returning every free page to the OS and
+    /// switching the database to incremental auto-vacuum mode if it is not already.
+    ///
+    /// WARNING:
+    /// This blocks all writes for the duration of the rebuild, which may take a
+    /// long time on large databases. Prefer [SqliteStorage::incremental_vacuum]
+    /// for routine maintenance.
+    pub async fn vacuum(&self) -> rusqlite::Result<()> {
+        self.inner
+            .submit_task(|conn| {
+                conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")
+            })
+            .await
+    }
+
+    /// Frees up to the configured number of pages after tombstones are purged.
+    pub(crate) async fn vacuum_after_purge(&self) {
+        let max_pages = match self.vacuum_pages {
+            None => return,
+            Some(max_pages) => max_pages,
+        };
+
+        if let Err(e) = self.incremental_vacuum(Some(max_pages)).await {
+            warn!(error = ?e, "Failed to run incremental vacuum after tombstone purge.");
+        }
+    }
+}
+
+fn backup(conn: &mut Connection, path: &Path) -> rusqlite::Result<()> {
+    let mut destination = Connection::open(path)?;
+    let backup = Backup::new(conn, &mut destination)?;
+
+    // Copying every page in a single step keeps the copy within one read transaction,
+    // otherwise any write would restart the backup.
+    match backup.step(-1)? {
+        StepResult::Done => Ok(()),
+        _ => Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_BUSY),
+            Some("The backup could not complete as the database is busy".into()),
+        )),
+    }
+}
+
+fn incremental_vacuum(
+    conn: &mut Connection,
+    max_pages: Option<u32>,
+) -> rusqlite::Result<u64> {
+    let freelist_count = |conn: &Connection| {
+        conn.query_row("PRAGMA freelist_count;", (), |row| row.get::<_, u64>(0))
+    };
+
+    let before = freelist_count(conn)?;
+    let sql = match max_pages {
+        None => "PRAGMA incremental_vacuum;".to_string(),
+        Some(max_pages) => format!("PRAGMA incremental_vacuum({max_pages});"),
+    };
+    // Each step of the pragma frees a single page, so the statement must be run to completion.
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(())?;
+    while rows.next()?.is_some() {}
+    drop(rows);
+    drop(stmt);
+
+    let after = freelist_count(conn)?;
+
+    Ok(before.saturating_sub(after))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use datacake_crdt::HLCTimestamp;
+    use datacake_eventual_consistency::{Document, Storage};
+
+    use super::*;
+
+    static KEYSPACE: &str = "maintenance";
+
+    async fn freelist_count(storage: &SqliteStorage) -> u64 {
+        storage
+            .handle()
+            .fetch_one::<_, (u64,)>("PRAGMA freelist_count;", ())
+            .await
+            .unwrap()
+            .unwrap()
+            .0
+    }
+
+    fn large_docs(num_docs: u64) -> impl Iterator<Item = Document> {
+        (0..num_docs)
+            .map(|id| Document::new(id, HLCTimestamp::from_u64(id), vec![0; 8192]))
+    }
+
+    #[tokio::test]
+    async fn test_backup_to() {
+        let dir = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let storage = SqliteStorage::open(dir.join("source.db")).await.unwrap();
+        storage.multi_put(KEYSPACE, large_docs(10)).await.unwrap();
+        storage.backup_to(dir.join("backup.db")).await.unwrap();
+
+        // Changes after the backup should not be included.
+        storage
+            .mark_as_tombstone(KEYSPACE, 1, HLCTimestamp::from_u64(100))
+            .await
+            .unwrap();
+
+        let backup = SqliteStorage::open(dir.join("backup.db")).await.unwrap();
+        let docs = backup
+            .multi_get(KEYSPACE, 0..10)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(docs, large_docs(10).collect::<Vec<_>>());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let storage = SqliteStorage::open(&path).await.unwrap();
+        storage.multi_put(KEYSPACE, large_docs(10)).await.unwrap();
+
+        let res = storage.checkpoint(CheckpointMode::Passive).await.unwrap();
+        assert!(!res.busy);
+        assert!(res.wal_frames > 0, "WAL should contain the writes.");
+        assert_eq!(res.wal_frames, res.checkpointed_frames);
+
+        let res = storage.checkpoint(CheckpointMode::Truncate).await.unwrap();
+        assert_eq!(res.wal_frames, 0, "WAL should be truncated.");
+
+        let wal_path = format!("{}-wal", path.display());
+        assert_eq!(std::fs::metadata(wal_path).unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_after_purge() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let storage = SqliteStorage::builder(&path)
+            .with_incremental_vacuum(None)
+            .open()
+            .await
+            .unwrap();
+
+        storage.multi_put(KEYSPACE, large_docs(50)).await.unwrap();
+        for id in 0..50 {
+            storage
+                .mark_as_tombstone(KEYSPACE, id, HLCTimestamp::from_u64(100 + id))
+                .await
+                .unwrap();
+        }
+        assert!(freelist_count(&storage).await > 0);
+
+        storage.remove_tombstones(KEYSPACE, 0..50).await.unwrap();
+        let freed = storage.incremental_vacuum(Some(10)).await.unwrap();
+        assert_eq!(freed, 10);
+        assert!(storage.incremental_vacuum(None).await.unwrap() > 0);
+        assert_eq!(freelist_count(&storage).await, 0);
+
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let storage = SqliteStorage::open(&path).await.unwrap();
+        storage.multi_put(KEYSPACE, large_docs(50)).await.unwrap();
+        for id in 0..50 {
+            storage
+                .mark_as_tombstone(KEYSPACE, id, HLCTimestamp::from_u64(100 + id))
+                .await
+                .unwrap();
+        }
+        storage.remove_tombstones(KEYSPACE, 0..50).await.unwrap();
+        assert_eq!(
+            freelist_count(&storage).await,
+            0,
+            "Purges should return free pages by default."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_converts_legacy_database() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch("CREATE TABLE legacy (id INTEGER PRIMARY KEY);")
+                .unwrap();
+        }
+
+        let storage = SqliteStorage::open(&path).await.unwrap();
+        let auto_vacuum = || async {
+            storage
+                .handle()
+                .fetch_one::<_, (i64,)>("PRAGMA auto_vacuum;", ())
+                .await
+                .unwrap()
+                .unwrap()
+                .0
+        };
+        assert_eq!(
+            auto_vacuum().await,
+            0,
+            "Existing databases need a full vacuum."
+        );
+
+        storage.vacuum().await.unwrap();
+        assert_eq!(
+            auto_vacuum().await,
+            2,
+            "Database should use incremental vacuum."
+        );
+    }
+}
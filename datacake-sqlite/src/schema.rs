@@ -0,0 +1,511 @@
+use std::str::FromStr;
+
+use datacake_crdt::HLCTimestamp;
+use rusqlite::types::Value;
+use rusqlite::{
+    ffi,
+    params_from_iter,
+    Connection,
+    OptionalExtension,
+    ParamsFromIter,
+    Transaction,
+};
+
+/// The latest version of the schema, this is stored in SQLite's `user_version` pragma.
+///
+/// Databases created before versioning was introduced report a version of `0`,
+/// their tables are adopted by the first migration.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The settings key the table layout is stored under.
+const LAYOUT_SETTING: &str = "table_layout";
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// How documents are laid out across tables.
+///
+/// The layout is fixed when the database is first created, opening an existing
+/// database with a different layout returns an error.
+pub enum TableLayout {
+    #[default]
+    /// Every keyspace shares the `state_entries` table.
+    Shared,
+    /// Each keyspace has its own table, created on its first write.
+    ///
+    /// This keeps the indexes of each keyspace small and independent of one another.
+    PerKeyspace,
+}
+
+impl TableLayout {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TableLayout::Shared => "shared",
+            TableLayout::PerKeyspace => "per-keyspace",
+        }
+    }
+
+    fn from_setting(value: &str) -> rusqlite::Result<Self> {
+        match value {
+            "shared" => Ok(TableLayout::Shared),
+            "per-keyspace" => Ok(TableLayout::PerKeyspace),
+            other => Err(schema_error(format!("Unknown table layout {other:?}"))),
+        }
+    }
+}
+
+/// A single, ordered change to the schema.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Every migration in the order they're applied.
+///
+/// Migrations must never be modified once released, changes to the schema
+/// should always be made by appending a new migration.
+static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create the shared entries and state snapshot tables",
+        apply: create_initial_tables,
+    },
+    Migration {
+        version: 2,
+        description: "Add an orderable timestamp column and index",
+        apply: add_timestamp_index,
+    },
+    Migration {
+        version: 3,
+        description: "Add the settings table and keyspace table registry",
+        apply: add_keyspace_tables,
+    },
+];
+
+/// Upgrades the database to the latest schema version and checks the table layout.
+///
+/// All pending migrations are applied within a single transaction, so a failure
+/// part way through leaves the database untouched.
+pub(crate) fn setup(conn: &mut Connection, layout: TableLayout) -> rusqlite::Result<()> {
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+    let current: u32 = tx.query_row("PRAGMA user_version;", (), |row| row.get(0))?;
+    if current > SCHEMA_VERSION {
+        return Err(schema_error(format!(
+            "Database schema version {current} is newer than the latest supported version {SCHEMA_VERSION}"
+        )));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        info!(
+            version = migration.version,
+            description = migration.description,
+            "Applying SQLite schema migration."
+        );
+        (migration.apply)(&tx)?;
+    }
+    tx.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION};"))?;
+
+    ensure_layout(&tx, layout)?;
+
+    tx.commit()
+}
+
+/// Records the layout on a new database, or checks it matches the existing layout.
+fn ensure_layout(tx: &Transaction, layout: TableLayout) -> rusqlite::Result<()> {
+    let existing = tx
+        .query_row(
+            "SELECT value FROM datacake_settings WHERE key = ?;",
+            (LAYOUT_SETTING,),
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+
+    let existing = match existing {
+        Some(value) => Some(TableLayout::from_setting(&value)?),
+        // Databases created before layouts existed always use the shared table.
+        None if has_shared_entries(tx)? => Some(TableLayout::Shared),
+        None => None,
+    };
+
+    match existing {
+        Some(existing) if existing != layout => Err(schema_error(format!(
+            "Database uses the {:?} table layout but {:?} was requested",
+            existing.as_str(),
+            layout.as_str(),
+        ))),
+        _ => {
+            tx.execute(
+                "INSERT OR IGNORE INTO datacake_settings (key, value) VALUES (?, ?);",
+                (LAYOUT_SETTING, layout.as_str()),
+            )?;
+            Ok(())
+        },
+    }
+}
+
+fn has_shared_entries(tx: &Transaction) -> rusqlite::Result<bool> {
+    tx.query_row("SELECT EXISTS(SELECT 1 FROM state_entries);", (), |row| {
+        row.get(0)
+    })
+}
+
+fn create_initial_tables(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS state_entries (
+            keyspace TEXT,
+            doc_id BIGINT,
+            ts TEXT,
+            data BLOB,
+            PRIMARY KEY (keyspace, doc_id)
+        );
+        CREATE TABLE IF NOT EXISTS state_snapshots (
+            keyspace TEXT PRIMARY KEY,
+            watermark TEXT,
+            state BLOB
+        );
+        "#,
+    )
+}
+
+fn add_timestamp_index(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE state_entries ADD COLUMN ts_order BIGINT;")?;
+
+    // Timestamps are stored as text which does not sort in timestamp order,
+    // so existing rows are back-filled with their orderable form.
+    let rows = {
+        let mut stmt = tx.prepare("SELECT rowid, ts FROM state_entries;")?;
+        let rows = stmt
+            .query_map((), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+
+    {
+        let mut stmt =
+            tx.prepare("UPDATE state_entries SET ts_order = ? WHERE rowid = ?;")?;
+        for (rowid, ts) in rows {
+            stmt.execute((ts_order(parse_timestamp(&ts)?), rowid))?;
+        }
+    }
+
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS state_entries_ts ON state_entries (keyspace, ts_order, doc_id);",
+    )
+}
+
+fn add_keyspace_tables(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS datacake_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS keyspace_tables (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        );
+        "#,
+    )
+}
+
+/// Gets or creates the dedicated table of a keyspace, returning the table name.
+pub(crate) fn create_keyspace_table(
+    conn: &mut Connection,
+    keyspace: &str,
+) -> rusqlite::Result<String> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT OR IGNORE INTO keyspace_tables (name) VALUES (?);",
+        (keyspace,),
+    )?;
+    let id: i64 = tx.query_row(
+        "SELECT id FROM keyspace_tables WHERE name = ?;",
+        (keyspace,),
+        |row| row.get(0),
+    )?;
+
+    let table = keyspace_table_name(id);
+    tx.execute_batch(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {table} (
+            doc_id INTEGER PRIMARY KEY,
+            ts TEXT,
+            ts_order BIGINT,
+            data BLOB
+        );
+        CREATE INDEX IF NOT EXISTS {table}_ts ON {table} (ts_order, doc_id);
+        "#
+    ))?;
+    tx.commit()?;
+
+    Ok(table)
+}
+
+/// Lists every keyspace with a dedicated table containing at least one entry.
+pub(crate) fn list_keyspace_tables(
+    conn: &mut Connection,
+) -> rusqlite::Result<Vec<String>> {
+    let tables = {
+        let mut stmt =
+            conn.prepare_cached("SELECT id, name FROM keyspace_tables ORDER BY name;")?;
+        let tables = stmt
+            .query_map((), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tables
+    };
+
+    let mut keyspaces = Vec::with_capacity(tables.len());
+    for (id, name) in tables {
+        let table = keyspace_table_name(id);
+        let has_entries: bool = conn.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {table});"),
+            (),
+            |row| row.get(0),
+        )?;
+        if has_entries {
+            keyspaces.push(name);
+        }
+    }
+
+    Ok(keyspaces)
+}
+
+#[inline]
+pub(crate) fn keyspace_table_name(id: i64) -> String {
+    format!("keyspace_{id}")
+}
+
+/// Converts the timestamp into an integer which sorts in the same order as the timestamp.
+///
+/// SQLite integers are signed, so the sign bit is flipped to keep large timestamps
+/// ordered after small ones.
+#[inline]
+pub(crate) fn ts_order(ts: HLCTimestamp) -> i64 {
+    (ts.as_u64() ^ (1 << 63)) as i64
+}
+
+pub(crate) fn parse_timestamp(ts: &str) -> rusqlite::Result<HLCTimestamp> {
+    HLCTimestamp::from_str(ts)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn schema_error(msg: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_ERROR), Some(msg))
+}
+
+/// The statements used to access the documents of a keyspace.
+///
+/// Tables shared between keyspaces are filtered by an additional `keyspace`
+/// parameter, which is bound automatically by [Queries::params].
+pub(crate) struct Queries {
+    pub(crate) table: String,
+    pub(crate) shared: bool,
+    pub(crate) insert: String,
+    pub(crate) select_doc: String,
+    pub(crate) select_metadata_list: String,
+    pub(crate) select_metadata_page_start: String,
+    pub(crate) select_metadata_page: String,
+    pub(crate) select_metadata_since_page: String,
+    pub(crate) set_tombstone: String,
+    pub(crate) delete_tombstone: String,
+}
+
+impl Queries {
+    /// The queries for the `state_entries` table shared by every keyspace.
+    pub(crate) fn shared() -> Self {
+        Self::build("state_entries", true)
+    }
+
+    /// The queries for a table dedicated to a single keyspace.
+    pub(crate) fn dedicated(table: &str) -> Self {
+        Self::build(table, false)
+    }
+
+    fn build(table: &str, shared: bool) -> Self {
+        let (key_cols, key_values, conflict, scope, scope_only) = if shared {
+            (
+                "keyspace, doc_id",
+                "?, ?",
+                "(keyspace, doc_id)",
+                "keyspace = ? AND ",
+                "WHERE keyspace = ?",
+            )
+        } else {
+            ("doc_id", "?", "(doc_id)", "", "")
+        };
+
+        Self {
+            table: table.to_string(),
+            shared,
+            insert: format!(
+                "INSERT INTO {table} ({key_cols}, ts, ts_order, data) VALUES ({key_values}, ?, ?, ?)
+                    ON CONFLICT {conflict} DO UPDATE SET ts = excluded.ts, ts_order = excluded.ts_order, data = excluded.data;"
+            ),
+            select_doc: format!(
+                "SELECT doc_id, ts, data FROM {table} WHERE {scope}doc_id = ? AND data IS NOT NULL;"
+            ),
+            select_metadata_list: format!(
+                "SELECT doc_id, ts, (data IS NULL) as tombstone FROM {table} {scope_only};"
+            ),
+            select_metadata_page_start: format!(
+                "SELECT doc_id, ts, (data IS NULL) as tombstone FROM {table} {scope_only}
+                    ORDER BY doc_id ASC LIMIT ?;"
+            ),
+            select_metadata_page: format!(
+                "SELECT doc_id, ts, (data IS NULL) as tombstone FROM {table}
+                    WHERE {scope}doc_id > ?
+                    ORDER BY doc_id ASC LIMIT ?;"
+            ),
+            select_metadata_since_page: format!(
+                "SELECT doc_id, ts, (data IS NULL) as tombstone, ts_order FROM {table}
+                    WHERE {scope}(ts_order, doc_id) > (?, ?)
+                    ORDER BY ts_order ASC, doc_id ASC LIMIT ?;"
+            ),
+            set_tombstone: format!(
+                "INSERT INTO {table} ({key_cols}, ts, ts_order, data) VALUES ({key_values}, ?, ?, NULL)
+                    ON CONFLICT {conflict} DO UPDATE SET ts = excluded.ts, ts_order = excluded.ts_order, data = NULL;"
+            ),
+            delete_tombstone: format!(
+                "DELETE FROM {table} WHERE {scope}doc_id = ?;"
+            ),
+        }
+    }
+
+    /// Builds the statement parameters, prefixed with the keyspace if the table is shared.
+    pub(crate) fn params(
+        &self,
+        keyspace: &str,
+        params: impl IntoIterator<Item = Value>,
+    ) -> ParamsFromIter<Vec<Value>> {
+        let mut values = Vec::with_capacity(5);
+        if self.shared {
+            values.push(Value::Text(keyspace.to_string()));
+        }
+        values.extend(params);
+        params_from_iter(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::*;
+
+    fn user_version(conn: &Connection) -> u32 {
+        conn.query_row("PRAGMA user_version;", (), |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_ts_order_preserves_ordering() {
+        let timestamps = [
+            0,
+            1,
+            u32::MAX as u64,
+            i64::MAX as u64,
+            i64::MAX as u64 + 1,
+            u64::MAX,
+        ];
+        for pair in timestamps.windows(2) {
+            let a = ts_order(HLCTimestamp::from_u64(pair[0]));
+            let b = ts_order(HLCTimestamp::from_u64(pair[1]));
+            assert!(a < b, "{} should sort before {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_fresh_database_setup() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        setup(&mut conn, TableLayout::PerKeyspace).expect("Setup schema");
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+
+        // Re-running the setup is a no-op.
+        setup(&mut conn, TableLayout::PerKeyspace).expect("Setup schema");
+
+        let err = setup(&mut conn, TableLayout::Shared)
+            .expect_err("Changing the layout should be rejected");
+        assert!(err.to_string().contains("per-keyspace"), "{err}");
+
+        let table = create_keyspace_table(&mut conn, "my-keyspace").unwrap();
+        assert_eq!(
+            create_keyspace_table(&mut conn, "my-keyspace").unwrap(),
+            table,
+            "Keyspace tables should be created once."
+        );
+        assert_ne!(create_keyspace_table(&mut conn, "other").unwrap(), table);
+    }
+
+    #[test]
+    fn test_upgrade_legacy_database() {
+        let path = temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let ts = HLCTimestamp::now(0, 0);
+
+        // The schema as it existed before versioning was introduced.
+        {
+            let conn = Connection::open(&path).unwrap();
+            create_legacy_tables(&conn);
+            conn.execute(
+                "INSERT INTO state_entries (keyspace, doc_id, ts, data) VALUES ('ks', 1, ?, x'00');",
+                (ts.to_string(),),
+            )
+            .unwrap();
+        }
+
+        let mut conn = Connection::open(&path).unwrap();
+        assert_eq!(user_version(&conn), 0);
+        let err = setup(&mut conn, TableLayout::PerKeyspace)
+            .expect_err("Existing shared data cannot switch layout");
+        assert!(err.to_string().contains("shared"), "{err}");
+        assert_eq!(
+            user_version(&conn),
+            0,
+            "Failed setups should not partially apply migrations."
+        );
+
+        setup(&mut conn, TableLayout::Shared).expect("Upgrade schema");
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+
+        let stored: i64 = conn
+            .query_row(
+                "SELECT ts_order FROM state_entries WHERE keyspace = 'ks' AND doc_id = 1;",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, ts_order(ts), "Existing rows should be back-filled.");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_reject_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!("PRAGMA user_version = {};", SCHEMA_VERSION + 1))
+            .unwrap();
+
+        let err = setup(&mut conn, TableLayout::Shared)
+            .expect_err("Newer schemas should be rejected");
+        assert!(err.to_string().contains("newer"), "{err}");
+    }
+
+    fn create_legacy_tables(conn: &Connection) {
+        conn.execute_batch(
+            r#"
+            CREATE TABLE state_entries (
+                keyspace TEXT,
+                doc_id BIGINT,
+                ts TEXT,
+                data BLOB,
+                PRIMARY KEY (keyspace, doc_id)
+            );
+            "#,
+        )
+        .unwrap();
+    }
+}
@@ -31,7 +31,7 @@ async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
     let args: Args = Args::parse();
 
-    let storage = ShardedStorage::open_in_dir(&args.data_dir).await?;
+    let storage = storage::open_in_dir(&args.data_dir).await?;
     let connection_cfg = ConnectionConfig::new(
         args.cluster_listen_addr,
         args.public_addr.unwrap_or(args.cluster_listen_addr),
@@ -8,6 +8,7 @@
 //! - Adjustable consistency levels when mutating state.
 //! - Data center aware replication prioritisation.
 //! - Pre-built test suite for `Storage` trait implementations to ensure correct functionality.
+//! - Optional TLS and mutual TLS for all cluster traffic via the `tls` feature.
 //!
 //! ### The packages
 //! Datacake provides several utility libraries as well as some pre-made data store handlers:
@@ -22,6 +23,12 @@
 //!   upon SQLite.
 //! - `datacake-lmdb` - A pre-built and tested implementation of the datacake `Storage` trait built
 //!   upon LMDB.
+//! - `datacake-redb` - A pre-built and tested implementation of the datacake `Storage` trait built
+//!   upon redb, requiring no C toolchain.
+//! - `datacake-memory` - A pre-built and tested in-memory implementation of the datacake `Storage`
+//!   trait, with optional periodic snapshots to disk.
+//! - `datacake-blob` - A wrapper around any datacake `Storage` implementation which keeps large
+//!   documents in content-addressed files on disk.
 //! - `datacake-rpc` - A fast, zero-copy RCP framework with a familiar actor-like feel to it.
 //!
 //! ### Examples
@@ -122,6 +129,10 @@
 //! - More storage implementations?
 //!
 
+#[cfg(feature = "datacake-blob")]
+/// A re-export of the `datacake-blob` package, a storage wrapper which keeps large documents
+/// in content-addressed files rather than within the wrapped store.
+pub use datacake_blob as blob;
 #[cfg(feature = "datacake-crdt")]
 /// A re-export of the `datacake-crdt` package, providing all of the
 /// hybrid logical clock and CRDT implementation.
@@ -135,10 +146,19 @@ pub use datacake_eventual_consistency as eventual_consistency;
 /// implementation for the eventually consistent store, although this may not be suited for
 /// all applications, it is useful for most.
 pub use datacake_lmdb as lmdb;
+#[cfg(feature = "datacake-memory")]
+/// A re-export of the `datacake-memory` package, giving you a pre-built and tested in-memory
+/// storage implementation for the eventually consistent store, this is ideal for ephemeral
+/// clusters such as caches.
+pub use datacake_memory as memory;
 #[cfg(feature = "datacake-node")]
 /// A re-export of the `datacake-node` package, the core membership system for building
 /// your own cluster system.
 pub use datacake_node as node;
+#[cfg(feature = "datacake-redb")]
+/// A re-export of the `datacake-redb` package, giving you a pre-built and tested storage
+/// implementation for the eventually consistent store built upon a pure-Rust embedded database.
+pub use datacake_redb as redb;
 #[cfg(feature = "datacake-rpc")]
 /// A re-export of the `datacake-rpc` package, this is built open HTTP/2 in a similar fashion
 /// to tonic, except it uses rust's `rkyv` package and support zero-copy deserialization
@@ -1,3 +1,5 @@
 mod addr;
+mod tls;
 
 pub use addr::get_unused_addr;
+pub use tls::TestCertificates;